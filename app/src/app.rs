@@ -4,34 +4,66 @@ const ABOUT_LOGO_MAX_WIDTH: f32 = 240.0;
 
 use crate::{
     assets,
-    config::{self, ProviderConfig, Scope, UiSettings},
-    settings::SettingsPanel,
+    config::{self, ProviderConfig, ProviderConfigWatcher, Scope, UiSettings},
+    highlight::CodeHighlighter,
+    images::RemoteImageLoader,
+    ipc::IpcCommand,
+    notify,
+    palette::{CommandPalette, PaletteAction, PaletteContext},
+    settings::{ConnectionTestRequest, ModelFetchRequest, SettingsForm, SettingsPanel},
+    streaming::StreamCoalescer,
     ui::{
         ChatPanel, ChatPanelState, InputBar, InputBarOutput, InputBarState, McpSidebarEntry,
-        McpStatus, MenuBar, MenuBarOutput, MenuBarState, Sidebar, SidebarOutput, SidebarState,
-        ThemeMode, ThemePalette,
+        McpToolList, MenuBar, MenuBarOutput, MenuBarState, Sidebar, SidebarOutput, SidebarState,
+        ThemeMode, ThemePalette, TimestampFormat,
     },
 };
 use anyhow::{anyhow, Result};
 use directories::ProjectDirs;
 use egui::{self, Margin, RichText, Stroke, TextureOptions};
 use egui_commonmark::CommonMarkCache;
+use patina_core::attachment::{with_attachments, ImageAttachment, MAX_TOTAL_ATTACHMENT_BYTES};
+use patina_core::mcp::McpEvent;
 use patina_core::project::ProjectHandle;
-use patina_core::state::AppState;
-use patina_core::{llm::LlmDriver, LlmStatus, StreamChunk};
+use patina_core::state::{AppState, ChatParameters, Conversation};
+use patina_core::{
+    llm::http_client, llm::LlmDriver, llm::ToolDefinition, telemetry, LlmStatus, StreamChunk,
+    TextAttachment, TokenCounter,
+};
 use rfd::FileDialog;
 use std::collections::HashSet;
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, warn};
 use uuid::Uuid;
 
 const SPLASH_DURATION: Duration = Duration::from_secs(1);
 const MANUAL_DISMISS_DELAY: Duration = Duration::from_millis(150);
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
+/// Long enough to read why the selected model changed.
+const MODEL_NOTICE_DURATION: Duration = Duration::from_secs(8);
+/// Repaint cadence while something animates; otherwise egui repaints on input or wake-up.
+const ACTIVE_REPAINT_INTERVAL: Duration = Duration::from_millis(16);
+/// Pause in typing before the sidebar query is run against message bodies.
+const MESSAGE_SEARCH_DELAY: Duration = Duration::from_millis(250);
+/// Quiet time after an edit to patina.yaml before it is re-read, so partial writes are
+/// not picked up.
+const PROVIDER_RELOAD_DELAY: Duration = Duration::from_millis(300);
+/// Quiet time after the sidebar or window stops changing size before the new size is
+/// saved, unless the pointer is released first.
+const LAYOUT_SAVE_DELAY: Duration = Duration::from_millis(300);
+/// How often "5m ago" style timestamps are redrawn while nothing else repaints.
+const RELATIVE_TIME_REFRESH: Duration = Duration::from_secs(30);
+/// How much one zoom shortcut changes the UI scale.
+const UI_SCALE_STEP: f32 = 0.1;
 
 #[derive(Clone, Copy)]
 enum AboutMode {
@@ -46,6 +78,27 @@ enum ModelValidation {
     InvalidSelection,
 }
 
+/// The user turn a streamed reply answers.
+enum ReplyTurn {
+    /// A new message from the input bar, with the images pasted into it.
+    Send {
+        content: String,
+        images: Vec<ImageAttachment>,
+    },
+    /// The conversation's unanswered last message, e.g. after a failed send.
+    Retry,
+    /// An earlier message with new content; later messages are dropped first.
+    Edit { message_id: Uuid, content: String },
+}
+
+/// An MCP tool call in flight, cancelled from the sidebar.
+struct RunningToolCall {
+    endpoint: String,
+    tool: String,
+    cancel: CancellationToken,
+    handle: tokio::task::JoinHandle<()>,
+}
+
 #[derive(Clone)]
 pub struct StreamingMessage {
     pub conversation_id: Uuid,
@@ -64,6 +117,9 @@ pub struct PatinaEguiApp {
     input_state: InputBarState,
     chat_panel_state: ChatPanelState,
     markdown_cache: CommonMarkCache,
+    code_highlighter: CodeHighlighter,
+    /// Tokenizer for the model the active chat uses.
+    token_counter: TokenCounter,
     scope: Scope,
     ui_settings: UiSettings,
     provider_config: ProviderConfig,
@@ -72,17 +128,58 @@ pub struct PatinaEguiApp {
     system_theme: Option<eframe::Theme>,
     error: Option<String>,
     mcp_entries: Vec<McpSidebarEntry>,
+    /// Events of the active project's MCP clients; `None` until they are started.
+    mcp_rx: Option<UnboundedReceiver<McpEvent>>,
+    /// The model MCP servers may sample from, as last handed to the active project.
+    mcp_sampling: Option<ChatParameters>,
+    /// Tool listings in flight, by endpoint id.
+    pending_tool_lists: Vec<(String, tokio::task::JoinHandle<Result<Vec<ToolDefinition>>>)>,
+    running_tool_calls: Vec<RunningToolCall>,
     pinned_lookup: HashSet<Uuid>,
     logo_texture: Option<egui::TextureHandle>,
     about_mode: Option<AboutMode>,
     pending_exit: bool,
     pending_title: Option<String>,
     current_workspace: Option<String>,
-    pending_save: Option<tokio::task::JoinHandle<()>>,
-    pending_provider_reload: Option<tokio::task::JoinHandle<Result<ProviderConfig>>>,
+    /// When the changes noted by [`Self::mark_dirty`] are due to be written.
+    settings_save_due: Option<Instant>,
+    /// Feeds the settings writer task started in [`Self::new`].
+    settings_tx: UnboundedSender<SettingsWrite>,
+    /// Set when the latest settings write failed after retrying; cleared by the next success.
+    last_save_failed: Arc<AtomicBool>,
+    pending_provider_reload: Option<tokio::task::JoinHandle<Result<(ProviderConfig, LlmDriver)>>>,
+    /// Reports edits to patina.yaml; started on the first frame, dropped on exit.
+    provider_watcher: Option<ProviderConfigWatcher>,
+    /// When the debounced reload after an edit to patina.yaml should run.
+    provider_reload_due: Option<Instant>,
+    /// When a changed sidebar width or window size should be saved.
+    layout_save_due: Option<Instant>,
+    pending_model_fetch: Option<(SettingsForm, tokio::task::JoinHandle<Result<Vec<String>>>)>,
+    pending_connection_test: Option<(SettingsForm, tokio::task::JoinHandle<Result<Duration>>)>,
     validation_error: Option<String>,
     streaming_message: Option<StreamingMessage>,
     stream_rx: Option<UnboundedReceiver<Result<StreamChunk>>>,
+    stream_buffer: StreamCoalescer,
+    last_failure: Option<FailedAction>,
+    /// A short message in the menu bar and when it goes away.
+    notice: Option<(String, Instant)>,
+    /// When the debounced message search should run.
+    message_search_due: Option<Instant>,
+    remote_images: Arc<AtomicBool>,
+    image_loaders_installed: bool,
+    forget_images: bool,
+    ipc_rx: Option<UnboundedReceiver<IpcCommand>>,
+    command_palette: CommandPalette,
+    repaint_ctx: Option<egui::Context>,
+    #[cfg(feature = "vision-attachments")]
+    paste_bridge: crate::images::PasteShortcutBridge,
+}
+
+/// The action behind the current error banner, used to offer a recovery button.
+#[derive(Debug, Clone, Copy)]
+enum FailedAction {
+    Send { conversation_id: Uuid },
+    Configuration,
 }
 
 impl PatinaEguiApp {
@@ -94,12 +191,17 @@ impl PatinaEguiApp {
         mut ui_settings: UiSettings,
         provider_config: ProviderConfig,
     ) -> Self {
-        let settings_panel = SettingsPanel::new();
+        let mut settings_panel = SettingsPanel::new();
+        settings_panel.set_group_chats_by_date(ui_settings.group_chats_by_date);
         let global_theme = settings_panel.app_settings().theme;
         if ui_settings.theme_mode != global_theme {
             ui_settings.theme_mode = global_theme;
         }
         let (tx, rx) = unbounded_channel();
+        let stream_flush = Duration::from_millis(ui_settings.stream_flush_ms);
+        let last_save_failed = Arc::new(AtomicBool::new(false));
+        let (settings_tx, settings_rx) = unbounded_channel();
+        runtime.spawn(write_settings(settings_rx, last_save_failed.clone()));
         let mut app = Self {
             state: None,
             driver,
@@ -108,10 +210,25 @@ impl PatinaEguiApp {
             rx,
             menu_state: MenuBarState {
                 theme_mode: global_theme,
+                autosave_delay_ms: ui_settings.autosave_delay_ms,
+                stream_flush_ms: ui_settings.stream_flush_ms,
+                ui_scale: ui_settings.clamped_ui_scale(),
+                load_remote_images: ui_settings.load_remote_images,
+                regenerate_on_model_change: ui_settings.regenerate_on_model_change,
+                allow_mcp_sampling: ui_settings.allow_mcp_sampling,
+                auto_title_chats: ui_settings.auto_title_chats,
+                group_chats_by_date: ui_settings.group_chats_by_date,
+                timestamp_format: ui_settings.timestamp_format.clone(),
+                profiles: provider_config.profiles.clone(),
+                active_profile: provider_config.active_profile.clone(),
+                ..MenuBarState::default()
             },
             sidebar_state: {
                 let mut sidebar = SidebarState::new();
                 sidebar.collapsed = !ui_settings.sidebar_visible;
+                sidebar.mcp_collapsed = ui_settings.mcp_collapsed;
+                sidebar.group_by_date = ui_settings.group_chats_by_date;
+                sidebar.timestamp_format = ui_settings.timestamp_format.clone();
                 sidebar
             },
             input_state: InputBarState::new(
@@ -119,19 +236,30 @@ impl PatinaEguiApp {
                 ui_settings.temperature,
                 ui_settings.retain_input,
             ),
-            chat_panel_state: ChatPanelState::default(),
+            chat_panel_state: {
+                let mut chat_panel = ChatPanelState::default();
+                chat_panel.timestamp_format = ui_settings.timestamp_format.clone();
+                chat_panel
+            },
             markdown_cache: CommonMarkCache::default(),
+            code_highlighter: CodeHighlighter::default(),
+            token_counter: TokenCounter::for_model(ui_settings.model.clone()),
             scope,
             ui_settings,
             provider_config,
             settings_panel,
             palette: match global_theme {
                 ThemeMode::Light => ThemePalette::for_light(),
+                ThemeMode::HighContrast => ThemePalette::for_high_contrast(),
                 _ => ThemePalette::for_dark(),
             },
             system_theme: None,
             error: None,
-            mcp_entries: default_mcp_entries(),
+            mcp_entries: Vec::new(),
+            mcp_rx: None,
+            mcp_sampling: None,
+            pending_tool_lists: Vec::new(),
+            running_tool_calls: Vec::new(),
             pinned_lookup: HashSet::new(),
             logo_texture: None,
             about_mode: Some(AboutMode::Splash {
@@ -140,13 +268,35 @@ impl PatinaEguiApp {
             pending_exit: false,
             pending_title: None,
             current_workspace: None,
-            pending_save: None,
+            settings_save_due: None,
+            settings_tx,
+            last_save_failed,
             pending_provider_reload: None,
+            provider_watcher: None,
+            provider_reload_due: None,
+            layout_save_due: None,
+            pending_model_fetch: None,
+            pending_connection_test: None,
             validation_error: None,
             streaming_message: None,
             stream_rx: None,
+            stream_buffer: StreamCoalescer::new(stream_flush),
+            last_failure: None,
+            notice: None,
+            message_search_due: None,
+            remote_images: Arc::new(AtomicBool::new(false)),
+            image_loaders_installed: false,
+            forget_images: false,
+            ipc_rx: None,
+            command_palette: CommandPalette::default(),
+            repaint_ctx: None,
+            #[cfg(feature = "vision-attachments")]
+            paste_bridge: Default::default(),
         };
+        app.remote_images
+            .store(app.ui_settings.load_remote_images, Ordering::Relaxed);
         app.refresh_pinned_cache();
+        app.reconcile_selected_model();
         if let Some(project) = project {
             app.activate_project(project);
         } else {
@@ -157,54 +307,238 @@ impl PatinaEguiApp {
             app.settings_panel.set_project(None);
             app.pending_title = Some("Patina".to_string());
             app.current_workspace = None;
-            app.spawn_save();
+            app.mark_dirty();
         }
         app
     }
 
+    /// Apply commands received on the IPC socket (see [`crate::ipc`]).
+    pub fn with_ipc(mut self, rx: UnboundedReceiver<IpcCommand>) -> Self {
+        self.ipc_rx = Some(rx);
+        self
+    }
+
+    fn process_ipc_commands(&mut self) {
+        let Some(rx) = &mut self.ipc_rx else {
+            return;
+        };
+        let mut commands = Vec::new();
+        while let Ok(command) = rx.try_recv() {
+            commands.push(command);
+        }
+        for command in commands {
+            match command {
+                IpcCommand::OpenProject { path } => match ProjectHandle::open(&path) {
+                    Ok(project) => self.activate_project(project),
+                    Err(err) => {
+                        error!(error = ?err, path = %path.display(), "IPC open-project failed");
+                        self.error = Some(err.to_string());
+                    }
+                },
+                IpcCommand::NewChat => self.create_new_chat(),
+            }
+        }
+    }
+
     fn process_background_results(&mut self) {
         while let Ok(result) = self.rx.try_recv() {
             if let Err(err) = result {
                 error!(error = ?err, "Failed to send message");
                 self.error = Some(err.to_string());
+                let unconfigured = self
+                    .state
+                    .as_ref()
+                    .is_some_and(|state| matches!(state.llm_status(), LlmStatus::Unconfigured(_)));
+                let streaming = self.streaming_message.take();
+                self.stream_rx = None;
+                self.last_failure = if unconfigured {
+                    Some(FailedAction::Configuration)
+                } else {
+                    streaming.map(|streaming| FailedAction::Send {
+                        conversation_id: streaming.conversation_id,
+                    })
+                };
             } else {
                 self.error = None;
+                self.last_failure = None;
             }
         }
     }
 
-    fn process_stream_chunks(&mut self) {
+    /// Add the replies finished since the last frame to the session's usage counter.
+    fn collect_session_usage(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        for (model, usage) in state.take_usage() {
+            self.menu_state.session_usage.record(&model, &usage);
+        }
+    }
+
+    fn process_stream_chunks(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
         if let Some(rx) = &mut self.stream_rx {
             while let Ok(result) = rx.try_recv() {
                 match result {
                     Ok(chunk) => {
                         if chunk.done {
-                            // Streaming complete, clear streaming state
-                            self.streaming_message = None;
+                            // Streaming complete; the reply is in the conversation now,
+                            // so drop the preview without waiting for the batch window.
+                            let rest = self.stream_buffer.flush();
+                            if let Some(mut streaming) = self.streaming_message.take() {
+                                streaming.content.extend(rest);
+                                let focused =
+                                    ctx.input(|input| input.viewport().focused.unwrap_or(true));
+                                if !focused && self.settings_panel.app_settings().notify_on_reply {
+                                    self.notify_reply_finished(streaming);
+                                }
+                            }
                             self.stream_rx = None;
                             break;
-                        } else if let Some(streaming) = &mut self.streaming_message {
-                            streaming.content.push_str(&chunk.delta);
+                        } else {
+                            self.stream_buffer.push(&chunk.delta, now);
                         }
                     }
                     Err(err) => {
                         error!(error = ?err, "Stream error");
+                        // Apply the deltas buffered before the failure, as when the reply completes.
+                        let rest = self.stream_buffer.flush();
+                        if let Some(streaming) = &mut self.streaming_message {
+                            streaming.content.extend(rest);
+                        }
                         self.error = Some(format!("Stream error: {err}"));
-                        self.streaming_message = None;
+                        self.last_failure =
+                            self.streaming_message
+                                .take()
+                                .map(|streaming| FailedAction::Send {
+                                    conversation_id: streaming.conversation_id,
+                                });
                         self.stream_rx = None;
                         break;
                     }
                 }
             }
         }
+        if let Some(text) = self.stream_buffer.poll(now) {
+            if let Some(streaming) = &mut self.streaming_message {
+                streaming.content.push_str(&text);
+            }
+        }
+    }
+
+    /// Read the active project's MCP servers from its manifest and (re)connect to them.
+    /// An invalid server list is shown in the error banner and leaves none running.
+    fn load_mcp_servers(&mut self) {
+        let Some(state) = self.state.clone() else {
+            return;
+        };
+        self.sidebar_state.tool_form = None;
+        for (_, handle) in self.pending_tool_lists.drain(..) {
+            handle.abort();
+        }
+        for call in self.running_tool_calls.drain(..) {
+            call.cancel.cancel();
+        }
+        let endpoints = match state.project().mcp_endpoints() {
+            Ok(endpoints) => endpoints,
+            Err(err) => {
+                error!(error = ?err, "Failed to load MCP servers");
+                self.error = Some(format!("{err:#}"));
+                Vec::new()
+            }
+        };
+        self.mcp_entries = endpoints
+            .iter()
+            .map(McpSidebarEntry::for_endpoint)
+            .collect();
+        self.runtime
+            .spawn(async move { state.connect_mcp_servers(endpoints).await });
+    }
+
+    /// Keep the model MCP servers sample from in step with the setting and the
+    /// selected model and temperature.
+    fn sync_mcp_sampling(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let wanted = self
+            .ui_settings
+            .allow_mcp_sampling
+            .then(|| self.chat_defaults());
+        if wanted != self.mcp_sampling {
+            state.set_mcp_sampling(wanted.as_ref());
+            self.mcp_sampling = wanted;
+        }
+    }
+
+    /// Pass the active project's MCP events on to the sidebar, starting the forwarding
+    /// on the first frame after the project opens so events can wake the UI.
+    fn process_mcp_events(&mut self) {
+        let Some(state) = self.state.clone() else {
+            return;
+        };
+        if let Some(mut events) = state.take_mcp_events() {
+            let (tx, rx) = unbounded_channel();
+            self.mcp_rx = Some(rx);
+            let repaint_ctx = self.repaint_ctx.clone();
+            self.runtime.spawn(async move {
+                while let Some(event) = events.recv().await {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                    wake_ui(&repaint_ctx);
+                }
+            });
+        }
+        if let Some(rx) = &mut self.mcp_rx {
+            while let Ok(event) = rx.try_recv() {
+                for entry in &mut self.mcp_entries {
+                    entry.apply_event(&event);
+                }
+            }
+        }
+        let (finished, pending) = std::mem::take(&mut self.pending_tool_lists)
+            .into_iter()
+            .partition(|(_, handle)| handle.is_finished());
+        self.pending_tool_lists = pending;
+        for (endpoint, handle) in finished {
+            let tools = match self.runtime.block_on(handle) {
+                Ok(Ok(tools)) => McpToolList::Loaded(tools),
+                Ok(Err(err)) => McpToolList::Failed(format!("{err:#}")),
+                Err(err) => McpToolList::Failed(format!("tool listing task failed: {err}")),
+            };
+            if let Some(entry) = self.mcp_entries.iter_mut().find(|e| e.id == endpoint) {
+                entry.tools = tools;
+            }
+        }
+        let (finished, running) = std::mem::take(&mut self.running_tool_calls)
+            .into_iter()
+            .partition(|call| call.handle.is_finished());
+        self.running_tool_calls = running;
+        for call in finished {
+            if let Some(entry) = self.mcp_entries.iter_mut().find(|e| e.id == call.endpoint) {
+                if let Some(index) = entry.running_tools.iter().position(|t| *t == call.tool) {
+                    entry.running_tools.remove(index);
+                }
+            }
+        }
     }
 
     fn poll_provider_config_reload(&mut self) {
         if let Some(handle) = self.pending_provider_reload.take() {
             if handle.is_finished() {
                 match self.runtime.block_on(handle) {
-                    Ok(Ok(config)) => {
+                    Ok(Ok((config, driver))) => {
+                        self.menu_state.profiles = config.profiles.clone();
+                        self.menu_state.active_profile = config.active_profile.clone();
                         self.provider_config = config;
+                        self.reconcile_selected_model();
+                        if let Some(state) = &self.state {
+                            state.set_llm(driver.clone());
+                        }
+                        self.driver = driver;
+                        // Sampling requests hold on to the driver they were set up with.
+                        self.mcp_sampling = None;
                         self.error = None;
                         self.validation_error = None;
                     }
@@ -230,7 +564,9 @@ impl PatinaEguiApp {
             let new_chat = command_only && input.key_pressed(egui::Key::N);
             let toggle_sidebar = command_only && input.key_pressed(egui::Key::M);
             let focus_search = command_only && input.key_pressed(egui::Key::K);
-            (new_chat, toggle_sidebar, focus_search)
+            let save_now = command_only && input.key_pressed(egui::Key::S);
+            let palette = command_only && input.key_pressed(egui::Key::P);
+            (new_chat, toggle_sidebar, focus_search, save_now, palette)
         });
         if shortcuts.0 {
             self.create_new_chat();
@@ -241,6 +577,161 @@ impl PatinaEguiApp {
         if shortcuts.2 {
             self.sidebar_state.request_search_focus();
         }
+        if shortcuts.3 {
+            self.save_now();
+        }
+        if shortcuts.4 {
+            self.command_palette.toggle();
+        }
+        let zoom = ctx.input_mut(|input| {
+            use egui::gui_zoom::kb_shortcuts::{ZOOM_IN, ZOOM_IN_SECONDARY, ZOOM_OUT, ZOOM_RESET};
+            if input.consume_shortcut(&ZOOM_RESET) {
+                Some(None)
+            } else if input.consume_shortcut(&ZOOM_IN) || input.consume_shortcut(&ZOOM_IN_SECONDARY)
+            {
+                Some(Some(UI_SCALE_STEP))
+            } else if input.consume_shortcut(&ZOOM_OUT) {
+                Some(Some(-UI_SCALE_STEP))
+            } else {
+                None
+            }
+        });
+        match zoom {
+            Some(Some(step)) => self.set_ui_scale(self.ui_settings.clamped_ui_scale() + step),
+            Some(None) => self.set_ui_scale(1.0),
+            None => {}
+        }
+    }
+
+    /// Store a new UI zoom, clamped to [`config::UI_SCALE_RANGE`] and rounded to 5%.
+    fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_settings.ui_scale = (scale * 20.0).round() / 20.0;
+        self.ui_settings.ui_scale = self.ui_settings.clamped_ui_scale();
+        self.menu_state.ui_scale = self.ui_settings.ui_scale;
+        self.mark_dirty();
+    }
+
+    /// Zoom the UI to the stored scale. egui's own zoom shortcuts are turned off so the
+    /// shortcuts above, which clamp and persist, are the only way to change it.
+    fn apply_scale(&self, ctx: &egui::Context) {
+        ctx.options_mut(|options| options.zoom_with_keyboard = false);
+        let scale = self.ui_settings.clamped_ui_scale();
+        if ctx.zoom_factor() != scale {
+            ctx.set_zoom_factor(scale);
+        }
+    }
+
+    fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let active = self
+            .state
+            .as_ref()
+            .and_then(|state| state.active_conversation());
+        let context = PaletteContext {
+            project_open: self.state.is_some(),
+            read_only: self
+                .state
+                .as_ref()
+                .is_some_and(|state| state.is_read_only()),
+            chat_active: active.is_some(),
+        };
+        if let Some(action) = self.command_palette.show(ctx, &self.palette, context) {
+            self.run_palette_action(ctx, action, active.map(|conversation| conversation.id));
+        }
+    }
+
+    /// Run a palette action through the same outputs the menu bar and sidebar produce.
+    fn run_palette_action(
+        &mut self,
+        ctx: &egui::Context,
+        action: PaletteAction,
+        active_chat: Option<Uuid>,
+    ) {
+        let mut menu = MenuBarOutput::default();
+        let mut sidebar = SidebarOutput::default();
+        match action {
+            PaletteAction::NewChat => menu.new_chat = true,
+            PaletteAction::NewProject => menu.new_project = true,
+            PaletteAction::OpenProject => menu.open_project = true,
+            PaletteAction::SaveProjectAs => menu.save_project_as = true,
+            PaletteAction::ImportConversation => menu.import_conversation = true,
+            PaletteAction::SaveNow => menu.save_now = true,
+            PaletteAction::PruneEmptyChats => menu.prune_empty_chats = true,
+            PaletteAction::ShowSettings => menu.show_settings = true,
+            PaletteAction::ToggleSidebar => menu.toggle_sidebar = true,
+            PaletteAction::FocusSearch => menu.focus_search = true,
+            PaletteAction::ClearInput => menu.clear_input = true,
+            PaletteAction::ToggleRemoteImages => {
+                self.menu_state.load_remote_images = !self.menu_state.load_remote_images;
+                menu.remote_images_changed = Some(self.menu_state.load_remote_images);
+            }
+            PaletteAction::ToggleGroupByDate => {
+                self.menu_state.group_chats_by_date = !self.menu_state.group_chats_by_date;
+                menu.group_chats_by_date_changed = Some(self.menu_state.group_chats_by_date);
+            }
+            PaletteAction::ToggleRegenerateOnModelChange => {
+                self.menu_state.regenerate_on_model_change =
+                    !self.menu_state.regenerate_on_model_change;
+                menu.regenerate_on_model_change_changed =
+                    Some(self.menu_state.regenerate_on_model_change);
+            }
+            PaletteAction::ToggleMcpSampling => {
+                self.menu_state.allow_mcp_sampling = !self.menu_state.allow_mcp_sampling;
+                menu.mcp_sampling_changed = Some(self.menu_state.allow_mcp_sampling);
+            }
+            PaletteAction::ToggleAutoTitle => {
+                self.menu_state.auto_title_chats = !self.menu_state.auto_title_chats;
+                menu.auto_title_changed = Some(self.menu_state.auto_title_chats);
+            }
+            PaletteAction::Theme(mode) => {
+                if mode != self.menu_state.theme_mode {
+                    menu.theme_changed = Some(mode);
+                }
+            }
+            PaletteAction::ResetSessionUsage => menu.reset_session_usage = true,
+            PaletteAction::ShowAbout => menu.show_about = true,
+            PaletteAction::Exit => menu.exit = true,
+            PaletteAction::TogglePinChat => {
+                if let Some(id) = active_chat {
+                    if self.pinned_lookup.contains(&id) {
+                        sidebar.unpin = Some(id);
+                    } else {
+                        sidebar.pin = Some(id);
+                    }
+                }
+            }
+            PaletteAction::CopyChatJson => sidebar.copy_json = active_chat,
+            PaletteAction::ExportChatJson => sidebar.export_json = active_chat,
+            PaletteAction::ReloadMcpServers => sidebar.reload_mcp = true,
+            PaletteAction::ResetTitle => sidebar.reset_title = active_chat,
+            PaletteAction::TogglePreview => {
+                if active_chat.is_some()
+                    && self.chat_panel_state.last_conversation_id == active_chat
+                {
+                    self.chat_panel_state.preview = !self.chat_panel_state.preview;
+                }
+            }
+        }
+        self.handle_menu_output(menu);
+        self.handle_sidebar_output(ctx, sidebar);
+    }
+
+    fn ensure_image_loaders(&mut self, ctx: &egui::Context) {
+        if !self.image_loaders_installed {
+            let client = http_client(self.settings_panel.proxy().as_ref()).unwrap_or_else(|err| {
+                warn!(error = ?err, "ignoring the configured proxy for remote images");
+                reqwest::Client::new()
+            });
+            ctx.add_bytes_loader(Arc::new(RemoteImageLoader::new(
+                self.remote_images.clone(),
+                self.runtime.handle().clone(),
+                client,
+            )));
+            egui_extras::install_image_loaders(ctx);
+            self.image_loaders_installed = true;
+        }
+        if std::mem::take(&mut self.forget_images) {
+            ctx.forget_all_images();
+        }
     }
 
     fn ensure_logo_texture(&mut self, ctx: &egui::Context) {
@@ -260,14 +751,17 @@ impl PatinaEguiApp {
             },
             mode => mode,
         };
+        // Custom colors would undo the high-contrast palette's guarantees, so it keeps its own.
+        let colors = &self.settings_panel.app_settings().colors;
         self.palette = match resolved_mode {
-            ThemeMode::Light => ThemePalette::for_light(),
-            _ => ThemePalette::for_dark(),
+            ThemeMode::Light => ThemePalette::for_light().with_overrides(colors),
+            ThemeMode::HighContrast => ThemePalette::for_high_contrast(),
+            _ => ThemePalette::for_dark().with_overrides(colors),
         };
-        ctx.set_visuals(
-            self.palette
-                .visuals(matches!(resolved_mode, ThemeMode::Dark)),
-        );
+        ctx.set_visuals(match resolved_mode {
+            ThemeMode::HighContrast => self.palette.high_contrast_visuals(),
+            mode => self.palette.visuals(matches!(mode, ThemeMode::Dark)),
+        });
     }
 
     fn layout(&mut self, ctx: &egui::Context) {
@@ -280,16 +774,38 @@ impl PatinaEguiApp {
                     .inner_margin(Margin::symmetric(12.0, 8.0)),
             )
             .show(ctx, |ui| {
+                self.menu_state.rate_limits = self.driver.rate_limits();
                 let output = MenuBar::show(
                     ui,
                     &mut self.menu_state,
                     self.logo_texture.as_ref(),
                     project_loaded,
                     self.current_workspace.as_deref(),
+                    &self.provider_config.pricing,
                 );
                 self.handle_menu_output(output);
-                if let Some(err) = &self.error {
-                    ui.colored_label(self.palette.warning, err);
+                self.error_banner(ui);
+                if let Some((notice, until)) = &self.notice {
+                    let remaining = until.saturating_duration_since(Instant::now());
+                    if !remaining.is_zero() {
+                        ui.colored_label(self.palette.accent, notice);
+                        ui.ctx().request_repaint_after(remaining);
+                    } else {
+                        self.notice = None;
+                    }
+                }
+                if self.last_save_failed() {
+                    ui.colored_label(self.palette.warning, "Couldn't save settings")
+                        .on_hover_text("Patina will try again the next time a setting changes");
+                }
+                if let Some(state) = self.state.as_ref().filter(|state| state.is_read_only()) {
+                    ui.colored_label(
+                        self.palette.warning,
+                        format!(
+                            "Read-only project: {} is not writable. Sending, renaming, and deleting are disabled.",
+                            state.project().paths().internal.display()
+                        ),
+                    );
                 }
                 if let LlmStatus::Unconfigured(message) = &llm_status {
                     ui.add_space(4.0);
@@ -302,8 +818,24 @@ impl PatinaEguiApp {
                 }
             });
 
-        if let Some(state) = self.state.as_ref() {
+        if let Some(state) = self.state.clone() {
             let active_conversation = state.active_conversation();
+            let read_only = state.is_read_only();
+            let chat_defaults = self.chat_defaults();
+            self.sidebar_state.read_only = read_only;
+            self.input_state.read_only = read_only;
+            self.input_state.busy = self.stream_rx.is_some();
+            self.input_state.locked = active_conversation
+                .as_ref()
+                .filter(|conversation| conversation.settings.locked)
+                .map(|conversation| conversation.settings.resolve(&chat_defaults));
+            let effective_model = active_conversation
+                .as_ref()
+                .map(|conversation| conversation.settings.resolve(&chat_defaults).model)
+                .unwrap_or_else(|| chat_defaults.model.clone());
+            if self.token_counter.model() != effective_model {
+                self.token_counter = TokenCounter::for_model(effective_model);
+            }
 
             if self.sidebar_state.collapsed {
                 egui::SidePanel::left("sidebar_collapsed")
@@ -329,6 +861,8 @@ impl PatinaEguiApp {
                 let response = egui::SidePanel::left("sidebar")
                     .resizable(true)
                     .min_width(220.0)
+                    // Keep room for the chat when zoomed in on a small window.
+                    .max_width((ctx.screen_rect().width() * 0.5).max(220.0))
                     .default_width(self.ui_settings.sidebar_width)
                     .frame(
                         egui::Frame::none()
@@ -356,35 +890,44 @@ impl PatinaEguiApp {
                             &pinned_order,
                             &mut self.mcp_entries,
                             active_id,
+                            &self.provider_config.pricing,
+                            &self.ui_settings.model,
                         );
-                        self.handle_sidebar_output(sidebar_output);
+                        self.handle_sidebar_output(ui.ctx(), sidebar_output);
                     });
 
                 let width = response.response.rect.width();
                 if (self.ui_settings.sidebar_width - width).abs() > 1.0 {
                     self.ui_settings.sidebar_width = width;
-                    self.spawn_save();
+                    self.layout_save_due = Some(Instant::now() + LAYOUT_SAVE_DELAY);
                 }
             }
 
+            let previewing = active_conversation.as_ref().is_some_and(|conversation| {
+                self.chat_panel_state.preview
+                    && self.chat_panel_state.last_conversation_id == Some(conversation.id)
+            });
             egui::TopBottomPanel::bottom("chat_input")
                 .frame(
                     egui::Frame::none()
                         .fill(self.palette.surface)
                         .inner_margin(Margin::same(12.0)),
                 )
-                .show(ctx, |ui| {
+                .show_animated(ctx, !previewing, |ui| {
                     let model_valid = matches!(self.model_validation(), ModelValidation::Ready);
                     let input_output = InputBar::show(
                         ui,
                         &mut self.input_state,
                         &self.palette,
-                        &self.provider_config.available_models,
+                        &self.provider_config,
                         model_valid,
+                        &self.token_counter,
                     );
                     self.handle_input_output(input_output);
                     self.input_state.selected_model = self.ui_settings.model.clone();
                     self.input_state.temperature = self.ui_settings.temperature;
+                    self.input_state.max_request_messages = self.ui_settings.max_request_messages;
+                    self.input_state.max_tokens = self.ui_settings.max_tokens;
                     self.input_state.retain_input = self.ui_settings.retain_input;
                 });
 
@@ -407,11 +950,41 @@ impl PatinaEguiApp {
                             conversation,
                             streaming,
                             &mut self.markdown_cache,
+                            &mut self.code_highlighter,
+                            &self.token_counter,
+                            &chat_defaults,
+                            &self.provider_config.available_models,
+                            &self.provider_config.pricing,
+                            read_only,
+                            streaming.is_some() || state.is_generating(conversation.id),
                         );
                         if chat_output.load_older {
                             self.chat_panel_state
                                 .request_more(conversation.messages.len());
                         }
+                        if let Some((id, settings)) = chat_output.settings_changed {
+                            if let Err(err) = state.set_conversation_settings(id, settings) {
+                                self.error = Some(err.to_string());
+                            }
+                        }
+                        if let Some((id, prompt)) = chat_output.system_prompt_changed {
+                            if let Err(err) = state.set_system_prompt(id, &prompt) {
+                                self.error = Some(err.to_string());
+                            }
+                        }
+                        if let Some(message_id) = chat_output.regenerate {
+                            self.regenerate_reply(state.clone(), conversation, message_id);
+                        }
+                        if let Some((message_id, content)) = chat_output.edit_message {
+                            self.resend_edited(state.clone(), conversation, message_id, content);
+                        }
+                        if let Some((message_id, index)) = chat_output.select_alternative {
+                            if let Err(err) =
+                                state.select_alternative(conversation.id, message_id, index)
+                            {
+                                self.error = Some(err.to_string());
+                            }
+                        }
                     } else {
                         ui.centered_and_justified(|ui| {
                             ui.label("Start a conversation to see the transcript here.");
@@ -434,15 +1007,87 @@ impl PatinaEguiApp {
     }
 
     fn handle_menu_output(&mut self, output: MenuBarOutput) {
+        if let Some(level) = output.log_level_changed {
+            if let Err(err) = telemetry::set_log_level(level) {
+                self.error = Some(format!("Failed to change the log level: {err}"));
+            }
+        }
+        if let Some(profile) = output.profile_changed {
+            match self.settings_panel.set_active_profile(profile.as_deref()) {
+                Ok(()) => self.reload_provider_config(),
+                Err(err) => {
+                    error!(error = ?err, "Failed to switch provider profile");
+                    self.error = Some(format!("Failed to switch profile: {err}"));
+                }
+            }
+        }
         if output.new_project {
             self.prompt_new_project();
         }
         if output.open_project {
             self.prompt_open_project();
         }
+        if output.save_project_as {
+            self.prompt_save_project_as();
+        }
+        if output.import_conversation {
+            self.prompt_import_conversation_json();
+        }
         if output.new_chat {
             self.create_new_chat();
         }
+        if output.save_now {
+            self.save_now();
+        }
+        if let Some(delay) = output.autosave_delay_changed {
+            self.ui_settings.autosave_delay_ms = delay;
+            self.mark_dirty();
+        }
+        if let Some(scale) = output.ui_scale_changed {
+            self.set_ui_scale(scale);
+        }
+        if let Some(flush_ms) = output.stream_flush_changed {
+            self.ui_settings.stream_flush_ms = flush_ms;
+            self.stream_buffer
+                .set_window(Duration::from_millis(flush_ms));
+            self.mark_dirty();
+        }
+        if let Some(enabled) = output.remote_images_changed {
+            self.ui_settings.load_remote_images = enabled;
+            self.remote_images.store(enabled, Ordering::Relaxed);
+            self.forget_images = true;
+            self.mark_dirty();
+        }
+        if let Some(enabled) = output.regenerate_on_model_change_changed {
+            self.ui_settings.regenerate_on_model_change = enabled;
+            self.mark_dirty();
+        }
+        if let Some(enabled) = output.mcp_sampling_changed {
+            self.ui_settings.allow_mcp_sampling = enabled;
+            self.mark_dirty();
+        }
+        if let Some(enabled) = output.auto_title_changed {
+            self.ui_settings.auto_title_chats = enabled;
+            if let Some(state) = self.state.as_ref() {
+                state.set_auto_title(enabled);
+            }
+            self.mark_dirty();
+        }
+        if let Some(enabled) = output.group_chats_by_date_changed {
+            self.set_group_chats_by_date(enabled);
+        }
+        if let Some(format) = output.timestamp_format_changed {
+            self.sidebar_state.timestamp_format = format.clone();
+            self.chat_panel_state.timestamp_format = format.clone();
+            self.ui_settings.timestamp_format = format;
+            self.mark_dirty();
+        }
+        if output.prune_empty_chats {
+            self.prune_empty_chats(true);
+        }
+        if output.reset_session_usage {
+            self.menu_state.session_usage.reset();
+        }
         if output.toggle_sidebar {
             self.toggle_sidebar();
         }
@@ -451,6 +1096,8 @@ impl PatinaEguiApp {
         }
         if output.clear_input {
             self.input_state.draft.clear();
+            self.input_state.attachments.clear();
+            self.input_state.images.clear();
         }
         if output.show_about {
             self.about_mode = Some(AboutMode::Manual {
@@ -466,14 +1113,14 @@ impl PatinaEguiApp {
         if let Some(mode) = output.theme_changed {
             self.menu_state.theme_mode = mode;
             self.ui_settings.theme_mode = mode;
-            self.spawn_save();
+            self.mark_dirty();
             if let Err(err) = self.settings_panel.apply_theme_selection(mode) {
                 error!(error = ?err, "Failed to persist theme change");
             }
         }
     }
 
-    fn handle_sidebar_output(&mut self, output: SidebarOutput) {
+    fn handle_sidebar_output(&mut self, ctx: &egui::Context, output: SidebarOutput) {
         let Some(state) = self.state.as_ref().cloned() else {
             return;
         };
@@ -481,6 +1128,12 @@ impl PatinaEguiApp {
             state.select_conversation(id);
             self.update_last_conversation(id);
         }
+        if let Some((conversation_id, message_id)) = output.open_message {
+            state.select_conversation(conversation_id);
+            self.update_last_conversation(conversation_id);
+            self.chat_panel_state
+                .scroll_to_message(conversation_id, message_id);
+        }
         if let Some((id, name)) = output.rename {
             if let Err(err) = state.rename_conversation(id, name.clone()) {
                 self.error = Some(err.to_string());
@@ -488,6 +1141,17 @@ impl PatinaEguiApp {
                 self.error = None;
             }
         }
+        if let Some(id) = output.reset_title {
+            if let Err(err) = state.retitle_from_content(id) {
+                self.error = Some(err.to_string());
+            }
+        }
+        if let Some(id) = output.duplicate {
+            match state.duplicate_conversation(id) {
+                Ok(copy) => self.update_last_conversation(copy),
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        }
         if let Some(id) = output.delete {
             match state.delete_conversation(id) {
                 Ok(true) => {
@@ -496,18 +1160,138 @@ impl PatinaEguiApp {
                         self.update_last_conversation(active.id);
                     } else {
                         self.ui_settings.last_conversation = None;
-                        self.spawn_save();
+                        self.mark_dirty();
                     }
                 }
                 Ok(false) => {}
                 Err(err) => self.error = Some(err.to_string()),
             }
         }
+        if let Some((into, sources)) = output.merge {
+            for from in sources {
+                match state.merge_conversations(into, from) {
+                    Ok(()) => self.unpin_chat(from),
+                    Err(err) => {
+                        self.error = Some(err.to_string());
+                        break;
+                    }
+                }
+            }
+            self.sidebar_state.merge_selection.clear();
+            self.sync_last_conversation();
+        }
         if let Some((dragged, target)) = output.reorder {
-            if let Err(err) = state.reorder_conversations(dragged, target) {
+            if self.pinned_lookup.contains(&dragged) && self.pinned_lookup.contains(&target) {
+                self.reorder_pinned_chat(dragged, target);
+            } else if let Err(err) = state.reorder_conversations(dragged, target) {
                 self.error = Some(err.to_string());
             }
         }
+        if let Some(query) = output.search_changed {
+            if query.trim().is_empty() {
+                self.sidebar_state.message_hits.clear();
+                self.message_search_due = None;
+            } else {
+                self.message_search_due = Some(Instant::now() + MESSAGE_SEARCH_DELAY);
+            }
+            self.remember_search_query(query);
+        }
+        if output.open_mcp_settings {
+            self.settings_panel.open();
+        }
+        if let Some(collapsed) = output.mcp_collapsed_changed {
+            self.ui_settings.mcp_collapsed = collapsed;
+            self.mark_dirty();
+        }
+        if output.reload_mcp {
+            self.error = None;
+            self.load_mcp_servers();
+        }
+        if let Some(endpoint) = output.reconnect_mcp {
+            let state = state.clone();
+            let tx = self.tx.clone();
+            let repaint_ctx = self.repaint_ctx.clone();
+            self.runtime.spawn(async move {
+                if let Err(err) = state.reconnect_mcp_server(&endpoint).await {
+                    let _ = tx.send(Err(
+                        err.context(format!("failed to reconnect MCP server `{endpoint}`"))
+                    ));
+                    wake_ui(&repaint_ctx);
+                }
+            });
+        }
+        if let Some(endpoint) = output.list_mcp_tools {
+            let state = state.clone();
+            let repaint_ctx = self.repaint_ctx.clone();
+            let handle = self.runtime.spawn({
+                let endpoint = endpoint.clone();
+                async move {
+                    let result = state.list_mcp_tools(&endpoint).await;
+                    wake_ui(&repaint_ctx);
+                    result
+                }
+            });
+            self.pending_tool_lists.push((endpoint, handle));
+        }
+        if let Some((endpoint, tool, arguments)) = output.call_mcp_tool {
+            let state = state.clone();
+            let tx = self.tx.clone();
+            let repaint_ctx = self.repaint_ctx.clone();
+            let cancel = CancellationToken::new();
+            // The result belongs to the chat the call was started from, even if the
+            // user switches chats before it finishes.
+            let conversation_id = state.ensure_active_conversation();
+            let handle = self.runtime.spawn({
+                let (endpoint, tool, cancel) = (endpoint.clone(), tool.clone(), cancel.clone());
+                async move {
+                    let result = state
+                        .call_mcp_tool(conversation_id, &endpoint, &tool, arguments, &cancel)
+                        .await
+                        .map(|_| ())
+                        .map_err(|err| err.context(format!("MCP tool `{tool}` failed")));
+                    // Cancelling is the user's own doing, not worth a banner.
+                    if !cancel.is_cancelled() {
+                        let _ = tx.send(result);
+                    }
+                    wake_ui(&repaint_ctx);
+                }
+            });
+            if let Some(entry) = self.mcp_entries.iter_mut().find(|e| e.id == endpoint) {
+                entry.running_tools.push(tool.clone());
+            }
+            self.running_tool_calls.push(RunningToolCall {
+                endpoint,
+                tool,
+                cancel,
+                handle,
+            });
+        }
+        if let Some((endpoint, tool)) = output.cancel_mcp_tool {
+            if let Some(call) = self
+                .running_tool_calls
+                .iter()
+                .find(|call| call.endpoint == endpoint && call.tool == tool)
+            {
+                call.cancel.cancel();
+            }
+        }
+        if let Some(endpoint) = output.close_mcp {
+            let state = state.clone();
+            self.runtime.spawn(async move {
+                if let Err(err) = state.disconnect_mcp_server(&endpoint).await {
+                    warn!(error = ?err, endpoint = %endpoint, "failed to close MCP connection");
+                }
+            });
+        }
+        if let Some(id) = output.copy_json {
+            match state.conversation_json(id) {
+                Ok(json) => ctx.output_mut(|o| o.copied_text = json),
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        }
+        if let Some(id) = output.export_json {
+            self.prompt_export_conversation_json(&state, id);
+        }
         if let Some(id) = output.pin {
             self.pin_chat(id);
         }
@@ -523,34 +1307,103 @@ impl PatinaEguiApp {
                 self.input_state.draft.clear();
             }
         }
+        if output.stop {
+            self.stop_generating();
+        }
         if output.clear {
             self.input_state.draft.clear();
+            self.input_state.attachments.clear();
+            self.input_state.images.clear();
+        }
+        if output.attach_files {
+            self.prompt_attach_files();
+        }
+        #[cfg(feature = "vision-attachments")]
+        if output.paste_image {
+            self.paste_clipboard_image();
         }
         if let Some(model) = output.model_changed {
             self.ui_settings.model = model;
-            self.spawn_save();
+            self.mark_dirty();
+            self.regenerate_after_model_change();
         }
         if let Some(temp) = output.temperature_changed {
             self.ui_settings.temperature = temp;
-            self.spawn_save();
+            self.mark_dirty();
+        }
+        if let Some(limit) = output.history_limit_changed {
+            self.ui_settings.max_request_messages = limit;
+            if let Some(state) = self.state.as_ref() {
+                state.set_max_request_messages(limit);
+            }
+            self.mark_dirty();
+        }
+        if let Some(max_tokens) = output.max_tokens_changed {
+            self.ui_settings.max_tokens = max_tokens;
+            if let Some(state) = self.state.as_ref() {
+                state.set_max_tokens(self.ui_settings.reply_token_limit());
+            }
+            self.mark_dirty();
         }
         if self.ui_settings.retain_input != self.input_state.retain_input {
             self.ui_settings.retain_input = self.input_state.retain_input;
-            self.spawn_save();
+            self.mark_dirty();
+        }
+        if let Some(locked) = output.lock_changed {
+            self.set_parameters_locked(locked);
+        }
+    }
+
+    fn regenerate_after_model_change(&mut self) {
+        let Some(state) = self.state.clone().filter(|_| self.stream_rx.is_none()) else {
+            return;
+        };
+        let Some(conversation) = state.active_conversation() else {
+            return;
+        };
+        if let Some(message_id) =
+            regeneration_on_model_change(&self.ui_settings, Some(&conversation))
+        {
+            self.regenerate_reply(state, &conversation, message_id);
+        }
+    }
+
+    fn set_parameters_locked(&mut self, locked: bool) {
+        let Some(state) = self.state.clone() else {
+            return;
+        };
+        let Some(conversation) = state.active_conversation() else {
+            return;
+        };
+        let result = if locked {
+            state.lock_parameters(conversation.id, &self.chat_defaults())
+        } else {
+            state.unlock_parameters(conversation.id)
+        };
+        if let Err(err) = result {
+            self.error = Some(err.to_string());
         }
     }
 
     fn submit_message(&mut self) {
+        if self.stream_rx.is_some() {
+            return;
+        }
         let content = self.input_state.draft.trim();
-        if content.is_empty() {
+        if content.is_empty()
+            && self.input_state.attachments.is_empty()
+            && self.input_state.images.is_empty()
+        {
             return;
         }
         match self.model_validation() {
             ModelValidation::Ready => {}
             ModelValidation::MissingModels => {
-                self.validation_error = Some(
-                    "No models are configured. Edit Settings to add models in patina.yaml.".into(),
-                );
+                self.validation_error = Some(match &self.provider_config.no_models {
+                    Some(reason) => reason.to_string(),
+                    None => "No models are configured. Edit Settings to add models in patina.yaml."
+                        .into(),
+                });
                 return;
             }
             ModelValidation::InvalidSelection => {
@@ -565,32 +1418,207 @@ impl PatinaEguiApp {
             return;
         };
 
-        let payload = content.to_owned();
-        let model = self.ui_settings.model.clone();
-        let temperature = self.ui_settings.temperature;
+        let payload = with_attachments(content, &self.input_state.attachments);
+        self.input_state.attachments.clear();
+        let images = std::mem::take(&mut self.input_state.images)
+            .into_iter()
+            .map(|image| image.attachment)
+            .collect();
+        let active = state.active_conversation();
+        let ChatParameters {
+            model, temperature, ..
+        } = active
+            .as_ref()
+            .map(|conversation| conversation.settings.resolve(&self.chat_defaults()))
+            .unwrap_or_else(|| self.chat_defaults());
 
         // Get current or start new conversation
-        let conversation_id = state
-            .active_conversation()
+        let conversation_id = active
             .map(|c| c.id)
             .unwrap_or_else(|| state.start_new_conversation());
 
+        self.start_stream(
+            state,
+            conversation_id,
+            ReplyTurn::Send {
+                content: payload,
+                images,
+            },
+            model,
+            temperature,
+        );
+    }
+
+    /// Re-run generation for the unanswered user turn left behind by a failed send.
+    fn retry_failed_send(&mut self, conversation_id: Uuid) {
+        let Some(state) = self.state.as_ref().cloned() else {
+            return;
+        };
+        let ChatParameters {
+            model, temperature, ..
+        } = state
+            .conversation_summaries()
+            .iter()
+            .any(|summary| summary.id == conversation_id)
+            .then(|| {
+                state.select_conversation(conversation_id);
+                state.active_conversation()
+            })
+            .flatten()
+            .map(|conversation| conversation.settings.resolve(&self.chat_defaults()))
+            .unwrap_or_else(|| self.chat_defaults());
+        self.error = None;
+        self.last_failure = None;
+        self.start_stream(state, conversation_id, ReplyTurn::Retry, model, temperature);
+    }
+
+    /// Stream a new reply to an edited user message, dropping everything after it.
+    fn resend_edited(
+        &mut self,
+        state: Arc<AppState>,
+        conversation: &Conversation,
+        message_id: Uuid,
+        content: String,
+    ) {
+        if self.stream_rx.is_some() {
+            return;
+        }
+        let ChatParameters {
+            model, temperature, ..
+        } = conversation.settings.resolve(&self.chat_defaults());
+        self.error = None;
+        self.last_failure = None;
+        self.start_stream(
+            state,
+            conversation.id,
+            ReplyTurn::Edit {
+                message_id,
+                content,
+            },
+            model,
+            temperature,
+        );
+    }
+
+    /// Regenerate an assistant reply in the background, keeping earlier variants.
+    fn regenerate_reply(
+        &mut self,
+        state: Arc<AppState>,
+        conversation: &Conversation,
+        message_id: Uuid,
+    ) {
+        let ChatParameters {
+            model, temperature, ..
+        } = conversation.settings.resolve(&self.chat_defaults());
+        let conversation_id = conversation.id;
+        self.error = None;
+        let tx = self.tx.clone();
+        let repaint_ctx = self.repaint_ctx.clone();
+        self.runtime.spawn(async move {
+            let result = state
+                .add_alternative(conversation_id, message_id, model, temperature)
+                .await;
+            let _ = tx.send(result);
+            wake_ui(&repaint_ctx);
+        });
+    }
+
+    /// Stop the reply streaming in; the text received so far stays in the conversation.
+    fn stop_generating(&mut self) {
+        let (Some(state), Some(streaming)) = (&self.state, &self.streaming_message) else {
+            return;
+        };
+        if !state.stop_generating(streaming.conversation_id) {
+            warn!("no reply to stop");
+        }
+    }
+
+    /// Raise a desktop notification for a reply that finished in the background.
+    fn notify_reply_finished(&self, reply: StreamingMessage) {
+        let title = self
+            .state
+            .as_ref()
+            .and_then(|state| {
+                state
+                    .conversation_summaries()
+                    .into_iter()
+                    .find(|summary| summary.id == reply.conversation_id)
+            })
+            .map(|summary| summary.title)
+            .unwrap_or_else(|| "Reply finished".to_string());
+        self.runtime
+            .spawn_blocking(move || notify::reply_finished(&title, &reply.content));
+    }
+
+    /// Stream a reply to `turn` into the UI.
+    fn start_stream(
+        &mut self,
+        state: Arc<AppState>,
+        conversation_id: Uuid,
+        turn: ReplyTurn,
+        model: String,
+        temperature: f32,
+    ) {
         let (stream_tx, stream_rx) = unbounded_channel();
         self.stream_rx = Some(stream_rx);
+        self.stream_buffer.flush();
 
         let tx = self.tx.clone();
+        let repaint_ctx = self.repaint_ctx.clone();
+        let window = self.stream_buffer.window();
         self.runtime.spawn(async move {
-            match state
-                .send_user_message_streaming(payload, model, temperature)
-                .await
-            {
+            let started = match turn {
+                ReplyTurn::Send { content, images } => {
+                    state
+                        .send_user_message_with_images_streaming(
+                            content,
+                            images,
+                            model,
+                            temperature,
+                        )
+                        .await
+                }
+                ReplyTurn::Retry => {
+                    state
+                        .retry_last_turn_streaming(conversation_id, model, temperature)
+                        .await
+                }
+                ReplyTurn::Edit {
+                    message_id,
+                    content,
+                } => {
+                    state
+                        .edit_message_and_truncate(
+                            conversation_id,
+                            message_id,
+                            content,
+                            model,
+                            temperature,
+                        )
+                        .await
+                }
+            };
+            match started {
                 Ok((_message_id, mut llm_stream)) => {
-                    // Forward stream chunks from LLM to UI
+                    // Forward stream chunks from LLM to UI, waking it at most once per
+                    // coalescing window; the last chunk and errors wake it right away.
+                    let mut window_started: Option<Instant> = None;
                     while let Some(chunk) = llm_stream.recv().await {
+                        let last = !matches!(&chunk, Ok(chunk) if !chunk.done);
                         if stream_tx.send(chunk).is_err() {
                             warn!("UI dropped stream receiver");
                             break;
                         }
+                        let now = Instant::now();
+                        match window_started {
+                            Some(started) if !last && now - started < window => {
+                                wake_ui_after(&repaint_ctx, window - (now - started));
+                            }
+                            _ => {
+                                window_started = Some(now);
+                                wake_ui(&repaint_ctx);
+                            }
+                        }
                     }
                     let _ = tx.send(Ok(()));
                 }
@@ -598,6 +1626,7 @@ impl PatinaEguiApp {
                     let _ = tx.send(Err(err));
                 }
             }
+            wake_ui(&repaint_ctx);
         });
 
         // Initialize streaming message for UI display
@@ -608,10 +1637,98 @@ impl PatinaEguiApp {
         });
     }
 
+    fn error_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(err) = self.error.clone() else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.colored_label(self.palette.warning, err);
+            match self.last_failure {
+                Some(FailedAction::Send { conversation_id }) => {
+                    let idle = self.stream_rx.is_none();
+                    if ui.add_enabled(idle, egui::Button::new("Retry")).clicked() {
+                        self.retry_failed_send(conversation_id);
+                    }
+                }
+                Some(FailedAction::Configuration) if ui.button("Open settings").clicked() => {
+                    self.settings_panel.open();
+                }
+                _ => {}
+            }
+            if ui
+                .add(egui::Button::new("✕").frame(false))
+                .on_hover_text("Dismiss")
+                .clicked()
+            {
+                self.error = None;
+                self.last_failure = None;
+            }
+        });
+    }
+
+    /// Global chat parameters that conversations inherit unless they override them.
+    fn chat_defaults(&self) -> ChatParameters {
+        ChatParameters {
+            model: self.ui_settings.model.clone(),
+            temperature: self.ui_settings.temperature,
+            max_request_messages: self.ui_settings.max_request_messages,
+        }
+    }
+
+    /// Attach the image on the clipboard to the next message; without one, the
+    /// shortcut's text paste, if any, is left to the message box.
+    #[cfg(feature = "vision-attachments")]
+    fn paste_clipboard_image(&mut self) {
+        match crate::images::clipboard_image() {
+            Ok(Some(image)) => self.input_state.images.push(image),
+            Ok(None) => {}
+            Err(err) => {
+                warn!(error = %err, "Clipboard image not attached");
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn prompt_attach_files(&mut self) {
+        let Some(paths) = FileDialog::new()
+            .set_title("Attach text files")
+            .pick_files()
+        else {
+            return;
+        };
+        for path in paths {
+            match TextAttachment::from_path(&path) {
+                Ok(attachment) => {
+                    if attachment.truncated {
+                        warn!(file = %attachment.name, "Attachment truncated");
+                        self.error = Some(format!(
+                            "{} is too large and was truncated.",
+                            attachment.name
+                        ));
+                    }
+                    let attached = &mut self.input_state.attachments;
+                    if !attachment.fits_with(attached) {
+                        self.error = Some(format!(
+                            "{} was not attached: a message can carry at most {} KB of files.",
+                            attachment.name,
+                            MAX_TOTAL_ATTACHMENT_BYTES / 1024
+                        ));
+                        continue;
+                    }
+                    attached.push(attachment);
+                }
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        }
+    }
+
     fn create_new_chat(&mut self) {
-        if let Some(state) = self.state.as_ref() {
+        if let Some(state) = self.state.as_ref().filter(|state| !state.is_read_only()) {
             let id = state.start_new_conversation();
             self.update_last_conversation(id);
+            if !self.settings_panel.is_open() {
+                self.input_state.request_focus();
+            }
         }
     }
 
@@ -623,13 +1740,13 @@ impl PatinaEguiApp {
     fn set_sidebar_visibility(&mut self, visible: bool) {
         if self.ui_settings.sidebar_visible != visible {
             self.ui_settings.sidebar_visible = visible;
-            self.spawn_save();
+            self.mark_dirty();
         }
     }
 
     fn update_last_conversation(&mut self, id: Uuid) {
         self.ui_settings.last_conversation = Some(id);
-        self.spawn_save();
+        self.mark_dirty();
     }
 
     fn pin_chat(&mut self, id: Uuid) {
@@ -637,7 +1754,7 @@ impl PatinaEguiApp {
             let list = &mut self.ui_settings.pinned_chats;
             list.insert(0, id);
             self.refresh_pinned_cache();
-            self.spawn_save();
+            self.mark_dirty();
         }
     }
 
@@ -646,7 +1763,23 @@ impl PatinaEguiApp {
             let list = &mut self.ui_settings.pinned_chats;
             list.retain(|candidate| candidate != &id);
             self.refresh_pinned_cache();
-            self.spawn_save();
+            self.mark_dirty();
+        }
+    }
+
+    /// Move a pinned chat to where another pinned chat is listed. Its place among the
+    /// unpinned chats, where it returns when unpinned, stays as it was.
+    fn reorder_pinned_chat(&mut self, dragged: Uuid, target: Uuid) {
+        let list = &mut self.ui_settings.pinned_chats;
+        let from = list.iter().position(|id| *id == dragged);
+        let to = list.iter().position(|id| *id == target);
+        if let (Some(from), Some(mut to)) = (from, to) {
+            if from < to {
+                to -= 1;
+            }
+            let id = list.remove(from);
+            list.insert(to, id);
+            self.mark_dirty();
         }
     }
 
@@ -654,6 +1787,29 @@ impl PatinaEguiApp {
         self.pinned_lookup = self.ui_settings.pinned_chats.iter().copied().collect();
     }
 
+    fn show_notice(&mut self, message: impl Into<String>, duration: Duration) {
+        self.notice = Some((message.into(), Instant::now() + duration));
+    }
+
+    /// Switch to the first available model when the saved one is not offered by the
+    /// current provider config, and say so; with no models there is nothing to pick.
+    fn reconcile_selected_model(&mut self) {
+        if self.model_validation() != ModelValidation::InvalidSelection {
+            return;
+        }
+        let Some(first) = self.provider_config.available_models.first().cloned() else {
+            return;
+        };
+        let previous = std::mem::replace(&mut self.ui_settings.model, first.clone());
+        let message = if previous.trim().is_empty() {
+            format!("Selected model {first}")
+        } else {
+            format!("Model {previous} is not available here; switched to {first}")
+        };
+        self.show_notice(message, MODEL_NOTICE_DURATION);
+        self.mark_dirty();
+    }
+
     fn model_validation(&self) -> ModelValidation {
         if self.provider_config.available_models.is_empty() {
             return ModelValidation::MissingModels;
@@ -674,60 +1830,265 @@ impl PatinaEguiApp {
         }
     }
 
-    fn spawn_save(&mut self) {
-        let scope = self.scope.clone();
-        let settings = self.ui_settings.clone();
-        if let Some(handle) = self.pending_save.take() {
-            handle.abort();
+    /// Note that the UI settings changed. Changes are written together once the auto-save
+    /// delay has passed since the first unsaved one, so a burst of edits costs one write.
+    fn mark_dirty(&mut self) {
+        if self.settings_save_due.is_none() {
+            let delay = Duration::from_millis(self.ui_settings.autosave_delay_ms);
+            self.settings_save_due = Some(Instant::now() + delay);
         }
-        let runtime = self.runtime.clone();
-        self.pending_save = Some(runtime.spawn(async move {
-            if let Err(err) = config::save_ui_settings(&scope, &settings).await {
-                error!(error = ?err, "Failed to save UI settings");
-            }
-        }));
     }
 
-    fn persist_now(&mut self) {
-        if let Some(handle) = self.pending_save.take() {
-            handle.abort();
+    fn run_due_settings_save(&mut self) {
+        match self.settings_save_due {
+            Some(due) if Instant::now() >= due => self.settings_save_due = None,
+            _ => return,
         }
+        self.queue_settings_write(None);
+    }
+
+    /// Hand the current settings to the writer task; `false` if it is gone.
+    fn queue_settings_write(&self, done: Option<oneshot::Sender<()>>) -> bool {
+        let write = SettingsWrite {
+            scope: self.scope.clone(),
+            settings: self.ui_settings.clone(),
+            repaint_ctx: self.repaint_ctx.clone(),
+            done,
+        };
+        self.settings_tx.send(write).is_ok()
+    }
+
+    /// Write the UI settings immediately, bypassing the auto-save delay.
+    ///
+    /// The write goes through the writer task, after any snapshot it still holds, and
+    /// this waits for it to finish.
+    pub fn persist_now(&mut self) {
+        self.settings_save_due = None;
+        let (done, written) = oneshot::channel();
+        if self.queue_settings_write(Some(done)) && self.runtime.block_on(written).is_ok() {
+            return;
+        }
+        // The writer task is gone, which only happens while the runtime shuts down.
         let scope = self.scope.clone();
         let settings = self.ui_settings.clone();
-        if let Err(err) = self
+        let result = self
             .runtime
-            .block_on(config::save_ui_settings(&scope, &settings))
-        {
+            .block_on(config::save_ui_settings_with_retry(&scope, &settings));
+        if let Err(err) = &result {
             error!(error = ?err, "Failed to save UI settings");
         }
+        self.last_save_failed
+            .store(result.is_err(), Ordering::Relaxed);
+    }
+
+    /// Whether the most recent settings write failed even after retrying.
+    pub fn last_save_failed(&self) -> bool {
+        self.last_save_failed.load(Ordering::Relaxed)
+    }
+
+    /// Flush settings on request and confirm it. Transcripts are appended as messages
+    /// arrive, so there is nothing buffered to flush for them.
+    fn save_now(&mut self) {
+        self.persist_now();
+        if !self.last_save_failed() {
+            self.show_notice("All changes saved", NOTICE_DURATION);
+        }
+    }
+
+    /// Remove never-used chats from the open project, optionally confirming the count.
+    fn prune_empty_chats(&mut self, announce: bool) {
+        let Some(state) = self.state.as_ref().filter(|state| !state.is_read_only()) else {
+            return;
+        };
+        match state.prune_empty_conversations() {
+            Ok(removed) if announce => {
+                let message = match removed {
+                    0 => "No empty chats to remove".to_string(),
+                    1 => "Removed 1 empty chat".to_string(),
+                    n => format!("Removed {n} empty chats"),
+                };
+                self.show_notice(message, NOTICE_DURATION);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                error!(error = ?err, "Failed to prune empty conversations");
+                if announce {
+                    self.error = Some(err.to_string());
+                }
+            }
+        }
     }
 
+    /// Re-read patina.yaml and rebuild the LLM driver from it. A file that does not
+    /// parse keeps the current model list and driver.
     fn reload_provider_config(&mut self) {
         let scope = self.scope.clone();
         if let Some(handle) = self.pending_provider_reload.take() {
             handle.abort();
         }
         let runtime = self.runtime.clone();
-        self.pending_provider_reload =
-            Some(runtime.spawn(async move { config::load_provider_config(&scope).await }));
+        let repaint_ctx = self.repaint_ctx.clone();
+        self.pending_provider_reload = Some(runtime.spawn(async move {
+            let result = match config::read_provider_config(&scope).await {
+                Ok(config) => Ok((config, config::llm_driver(&scope).await)),
+                Err(err) => Err(err),
+            };
+            wake_ui(&repaint_ctx);
+            result
+        }));
+    }
+
+    fn watch_provider_config(&mut self, ctx: &egui::Context) {
+        let ctx = ctx.clone();
+        match ProviderConfigWatcher::new(&self.scope, move || ctx.request_repaint()) {
+            Ok(watcher) => self.provider_watcher = Some(watcher),
+            Err(err) => warn!(error = ?err, "not watching patina.yaml for changes"),
+        }
+    }
+
+    fn run_due_provider_reload(&mut self) {
+        if self
+            .provider_watcher
+            .as_ref()
+            .is_some_and(ProviderConfigWatcher::take_changed)
+        {
+            self.provider_reload_due = Some(Instant::now() + PROVIDER_RELOAD_DELAY);
+        }
+        match self.provider_reload_due {
+            Some(due) if Instant::now() >= due => self.provider_reload_due = None,
+            _ => return,
+        }
+        self.reload_provider_config();
+    }
+
+    fn fetch_models(&mut self, request: ModelFetchRequest) {
+        if let Some((_, handle)) = self.pending_model_fetch.take() {
+            handle.abort();
+        }
+        let repaint_ctx = self.repaint_ctx.clone();
+        let handle = self.runtime.spawn(async move {
+            let result = match LlmDriver::from_settings(request.settings).await {
+                Ok(driver) => driver.list_models().await,
+                Err(err) => Err(err),
+            };
+            wake_ui(&repaint_ctx);
+            result
+        });
+        self.pending_model_fetch = Some((request.form, handle));
+    }
+
+    fn poll_model_fetch(&mut self) {
+        if let Some((form, handle)) = self.pending_model_fetch.take() {
+            if handle.is_finished() {
+                let result = self
+                    .runtime
+                    .block_on(handle)
+                    .unwrap_or_else(|err| Err(anyhow!("model listing task failed: {err}")));
+                self.settings_panel.finish_model_fetch(form, result);
+            } else {
+                self.pending_model_fetch = Some((form, handle));
+            }
+        }
+    }
+
+    fn test_connection(&mut self, request: ConnectionTestRequest) {
+        if let Some((_, handle)) = self.pending_connection_test.take() {
+            handle.abort();
+        }
+        let repaint_ctx = self.repaint_ctx.clone();
+        let handle = self.runtime.spawn(async move {
+            let result = match LlmDriver::from_settings(request.settings).await {
+                Ok(driver) => driver.check_connection().await,
+                Err(err) => Err(err),
+            };
+            wake_ui(&repaint_ctx);
+            result
+        });
+        self.pending_connection_test = Some((request.form, handle));
+    }
+
+    fn poll_connection_test(&mut self) {
+        if let Some((form, handle)) = self.pending_connection_test.take() {
+            if handle.is_finished() {
+                let result = self
+                    .runtime
+                    .block_on(handle)
+                    .unwrap_or_else(|err| Err(anyhow!("connection test task failed: {err}")));
+                self.settings_panel.finish_connection_test(form, result);
+            } else {
+                self.pending_connection_test = Some((form, handle));
+            }
+        }
     }
 
     fn activate_project(&mut self, project: ProjectHandle) {
+        self.prune_empty_chats(false);
         self.settings_panel.set_project(Some(&project));
         let last_selected = self.ui_settings.last_conversation;
-        let state = Arc::new(AppState::new(project.clone(), self.driver.clone()));
+        if let Some(previous) = self.state.take() {
+            self.runtime
+                .spawn(async move { previous.disconnect_mcp_servers().await });
+        }
+        let state = {
+            // Entered so the state starts its MCP token refresh on the runtime.
+            let _runtime = self.runtime.enter();
+            Arc::new(
+                AppState::new(project.clone(), self.driver.clone()).with_background_refresh(true),
+            )
+        };
+        state.set_max_request_messages(self.ui_settings.max_request_messages);
+        state.set_max_tokens(self.ui_settings.reply_token_limit());
+        state.set_top_p(self.ui_settings.top_p);
+        state.set_auto_title(self.ui_settings.auto_title_chats);
         if let Some(last) = last_selected {
             state.select_conversation(last);
         }
         self.state = Some(state);
         self.error = None;
         self.remember_project(&project);
+        self.sidebar_state.mcp_root = Some(project.paths().root.clone());
+        self.mcp_rx = None;
+        self.mcp_sampling = None;
+        self.load_mcp_servers();
+        self.sidebar_state.search_query = self
+            .ui_settings
+            .current_project
+            .as_ref()
+            .and_then(|root| self.ui_settings.search_queries.get(root))
+            .cloned()
+            .unwrap_or_default();
+        self.sidebar_state.message_hits.clear();
+        self.message_search_due = Some(Instant::now());
         self.refresh_pinned_cache();
         self.pending_title = Some(format!("Patina — {}", project.name()));
         self.current_workspace = Some(project.name().to_string());
         self.sync_last_conversation();
     }
 
+    fn run_due_message_search(&mut self) {
+        match self.message_search_due {
+            Some(due) if Instant::now() >= due => self.message_search_due = None,
+            _ => return,
+        }
+        self.sidebar_state.message_hits = self
+            .state
+            .as_ref()
+            .map(|state| state.search_messages(&self.sidebar_state.search_query))
+            .unwrap_or_default();
+    }
+
+    fn remember_search_query(&mut self, query: String) {
+        let Some(root) = self.ui_settings.current_project.clone() else {
+            return;
+        };
+        if query.trim().is_empty() {
+            self.ui_settings.search_queries.remove(&root);
+        } else {
+            self.ui_settings.search_queries.insert(root, query);
+        }
+        self.mark_dirty();
+    }
+
     fn remember_project(&mut self, project: &ProjectHandle) {
         let root = project.paths().root.to_string_lossy().to_string();
         self.ui_settings.current_project = Some(root.clone());
@@ -738,7 +2099,7 @@ impl PatinaEguiApp {
         if self.ui_settings.recent_projects.len() > 10 {
             self.ui_settings.recent_projects.truncate(10);
         }
-        self.spawn_save();
+        self.mark_dirty();
     }
 
     fn sync_last_conversation(&mut self) {
@@ -747,7 +2108,7 @@ impl PatinaEguiApp {
             .as_ref()
             .and_then(|state| state.active_conversation().map(|c| c.id));
         self.ui_settings.last_conversation = active;
-        self.spawn_save();
+        self.mark_dirty();
     }
 
     fn prompt_new_project(&mut self) {
@@ -785,18 +2146,72 @@ impl PatinaEguiApp {
         }
     }
 
-    fn create_project_from_path(&self, path: &Path) -> Result<ProjectHandle> {
-        let name = if path.extension().and_then(|ext| ext.to_str()) == Some("pat") {
-            path.file_stem()
-                .and_then(|stem| stem.to_str())
-                .ok_or_else(|| anyhow!("project file must have a valid name"))?
-                .to_string()
-        } else {
-            path.file_name()
-                .and_then(|stem| stem.to_str())
-                .ok_or_else(|| anyhow!("project path must have a valid name"))?
-                .to_string()
+    fn prompt_save_project_as(&mut self) {
+        let Some(current) = self.state.as_ref().map(|state| state.project().clone()) else {
+            return;
+        };
+        let mut dialog = FileDialog::new()
+            .set_title("Save Patina Project As")
+            .add_filter("Patina Project", &["pat"])
+            .set_file_name(format!("{} copy.pat", current.name()));
+        if let Some(parent) = current.paths().root.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        if let Some(path) = dialog.save_file() {
+            let result =
+                project_name_from_path(&path).and_then(|name| current.save_as(&path, &name, false));
+            match result {
+                Ok(project) => self.activate_project(project),
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        }
+    }
+
+    fn prompt_export_conversation_json(&mut self, state: &AppState, id: Uuid) {
+        let title = state
+            .conversation_summaries()
+            .into_iter()
+            .find(|summary| summary.id == id)
+            .map(|summary| summary.title)
+            .unwrap_or_else(|| "conversation".to_string());
+        let Some(path) = FileDialog::new()
+            .set_title("Export conversation as JSON")
+            .add_filter("JSON", &["json"])
+            .set_file_name(format!("{}.json", title.replace(['/', '\\'], "-")))
+            .save_file()
+        else {
+            return;
+        };
+        let result = state
+            .conversation_json(id)
+            .and_then(|json| Ok(fs::write(&path, json)?));
+        if let Err(err) = result {
+            self.error = Some(format!("Failed to export conversation: {err}"));
+        }
+    }
+
+    fn prompt_import_conversation_json(&mut self) {
+        let Some(state) = self.state.as_ref().cloned() else {
+            return;
+        };
+        let Some(path) = FileDialog::new()
+            .set_title("Import conversation")
+            .add_filter("JSON", &["json"])
+            .pick_file()
+        else {
+            return;
         };
+        let result = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| state.import_conversation_json(&json));
+        match result {
+            Ok(id) => self.update_last_conversation(id),
+            Err(err) => self.error = Some(format!("Failed to import conversation: {err}")),
+        }
+    }
+
+    fn create_project_from_path(&self, path: &Path) -> Result<ProjectHandle> {
+        let name = project_name_from_path(path)?;
         ProjectHandle::create(path, &name)
     }
 
@@ -810,29 +2225,68 @@ impl PatinaEguiApp {
 
     fn capture_window_size(&mut self, ctx: &egui::Context) {
         if let Some(rect) = ctx.input(|input| input.viewport().inner_rect) {
-            let size = rect.size();
+            // The window is restored before any zoom applies, so store it unzoomed.
+            let size = rect.size() * ctx.zoom_factor();
             let stored = self.ui_settings.window_size;
             if (stored[0] - size.x).abs() > 1.0 || (stored[1] - size.y).abs() > 1.0 {
                 self.ui_settings.window_size = [size.x, size.y];
-                self.spawn_save();
+                self.layout_save_due = Some(Instant::now() + LAYOUT_SAVE_DELAY);
             }
         }
     }
 
+    /// Save a resized sidebar or window once the drag ends, or once the size has held
+    /// still for [`LAYOUT_SAVE_DELAY`] when no pointer is down.
+    fn run_due_layout_save(&mut self, ctx: &egui::Context) {
+        let Some(due) = self.layout_save_due else {
+            return;
+        };
+        let (pointer_down, released) =
+            ctx.input(|input| (input.pointer.any_down(), input.pointer.any_released()));
+        let now = Instant::now();
+        if pointer_down {
+            // Check back later rather than repainting continuously while a drag holds still.
+            self.layout_save_due = Some(due.max(now + LAYOUT_SAVE_DELAY));
+            return;
+        }
+        if !released && now < due {
+            return;
+        }
+        self.layout_save_due = None;
+        self.mark_dirty();
+    }
+
     fn render(&mut self, ctx: &egui::Context) {
+        if self.repaint_ctx.is_none() {
+            self.repaint_ctx = Some(ctx.clone());
+            self.watch_provider_config(ctx);
+        }
         self.apply_theme(ctx);
+        self.apply_scale(ctx);
+        self.process_ipc_commands();
         self.process_background_results();
-        self.process_stream_chunks();
+        self.process_stream_chunks(ctx);
+        self.collect_session_usage();
+        self.process_mcp_events();
+        self.sync_mcp_sampling();
+        self.run_due_message_search();
+        self.run_due_provider_reload();
         self.poll_provider_config_reload();
+        self.poll_model_fetch();
+        self.poll_connection_test();
         if !matches!(self.about_mode, Some(AboutMode::Manual { .. })) {
             self.handle_shortcuts(ctx);
         }
         self.ensure_logo_texture(ctx);
+        self.ensure_image_loaders(ctx);
         self.layout(ctx);
+        self.show_command_palette(ctx);
         self.show_settings_panel(ctx);
         self.draw_about_dialog(ctx);
         self.show_validation_modal(ctx);
         self.capture_window_size(ctx);
+        self.run_due_layout_save(ctx);
+        self.run_due_settings_save();
         if let Some(title) = self.pending_title.take() {
             ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
         }
@@ -951,6 +2405,14 @@ impl PatinaEguiApp {
         }
     }
 
+    /// Apply the sidebar grouping from the View menu, the palette or the Settings window.
+    fn set_group_chats_by_date(&mut self, enabled: bool) {
+        self.ui_settings.group_chats_by_date = enabled;
+        self.sidebar_state.group_by_date = enabled;
+        self.settings_panel.set_group_chats_by_date(enabled);
+        self.mark_dirty();
+    }
+
     fn show_settings_panel(&mut self, ctx: &egui::Context) {
         let response = self.settings_panel.show(ctx, &self.palette);
         if response.app_saved {
@@ -960,53 +2422,179 @@ impl PatinaEguiApp {
                     self.menu_state.theme_mode = theme;
                     if self.ui_settings.theme_mode != theme {
                         self.ui_settings.theme_mode = theme;
-                        self.spawn_save();
+                        self.mark_dirty();
                     }
                     self.apply_theme(ctx);
                 }
             }
         }
+        if let Some(enabled) = response.group_chats_by_date_changed {
+            self.menu_state.group_chats_by_date = enabled;
+            self.set_group_chats_by_date(enabled);
+        }
         if response.project_saved {
             // Placeholder for future integration (e.g., reload drivers)
         }
+        if let Some(request) = response.fetch_models {
+            self.fetch_models(request);
+        }
+        if let Some(request) = response.test_connection {
+            self.test_connection(request);
+        }
+    }
+}
+
+impl PatinaEguiApp {
+    /// When the UI needs to repaint without new input, if at all.
+    fn next_repaint(&self) -> Option<Duration> {
+        let now = Instant::now();
+        if self.pending_provider_reload.is_some()
+            || self.pending_model_fetch.is_some()
+            || self.pending_connection_test.is_some()
+            || self
+                .state
+                .as_ref()
+                .is_some_and(|state| state.is_generating_title())
+            || !self.pending_tool_lists.is_empty()
+            || !self.running_tool_calls.is_empty()
+        {
+            return Some(ACTIVE_REPAINT_INTERVAL);
+        }
+        // While streaming, the forwarding task wakes the UI for new chunks; buffered
+        // text only needs a frame once its coalescing window is over.
+        let stream_flush = self
+            .stream_rx
+            .as_ref()
+            .and_then(|_| self.stream_buffer.next_flush(now))
+            .map(|wait| now + wait);
+        if let Some(due) = [
+            self.message_search_due,
+            self.provider_reload_due,
+            self.layout_save_due,
+            self.settings_save_due,
+            stream_flush,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        {
+            return Some(due.saturating_duration_since(now));
+        }
+        match &self.about_mode {
+            Some(AboutMode::Splash { opened }) => Some(
+                SPLASH_DURATION
+                    .saturating_sub(opened.elapsed())
+                    .max(ACTIVE_REPAINT_INTERVAL),
+            ),
+            _ if self.ui_settings.timestamp_format == TimestampFormat::Relative => {
+                Some(RELATIVE_TIME_REFRESH)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The reply to regenerate after the global model changes, if the option is enabled.
+///
+/// Only a conversation ending in a completed reply qualifies; conversations that pin
+/// their own model (including locked ones) are unaffected by the global model.
+pub fn regeneration_on_model_change(
+    settings: &UiSettings,
+    conversation: Option<&Conversation>,
+) -> Option<Uuid> {
+    if !settings.regenerate_on_model_change {
+        return None;
+    }
+    let conversation = conversation.filter(|conversation| conversation.settings.model.is_none())?;
+    conversation.last_completed_reply().map(|reply| reply.id)
+}
+
+/// Wake the UI from a background task so its result is picked up without polling.
+fn wake_ui(ctx: &Option<egui::Context>) {
+    if let Some(ctx) = ctx {
+        ctx.request_repaint();
+    }
+}
+
+/// [`wake_ui`] once `delay` has passed; requests made meanwhile share one repaint.
+fn wake_ui_after(ctx: &Option<egui::Context>, delay: Duration) {
+    if let Some(ctx) = ctx {
+        ctx.request_repaint_after(delay);
+    }
+}
+
+/// A UI settings snapshot for [`write_settings`].
+struct SettingsWrite {
+    scope: Scope,
+    settings: UiSettings,
+    repaint_ctx: Option<egui::Context>,
+    /// Told once this snapshot, or a newer one, has been written.
+    done: Option<oneshot::Sender<()>>,
+}
+
+/// Write settings snapshots in order for as long as the app runs. Snapshots queued
+/// while a write is running are coalesced, so only the newest is written.
+async fn write_settings(
+    mut rx: UnboundedReceiver<SettingsWrite>,
+    last_save_failed: Arc<AtomicBool>,
+) {
+    while let Some(mut write) = rx.recv().await {
+        let mut waiting: Vec<_> = write.done.take().into_iter().collect();
+        while let Ok(mut newer) = rx.try_recv() {
+            waiting.extend(newer.done.take());
+            write = newer;
+        }
+        let result = config::save_ui_settings_with_retry(&write.scope, &write.settings).await;
+        if let Err(err) = &result {
+            error!(error = ?err, "Failed to save UI settings");
+        }
+        if last_save_failed.swap(result.is_err(), Ordering::Relaxed) != result.is_err() {
+            wake_ui(&write.repaint_ctx);
+        }
+        for done in waiting {
+            let _ = done.send(());
+        }
     }
 }
 
 impl eframe::App for PatinaEguiApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         self.system_theme = frame.info().system_theme;
+        #[cfg(feature = "vision-attachments")]
+        self.paste_bridge.restore(ctx);
         render_ui(ctx, self);
-        ctx.request_repaint_after(Duration::from_millis(16));
+        if let Some(after) = self.next_repaint() {
+            ctx.request_repaint_after(after);
+        }
     }
 
     fn save(&mut self, _storage: &mut dyn eframe::Storage) {
         self.persist_now();
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.provider_watcher = None;
+        let layout_changed = self.layout_save_due.take().is_some();
+        if layout_changed || self.settings_save_due.is_some() {
+            self.persist_now();
+        }
+        self.prune_empty_chats(false);
+    }
 }
 
 pub fn render_ui(ctx: &egui::Context, app_state: &mut PatinaEguiApp) {
     app_state.render(ctx);
 }
 
-fn default_mcp_entries() -> Vec<McpSidebarEntry> {
-    vec![
-        McpSidebarEntry {
-            id: "github".into(),
-            name: "GitHub".into(),
-            description: "Issues & Reviews".into(),
-            status: McpStatus::Connected,
-        },
-        McpSidebarEntry {
-            id: "playwright".into(),
-            name: "Playwright".into(),
-            description: "Browser automation".into(),
-            status: McpStatus::Disconnected,
-        },
-        McpSidebarEntry {
-            id: "notion".into(),
-            name: "Notion".into(),
-            description: "Docs search".into(),
-            status: McpStatus::Connecting,
-        },
-    ]
+fn project_name_from_path(path: &Path) -> Result<String> {
+    let name = if path.extension().and_then(|ext| ext.to_str()) == Some("pat") {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("project file must have a valid name"))?
+    } else {
+        path.file_name()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("project path must have a valid name"))?
+    };
+    Ok(name.to_string())
 }
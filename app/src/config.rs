@@ -1,15 +1,28 @@
 use anyhow::{Context, Result};
 use directories::BaseDirs;
+use patina_core::config::merge_yaml;
+use patina_core::llm::{LlmDriver, ModelUsage};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::warn;
 
+/// How many times [`save_ui_settings_with_retry`] tries to write before giving up.
+pub const SAVE_ATTEMPTS: u32 = 3;
+/// Pause between failed settings writes.
+pub const SAVE_RETRY_DELAY: Duration = Duration::from_millis(250);
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Scope {
     User,
     Project(PathBuf),
 }
 
+/// Zoom factors the UI can be scaled to.
+pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.75..=2.0;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UiSettings {
     #[serde(default = "UiSettings::default_theme_mode")]
@@ -20,6 +33,9 @@ pub struct UiSettings {
     pub sidebar_visible: bool,
     #[serde(default = "UiSettings::default_window_size")]
     pub window_size: [f32; 2],
+    /// Zoom applied to the whole UI, within [`UI_SCALE_RANGE`].
+    #[serde(default = "UiSettings::default_ui_scale")]
+    pub ui_scale: f32,
     #[serde(default)]
     pub pinned_chats: Vec<uuid::Uuid>,
     #[serde(default)]
@@ -31,6 +47,45 @@ pub struct UiSettings {
     #[serde(default = "UiSettings::default_retain_input")]
     pub retain_input: bool,
     #[serde(default)]
+    pub max_request_messages: usize,
+    /// Longest reply to request, in tokens; 0 leaves it to the provider.
+    #[serde(default)]
+    pub max_tokens: u32,
+    /// Nucleus sampling cutoff sent with each request; unset leaves it to the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default = "UiSettings::default_mcp_collapsed")]
+    pub mcp_collapsed: bool,
+    /// How long settings changes are debounced before being written; 0 writes immediately.
+    #[serde(default = "UiSettings::default_autosave_delay_ms")]
+    pub autosave_delay_ms: u64,
+    /// How long streamed text is batched before the reply preview is updated; 0 applies
+    /// every delta immediately.
+    #[serde(default = "UiSettings::default_stream_flush_ms")]
+    pub stream_flush_ms: u64,
+    #[serde(default)]
+    pub load_remote_images: bool,
+    /// Re-ask the last prompt with the newly selected model after a model change.
+    #[serde(default)]
+    pub regenerate_on_model_change: bool,
+    /// Answer MCP servers' sampling requests with the selected model. Off by default,
+    /// since every request spends tokens.
+    #[serde(default)]
+    pub allow_mcp_sampling: bool,
+    /// Ask the model for a short title after a chat's first exchange instead of keeping
+    /// the start of the first message.
+    #[serde(default)]
+    pub auto_title_chats: bool,
+    /// Show non-pinned chats under Today/Yesterday/This week/Older headers.
+    #[serde(default)]
+    pub group_chats_by_date: bool,
+    /// How chat and message times are shown in the sidebar and bubble headers.
+    #[serde(default)]
+    pub timestamp_format: crate::ui::TimestampFormat,
+    /// Last sidebar search query, keyed by project root.
+    #[serde(default)]
+    pub search_queries: std::collections::HashMap<String, String>,
+    #[serde(default)]
     pub recent_projects: Vec<String>,
     #[serde(default)]
     pub current_project: Option<String>,
@@ -43,11 +98,25 @@ impl Default for UiSettings {
             sidebar_width: UiSettings::default_sidebar_width(),
             sidebar_visible: UiSettings::default_sidebar_visible(),
             window_size: UiSettings::default_window_size(),
+            ui_scale: UiSettings::default_ui_scale(),
             pinned_chats: Vec::new(),
             last_conversation: None,
             model: UiSettings::default_model(),
             temperature: UiSettings::default_temperature(),
             retain_input: UiSettings::default_retain_input(),
+            max_request_messages: 0,
+            max_tokens: 0,
+            top_p: None,
+            mcp_collapsed: UiSettings::default_mcp_collapsed(),
+            autosave_delay_ms: UiSettings::default_autosave_delay_ms(),
+            stream_flush_ms: UiSettings::default_stream_flush_ms(),
+            load_remote_images: false,
+            regenerate_on_model_change: false,
+            allow_mcp_sampling: false,
+            auto_title_chats: false,
+            group_chats_by_date: false,
+            timestamp_format: crate::ui::TimestampFormat::default(),
+            search_queries: std::collections::HashMap::new(),
             recent_projects: Vec::new(),
             current_project: None,
         }
@@ -55,6 +124,22 @@ impl Default for UiSettings {
 }
 
 impl UiSettings {
+    /// The reply length cap to send with requests, if any.
+    pub fn reply_token_limit(&self) -> Option<u32> {
+        (self.max_tokens > 0).then_some(self.max_tokens)
+    }
+
+    /// [`Self::ui_scale`] kept within [`UI_SCALE_RANGE`]; a value that is not a number
+    /// falls back to 1.0.
+    pub fn clamped_ui_scale(&self) -> f32 {
+        if self.ui_scale.is_finite() {
+            self.ui_scale
+                .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end())
+        } else {
+            UiSettings::default_ui_scale()
+        }
+    }
+
     fn default_theme_mode() -> crate::ui::ThemeMode {
         crate::ui::ThemeMode::System
     }
@@ -71,6 +156,10 @@ impl UiSettings {
         [1280.0, 820.0]
     }
 
+    fn default_ui_scale() -> f32 {
+        1.0
+    }
+
     fn default_model() -> String {
         "gpt-4o".to_string()
     }
@@ -82,12 +171,158 @@ impl UiSettings {
     fn default_retain_input() -> bool {
         true
     }
+
+    fn default_mcp_collapsed() -> bool {
+        true
+    }
+
+    fn default_autosave_delay_ms() -> u64 {
+        500
+    }
+
+    fn default_stream_flush_ms() -> u64 {
+        50
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct ProviderConfig {
     #[serde(default)]
     pub available_models: Vec<String>,
+    /// Names of the profiles under `app.profiles`, in alphabetical order.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    /// The profile `available_models` came from; `None` for the top-level settings.
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    /// Why `available_models` is empty, when it is.
+    #[serde(skip)]
+    pub no_models: Option<NoModelsReason>,
+    #[serde(default)]
+    pub pricing: Pricing,
+}
+
+/// Dollars per 1,000 tokens of one model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub prompt: f64,
+    #[serde(default)]
+    pub completion: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &ModelUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt
+            + usage.completion_tokens as f64 * self.completion)
+            / 1000.0
+    }
+}
+
+/// Model prices from `app.pricing` in patina.yaml. Without any, the UI shows token
+/// counts only.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Pricing(BTreeMap<String, ModelPrice>);
+
+impl Pricing {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The price of `model`, whose name is matched ignoring case.
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        self.0
+            .get(model)
+            .or_else(|| {
+                self.0
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(model))
+                    .map(|(_, price)| price)
+            })
+            .copied()
+    }
+
+    /// What `usage` of `model` cost, when the model has a price.
+    pub fn cost(&self, model: &str, usage: &ModelUsage) -> Option<f64> {
+        Some(self.price(model)?.cost(usage))
+    }
+
+    /// The cost of a conversation's [`usage`](patina_core::state::Conversation::usage),
+    /// charging replies of an unknown model as `fallback_model`. `None` when one of the
+    /// models has no price.
+    pub fn total_cost(
+        &self,
+        usage: &BTreeMap<Option<String>, ModelUsage>,
+        fallback_model: &str,
+    ) -> Option<f64> {
+        usage
+            .iter()
+            .map(|(model, usage)| self.cost(model.as_deref().unwrap_or(fallback_model), usage))
+            .sum()
+    }
+}
+
+impl FromIterator<(String, ModelPrice)> for Pricing {
+    fn from_iter<I: IntoIterator<Item = (String, ModelPrice)>>(prices: I) -> Self {
+        Self(prices.into_iter().collect())
+    }
+}
+
+/// Why patina.yaml yielded no models, so the UI can say what to fix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoModelsReason {
+    /// There is no patina.yaml for the scope.
+    NoConfigFile,
+    /// patina.yaml could not be read or decoded.
+    Unreadable(String),
+    /// patina.yaml has no `app` section.
+    NoAppSection,
+    /// The selected provider has no section, e.g. `app.azure_openai`.
+    MissingSection { provider: String, section: String },
+    /// The selected provider's section lists no models at `path`.
+    EmptySection { provider: String, path: String },
+}
+
+impl NoModelsReason {
+    /// A few words for the input bar; the [`Display`](std::fmt::Display) text has the
+    /// full explanation.
+    pub fn hint(&self) -> String {
+        match self {
+            NoModelsReason::NoConfigFile => "No patina.yaml found".to_string(),
+            NoModelsReason::Unreadable(_) => "patina.yaml could not be read".to_string(),
+            NoModelsReason::NoAppSection => "patina.yaml has no app section".to_string(),
+            NoModelsReason::MissingSection { section, .. } => format!("Add a {section} section"),
+            NoModelsReason::EmptySection { path, .. } => format!("Add models under {path}"),
+        }
+    }
+}
+
+impl std::fmt::Display for NoModelsReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoModelsReason::NoConfigFile => write!(
+                f,
+                "No patina.yaml was found. Open Settings to pick a provider and list its models."
+            ),
+            NoModelsReason::Unreadable(error) => {
+                write!(f, "No models are available because {error}.")
+            }
+            NoModelsReason::NoAppSection => write!(
+                f,
+                "patina.yaml has no `app` section. Add one with a provider and its models."
+            ),
+            NoModelsReason::MissingSection { provider, section } => write!(
+                f,
+                "patina.yaml selects the {provider} provider but has no `{section}` section. \
+                 Add it with the models to use."
+            ),
+            NoModelsReason::EmptySection { provider, path } => write!(
+                f,
+                "The {provider} provider lists no models. Add them under `{path}` in patina.yaml."
+            ),
+        }
+    }
 }
 
 pub async fn load_ui_settings(scope: &Scope) -> Result<UiSettings> {
@@ -127,54 +362,265 @@ pub async fn save_ui_settings(scope: &Scope, settings: &UiSettings) -> Result<()
         .with_context(|| format!("failed to write ui_settings.json at {}", path.display()))
 }
 
+/// Run `save` up to `attempts` times, waiting `delay` after each failure, and return
+/// the last error if every attempt fails.
+pub async fn retry_save<F, Fut>(attempts: u32, delay: Duration, mut save: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut attempt = 1;
+    loop {
+        match save().await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < attempts => {
+                warn!(error = ?err, attempt, "failed to save UI settings, retrying");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// [`save_ui_settings`] with a few retries to ride out transient disk errors.
+pub async fn save_ui_settings_with_retry(scope: &Scope, settings: &UiSettings) -> Result<()> {
+    retry_save(SAVE_ATTEMPTS, SAVE_RETRY_DELAY, || {
+        save_ui_settings(scope, settings)
+    })
+    .await
+}
+
+/// The provider config of `scope`, or no models when patina.yaml cannot be read.
 pub async fn load_provider_config(scope: &Scope) -> Result<ProviderConfig> {
-    for path in provider_config_candidates(scope) {
+    match read_provider_config(scope).await {
+        Ok(config) => Ok(config),
+        Err(err) => {
+            warn!(error = ?err, "{err}");
+            Ok(ProviderConfig {
+                no_models: Some(NoModelsReason::Unreadable(format!("{err:#}"))),
+                ..ProviderConfig::default()
+            })
+        }
+    }
+}
+
+/// Like [`load_provider_config`], but a patina.yaml that cannot be read or decoded is
+/// an error, so a reload can keep the config it already has.
+///
+/// A project's patina.yaml is layered over the user's: the keys it sets win and the
+/// rest are inherited.
+pub async fn read_provider_config(scope: &Scope) -> Result<ProviderConfig> {
+    let mut files = Vec::new();
+    for dir in provider_config_dirs(scope) {
+        files.extend(read_first_provider_config(&dir).await?);
+    }
+    layer_provider_configs(files)
+}
+
+/// Decode the patina.yaml `files` (path and contents), each overriding the keys it
+/// sets in the ones before it.
+pub fn layer_provider_configs(files: Vec<(PathBuf, String)>) -> Result<ProviderConfig> {
+    let mut merged = None;
+    let mut paths = Vec::new();
+    for (path, contents) in files {
+        let layer: serde_yaml::Value = serde_yaml::from_str(&contents)
+            .with_context(|| format!("failed to decode patina.yaml at {}", path.display()))?;
+        match &mut merged {
+            Some(base) => merge_yaml(base, layer),
+            None => merged = Some(layer),
+        }
+        paths.push(path.display().to_string());
+    }
+
+    let Some(merged) = merged else {
+        return Ok(ProviderConfig {
+            no_models: Some(NoModelsReason::NoConfigFile),
+            ..ProviderConfig::default()
+        });
+    };
+    decode_provider_config(merged)
+        .with_context(|| format!("failed to decode patina.yaml at {}", paths.join(" and ")))
+}
+
+/// The LLM driver for `scope`, built from the same layered patina.yaml as
+/// [`read_provider_config`].
+pub async fn llm_driver(scope: &Scope) -> LlmDriver {
+    let project_dir = match scope {
+        Scope::User => None,
+        Scope::Project(_) => Some(provider_config_dir(scope)),
+    };
+    LlmDriver::from_layered_environment(project_dir.as_deref()).await
+}
+
+/// The path and contents of the first patina.yaml found in `dir`.
+async fn read_first_provider_config(dir: &Path) -> Result<Option<(PathBuf, String)>> {
+    for path in provider_config_candidates(dir) {
         match tokio::fs::read_to_string(&path).await {
-            Ok(contents) => match parse_provider_config(&contents) {
-                Ok(config) => return Ok(config),
-                Err(err) => {
-                    warn!(
-                        error = ?err,
-                        "failed to decode patina.yaml at {}",
-                        path.display()
-                    );
-                    return Ok(ProviderConfig {
-                        available_models: Vec::new(),
-                    });
-                }
-            },
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                continue;
-            }
+            Ok(contents) => return Ok(Some((path, contents))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
             Err(err) => {
-                warn!(
-                    error = ?err,
-                    "failed to read patina.yaml at {}",
-                    path.display()
-                );
-                return Ok(ProviderConfig {
-                    available_models: Vec::new(),
-                });
+                return Err(err)
+                    .with_context(|| format!("failed to read patina.yaml at {}", path.display()));
             }
         }
     }
+    Ok(None)
+}
 
-    Ok(ProviderConfig {
-        available_models: Vec::new(),
-    })
+/// Decode patina.yaml `layers`, each overriding the keys it sets in the ones before it.
+pub fn parse_provider_config(layers: &[&str]) -> Result<ProviderConfig> {
+    let mut merged = serde_yaml::Value::Null;
+    for layer in layers {
+        merge_yaml(&mut merged, serde_yaml::from_str(layer)?);
+    }
+    decode_provider_config(merged)
+}
+
+/// Watches the directory holding patina.yaml and reports edits to it, so hand edits
+/// are picked up without a restart. Dropping the watcher stops it.
+pub struct ProviderConfigWatcher {
+    _watcher: ::notify::RecommendedWatcher,
+    changes: std::sync::mpsc::Receiver<()>,
+}
+
+impl ProviderConfigWatcher {
+    /// Watch the provider config of `scope`; `on_change` runs on the watcher's thread
+    /// after every change, e.g. to wake the UI.
+    pub fn new(scope: &Scope, on_change: impl Fn() + Send + 'static) -> Result<Self> {
+        use ::notify::Watcher;
+
+        let dir = provider_config_dir(scope);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let (tx, changes) = std::sync::mpsc::channel();
+        let mut watcher =
+            ::notify::recommended_watcher(move |event: ::notify::Result<::notify::Event>| {
+                let Ok(event) = event else { return };
+                if event.kind.is_access() || !event.paths.iter().any(|p| is_provider_config(p)) {
+                    return;
+                }
+                if tx.send(()).is_ok() {
+                    on_change();
+                }
+            })?;
+        watcher
+            .watch(&dir, ::notify::RecursiveMode::NonRecursive)
+            .with_context(|| format!("failed to watch {}", dir.display()))?;
+        // A project inherits the user's patina.yaml, so edits there count too.
+        for inherited in provider_config_dirs(scope) {
+            if inherited != dir && inherited.is_dir() {
+                watcher
+                    .watch(&inherited, ::notify::RecursiveMode::NonRecursive)
+                    .with_context(|| format!("failed to watch {}", inherited.display()))?;
+            }
+        }
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Whether the config changed since the last call.
+    pub fn take_changed(&self) -> bool {
+        self.changes.try_iter().count() > 0
+    }
 }
 
-fn parse_provider_config(contents: &str) -> Result<ProviderConfig> {
-    let raw: RawConfig = serde_yaml::from_str(contents)?;
-    let models = raw
-        .app
-        .map(|section| select_models(&section))
-        .unwrap_or_default();
+fn is_provider_config(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == "patina.yaml" || name == "patina.yml")
+}
+
+fn decode_provider_config(value: serde_yaml::Value) -> Result<ProviderConfig> {
+    // An empty file is a null document; read it as one without an `app` section.
+    let raw: RawConfig = if value.is_null() {
+        RawConfig { app: None }
+    } else {
+        serde_yaml::from_value(value)?
+    };
+    let Some(app) = raw.app else {
+        return Ok(ProviderConfig {
+            no_models: Some(NoModelsReason::NoAppSection),
+            ..ProviderConfig::default()
+        });
+    };
+    let active_profile = app
+        .active_profile
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    let section = match active_profile {
+        Some(name) => app.profiles.get(name).ok_or_else(|| {
+            anyhow::anyhow!("active profile `{name}` is not defined under app.profiles")
+        })?,
+        None => &app,
+    };
+    let available_models = normalize_models(select_models(section));
+    let no_models = available_models.is_empty().then(|| {
+        let prefix = match active_profile {
+            Some(name) => format!("app.profiles.{name}"),
+            None => "app".to_string(),
+        };
+        no_models_reason(section, &prefix)
+    });
+    // A profile's prices add to and override the top-level ones.
+    let mut pricing = app.pricing.clone();
+    if active_profile.is_some() {
+        pricing.0.extend(section.pricing.0.clone());
+    }
     Ok(ProviderConfig {
-        available_models: normalize_models(models),
+        available_models,
+        profiles: app.profiles.keys().cloned().collect(),
+        active_profile: active_profile.map(str::to_string),
+        no_models,
+        pricing,
     })
 }
 
+/// The provider `app` selects: its display name and the key of its section, which is
+/// empty for the mock provider's top-level list.
+fn selected_provider(app: &RawAppSection) -> (&'static str, &'static str) {
+    let provider = app
+        .provider
+        .as_deref()
+        .unwrap_or("openai")
+        .to_ascii_lowercase();
+    match provider.as_str() {
+        "azure_openai" | "azure-openai" | "azure_open_ai" => ("Azure OpenAI", "azure_openai"),
+        "ollama" => ("Ollama", "ollama"),
+        "mock" => ("Mock", ""),
+        _ => ("OpenAI", "openai"),
+    }
+}
+
+/// Where `app`, found at `prefix` in patina.yaml, should list the models it lacks.
+fn no_models_reason(app: &RawAppSection, prefix: &str) -> NoModelsReason {
+    let (provider, key) = selected_provider(app);
+    let present = match key {
+        "azure_openai" => app.azure_openai.is_some(),
+        "ollama" => app.ollama.is_some(),
+        "openai" => app.openai.is_some(),
+        _ => true,
+    };
+    let (section, list) = match key {
+        "" => (prefix.to_string(), "available_models"),
+        "ollama" => (format!("{prefix}.{key}"), "model"),
+        _ => (format!("{prefix}.{key}"), "available_models"),
+    };
+    if present {
+        NoModelsReason::EmptySection {
+            provider: provider.to_string(),
+            path: format!("{section}.{list}"),
+        }
+    } else {
+        NoModelsReason::MissingSection {
+            provider: provider.to_string(),
+            section,
+        }
+    }
+}
+
 fn normalize_models(models: Vec<String>) -> Vec<String> {
     let mut seen = std::collections::BTreeSet::new();
     let mut output = Vec::new();
@@ -192,18 +638,22 @@ fn normalize_models(models: Vec<String>) -> Vec<String> {
 }
 
 fn select_models(app: &RawAppSection) -> Vec<String> {
-    let provider = app
-        .provider
-        .as_deref()
-        .unwrap_or("openai")
-        .to_ascii_lowercase();
-    let mut models = match provider.as_str() {
-        "azure_openai" | "azure-openai" => app
+    let mut models = match selected_provider(app).1 {
+        "azure_openai" => app
             .azure_openai
             .as_ref()
             .map(|section| section.available_models.clone())
             .unwrap_or_default(),
-        "mock" => app.available_models.clone(),
+        "ollama" => app
+            .ollama
+            .as_ref()
+            .map(|section| {
+                let mut models: Vec<String> = section.model.iter().cloned().collect();
+                models.extend(section.available_models.iter().cloned());
+                models
+            })
+            .unwrap_or_default(),
+        "" => app.available_models.clone(),
         _ => app
             .openai
             .as_ref()
@@ -236,28 +686,39 @@ fn ui_settings_path(scope: &Scope) -> PathBuf {
     }
 }
 
-fn provider_config_candidates(scope: &Scope) -> Vec<PathBuf> {
-    let mut paths = Vec::new();
+fn provider_config_dir(scope: &Scope) -> PathBuf {
     match scope {
-        Scope::User => {
-            let dir = config_dir();
-            paths.push(dir.join("patina.yaml"));
-            paths.push(dir.join("patina.yml"));
-        }
-        Scope::Project(path) => {
-            let base = project_dir(path);
-            paths.push(base.join("patina.yaml"));
-            paths.push(base.join("patina.yml"));
-        }
+        Scope::User => config_dir(),
+        Scope::Project(path) => project_dir(path),
+    }
+}
+
+/// The directories whose patina.yaml make up the config of `scope`, the one that wins
+/// last.
+fn provider_config_dirs(scope: &Scope) -> Vec<PathBuf> {
+    match scope {
+        Scope::User => vec![config_dir()],
+        Scope::Project(_) => vec![config_dir(), provider_config_dir(scope)],
     }
-    paths
+}
+
+fn provider_config_candidates(dir: &Path) -> Vec<PathBuf> {
+    vec![dir.join("patina.yaml"), dir.join("patina.yml")]
+}
+
+/// The patina.yaml of the project at `path`, if it has one.
+pub fn project_provider_config_file(path: &Path) -> Option<PathBuf> {
+    provider_config_candidates(&project_dir(path))
+        .into_iter()
+        .find(|candidate| candidate.exists())
 }
 
 fn project_dir(path: &Path) -> PathBuf {
     path.join(".patina")
 }
 
-fn config_dir() -> PathBuf {
+/// Where the user-scope UI settings and patina.yaml live.
+pub fn config_dir() -> PathBuf {
     if let Some(base) = BaseDirs::new() {
         base.config_dir().join("patina")
     } else {
@@ -272,6 +733,10 @@ struct RawConfig {
 
 #[derive(Debug, Deserialize)]
 struct RawAppSection {
+    #[serde(default)]
+    active_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, RawAppSection>,
     #[serde(default)]
     provider: Option<String>,
     #[serde(default)]
@@ -280,6 +745,10 @@ struct RawAppSection {
     openai: Option<RawProviderSection>,
     #[serde(default, rename = "azure_openai")]
     azure_openai: Option<RawProviderSection>,
+    #[serde(default)]
+    ollama: Option<RawOllamaSection>,
+    #[serde(default)]
+    pricing: Pricing,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -287,3 +756,11 @@ struct RawProviderSection {
     #[serde(default)]
     available_models: Vec<String>,
 }
+
+#[derive(Debug, Default, Deserialize)]
+struct RawOllamaSection {
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    available_models: Vec<String>,
+}
@@ -0,0 +1,427 @@
+//! `patina doctor`: the configuration Patina would run with, in a form that can be
+//! pasted into an issue. Secrets are never printed, only whether they are set.
+
+use crate::config::{self, NoModelsReason, ProviderConfig};
+use patina_core::config::{
+    locate_config_file, patina_yaml_candidates, AiRuntimeSettings, AZURE_OPENAI_API_KEY_VAR,
+    OPENAI_API_KEY_VAR,
+};
+use patina_core::llm::{LlmDriver, LlmProviderKind};
+use patina_core::telemetry::{LogFile, LOG_FILE_VAR};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Problem,
+    /// Neither good nor bad, such as a directory or a skipped check.
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub status: Status,
+    pub label: &'static str,
+    pub detail: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn push(&mut self, status: Status, label: &'static str, detail: impl Into<String>) {
+        self.checks.push(Check {
+            status,
+            label,
+            detail: detail.into(),
+        });
+    }
+
+    pub fn problems(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == Status::Problem)
+            .count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Patina {}", env!("CARGO_PKG_VERSION"))?;
+        for check in &self.checks {
+            let tag = match check.status {
+                Status::Ok => "[ok]  ",
+                Status::Problem => "[FAIL]",
+                Status::Info => "[info]",
+            };
+            writeln!(f, "{tag} {}: {}", check.label, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where [`diagnose_with`] reads the configuration from.
+pub struct Sources<'a> {
+    /// The user's patina.yaml, if one was found.
+    pub user_file: Option<PathBuf>,
+    /// A project's patina.yaml, whose keys override the user's.
+    pub project_file: Option<PathBuf>,
+    pub env: &'a dyn Fn(&str) -> Option<String>,
+}
+
+/// Check patina.yaml, the provider settings and models, and list the directories
+/// Patina uses; with `check_network`, also send the provider a one-line prompt.
+///
+/// With a `project`, its patina.yaml is layered over the user's, as the app does.
+pub async fn diagnose(project: Option<&Path>, check_network: bool) -> Report {
+    let env = |name: &str| std::env::var(name).ok();
+    let sources = Sources {
+        user_file: locate_config_file(),
+        project_file: project.and_then(config::project_provider_config_file),
+        env: &env,
+    };
+    diagnose_with(&sources, check_network).await
+}
+
+/// [`diagnose`] with the configuration files and environment given by `sources`.
+pub async fn diagnose_with(sources: &Sources<'_>, check_network: bool) -> Report {
+    let mut report = Report::default();
+    match &sources.user_file {
+        Some(path) => report.push(Status::Ok, "patina.yaml", path.display().to_string()),
+        None => {
+            let searched: Vec<String> = patina_yaml_candidates()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            // A project's patina.yaml can hold the whole configuration on its own.
+            let status = match sources.project_file {
+                Some(_) => Status::Info,
+                None => Status::Problem,
+            };
+            report.push(
+                status,
+                "patina.yaml",
+                format!("not found; looked for {}", searched.join(", ")),
+            );
+        }
+    }
+    if let Some(path) = &sources.project_file {
+        report.push(
+            Status::Ok,
+            "project patina.yaml",
+            path.display().to_string(),
+        );
+    }
+    let layers: Vec<PathBuf> = sources
+        .user_file
+        .iter()
+        .chain(&sources.project_file)
+        .cloned()
+        .collect();
+
+    let settings = match AiRuntimeSettings::load_files(&layers, sources.env) {
+        Ok(settings) => {
+            report.push(Status::Ok, "provider", describe_provider(&settings));
+            report_api_key(&mut report, &settings, sources.env);
+            Some(settings)
+        }
+        Err(err) => {
+            report.push(Status::Problem, "provider", err.user_message());
+            None
+        }
+    };
+
+    // An unreadable patina.yaml comes back as the reason for no models.
+    let provider = read_layers(&layers)
+        .and_then(config::layer_provider_configs)
+        .unwrap_or_else(|err| ProviderConfig {
+            no_models: Some(NoModelsReason::Unreadable(format!("{err:#}"))),
+            ..ProviderConfig::default()
+        });
+    if let Some(profile) = &provider.active_profile {
+        report.push(Status::Info, "active profile", profile.clone());
+    }
+    match provider.no_models {
+        _ if !provider.available_models.is_empty() => {
+            report.push(Status::Ok, "models", provider.available_models.join(", "));
+        }
+        Some(reason) => report.push(Status::Problem, "models", reason.to_string()),
+        None => report.push(Status::Problem, "models", "none configured"),
+    }
+
+    report.push(
+        Status::Info,
+        "config directory",
+        config::config_dir().display().to_string(),
+    );
+    let log_dir = LogFile::default_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let log_detail = match LogFile::from_env() {
+        Some(_) => format!("{log_dir} (writing, {LOG_FILE_VAR} is set)"),
+        None => format!("{log_dir} (off; set {LOG_FILE_VAR}=1 to write logs)"),
+    };
+    report.push(Status::Info, "log directory", log_detail);
+
+    match (check_network, settings) {
+        (false, _) => report.push(
+            Status::Info,
+            "connection",
+            "not checked; pass --check-network to send a test prompt",
+        ),
+        (true, None) => report.push(
+            Status::Problem,
+            "connection",
+            "not checked; the provider settings are invalid",
+        ),
+        (true, Some(settings)) => {
+            let result = match LlmDriver::from_settings(settings).await {
+                Ok(driver) => driver.check_connection().await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(elapsed) => report.push(
+                    Status::Ok,
+                    "connection",
+                    format!("answered in {} ms", elapsed.as_millis()),
+                ),
+                Err(err) => report.push(Status::Problem, "connection", format!("{err:#}")),
+            }
+        }
+    }
+    report
+}
+
+fn read_layers(paths: &[PathBuf]) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    paths
+        .iter()
+        .map(|path| {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", path.display()))?;
+            Ok((path.clone(), contents))
+        })
+        .collect()
+}
+
+fn describe_provider(settings: &AiRuntimeSettings) -> String {
+    match settings.provider {
+        LlmProviderKind::OpenAi => {
+            let openai = settings.openai.as_ref();
+            let base_url = openai
+                .and_then(|openai| openai.base_url.as_deref())
+                .unwrap_or(patina_core::config::DEFAULT_OPENAI_BASE_URL);
+            let model = openai
+                .and_then(|openai| openai.model.as_deref())
+                .unwrap_or("default model");
+            format!("OpenAI at {base_url}, {model}")
+        }
+        LlmProviderKind::AzureOpenAi => match &settings.azure {
+            Some(azure) => format!(
+                "Azure OpenAI at {}, deployment {}, API version {}",
+                azure.endpoint, azure.deployment_name, azure.api_version
+            ),
+            None => "Azure OpenAI".to_string(),
+        },
+        LlmProviderKind::Ollama => match &settings.ollama {
+            Some(ollama) => format!("Ollama at {}, {}", ollama.base_url, ollama.model),
+            None => "Ollama".to_string(),
+        },
+        LlmProviderKind::Mock => "Mock".to_string(),
+    }
+}
+
+fn report_api_key(
+    report: &mut Report,
+    settings: &AiRuntimeSettings,
+    env: &dyn Fn(&str) -> Option<String>,
+) {
+    let (key, var) = match settings.provider {
+        LlmProviderKind::OpenAi => (
+            settings.openai.as_ref().map(|openai| &openai.api_key),
+            OPENAI_API_KEY_VAR,
+        ),
+        LlmProviderKind::AzureOpenAi => (
+            settings.azure.as_ref().map(|azure| &azure.api_key),
+            AZURE_OPENAI_API_KEY_VAR,
+        ),
+        LlmProviderKind::Ollama | LlmProviderKind::Mock => {
+            report.push(Status::Info, "API key", "not needed");
+            return;
+        }
+    };
+    match key.filter(|key| !key.trim().is_empty()) {
+        Some(_) if env(var).is_some() => {
+            report.push(Status::Ok, "API key", format!("set from {var}"));
+        }
+        Some(_) => report.push(Status::Ok, "API key", "set in patina.yaml"),
+        None => report.push(
+            Status::Problem,
+            "API key",
+            format!("missing; set {var} or add it to patina.yaml"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    /// An environment holding only `vars`, in place of the process environment.
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    /// Write `contents` to a patina.yaml in a fresh directory named after `name`.
+    fn patina_yaml(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("patina-doctor-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let path = dir.join("patina.yaml");
+        std::fs::write(&path, contents).expect("patina.yaml");
+        path
+    }
+
+    fn settings(yaml: &str, env: &dyn Fn(&str) -> Option<String>) -> AiRuntimeSettings {
+        static FILES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let index = FILES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = patina_yaml(&format!("settings-{index}"), yaml);
+        AiRuntimeSettings::load_files(&[path], env).expect("settings")
+    }
+
+    fn detail<'a>(report: &'a Report, label: &str) -> (Status, &'a str) {
+        let check = report
+            .checks
+            .iter()
+            .find(|check| check.label == label)
+            .unwrap_or_else(|| panic!("no {label} check in {report}"));
+        (check.status, check.detail.as_str())
+    }
+
+    #[test]
+    fn providers_are_described_by_where_they_send_requests() {
+        let openai = settings(
+            "app:\n  provider: open_ai\n  openai:\n    api_key: sk-file\n    base_url: http://localhost:4000\n",
+            &no_env,
+        );
+        assert_eq!(
+            describe_provider(&openai),
+            "OpenAI at http://localhost:4000, default model"
+        );
+
+        let ollama = settings(
+            "app:\n  provider: ollama\n  ollama:\n    base_url: http://localhost:11434\n    model: llama3.2\n",
+            &no_env,
+        );
+        assert_eq!(
+            describe_provider(&ollama),
+            "Ollama at http://localhost:11434, llama3.2"
+        );
+        assert_eq!(
+            describe_provider(&settings("app:\n  provider: mock\n", &no_env)),
+            "Mock"
+        );
+    }
+
+    #[test]
+    fn api_keys_are_reported_by_source_and_never_printed() {
+        let yaml = "app:\n  provider: open_ai\n  openai:\n    api_key: sk-from-file\n";
+        let mut report = Report::default();
+        report_api_key(&mut report, &settings(yaml, &no_env), &no_env);
+        assert_eq!(
+            detail(&report, "API key"),
+            (Status::Ok, "set in patina.yaml")
+        );
+        assert!(!report.to_string().contains("sk-from-file"));
+
+        let vars = [(OPENAI_API_KEY_VAR, "sk-from-env")];
+        let mut report = Report::default();
+        report_api_key(&mut report, &settings(yaml, &env(&vars)), &env(&vars));
+        assert_eq!(
+            detail(&report, "API key"),
+            (Status::Ok, "set from OPENAI_API_KEY")
+        );
+        assert!(!report.to_string().contains("sk-from-env"));
+
+        let mut report = Report::default();
+        report_api_key(
+            &mut report,
+            &settings("app:\n  provider: mock\n", &env(&vars)),
+            &env(&vars),
+        );
+        assert_eq!(detail(&report, "API key"), (Status::Info, "not needed"));
+    }
+
+    #[tokio::test]
+    async fn a_project_patina_yaml_overrides_the_user_settings() {
+        let user_file = patina_yaml(
+            "user",
+            "app:\n  provider: open_ai\n  openai:\n    api_key: sk-user-secret\n    available_models: [gpt-4o]\n",
+        );
+        let project_file = patina_yaml(
+            "project",
+            "app:\n  openai:\n    base_url: http://localhost:4000\n    available_models: [gpt-4o-mini, o3]\n",
+        );
+        let vars = [(OPENAI_API_KEY_VAR, "sk-env-secret")];
+        let env = env(&vars);
+        let sources = Sources {
+            user_file: Some(user_file.clone()),
+            project_file: Some(project_file.clone()),
+            env: &env,
+        };
+        let report = diagnose_with(&sources, false).await;
+
+        assert_eq!(
+            detail(&report, "project patina.yaml"),
+            (Status::Ok, project_file.display().to_string().as_str())
+        );
+        assert_eq!(
+            detail(&report, "provider"),
+            (Status::Ok, "OpenAI at http://localhost:4000, default model")
+        );
+        assert_eq!(
+            detail(&report, "API key"),
+            (Status::Ok, "set from OPENAI_API_KEY")
+        );
+        assert_eq!(detail(&report, "models"), (Status::Ok, "gpt-4o-mini, o3"));
+        let printed = report.to_string();
+        assert!(!printed.contains("sk-user-secret") && !printed.contains("sk-env-secret"));
+
+        // Without the project, the user's file is all there is.
+        let sources = Sources {
+            user_file: Some(user_file),
+            project_file: None,
+            env: &no_env,
+        };
+        let report = diagnose_with(&sources, false).await;
+        assert_eq!(detail(&report, "models"), (Status::Ok, "gpt-4o"));
+        assert_eq!(
+            detail(&report, "API key"),
+            (Status::Ok, "set in patina.yaml")
+        );
+        assert!(!report.to_string().contains("sk-user-secret"));
+    }
+
+    #[tokio::test]
+    async fn missing_configuration_is_reported_as_problems() {
+        let sources = Sources {
+            user_file: None,
+            project_file: None,
+            env: &no_env,
+        };
+        let report = diagnose_with(&sources, false).await;
+        assert_eq!(detail(&report, "patina.yaml").0, Status::Problem);
+        assert_eq!(detail(&report, "provider").0, Status::Problem);
+        assert_eq!(detail(&report, "models").0, Status::Problem);
+        assert_eq!(report.problems(), 3);
+    }
+}
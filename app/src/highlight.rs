@@ -0,0 +1,194 @@
+//! Syntax highlighting for fenced code blocks in chat messages.
+
+use egui::text::LayoutJob;
+use egui::{Color32, TextFormat, TextStyle};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use uuid::Uuid;
+
+const THEME_DARK: &str = "base16-ocean.dark";
+const THEME_LIGHT: &str = "base16-ocean.light";
+/// Highlighted blocks kept before the cache is emptied and refilled from what is shown.
+const MAX_CACHED_BLOCKS: usize = 512;
+
+/// A run of a message: markdown prose or the body of a fenced code block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Markdown(&'a str),
+    Code { language: &'a str, code: &'a str },
+}
+
+/// Split `content` at its fenced code blocks; an unclosed fence runs to the end.
+pub fn split_code_blocks(content: &str) -> Vec<Segment<'_>> {
+    // Fence character, fence length, language and where the code starts.
+    let mut open: Option<(char, usize, &str, usize)> = None;
+    let mut segments = Vec::new();
+    let mut prose_start = 0;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start_matches(' ');
+        if line.len() - trimmed.len() > 3 {
+            continue;
+        }
+        let fence = match trimmed.chars().next() {
+            Some(fence @ ('`' | '~')) => fence,
+            _ => continue,
+        };
+        let length = trimmed.chars().take_while(|c| *c == fence).count();
+        if length < 3 {
+            continue;
+        }
+        let info = trimmed[length..].trim();
+        match open {
+            None if fence == '`' && info.contains('`') => {}
+            None => {
+                if line_start > prose_start {
+                    segments.push(Segment::Markdown(&content[prose_start..line_start]));
+                }
+                let language = info.split_whitespace().next().unwrap_or_default();
+                open = Some((fence, length, language, offset));
+            }
+            Some((open_fence, open_length, language, code_start))
+                if fence == open_fence && length >= open_length && info.is_empty() =>
+            {
+                segments.push(Segment::Code {
+                    language,
+                    code: &content[code_start..line_start],
+                });
+                open = None;
+                prose_start = offset;
+            }
+            Some(_) => {}
+        }
+    }
+    match open {
+        Some((_, _, language, code_start)) => segments.push(Segment::Code {
+            language,
+            code: &content[code_start..],
+        }),
+        None if prose_start < content.len() => {
+            segments.push(Segment::Markdown(&content[prose_start..]))
+        }
+        None => {}
+    }
+    segments
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct BlockKey {
+    message: Uuid,
+    index: usize,
+    dark: bool,
+    code: u64,
+}
+
+/// Highlights code blocks with `syntect`, keeping each message's blocks so they are
+/// not re-highlighted every frame.
+pub struct CodeHighlighter {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+    blocks: HashMap<BlockKey, LayoutJob>,
+}
+
+impl Default for CodeHighlighter {
+    fn default() -> Self {
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            themes: ThemeSet::load_defaults(),
+            blocks: HashMap::new(),
+        }
+    }
+}
+
+impl CodeHighlighter {
+    fn syntax(&self, language: &str) -> Option<&SyntaxReference> {
+        if language.is_empty() {
+            return None;
+        }
+        self.syntaxes.find_syntax_by_token(language)
+    }
+
+    fn theme(&self, dark: bool) -> &Theme {
+        &self.themes.themes[if dark { THEME_DARK } else { THEME_LIGHT }]
+    }
+
+    /// Background of code blocks in the light or dark highlight theme.
+    pub fn background(&self, dark: bool) -> Option<Color32> {
+        self.theme(dark).settings.background.map(to_egui)
+    }
+
+    /// Lay out `code` in the colors of `language`; unknown languages keep `plain`.
+    pub fn highlight(
+        &self,
+        language: &str,
+        code: &str,
+        dark: bool,
+        plain: TextFormat,
+    ) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        let Some(syntax) = self.syntax(language) else {
+            job.append(code, 0.0, plain);
+            return job;
+        };
+        let mut lines = HighlightLines::new(syntax, self.theme(dark));
+        for line in LinesWithEndings::from(code) {
+            match lines.highlight_line(line, &self.syntaxes) {
+                Ok(ranges) => {
+                    for (style, text) in ranges {
+                        let format = TextFormat {
+                            color: to_egui(style.foreground),
+                            ..plain.clone()
+                        };
+                        job.append(text, 0.0, format);
+                    }
+                }
+                Err(_) => job.append(line, 0.0, plain.clone()),
+            }
+        }
+        job
+    }
+
+    /// [`Self::highlight`] for block `index` of `message`, in the theme matching `ui`.
+    pub fn layout(
+        &mut self,
+        ui: &egui::Ui,
+        message: Uuid,
+        index: usize,
+        language: &str,
+        code: &str,
+    ) -> LayoutJob {
+        let dark = ui.visuals().dark_mode;
+        let mut hasher = DefaultHasher::new();
+        (language, code).hash(&mut hasher);
+        let key = BlockKey {
+            message,
+            index,
+            dark,
+            code: hasher.finish(),
+        };
+        if let Some(job) = self.blocks.get(&key) {
+            return job.clone();
+        }
+        if self.blocks.len() >= MAX_CACHED_BLOCKS {
+            self.blocks.clear();
+        }
+        let plain = TextFormat::simple(
+            TextStyle::Monospace.resolve(ui.style()),
+            ui.visuals().text_color(),
+        );
+        let job = self.highlight(language, code, dark, plain);
+        self.blocks.insert(key, job.clone());
+        job
+    }
+}
+
+fn to_egui(color: syntect::highlighting::Color) -> Color32 {
+    Color32::from_rgb(color.r, color.g, color.b)
+}
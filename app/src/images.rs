@@ -0,0 +1,328 @@
+use base64::Engine;
+use egui::load::{Bytes, BytesLoadResult, BytesLoader, BytesPoll, LoadError};
+use futures::StreamExt;
+use image::imageops::FilterType;
+use parking_lot::Mutex;
+use patina_core::attachment::ImageAttachment;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::runtime::Handle;
+use tracing::warn;
+
+/// Largest image body fetched or decoded from a data URL.
+pub const MAX_REMOTE_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Longest side of an image sent to a vision model; larger pastes are scaled down.
+pub const MAX_ATTACHED_IMAGE_SIDE: u32 = 2048;
+
+/// Longest side of a clipboard image that is accepted at all, before scaling.
+pub const MAX_PASTED_IMAGE_SIDE: usize = 16_384;
+
+/// Longest side of the thumbnail shown in the input bar.
+const THUMBNAIL_SIDE: u32 = 96;
+
+/// The shortcut the input bar answers by attaching the clipboard image.
+pub const PASTE_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::V);
+
+#[derive(Clone)]
+struct Fetched {
+    bytes: Arc<[u8]>,
+    mime: Option<String>,
+}
+
+type Entry = Poll<Result<Fetched, String>>;
+
+/// Loads markdown images from `http(s)://` and `data:` URLs for the chat transcript.
+///
+/// Remote fetches only happen while the shared `enabled` flag is set, so images stay
+/// blocked by default for privacy and bandwidth.
+pub struct RemoteImageLoader {
+    enabled: Arc<AtomicBool>,
+    runtime: Handle,
+    client: reqwest::Client,
+    cache: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl RemoteImageLoader {
+    pub const ID: &'static str = egui::generate_loader_id!(RemoteImageLoader);
+
+    /// Fetch with `client`, usually [`patina_core::llm::http_client`] so images go
+    /// through the same proxy as provider requests.
+    pub fn new(enabled: Arc<AtomicBool>, runtime: Handle, client: reqwest::Client) -> Self {
+        Self {
+            enabled,
+            runtime,
+            client,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl BytesLoader for RemoteImageLoader {
+    fn id(&self) -> &str {
+        Self::ID
+    }
+
+    fn load(&self, ctx: &egui::Context, uri: &str) -> BytesLoadResult {
+        if uri.starts_with("data:") {
+            return match decode_data_url(uri) {
+                Ok((bytes, mime)) => Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: Bytes::from(bytes),
+                    mime,
+                }),
+                Err(err) => Err(LoadError::Loading(err)),
+            };
+        }
+        if !(uri.starts_with("http://") || uri.starts_with("https://")) {
+            return Err(LoadError::NotSupported);
+        }
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Err(LoadError::Loading(
+                "Remote images are disabled (View → Load remote images)".to_string(),
+            ));
+        }
+
+        let mut cache = self.cache.lock();
+        if let Some(entry) = cache.get(uri).cloned() {
+            return match entry {
+                Poll::Ready(Ok(file)) => Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: Bytes::Shared(file.bytes),
+                    mime: file.mime,
+                }),
+                Poll::Ready(Err(err)) => Err(LoadError::Loading(err)),
+                Poll::Pending => Ok(BytesPoll::Pending { size: None }),
+            };
+        }
+
+        let uri = uri.to_owned();
+        cache.insert(uri.clone(), Poll::Pending);
+        drop(cache);
+
+        let ctx = ctx.clone();
+        let cache = self.cache.clone();
+        let client = self.client.clone();
+        self.runtime.spawn(async move {
+            let result = fetch_image(&client, &uri).await;
+            if let Err(err) = &result {
+                warn!(%uri, error = %err, "failed to load markdown image");
+            }
+            cache.lock().insert(uri, Poll::Ready(result));
+            ctx.request_repaint();
+        });
+
+        Ok(BytesPoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.cache.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.cache.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.cache
+            .lock()
+            .values()
+            .map(|entry| match entry {
+                Poll::Ready(Ok(file)) => file.bytes.len(),
+                Poll::Ready(Err(err)) => err.len(),
+                Poll::Pending => 0,
+            })
+            .sum()
+    }
+}
+
+async fn fetch_image(client: &reqwest::Client, uri: &str) -> Result<Fetched, String> {
+    let response = client
+        .get(uri)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("failed to load image: {err}"))?;
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > MAX_REMOTE_IMAGE_BYTES)
+    {
+        return Err("image is larger than the size limit".to_string());
+    }
+    let mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    let mut body = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| format!("failed to read image: {err}"))?;
+        if body.len() + chunk.len() > MAX_REMOTE_IMAGE_BYTES {
+            return Err("image is larger than the size limit".to_string());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Fetched {
+        bytes: body.into(),
+        mime,
+    })
+}
+
+/// Decode a base64 `data:` URL into its bytes and optional MIME type.
+pub fn decode_data_url(uri: &str) -> Result<(Vec<u8>, Option<String>), String> {
+    let rest = uri
+        .strip_prefix("data:")
+        .ok_or_else(|| "not a data URL".to_string())?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| "malformed data URL".to_string())?;
+    let Some(mime) = header.strip_suffix(";base64") else {
+        return Err("only base64 data URLs are supported".to_string());
+    };
+    if payload.len() / 4 * 3 > MAX_REMOTE_IMAGE_BYTES {
+        return Err("image is larger than the size limit".to_string());
+    }
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(payload.trim())
+        .map_err(|err| format!("invalid base64 image data: {err}"))?;
+    let mime = (!mime.is_empty()).then(|| mime.to_string());
+    Ok((bytes, mime))
+}
+
+/// An image pasted into the input bar: the PNG sent with the next message and the
+/// thumbnail shown until then.
+#[derive(Clone)]
+pub struct PastedImage {
+    pub attachment: ImageAttachment,
+    pub thumbnail: egui::ColorImage,
+    /// The image was scaled down to [`MAX_ATTACHED_IMAGE_SIDE`].
+    pub downscaled: bool,
+    texture: Option<egui::TextureHandle>,
+}
+
+impl PastedImage {
+    /// Encode `width` × `height` RGBA pixels as PNG, scaled down to fit
+    /// [`MAX_ATTACHED_IMAGE_SIDE`]. Images beyond [`MAX_PASTED_IMAGE_SIDE`] are rejected.
+    pub fn from_rgba(width: usize, height: usize, rgba: Vec<u8>) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err("The clipboard image is empty.".to_string());
+        }
+        if width.max(height) > MAX_PASTED_IMAGE_SIDE {
+            return Err(format!(
+                "The clipboard image is too large ({width}×{height}); images up to \
+                 {MAX_PASTED_IMAGE_SIDE} pixels per side can be pasted."
+            ));
+        }
+        let pixels = image::RgbaImage::from_raw(width as u32, height as u32, rgba)
+            .ok_or_else(|| "The clipboard image data is incomplete.".to_string())?;
+        let mut image = image::DynamicImage::ImageRgba8(pixels);
+        let downscaled = width.max(height) > MAX_ATTACHED_IMAGE_SIDE as usize;
+        if downscaled {
+            image = image.resize(
+                MAX_ATTACHED_IMAGE_SIDE,
+                MAX_ATTACHED_IMAGE_SIDE,
+                FilterType::Triangle,
+            );
+        }
+        let mut png = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .map_err(|err| format!("Failed to encode the clipboard image: {err}"))?;
+        let thumbnail = image.thumbnail(THUMBNAIL_SIDE, THUMBNAIL_SIDE).to_rgba8();
+        Ok(Self {
+            attachment: ImageAttachment {
+                mime_type: "image/png".to_string(),
+                data: base64::engine::general_purpose::STANDARD.encode(png),
+            },
+            thumbnail: egui::ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width() as usize, thumbnail.height() as usize],
+                thumbnail.as_raw(),
+            ),
+            downscaled,
+            texture: None,
+        })
+    }
+
+    /// The thumbnail as a texture, uploaded the first time it is shown.
+    pub fn texture(&mut self, ctx: &egui::Context) -> &egui::TextureHandle {
+        let thumbnail = &self.thumbnail;
+        self.texture.get_or_insert_with(|| {
+            ctx.load_texture("pasted-image", thumbnail.clone(), Default::default())
+        })
+    }
+}
+
+/// The image on the system clipboard, or `None` when it holds none.
+#[cfg(feature = "vision-attachments")]
+pub fn clipboard_image() -> Result<Option<PastedImage>, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|err| format!("The clipboard is unavailable: {err}"))?;
+    match clipboard.get_image() {
+        Ok(image) => {
+            PastedImage::from_rgba(image.width, image.height, image.bytes.into_owned()).map(Some)
+        }
+        Err(arboard::Error::ContentNotAvailable) => Ok(None),
+        Err(err) => Err(format!("Failed to read the clipboard image: {err}")),
+    }
+}
+
+/// Restores the key press of [`PASTE_SHORTCUT`] that the native backend swallows.
+///
+/// egui-winit turns the shortcut into an [`egui::Event::Paste`] when the clipboard holds
+/// text and drops the press altogether when it does not, which is exactly the case of a
+/// copied image. Only the key release still arrives, so a release with no paste or press
+/// seen since the last one is given its press back for the input bar to consume.
+#[cfg(feature = "vision-attachments")]
+#[derive(Default)]
+pub struct PasteShortcutBridge {
+    seen: bool,
+}
+
+#[cfg(feature = "vision-attachments")]
+impl PasteShortcutBridge {
+    /// Call once per frame, before the input bar is shown.
+    pub fn restore(&mut self, ctx: &egui::Context) {
+        ctx.input_mut(|input| {
+            let mut released = None;
+            for event in &input.events {
+                match event {
+                    egui::Event::Paste(_) => self.seen = true,
+                    egui::Event::Key {
+                        key,
+                        pressed,
+                        modifiers,
+                        ..
+                    } if *key == PASTE_SHORTCUT.logical_key
+                        && modifiers.matches_logically(PASTE_SHORTCUT.modifiers) =>
+                    {
+                        if *pressed {
+                            self.seen = true;
+                        } else {
+                            released = Some(*modifiers);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(modifiers) = released {
+                if !std::mem::take(&mut self.seen) {
+                    input.events.push(egui::Event::Key {
+                        key: PASTE_SHORTCUT.logical_key,
+                        physical_key: None,
+                        pressed: true,
+                        repeat: false,
+                        modifiers,
+                    });
+                }
+            }
+        });
+    }
+}
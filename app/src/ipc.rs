@@ -0,0 +1,159 @@
+//! Local remote-control socket for a running Patina instance.
+//!
+//! When started with `--ipc`, Patina listens on a Unix socket (see [`socket_path`]).
+//! Clients write one JSON object per line and receive one JSON reply line:
+//!
+//! ```text
+//! → {"command":"open-project","path":"/work/Notes"}
+//! ← {"ok":true}
+//! → {"command":"new-chat"}
+//! ← {"ok":true}
+//! → {"command":"bogus"}
+//! ← {"ok":false,"error":"unknown variant `bogus`, …"}
+//! ```
+//!
+//! A reply of `ok` only means the command was accepted; it is applied on the next frame.
+
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum IpcCommand {
+    /// Open the project at `path` (a project directory or `.pat` manifest).
+    OpenProject { path: PathBuf },
+    /// Start a new conversation in the current project.
+    NewChat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpcReply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Parse one line of the IPC protocol.
+pub fn parse_command(line: &str) -> Result<IpcCommand> {
+    serde_json::from_str(line.trim()).context("invalid IPC command")
+}
+
+/// Encode a command as a single protocol line, including the trailing newline.
+pub fn encode_command(command: &IpcCommand) -> String {
+    let mut line = serde_json::to_string(command).expect("IPC commands always serialize");
+    line.push('\n');
+    line
+}
+
+/// Where the running instance listens: the user's runtime directory when available,
+/// otherwise the Patina config directory.
+pub fn socket_path() -> PathBuf {
+    let dir = BaseDirs::new()
+        .map(|base| {
+            base.runtime_dir()
+                .map(|dir| dir.to_path_buf())
+                .unwrap_or_else(|| base.config_dir().join("patina"))
+        })
+        .unwrap_or_else(|| PathBuf::from(".patina"));
+    dir.join("patina.sock")
+}
+
+/// Send `command` to an already running instance.
+///
+/// Returns `Ok(false)` when no instance is listening, so the caller should start the UI.
+#[cfg(unix)]
+pub fn forward(command: &IpcCommand) -> Result<bool> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = match UnixStream::connect(socket_path()) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(false),
+    };
+    stream
+        .write_all(encode_command(command).as_bytes())
+        .context("failed to send IPC command")?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .context("failed to read IPC reply")?;
+    let reply: IpcReply = serde_json::from_str(&reply).context("invalid IPC reply")?;
+    match reply.error {
+        Some(error) if !reply.ok => Err(anyhow::anyhow!(error)),
+        _ => Ok(true),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn forward(_command: &IpcCommand) -> Result<bool> {
+    Ok(false)
+}
+
+/// Bind the IPC socket and deliver accepted commands on the returned channel,
+/// waking `ctx` so an idle window applies them promptly.
+///
+/// A stale socket left behind by a crashed instance is replaced.
+#[cfg(unix)]
+pub fn listen(ctx: egui::Context) -> Result<UnboundedReceiver<IpcCommand>> {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    let path = socket_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    if path.exists() {
+        fs::remove_file(&path)
+            .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("failed to listen on {}", path.display()))?;
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let Ok(mut writer) = stream.try_clone() else {
+                continue;
+            };
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let reply = match parse_command(&line) {
+                    Ok(command) => {
+                        if tx.send(command).is_err() {
+                            return;
+                        }
+                        ctx.request_repaint();
+                        IpcReply {
+                            ok: true,
+                            error: None,
+                        }
+                    }
+                    Err(err) => IpcReply {
+                        ok: false,
+                        error: Some(format!("{err:#}")),
+                    },
+                };
+                let mut encoded = serde_json::to_string(&reply).unwrap_or_default();
+                encoded.push('\n');
+                if writer.write_all(encoded.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(rx)
+}
+
+#[cfg(not(unix))]
+pub fn listen(_ctx: egui::Context) -> Result<UnboundedReceiver<IpcCommand>> {
+    anyhow::bail!("the IPC socket is only supported on Unix platforms")
+}
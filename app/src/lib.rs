@@ -1,7 +1,14 @@
 pub mod app;
 pub mod assets;
 pub mod config;
+pub mod doctor;
+pub mod highlight;
+pub mod images;
+pub mod ipc;
+pub mod notify;
+pub mod palette;
 pub mod settings;
+pub mod streaming;
 pub mod ui;
 
 pub use app::{render_ui, PatinaEguiApp};
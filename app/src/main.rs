@@ -1,16 +1,23 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use eframe::egui;
 use patina::{
-    config::{load_provider_config, load_ui_settings, Scope, UiSettings},
+    config::{
+        llm_driver, load_provider_config, load_ui_settings, save_ui_settings, Scope, UiSettings,
+    },
+    doctor,
+    ipc::{self, IpcCommand},
     logo_png_bytes, PatinaEguiApp,
 };
 use patina_core::llm::LlmDriver;
 use patina_core::project::ProjectHandle;
-use patina_core::telemetry;
+use patina_core::state::AppState;
+use patina_core::telemetry::{self, LogFile};
 use std::fs::File;
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::runtime::Runtime;
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
@@ -24,12 +31,204 @@ struct Cli {
     new: Option<PathBuf>,
     #[arg(long)]
     name: Option<String>,
+    /// Remote-control a running instance over a local socket, or listen for one.
+    #[arg(long)]
+    ipc: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    Export { project: PathBuf, out: PathBuf },
-    Import { zip: PathBuf, into: PathBuf },
+    Export {
+        project: PathBuf,
+        out: PathBuf,
+        /// Write a zip archive, or copy the project into the empty directory `out`.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Zip)]
+        format: ExportFormat,
+    },
+    Import {
+        zip: PathBuf,
+        into: PathBuf,
+    },
+    /// Write every conversation as newline-delimited JSON for analysis.
+    ExportNdjson {
+        #[arg(long)]
+        project: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+        /// Emit one record per message, tagged with its conversation id.
+        #[arg(long)]
+        flatten_messages: bool,
+        /// Only emit messages added since the previous incremental export (implies
+        /// `--flatten-messages`).
+        #[arg(long)]
+        incremental: bool,
+    },
+    /// Send one message in a new chat of a project and print the reply.
+    Chat {
+        #[arg(long)]
+        project: PathBuf,
+        /// Defaults to the model last picked in the app.
+        #[arg(long)]
+        model: Option<String>,
+        /// The message to send; read from stdin when omitted.
+        #[arg(long)]
+        message: Option<String>,
+        /// Print the reply as it is generated.
+        #[arg(long)]
+        stream: bool,
+    },
+    /// List recently opened projects, dropping the ones that no longer open.
+    List {
+        /// Print a JSON array instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report the configuration in use, without secrets, for bug reports.
+    Doctor {
+        /// Also send the provider a one-line prompt.
+        #[arg(long)]
+        check_network: bool,
+        /// Include the overrides in this project's patina.yaml.
+        #[arg(long)]
+        project: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    Dir,
+    Zip,
+}
+
+/// A progress callback that keeps rewriting one `label done/total` line on stderr.
+fn print_progress(label: &'static str) -> impl FnMut(u64, u64) {
+    move |done, total| {
+        eprint!("\r{label} {done}/{total} entries");
+        if done == total {
+            eprintln!();
+        }
+    }
+}
+
+/// Send `message`, or stdin, in a new chat of `project` and print the reply to stdout.
+fn run_chat(
+    project: &Path,
+    model: Option<String>,
+    message: Option<String>,
+    stream: bool,
+) -> anyhow::Result<()> {
+    let message = match message {
+        Some(message) => message,
+        None => {
+            let mut message = String::new();
+            std::io::stdin().read_to_string(&mut message)?;
+            message
+        }
+    };
+    if message.trim().is_empty() {
+        anyhow::bail!("no message to send; pass --message or pipe one to stdin");
+    }
+    let runtime = Runtime::new()?;
+    let handle = ProjectHandle::open(project)?;
+    let ui_settings = runtime.block_on(load_ui_settings(&Scope::User))?;
+    let model = model.unwrap_or(ui_settings.model);
+    if model.trim().is_empty() {
+        anyhow::bail!("no model; pass --model");
+    }
+    let driver = runtime.block_on(LlmDriver::from_environment());
+    // A single message does not outlive the MCP tokens; no background refresh.
+    let state = AppState::new(handle, driver).with_background_refresh(false);
+    state.set_max_request_messages(ui_settings.max_request_messages);
+    state.start_new_conversation();
+
+    runtime.block_on(async {
+        if stream {
+            let (_, mut chunks) = state
+                .send_user_message_streaming(message, model, ui_settings.temperature)
+                .await?;
+            let mut stdout = std::io::stdout();
+            while let Some(chunk) = chunks.recv().await {
+                let chunk = chunk?;
+                write!(stdout, "{}", chunk.delta)?;
+                stdout.flush()?;
+                if chunk.done {
+                    break;
+                }
+            }
+            writeln!(stdout)?;
+        } else {
+            state
+                .send_user_message(message, model, ui_settings.temperature)
+                .await?;
+            let reply = state
+                .active_conversation()
+                .and_then(|conversation| conversation.last_completed_reply().cloned())
+                .ok_or_else(|| anyhow::anyhow!("the model sent no reply"))?;
+            println!("{}", reply.content);
+        }
+        Ok(())
+    })
+}
+
+/// Print the recent projects that still open, and forget the ones that do not.
+fn list_projects(json: bool) -> anyhow::Result<()> {
+    let runtime = Runtime::new()?;
+    let mut ui_settings = runtime.block_on(load_ui_settings(&Scope::User))?;
+    let mut projects = Vec::new();
+    let mut missing = Vec::new();
+    for entry in &ui_settings.recent_projects {
+        if !Path::new(entry).exists() {
+            missing.push((entry.clone(), "it no longer exists".to_string()));
+            continue;
+        }
+        match ProjectHandle::open(Path::new(entry)) {
+            Ok(handle) => projects.push(handle),
+            Err(err) => missing.push((entry.clone(), err.to_string())),
+        }
+    }
+    if !missing.is_empty() {
+        for (entry, err) in &missing {
+            eprintln!("Removed {entry} from the recent projects: {err}");
+        }
+        ui_settings
+            .recent_projects
+            .retain(|entry| !missing.iter().any(|(gone, _)| gone == entry));
+        runtime.block_on(save_ui_settings(&Scope::User, &ui_settings))?;
+    }
+
+    let rows: Vec<_> = projects
+        .iter()
+        .map(|handle| {
+            let conversations = handle.transcript_store().load_conversation_headers().len();
+            (handle, conversations)
+        })
+        .collect();
+    if json {
+        let records: Vec<_> = rows
+            .iter()
+            .map(|(handle, conversations)| {
+                serde_json::json!({
+                    "name": handle.name(),
+                    "path": handle.paths().root,
+                    "created_utc": handle.created_at(),
+                    "conversations": conversations,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&records)?);
+    } else if rows.is_empty() {
+        println!("No recent projects");
+    } else {
+        for (handle, conversations) in rows {
+            println!(
+                "{}\t{}\tcreated {}\t{conversations} conversations",
+                handle.name(),
+                handle.paths().root.display(),
+                handle.created_at().format("%Y-%m-%d"),
+            );
+        }
+    }
+    Ok(())
 }
 
 fn load_application_icon() -> Option<egui::IconData> {
@@ -43,20 +242,37 @@ fn load_application_icon() -> Option<egui::IconData> {
 }
 
 fn main() -> anyhow::Result<()> {
-    telemetry::init_tracing(EnvFilter::from_default_env())?;
+    // A log file is for reading after the fact, so it records info by default rather
+    // than only errors.
+    let log_file = LogFile::from_env();
+    let filter = match log_file {
+        Some(_) => EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy(),
+        None => EnvFilter::from_default_env(),
+    };
+    telemetry::init_tracing(filter, log_file)?;
 
     let cli = Cli::parse();
 
     match &cli.command {
-        Some(Command::Export { project, out }) => {
+        Some(Command::Export {
+            project,
+            out,
+            format,
+        }) => {
             let handle = ProjectHandle::open(project)?;
-            let file = File::create(out)?;
-            handle.export_zip(file)?;
+            match format {
+                ExportFormat::Zip => handle
+                    .export_zip_with_progress(File::create(out)?, print_progress("Exporting"))?,
+                ExportFormat::Dir => handle.export_to_dir(out)?,
+            }
             return Ok(());
         }
         Some(Command::Import { zip, into }) => {
             let file = File::open(zip)?;
-            let imported = ProjectHandle::import_zip(file, into)?;
+            let imported =
+                ProjectHandle::import_zip_with_progress(file, into, print_progress("Importing"))?;
             println!(
                 "Imported project {} at {}",
                 imported.name(),
@@ -64,19 +280,68 @@ fn main() -> anyhow::Result<()> {
             );
             return Ok(());
         }
+        Some(Command::ExportNdjson {
+            project,
+            out,
+            flatten_messages,
+            incremental,
+        }) => {
+            let handle = ProjectHandle::open(project)?;
+            let file = BufWriter::new(File::create(out)?);
+            let store = handle.transcript_store();
+            let records = if *incremental {
+                store.export_incremental(&store.load_conversations_skipping_invalid(), file)?
+            } else {
+                store.export_ndjson(file, *flatten_messages)?
+            };
+            println!("Wrote {records} records to {}", out.display());
+            return Ok(());
+        }
+        Some(Command::Chat {
+            project,
+            model,
+            message,
+            stream,
+        }) => return run_chat(project, model.clone(), message.clone(), *stream),
+        Some(Command::List { json }) => return list_projects(*json),
+        Some(Command::Doctor {
+            check_network,
+            project,
+        }) => {
+            let report =
+                Runtime::new()?.block_on(doctor::diagnose(project.as_deref(), *check_network));
+            print!("{report}");
+            match report.problems() {
+                0 => return Ok(()),
+                1 => anyhow::bail!("found 1 problem"),
+                problems => anyhow::bail!("found {problems} problems"),
+            }
+        }
         None => {}
     }
 
-    let runtime = Arc::new(Runtime::new()?);
-    let driver = runtime.block_on(LlmDriver::from_environment());
+    if cli.ipc && cli.new.is_none() {
+        let command = match &cli.project {
+            Some(path) => IpcCommand::OpenProject {
+                path: std::path::absolute(path)?,
+            },
+            None => IpcCommand::NewChat,
+        };
+        if ipc::forward(&command)? {
+            return Ok(());
+        }
+    }
 
+    let runtime = Arc::new(Runtime::new()?);
     let scope = Scope::User;
+    let driver = runtime.block_on(llm_driver(&scope));
     let mut ui_settings = runtime.block_on(load_ui_settings(&scope))?;
     let provider_config = runtime.block_on(load_provider_config(&scope))?;
     let project = resolve_project(&cli, &mut ui_settings)?;
     let runtime_for_ui = runtime.clone();
     let mut settings = Some(ui_settings);
     let mut provider = Some(provider_config);
+    let listen_ipc = cli.ipc;
     let scope_for_ui = scope.clone();
     let initial_size = settings.as_ref().unwrap().window_size;
     let inner_size = egui::vec2(initial_size[0].max(1024.0), initial_size[1].max(720.0));
@@ -101,17 +366,27 @@ fn main() -> anyhow::Result<()> {
     eframe::run_native(
         &window_title,
         native_options,
-        Box::new(move |_cc| {
+        Box::new(move |cc| {
             let ui_settings = settings.take().expect("UI settings already consumed");
             let provider_config = provider.take().expect("provider config already consumed");
-            Box::new(PatinaEguiApp::new(
+            let app = PatinaEguiApp::new(
                 project.clone(),
                 driver.clone(),
                 runtime_for_ui.clone(),
                 scope_for_ui.clone(),
                 ui_settings,
                 provider_config,
-            ))
+            );
+            if !listen_ipc {
+                return Box::new(app);
+            }
+            match ipc::listen(cc.egui_ctx.clone()) {
+                Ok(rx) => Box::new(app.with_ipc(rx)),
+                Err(err) => {
+                    tracing::warn!(error = ?err, "IPC socket unavailable");
+                    Box::new(app)
+                }
+            }
         }),
     )
     .map_err(|err| anyhow::anyhow!(err.to_string()))?;
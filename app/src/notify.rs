@@ -0,0 +1,34 @@
+//! Desktop notifications for replies that finish while the window is in the background.
+
+use tracing::warn;
+
+/// Longest reply excerpt shown in a notification, in characters.
+const EXCERPT_CHARS: usize = 120;
+
+/// The first non-blank line of `reply`, cut to [`EXCERPT_CHARS`] with an ellipsis.
+pub fn reply_excerpt(reply: &str) -> String {
+    let line = reply
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if line.chars().count() <= EXCERPT_CHARS {
+        return line.to_string();
+    }
+    let mut excerpt: String = line.chars().take(EXCERPT_CHARS - 1).collect();
+    excerpt.push('…');
+    excerpt
+}
+
+/// Tell the user the reply in the chat `title` has finished. Blocks on the platform's
+/// notification service; a missing service is logged, not reported.
+pub fn reply_finished(title: &str, reply: &str) {
+    let result = notify_rust::Notification::new()
+        .appname("Patina")
+        .summary(title)
+        .body(&reply_excerpt(reply))
+        .show();
+    if let Err(err) = result {
+        warn!(error = %err, "failed to show desktop notification");
+    }
+}
@@ -0,0 +1,418 @@
+use crate::ui::{ThemeMode, ThemePalette};
+use egui::{self, Margin, RichText, Stroke};
+
+/// An action the command palette can run; each maps onto the existing menu or
+/// sidebar outputs in [`crate::app`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteAction {
+    NewChat,
+    NewProject,
+    OpenProject,
+    SaveProjectAs,
+    ImportConversation,
+    SaveNow,
+    PruneEmptyChats,
+    ShowSettings,
+    ToggleSidebar,
+    FocusSearch,
+    ClearInput,
+    ToggleRemoteImages,
+    ToggleGroupByDate,
+    ToggleRegenerateOnModelChange,
+    ToggleMcpSampling,
+    ToggleAutoTitle,
+    Theme(ThemeMode),
+    TogglePinChat,
+    CopyChatJson,
+    ExportChatJson,
+    ResetTitle,
+    TogglePreview,
+    ResetSessionUsage,
+    ReloadMcpServers,
+    ShowAbout,
+    Exit,
+}
+
+/// What an action needs before it is offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Requires {
+    Nothing,
+    Project,
+    /// An open project that can be written to.
+    WritableProject,
+    ActiveChat,
+}
+
+pub struct PaletteEntry {
+    pub action: PaletteAction,
+    pub name: &'static str,
+    pub shortcut: Option<&'static str>,
+    pub requires: Requires,
+}
+
+const fn entry(
+    action: PaletteAction,
+    name: &'static str,
+    shortcut: Option<&'static str>,
+    requires: Requires,
+) -> PaletteEntry {
+    PaletteEntry {
+        action,
+        name,
+        shortcut,
+        requires,
+    }
+}
+
+/// Every action the palette knows about, in the order shown for an empty query.
+pub const ACTIONS: &[PaletteEntry] = &[
+    entry(
+        PaletteAction::NewChat,
+        "New chat",
+        Some("Ctrl+N"),
+        Requires::WritableProject,
+    ),
+    entry(
+        PaletteAction::FocusSearch,
+        "Search chats",
+        Some("Ctrl+K"),
+        Requires::Project,
+    ),
+    entry(
+        PaletteAction::ToggleSidebar,
+        "Toggle sidebar",
+        Some("Ctrl+M"),
+        Requires::Project,
+    ),
+    entry(
+        PaletteAction::SaveNow,
+        "Save now",
+        Some("Ctrl+S"),
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ShowSettings,
+        "Open settings",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::TogglePinChat,
+        "Pin or unpin chat",
+        None,
+        Requires::ActiveChat,
+    ),
+    entry(
+        PaletteAction::ResetTitle,
+        "Reset chat title",
+        None,
+        Requires::ActiveChat,
+    ),
+    entry(
+        PaletteAction::CopyChatJson,
+        "Copy chat as JSON",
+        None,
+        Requires::ActiveChat,
+    ),
+    entry(
+        PaletteAction::ExportChatJson,
+        "Export chat as JSON…",
+        None,
+        Requires::ActiveChat,
+    ),
+    entry(
+        PaletteAction::TogglePreview,
+        "Toggle read-only preview",
+        None,
+        Requires::ActiveChat,
+    ),
+    entry(
+        PaletteAction::ClearInput,
+        "Clear input",
+        None,
+        Requires::Project,
+    ),
+    entry(
+        PaletteAction::PruneEmptyChats,
+        "Prune empty chats",
+        None,
+        Requires::WritableProject,
+    ),
+    entry(
+        PaletteAction::NewProject,
+        "New project…",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::OpenProject,
+        "Open project…",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::SaveProjectAs,
+        "Save project as…",
+        None,
+        Requires::Project,
+    ),
+    entry(
+        PaletteAction::ImportConversation,
+        "Import conversation…",
+        None,
+        Requires::Project,
+    ),
+    entry(
+        PaletteAction::ToggleRemoteImages,
+        "Toggle remote images",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ToggleGroupByDate,
+        "Toggle grouping chats by date",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ToggleRegenerateOnModelChange,
+        "Toggle regenerate on model change",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ToggleMcpSampling,
+        "Toggle MCP sampling",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ToggleAutoTitle,
+        "Toggle generated chat titles",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::Theme(ThemeMode::Light),
+        "Theme: Light",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::Theme(ThemeMode::Dark),
+        "Theme: Dark",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::Theme(ThemeMode::System),
+        "Theme: System",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::Theme(ThemeMode::HighContrast),
+        "Theme: High contrast",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ResetSessionUsage,
+        "Reset session token counter",
+        None,
+        Requires::Nothing,
+    ),
+    entry(
+        PaletteAction::ReloadMcpServers,
+        "Reload MCP servers",
+        None,
+        Requires::Project,
+    ),
+    entry(
+        PaletteAction::ShowAbout,
+        "About Patina",
+        None,
+        Requires::Nothing,
+    ),
+    entry(PaletteAction::Exit, "Exit", None, Requires::Nothing),
+];
+
+/// The app state that decides which actions are offered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PaletteContext {
+    pub project_open: bool,
+    pub read_only: bool,
+    pub chat_active: bool,
+}
+
+impl PaletteContext {
+    pub fn allows(&self, requires: Requires) -> bool {
+        match requires {
+            Requires::Nothing => true,
+            Requires::Project => self.project_open,
+            Requires::WritableProject => self.project_open && !self.read_only,
+            Requires::ActiveChat => self.project_open && self.chat_active,
+        }
+    }
+}
+
+/// Score `candidate` against `query` as a case-insensitive subsequence match.
+///
+/// Returns `None` when some query character is missing. Consecutive matches and
+/// matches at the start of a word score higher, gaps between matched characters lower;
+/// an empty query matches everything.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (position..candidate.len()).find(|&index| candidate[index] == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if previous.is_some() {
+            score -= (found - position) as i32;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// Actions available in `context` that match `query`, best match first.
+pub fn matching_actions(query: &str, context: PaletteContext) -> Vec<&'static PaletteEntry> {
+    let mut scored: Vec<(i32, usize, &PaletteEntry)> = ACTIONS
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| context.allows(entry.requires))
+        .filter_map(|(order, entry)| Some((fuzzy_score(query, entry.name)?, order, entry)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, _, entry)| entry).collect()
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+    focus_pending: bool,
+}
+
+impl CommandPalette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        if self.open {
+            self.open = false;
+        } else {
+            self.open = true;
+            self.query.clear();
+            self.selected = 0;
+            self.focus_pending = true;
+        }
+    }
+
+    /// Draw the palette when open and return the action the user picked.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        palette: &ThemePalette,
+        context: PaletteContext,
+    ) -> Option<PaletteAction> {
+        if !self.open {
+            return None;
+        }
+        let matches = matching_actions(&self.query, context);
+        let (up, down, enter, escape) = ctx.input(|input| {
+            (
+                input.key_pressed(egui::Key::ArrowUp),
+                input.key_pressed(egui::Key::ArrowDown),
+                input.key_pressed(egui::Key::Enter),
+                input.key_pressed(egui::Key::Escape),
+            )
+        });
+        if escape {
+            self.open = false;
+            return None;
+        }
+        if down && self.selected + 1 < matches.len() {
+            self.selected += 1;
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let mut chosen = None;
+        egui::Area::new(egui::Id::new("command_palette"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 80.0))
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(palette.surface)
+                    .stroke(Stroke::new(1.0, palette.border))
+                    .rounding(8.0)
+                    .inner_margin(Margin::same(10.0))
+                    .show(ui, |ui| {
+                        ui.set_width(420.0);
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut self.query)
+                                .hint_text("Type a command…")
+                                .desired_width(f32::INFINITY),
+                        );
+                        if self.focus_pending {
+                            response.request_focus();
+                            self.focus_pending = false;
+                        }
+                        if response.changed() {
+                            self.selected = 0;
+                        }
+                        ui.separator();
+                        if matches.is_empty() {
+                            ui.label(
+                                RichText::new("No matching commands").color(palette.text_secondary),
+                            );
+                        }
+                        egui::ScrollArea::vertical()
+                            .max_height(320.0)
+                            .show(ui, |ui| {
+                                for (index, entry) in matches.iter().enumerate() {
+                                    let selected = index == self.selected;
+                                    let row = ui.horizontal(|ui| {
+                                        let label = ui.selectable_label(selected, entry.name);
+                                        if let Some(shortcut) = entry.shortcut {
+                                            ui.label(
+                                                RichText::new(shortcut)
+                                                    .small()
+                                                    .color(palette.text_secondary),
+                                            );
+                                        }
+                                        label
+                                    });
+                                    if selected && (up || down) {
+                                        row.inner.scroll_to_me(None);
+                                    }
+                                    if row.inner.clicked() {
+                                        chosen = Some(entry.action);
+                                    }
+                                }
+                            });
+                    });
+            });
+        if enter {
+            chosen = chosen.or_else(|| matches.get(self.selected).map(|entry| entry.action));
+        }
+        if chosen.is_some() {
+            self.open = false;
+        }
+        chosen
+    }
+}
@@ -1,11 +1,17 @@
-use crate::ui::{ThemeMode, ThemePalette};
+use crate::ui::{parse_hex_color, ThemeMode, ThemePalette};
 use anyhow::{Context, Result};
 use directories::BaseDirs;
 use egui::{
     self, Align, Color32, Frame, Grid, Id, Label, Layout, Margin, RichText, ScrollArea, Stroke,
     Vec2,
 };
-use patina_core::llm::LlmProviderKind;
+use patina_core::config::{
+    is_http_url, AiRuntimeSettings, AzureOpenAiSettings, OpenAiSettings, ProxySettings,
+    AZURE_OPENAI_API_KEY_VAR, AZURE_OPENAI_API_VERSION_VAR, AZURE_OPENAI_DEPLOYMENT_VAR,
+    AZURE_OPENAI_ENDPOINT_VAR, DEFAULT_OLLAMA_BASE_URL, DEFAULT_OPENAI_BASE_URL,
+    OPENAI_API_KEY_VAR,
+};
+use patina_core::llm::{LlmProviderKind, RetryPolicy};
 use patina_core::project::ProjectHandle;
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
@@ -18,6 +24,12 @@ use tracing::warn;
 use url::Url;
 
 const DEFAULT_MODEL_NAMES: [&str; 3] = ["gpt-5", "gpt-5-mini", "gpt-5 nano"];
+const FEEDBACK_DURATION: Duration = Duration::from_secs(4);
+const SELECTABLE_PROVIDERS: [LlmProviderKind; 3] = [
+    LlmProviderKind::OpenAi,
+    LlmProviderKind::AzureOpenAi,
+    LlmProviderKind::Ollama,
+];
 
 fn default_model_names() -> Vec<String> {
     DEFAULT_MODEL_NAMES
@@ -45,30 +57,65 @@ fn normalized_models(list: Vec<String>) -> Vec<String> {
     }
 }
 
-fn parse_models_input(input: &str) -> Vec<String> {
-    let mut items = Vec::new();
-    let mut seen = HashSet::new();
-    for segment in input.split([',', ';']) {
-        let trimmed = segment.trim();
-        if trimmed.is_empty() {
-            continue;
+/// Ordered, individually editable list of model names. The first entry is the default.
+struct ModelListEditor {
+    entries: Vec<String>,
+    new_entry: String,
+    bulk_input: String,
+}
+
+impl ModelListEditor {
+    fn from_models(models: &[String]) -> Self {
+        Self {
+            entries: models.to_vec(),
+            new_entry: String::new(),
+            bulk_input: String::new(),
         }
-        if seen.insert(trimmed.to_ascii_lowercase()) {
-            items.push(trimmed.to_string());
+    }
+
+    fn to_models(&self) -> Vec<String> {
+        normalized_models(self.entries.clone())
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.trim().eq_ignore_ascii_case(name))
+    }
+
+    fn push(&mut self, name: &str) -> bool {
+        let trimmed = name.trim();
+        if trimmed.is_empty() || self.contains(trimmed) {
+            return false;
         }
+        self.entries.push(trimmed.to_string());
+        true
     }
-    if items.is_empty() {
-        default_model_names()
-    } else {
-        items
+
+    /// Replace the list with `models`, keeping the current default first when the
+    /// provider still offers it.
+    fn replace(&mut self, models: Vec<String>) {
+        let default = self.entries.first().map(|name| name.trim().to_string());
+        self.entries = normalized_models(models);
+        if let Some(default) = default {
+            if let Some(index) = self
+                .entries
+                .iter()
+                .position(|entry| entry.eq_ignore_ascii_case(&default))
+            {
+                let entry = self.entries.remove(index);
+                self.entries.insert(0, entry);
+            }
+        }
     }
-}
 
-fn models_to_input(models: &[String]) -> String {
-    if models.is_empty() {
-        String::new()
-    } else {
-        models.join(", ")
+    fn import_bulk(&mut self) -> bool {
+        let input = std::mem::take(&mut self.bulk_input);
+        let mut added = false;
+        for segment in input.split([',', ';', '\n']) {
+            added |= self.push(segment);
+        }
+        added
     }
 }
 
@@ -82,6 +129,19 @@ fn ensure_mapping(value: &mut Value) -> &mut Mapping {
     }
 }
 
+/// The `app` mapping of a patina.yaml document, created if missing.
+fn app_mapping(document: &mut Value) -> &mut Mapping {
+    let root = ensure_mapping(document);
+    if !root.contains_key("app") {
+        root.insert("app".into(), Value::Mapping(Mapping::new()));
+    }
+    ensure_mapping(root.get_mut("app").expect("inserted above"))
+}
+
+fn default_ollama_base_url() -> String {
+    DEFAULT_OLLAMA_BASE_URL.to_string()
+}
+
 fn default_provider() -> LlmProviderKind {
     LlmProviderKind::OpenAi
 }
@@ -91,6 +151,7 @@ pub struct ProviderSelection {
     pub provider: LlmProviderKind,
     pub openai: OpenAiSettingsData,
     pub azure: AzureSettingsData,
+    pub ollama: OllamaSettingsData,
 }
 
 impl Default for ProviderSelection {
@@ -99,6 +160,7 @@ impl Default for ProviderSelection {
             provider: default_provider(),
             openai: OpenAiSettingsData::default(),
             azure: AzureSettingsData::default(),
+            ollama: OllamaSettingsData::default(),
         }
     }
 }
@@ -106,6 +168,12 @@ impl Default for ProviderSelection {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OpenAiSettingsData {
     pub api_key: String,
+    /// Host of an OpenAI-compatible gateway; empty means api.openai.com.
+    pub base_url: String,
+    /// `OpenAI-Organization` header; empty sends none.
+    pub organization: String,
+    /// `OpenAI-Project` header; empty sends none.
+    pub project: String,
     pub available_models: Vec<String>,
 }
 
@@ -113,6 +181,9 @@ impl Default for OpenAiSettingsData {
     fn default() -> Self {
         Self {
             api_key: String::new(),
+            base_url: String::new(),
+            organization: String::new(),
+            project: String::new(),
             available_models: default_model_names(),
         }
     }
@@ -122,6 +193,9 @@ impl OpenAiSettingsData {
     fn from_file(file: FileOpenAiSettings) -> Self {
         Self {
             api_key: file.api_key,
+            base_url: file.base_url,
+            organization: file.organization,
+            project: file.project,
             available_models: normalized_models(file.available_models),
         }
     }
@@ -129,6 +203,9 @@ impl OpenAiSettingsData {
     fn to_file(&self) -> FileOpenAiSettings {
         FileOpenAiSettings {
             api_key: self.api_key.clone(),
+            base_url: self.base_url.clone(),
+            organization: self.organization.clone(),
+            project: self.project.clone(),
             available_models: if self.available_models.is_empty() {
                 default_model_names()
             } else {
@@ -185,10 +262,77 @@ impl AzureSettingsData {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OllamaSettingsData {
+    pub base_url: String,
+    pub model: String,
+}
+
+impl Default for OllamaSettingsData {
+    fn default() -> Self {
+        Self {
+            base_url: default_ollama_base_url(),
+            model: String::new(),
+        }
+    }
+}
+
+impl OllamaSettingsData {
+    fn from_file(file: FileOllamaSettings) -> Self {
+        Self {
+            base_url: file.base_url,
+            model: file.model,
+        }
+    }
+
+    fn to_file(&self) -> FileOllamaSettings {
+        FileOllamaSettings {
+            base_url: self.base_url.clone(),
+            model: self.model.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AppSettingsData {
     pub theme: ThemeMode,
     pub provider: ProviderSelection,
+    /// Show a desktop notification when a reply finishes while the window is unfocused.
+    pub notify_on_reply: bool,
+    pub colors: ColorOverrides,
+}
+
+/// `#RRGGBB` colors laid over the built-in light and dark palettes; an empty field
+/// keeps the built-in color.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorOverrides {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub accent: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_bubble: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub assistant_bubble: String,
+}
+
+impl ColorOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.accent.is_empty() && self.user_bubble.is_empty() && self.assistant_bubble.is_empty()
+    }
+
+    /// Whether every color that is set parses.
+    pub fn is_valid(&self) -> bool {
+        [&self.accent, &self.user_bubble, &self.assistant_bubble]
+            .into_iter()
+            .all(|hex| hex.is_empty() || parse_hex_color(hex).is_some())
+    }
+
+    fn trimmed(&self) -> Self {
+        Self {
+            accent: self.accent.trim().to_string(),
+            user_bubble: self.user_bubble.trim().to_string(),
+            assistant_bubble: self.assistant_bubble.trim().to_string(),
+        }
+    }
 }
 
 impl Default for AppSettingsData {
@@ -196,6 +340,8 @@ impl Default for AppSettingsData {
         Self {
             theme: ThemeMode::System,
             provider: ProviderSelection::default(),
+            notify_on_reply: false,
+            colors: ColorOverrides::default(),
         }
     }
 }
@@ -206,10 +352,13 @@ impl AppSettingsData {
             provider: file.provider,
             openai: OpenAiSettingsData::from_file(file.openai),
             azure: AzureSettingsData::from_file(file.azure),
+            ollama: OllamaSettingsData::from_file(file.ollama),
         };
         Self {
             theme: file.theme,
             provider,
+            notify_on_reply: file.notify_on_reply,
+            colors: file.colors,
         }
     }
 
@@ -219,6 +368,9 @@ impl AppSettingsData {
             provider: self.provider.provider,
             openai: self.provider.openai.to_file(),
             azure: self.provider.azure.to_file(),
+            ollama: self.provider.ollama.to_file(),
+            notify_on_reply: self.notify_on_reply,
+            colors: self.colors.clone(),
         }
     }
 }
@@ -243,12 +395,14 @@ impl ProjectSettingsData {
         let provider_kind = file.provider.unwrap_or_else(default_provider);
         let openai = file.openai.unwrap_or_default();
         let azure = file.azure.unwrap_or_default();
+        let ollama = file.ollama.unwrap_or_default();
         Self {
             inherit_app: file.inherit_app,
             provider: ProviderSelection {
                 provider: provider_kind,
                 openai: OpenAiSettingsData::from_file(openai),
                 azure: AzureSettingsData::from_file(azure),
+                ollama: OllamaSettingsData::from_file(ollama),
             },
         }
     }
@@ -260,6 +414,7 @@ impl ProjectSettingsData {
                 provider: None,
                 openai: None,
                 azure: None,
+                ollama: None,
             }
         } else {
             ProjectSettingsFile {
@@ -267,6 +422,7 @@ impl ProjectSettingsData {
                 provider: Some(self.provider.provider),
                 openai: Some(self.provider.openai.to_file()),
                 azure: Some(self.provider.azure.to_file()),
+                ollama: Some(self.provider.ollama.to_file()),
             }
         }
     }
@@ -276,6 +432,12 @@ impl ProjectSettingsData {
 struct FileOpenAiSettings {
     #[serde(default)]
     api_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    base_url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    organization: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    project: String,
     #[serde(default = "default_model_names")]
     available_models: Vec<String>,
 }
@@ -284,6 +446,9 @@ impl Default for FileOpenAiSettings {
     fn default() -> Self {
         Self {
             api_key: String::new(),
+            base_url: String::new(),
+            organization: String::new(),
+            project: String::new(),
             available_models: default_model_names(),
         }
     }
@@ -315,6 +480,23 @@ impl Default for FileAzureSettings {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileOllamaSettings {
+    #[serde(default = "default_ollama_base_url")]
+    base_url: String,
+    #[serde(default)]
+    model: String,
+}
+
+impl Default for FileOllamaSettings {
+    fn default() -> Self {
+        Self {
+            base_url: default_ollama_base_url(),
+            model: String::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct AppSettingsFile {
     #[serde(default)]
@@ -325,6 +507,12 @@ struct AppSettingsFile {
     openai: FileOpenAiSettings,
     #[serde(default, rename = "azure_openai")]
     azure: FileAzureSettings,
+    #[serde(default)]
+    ollama: FileOllamaSettings,
+    #[serde(default)]
+    notify_on_reply: bool,
+    #[serde(default, skip_serializing_if = "ColorOverrides::is_empty")]
+    colors: ColorOverrides,
 }
 
 impl Default for AppSettingsFile {
@@ -334,6 +522,9 @@ impl Default for AppSettingsFile {
             provider: default_provider(),
             openai: FileOpenAiSettings::default(),
             azure: FileAzureSettings::default(),
+            ollama: FileOllamaSettings::default(),
+            notify_on_reply: false,
+            colors: ColorOverrides::default(),
         }
     }
 }
@@ -352,6 +543,8 @@ struct ProjectSettingsFile {
         skip_serializing_if = "Option::is_none"
     )]
     azure: Option<FileAzureSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ollama: Option<FileOllamaSettings>,
 }
 
 impl Default for ProjectSettingsFile {
@@ -361,6 +554,7 @@ impl Default for ProjectSettingsFile {
             provider: None,
             openai: None,
             azure: None,
+            ollama: None,
         }
     }
 }
@@ -389,6 +583,16 @@ impl GlobalSettingsStore {
         &self.data
     }
 
+    /// The proxy provider requests go through; see [`configured_proxy`].
+    pub fn proxy(&self) -> Option<ProxySettings> {
+        configured_proxy(&self.document)
+    }
+
+    /// The profile the provider settings are read from and saved to, if one is active.
+    pub fn active_profile(&self) -> Option<&str> {
+        active_profile_name(&self.document)
+    }
+
     pub fn set(&mut self, data: AppSettingsData) {
         self.data = data;
         self.dirty = true;
@@ -399,9 +603,48 @@ impl GlobalSettingsStore {
             return Ok(());
         }
         let mut value = self.document.clone();
-        let mapping = ensure_mapping(&mut value);
-        let serialized = serde_yaml::to_value(self.data.to_file())?;
-        mapping.insert(Value::String("app".to_string()), serialized);
+        let profile = active_profile_name(&value).map(str::to_string);
+        // Keys the form does not edit, such as `profiles` or `retry`, are kept.
+        let app = app_mapping(&mut value);
+        if let Value::Mapping(mut fields) = serde_yaml::to_value(self.data.to_file())? {
+            // With a profile active, its section holds the provider settings in use.
+            if let Some(profile) = profile.and_then(|name| profile_mapping(app, &name)) {
+                for key in PROFILE_KEYS {
+                    if let Some(field) = fields.remove(key) {
+                        profile.insert(key.into(), field);
+                    }
+                }
+            }
+            app.extend(fields);
+        }
+        if self.data.colors.is_empty() {
+            app.remove("colors");
+        }
+        self.write(value)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Switch `app.active_profile` to `name`, or back to the top-level provider settings
+    /// when `None`, and write the file.
+    pub fn set_active_profile(&mut self, name: Option<&str>) -> Result<()> {
+        let mut value = self.document.clone();
+        let app = app_mapping(&mut value);
+        match name {
+            Some(name) => {
+                app.insert("active_profile".into(), name.into());
+            }
+            None => {
+                app.remove("active_profile");
+            }
+        }
+        self.write(value)?;
+        self.data = extract_app_settings(&self.document);
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn write(&mut self, value: Value) -> Result<()> {
         if let Some(parent) = self.path.parent() {
             fs::create_dir_all(parent).with_context(|| {
                 format!("failed to create config directory at {}", parent.display())
@@ -411,7 +654,6 @@ impl GlobalSettingsStore {
         fs::write(&self.path, contents)
             .with_context(|| format!("failed to write settings to {}", self.path.display()))?;
         self.document = value;
-        self.dirty = false;
         Ok(())
     }
 }
@@ -516,15 +758,67 @@ fn ensure_toml_table(value: &mut TomlValue) -> &mut toml::map::Map<String, TomlV
         .expect("value converted to table in ensure_toml_table")
 }
 
+/// The `app` keys a profile replaces while it is active.
+const PROFILE_KEYS: [&str; 4] = ["provider", "openai", "azure_openai", "ollama"];
+
+/// `app.active_profile`, when it names a section under `app.profiles`.
+fn active_profile_name(document: &Value) -> Option<&str> {
+    let app = document.get("app")?;
+    let name = app.get("active_profile")?.as_str()?;
+    app.get("profiles")?.get(name)?.as_mapping().map(|_| name)
+}
+
+fn profile_mapping<'a>(app: &'a mut Mapping, name: &str) -> Option<&'a mut Mapping> {
+    app.get_mut("profiles")?.get_mut(name)?.as_mapping_mut()
+}
+
 fn extract_app_settings(document: &Value) -> AppSettingsData {
-    let section = document
+    let mut section = document
         .get("app")
         .cloned()
         .unwrap_or(Value::Mapping(Mapping::new()));
+    if let Some(name) = active_profile_name(document).map(str::to_string) {
+        if let Value::Mapping(app) = &mut section {
+            let profile = profile_mapping(app, &name).cloned().unwrap_or_default();
+            for key in PROFILE_KEYS {
+                match profile.get(key) {
+                    Some(field) => app.insert(key.into(), field.clone()),
+                    None => app.remove(key),
+                };
+            }
+        }
+    }
     let file: AppSettingsFile = serde_yaml::from_value(section).unwrap_or_default();
     AppSettingsData::from_file(file)
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct FileProxySettings {
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+}
+
+/// The proxy patina.yaml sets for provider requests: the active profile's, otherwise
+/// `app.proxy`. The form does not edit it, but requests made from the form use it.
+fn configured_proxy(document: &Value) -> Option<ProxySettings> {
+    let app = document.get("app")?;
+    let profile_proxy = app
+        .get("active_profile")
+        .and_then(Value::as_str)
+        .and_then(|name| app.get("profiles")?.get(name)?.get("proxy"));
+    let section = profile_proxy.or_else(|| app.get("proxy"))?;
+    let file: FileProxySettings = serde_yaml::from_value(section.clone()).ok()?;
+    Some(ProxySettings {
+        url: non_empty(&file.url)?,
+        username: non_empty(&file.username),
+        password: non_empty(&file.password),
+    })
+}
+
 fn extract_project_settings(document: &TomlValue) -> ProjectSettingsData {
     if let Some(section) = document.get("settings") {
         match section.clone().try_into::<ProjectSettingsFile>() {
@@ -606,15 +900,38 @@ impl Feedback {
     }
 
     fn is_fresh(&self) -> bool {
-        self.created.elapsed() < Duration::from_secs(4)
+        self.created.elapsed() < FEEDBACK_DURATION
     }
 }
 
+/// Which form of the settings window something belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsForm {
+    App,
+    Project,
+}
+
+/// The credentials currently in a form, for the app to list the provider's models with.
+pub struct ModelFetchRequest {
+    pub form: SettingsForm,
+    pub settings: AiRuntimeSettings,
+}
+
+/// The unsaved provider settings of a form, for the app to send a test request with.
+pub struct ConnectionTestRequest {
+    pub form: SettingsForm,
+    pub settings: AiRuntimeSettings,
+}
+
 #[derive(Default)]
 pub struct SettingsResponse {
     pub app_saved: bool,
     pub project_saved: bool,
     pub theme_changed: Option<ThemeMode>,
+    /// The sidebar grouping toggle changed; unlike the form fields it applies right away.
+    pub group_chats_by_date_changed: Option<bool>,
+    pub fetch_models: Option<ModelFetchRequest>,
+    pub test_connection: Option<ConnectionTestRequest>,
 }
 
 pub struct SettingsPanel {
@@ -622,6 +939,9 @@ pub struct SettingsPanel {
     project: Option<ProjectSettingsStore>,
     project_name: Option<String>,
     state: ModalState,
+    fetch_request: Option<ModelFetchRequest>,
+    test_request: Option<ConnectionTestRequest>,
+    group_chats_by_date: bool,
 }
 
 impl SettingsPanel {
@@ -637,13 +957,33 @@ impl SettingsPanel {
                 app: app_form,
                 project: None,
             },
+            fetch_request: None,
+            test_request: None,
+            group_chats_by_date: false,
         }
     }
 
+    /// Keep the sidebar grouping checkbox in step with the View menu.
+    pub fn set_group_chats_by_date(&mut self, enabled: bool) {
+        self.group_chats_by_date = enabled;
+    }
+
     pub fn app_settings(&self) -> &AppSettingsData {
         self.global.data()
     }
 
+    /// The proxy patina.yaml configures for provider requests.
+    pub fn proxy(&self) -> Option<ProxySettings> {
+        self.global.proxy()
+    }
+
+    /// See [`GlobalSettingsStore::set_active_profile`].
+    pub fn set_active_profile(&mut self, name: Option<&str>) -> Result<()> {
+        self.global.set_active_profile(name)?;
+        self.state.app.reset(self.global.data().clone());
+        Ok(())
+    }
+
     pub fn open(&mut self) {
         self.state.app.reset(self.global.data().clone());
         if let Some(project_store) = self.project.as_ref() {
@@ -706,6 +1046,7 @@ impl SettingsPanel {
                         if app_section.theme.is_some() {
                             result.theme_changed = app_section.theme;
                         }
+                        result.group_chats_by_date_changed = app_section.group_chats_by_date;
                         ui.add_space(24.0);
                         let project_section = self.render_project_settings(ui, palette);
                         if project_section.saved {
@@ -716,10 +1057,64 @@ impl SettingsPanel {
         if !open {
             self.state.open = false;
         }
+        result.fetch_models = self.fetch_request.take();
+        result.test_connection = self.test_request.take();
 
         result
     }
 
+    /// Fill a form's model list with the outcome of a [`ModelFetchRequest`].
+    pub fn finish_model_fetch(&mut self, form: SettingsForm, result: Result<Vec<String>>) {
+        let editor = match form {
+            SettingsForm::App => Some(&mut self.state.app.editor.provider),
+            SettingsForm::Project => self
+                .state
+                .project
+                .as_mut()
+                .map(|project| &mut project.editor.provider),
+        };
+        let Some(editor) = editor else {
+            return;
+        };
+        editor.fetch.in_flight = false;
+        match result {
+            Ok(models) if models.is_empty() => {
+                editor.fetch.warning = Some("The provider returned no models".to_string());
+            }
+            Ok(models) => {
+                editor.fetch.warning = None;
+                match editor.provider {
+                    LlmProviderKind::AzureOpenAi => editor.azure.models.replace(models),
+                    _ => editor.openai.models.replace(models),
+                }
+            }
+            Err(err) => {
+                editor.fetch.warning = Some(format!("Couldn't fetch models: {err:#}"));
+            }
+        }
+    }
+
+    /// Report the outcome of a [`ConnectionTestRequest`] in its form's feedback line.
+    pub fn finish_connection_test(&mut self, form: SettingsForm, result: Result<Duration>) {
+        let (editor, feedback) = match form {
+            SettingsForm::App => (
+                &mut self.state.app.editor.provider,
+                &mut self.state.app.feedback,
+            ),
+            SettingsForm::Project => match self.state.project.as_mut() {
+                Some(project) => (&mut project.editor.provider, &mut project.feedback),
+                None => return,
+            },
+        };
+        editor.test.in_flight = false;
+        *feedback = Some(match result {
+            Ok(latency) => {
+                Feedback::success(format!("Connection works ({} ms)", latency.as_millis()))
+            }
+            Err(err) => Feedback::failure(format!("Connection failed: {err:#}")),
+        });
+    }
+
     fn render_app_settings(
         &mut self,
         ui: &mut egui::Ui,
@@ -739,6 +1134,7 @@ impl SettingsPanel {
                 ui.heading("App settings");
                 if let Some(feedback) = self.state.app.feedback.as_ref() {
                     if feedback.is_fresh() {
+                        ui.ctx().request_repaint_after(FEEDBACK_DURATION);
                         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                             let color = if feedback.success {
                                 Color32::from_rgb(46, 125, 50)
@@ -750,6 +1146,15 @@ impl SettingsPanel {
                     }
                 }
             });
+            if let Some(profile) = self.global.active_profile() {
+                ui.add_space(4.0);
+                ui.label(
+                    RichText::new(format!(
+                        "Provider settings below belong to the active profile “{profile}”."
+                    ))
+                    .color(palette.text_secondary),
+                );
+            }
             ui.add_space(12.0);
             let mut dirty = false;
             Grid::new("app_settings_grid")
@@ -784,8 +1189,7 @@ impl SettingsPanel {
                     egui::ComboBox::from_id_source("app_provider")
                         .selected_text(provider_before_label(previous_provider))
                         .show_ui(ui, |ui| {
-                            for candidate in [LlmProviderKind::OpenAi, LlmProviderKind::AzureOpenAi]
-                            {
+                            for candidate in SELECTABLE_PROVIDERS {
                                 let label = provider_before_label(candidate);
                                 if ui
                                     .selectable_value(&mut selection, candidate, label)
@@ -799,6 +1203,54 @@ impl SettingsPanel {
                         self.state.app.editor.provider.provider = selection;
                     }
                     ui.end_row();
+
+                    ui.label(RichText::new("Notifications").strong());
+                    if ui
+                        .checkbox(
+                            &mut self.state.app.editor.notify_on_reply,
+                            "Notify me when a reply finishes in the background",
+                        )
+                        .changed()
+                    {
+                        dirty = true;
+                    }
+                    ui.end_row();
+
+                    ui.label(RichText::new("Sidebar").strong());
+                    if ui
+                        .checkbox(&mut self.group_chats_by_date, "Group chats by date")
+                        .on_hover_text(
+                            "Show Today, Yesterday, This week and Older headers. \
+                             Applies right away.",
+                        )
+                        .changed()
+                    {
+                        outcome.group_chats_by_date = Some(self.group_chats_by_date);
+                    }
+                    ui.end_row();
+
+                    let colors = &mut self.state.app.editor.colors;
+                    for (label, hex) in [
+                        ("Accent color", &mut colors.accent),
+                        ("Your messages", &mut colors.user_bubble),
+                        ("Assistant messages", &mut colors.assistant_bubble),
+                    ] {
+                        ui.label(RichText::new(label).strong());
+                        if render_color_field(ui, palette, hex) {
+                            dirty = true;
+                        }
+                        ui.end_row();
+                    }
+                    ui.label("");
+                    if ui
+                        .add_enabled(!colors.is_empty(), egui::Button::new("Reset colors"))
+                        .on_hover_text("Go back to the built-in light and dark palettes")
+                        .clicked()
+                    {
+                        *colors = ColorOverrides::default();
+                        dirty = true;
+                    }
+                    ui.end_row();
                 });
 
             ui.add_space(16.0);
@@ -811,11 +1263,27 @@ impl SettingsPanel {
                 false,
                 &mut dirty,
             );
+            let proxy = self.global.proxy();
+            if let Some(request) = take_fetch_request(
+                SettingsForm::App,
+                &mut self.state.app.editor.provider,
+                proxy.as_ref(),
+            ) {
+                self.fetch_request = Some(request);
+            }
+            if let Some(request) = take_connection_test(
+                SettingsForm::App,
+                &mut self.state.app.editor.provider,
+                &mut self.state.app.feedback,
+                proxy.as_ref(),
+            ) {
+                self.test_request = Some(request);
+            }
 
             ui.add_space(20.0);
             let data = self.state.app.current_data();
             let is_dirty = dirty || data != self.state.app.original;
-            let can_save = validation.is_valid();
+            let can_save = validation.is_valid() && data.colors.is_valid();
             ui.horizontal(|ui| {
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     let save_enabled = is_dirty && can_save;
@@ -873,6 +1341,7 @@ impl SettingsPanel {
                 if let Some(form) = self.state.project.as_ref() {
                     if let Some(feedback) = form.feedback.as_ref() {
                         if feedback.is_fresh() {
+                            ui.ctx().request_repaint_after(FEEDBACK_DURATION);
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                 let color = if feedback.success {
                                     Color32::from_rgb(46, 125, 50)
@@ -929,8 +1398,7 @@ impl SettingsPanel {
                     egui::ComboBox::from_id_source("project_provider")
                         .selected_text(provider_before_label(previous_provider))
                         .show_ui(ui, |ui| {
-                            for candidate in [LlmProviderKind::OpenAi, LlmProviderKind::AzureOpenAi]
-                            {
+                            for candidate in SELECTABLE_PROVIDERS {
                                 ui.selectable_value(
                                     &mut selection,
                                     candidate,
@@ -956,6 +1424,22 @@ impl SettingsPanel {
                 form.editor.inherit_app,
                 &mut dirty,
             );
+            let proxy = self.global.proxy();
+            if let Some(request) = take_fetch_request(
+                SettingsForm::Project,
+                &mut form.editor.provider,
+                proxy.as_ref(),
+            ) {
+                self.fetch_request = Some(request);
+            }
+            if let Some(request) = take_connection_test(
+                SettingsForm::Project,
+                &mut form.editor.provider,
+                &mut form.feedback,
+                proxy.as_ref(),
+            ) {
+                self.test_request = Some(request);
+            }
 
             ui.add_space(20.0);
             let data = form.current_data();
@@ -1093,6 +1577,8 @@ impl ProjectFormState {
 struct AppFormEditor {
     theme: ThemeMode,
     provider: ProviderEditor,
+    notify_on_reply: bool,
+    colors: ColorOverrides,
 }
 
 impl AppFormEditor {
@@ -1100,6 +1586,8 @@ impl AppFormEditor {
         Self {
             theme: data.theme,
             provider: ProviderEditor::from_selection(&data.provider),
+            notify_on_reply: data.notify_on_reply,
+            colors: data.colors.clone(),
         }
     }
 
@@ -1107,6 +1595,8 @@ impl AppFormEditor {
         AppSettingsData {
             theme: self.theme,
             provider: self.provider.to_selection(),
+            notify_on_reply: self.notify_on_reply,
+            colors: self.colors.trimmed(),
         }
     }
 }
@@ -1136,6 +1626,9 @@ struct ProviderEditor {
     provider: LlmProviderKind,
     openai: OpenAiEditor,
     azure: AzureEditor,
+    ollama: OllamaEditor,
+    fetch: ModelFetchState,
+    test: ConnectionTestState,
     details_expanded: bool,
 }
 
@@ -1145,6 +1638,9 @@ impl ProviderEditor {
             provider: selection.provider,
             openai: OpenAiEditor::from_data(&selection.openai),
             azure: AzureEditor::from_data(&selection.azure),
+            ollama: OllamaEditor::from_data(&selection.ollama),
+            fetch: ModelFetchState::default(),
+            test: ConnectionTestState::default(),
             details_expanded: true,
         }
     }
@@ -1154,6 +1650,7 @@ impl ProviderEditor {
             provider: self.provider,
             openai: self.openai.to_data(),
             azure: self.azure.to_data(),
+            ollama: self.ollama.to_data(),
         }
     }
 }
@@ -1161,7 +1658,10 @@ impl ProviderEditor {
 struct OpenAiEditor {
     api_key: String,
     reveal: bool,
-    models_input: String,
+    base_url: String,
+    organization: String,
+    project: String,
+    models: ModelListEditor,
 }
 
 impl OpenAiEditor {
@@ -1169,14 +1669,20 @@ impl OpenAiEditor {
         Self {
             api_key: data.api_key.clone(),
             reveal: false,
-            models_input: models_to_input(&data.available_models),
+            base_url: data.base_url.clone(),
+            organization: data.organization.clone(),
+            project: data.project.clone(),
+            models: ModelListEditor::from_models(&data.available_models),
         }
     }
 
     fn to_data(&self) -> OpenAiSettingsData {
         OpenAiSettingsData {
             api_key: self.api_key.trim().to_string(),
-            available_models: parse_models_input(&self.models_input),
+            base_url: self.base_url.trim().to_string(),
+            organization: self.organization.trim().to_string(),
+            project: self.project.trim().to_string(),
+            available_models: self.models.to_models(),
         }
     }
 }
@@ -1187,7 +1693,7 @@ struct AzureEditor {
     endpoint: String,
     api_version: String,
     deployment_name: String,
-    models_input: String,
+    models: ModelListEditor,
 }
 
 impl AzureEditor {
@@ -1198,7 +1704,7 @@ impl AzureEditor {
             endpoint: data.endpoint.clone(),
             api_version: data.api_version.clone(),
             deployment_name: data.deployment_name.clone(),
-            models_input: models_to_input(&data.available_models),
+            models: ModelListEditor::from_models(&data.available_models),
         }
     }
 
@@ -1208,7 +1714,41 @@ impl AzureEditor {
             endpoint: self.endpoint.trim().to_string(),
             api_version: self.api_version.trim().to_string(),
             deployment_name: self.deployment_name.trim().to_string(),
-            available_models: parse_models_input(&self.models_input),
+            available_models: self.models.to_models(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ModelFetchState {
+    requested: bool,
+    in_flight: bool,
+    warning: Option<String>,
+}
+
+#[derive(Default)]
+struct ConnectionTestState {
+    requested: bool,
+    in_flight: bool,
+}
+
+struct OllamaEditor {
+    base_url: String,
+    model: String,
+}
+
+impl OllamaEditor {
+    fn from_data(data: &OllamaSettingsData) -> Self {
+        Self {
+            base_url: data.base_url.clone(),
+            model: data.model.clone(),
+        }
+    }
+
+    fn to_data(&self) -> OllamaSettingsData {
+        OllamaSettingsData {
+            base_url: self.base_url.trim().to_string(),
+            model: self.model.trim().to_string(),
         }
     }
 }
@@ -1216,23 +1756,32 @@ impl AzureEditor {
 #[derive(Default)]
 struct ProviderValidation {
     openai_key_warning: Option<String>,
+    openai_base_url_error: Option<String>,
     azure_key_warning: Option<String>,
     azure_endpoint_error: Option<String>,
     azure_version_error: Option<String>,
     azure_deployment_error: Option<String>,
+    ollama_base_url_error: Option<String>,
+    ollama_model_error: Option<String>,
+    /// Fields left to environment variables, which take precedence at runtime.
+    env_hints: Vec<String>,
 }
 
 impl ProviderValidation {
     fn is_valid(&self) -> bool {
-        self.azure_endpoint_error.is_none()
+        self.openai_base_url_error.is_none()
+            && self.azure_endpoint_error.is_none()
             && self.azure_version_error.is_none()
             && self.azure_deployment_error.is_none()
+            && self.ollama_base_url_error.is_none()
+            && self.ollama_model_error.is_none()
     }
 }
 
 struct AppSectionResult {
     saved: bool,
     theme: Option<ThemeMode>,
+    group_chats_by_date: Option<bool>,
 }
 
 impl AppSectionResult {
@@ -1240,6 +1789,7 @@ impl AppSectionResult {
         Self {
             saved: false,
             theme: None,
+            group_chats_by_date: None,
         }
     }
 }
@@ -1254,42 +1804,242 @@ impl ProjectSectionResult {
     }
 }
 
-fn validate_provider(provider: LlmProviderKind, editor: &ProviderEditor) -> ProviderValidation {
+/// Check the form's values, reading credential overrides through `env`; a field whose
+/// environment variable is set is satisfied and gets a hint instead of an error.
+fn validate_provider(
+    provider: LlmProviderKind,
+    editor: &ProviderEditor,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> ProviderValidation {
     let mut validation = ProviderValidation::default();
+    let mut from_env = |label: &str, var: &str| {
+        let set = env_value(env, var).is_some();
+        if set {
+            validation
+                .env_hints
+                .push(format!("{label} from environment ({var})"));
+        }
+        set
+    };
     match provider {
         LlmProviderKind::OpenAi => {
-            if editor.openai.api_key.trim().is_empty() {
+            let key_from_env = from_env("API key", OPENAI_API_KEY_VAR);
+            if !key_from_env && editor.openai.api_key.trim().is_empty() {
                 validation.openai_key_warning = Some("API key is empty".to_string());
             }
+            let base_url = editor.openai.base_url.trim();
+            if !base_url.is_empty() && !is_http_url(base_url) {
+                validation.openai_base_url_error =
+                    Some("Base URL must start with http:// or https://".to_string());
+            }
         }
         LlmProviderKind::AzureOpenAi => {
-            if editor.azure.api_key.trim().is_empty() {
+            let key_from_env = from_env("API key", AZURE_OPENAI_API_KEY_VAR);
+            let endpoint_from_env = from_env("Endpoint", AZURE_OPENAI_ENDPOINT_VAR);
+            let version_from_env = from_env("API version", AZURE_OPENAI_API_VERSION_VAR);
+            let deployment_from_env = from_env("Deployment name", AZURE_OPENAI_DEPLOYMENT_VAR);
+            if !key_from_env && editor.azure.api_key.trim().is_empty() {
                 validation.azure_key_warning = Some("API key is empty".to_string());
             }
             let endpoint = editor.azure.endpoint.trim();
-            if endpoint.is_empty() {
-                validation.azure_endpoint_error = Some("Endpoint is required".to_string());
-            } else if Url::parse(endpoint).is_err() {
-                validation.azure_endpoint_error = Some("Endpoint must be a valid URL".to_string());
+            if !endpoint_from_env {
+                if endpoint.is_empty() {
+                    validation.azure_endpoint_error = Some("Endpoint is required".to_string());
+                } else if Url::parse(endpoint).is_err() {
+                    validation.azure_endpoint_error =
+                        Some("Endpoint must be a valid URL".to_string());
+                }
             }
-            if editor.azure.api_version.trim().is_empty() {
+            if !version_from_env && editor.azure.api_version.trim().is_empty() {
                 validation.azure_version_error = Some("API version is required".to_string());
             }
             let deployment_raw = editor.azure.deployment_name.as_str();
-            if !deployment_raw.is_empty() && deployment_raw.trim().is_empty() {
+            if !deployment_from_env
+                && !deployment_raw.is_empty()
+                && deployment_raw.trim().is_empty()
+            {
                 validation.azure_deployment_error =
                     Some("Deployment name cannot be whitespace".to_string());
             }
         }
+        LlmProviderKind::Ollama => {
+            let base_url = editor.ollama.base_url.trim();
+            if base_url.is_empty() {
+                validation.ollama_base_url_error = Some("Base URL is required".to_string());
+            } else if Url::parse(base_url).is_err() {
+                validation.ollama_base_url_error = Some("Base URL must be a valid URL".to_string());
+            }
+            if editor.ollama.model.trim().is_empty() {
+                validation.ollama_model_error = Some("Model is required".to_string());
+            }
+        }
         LlmProviderKind::Mock => {}
     }
     validation
 }
 
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// The value of `var` in `env`, unless it is unset or blank.
+fn env_value(env: &dyn Fn(&str) -> Option<String>, var: &str) -> Option<String> {
+    env(var).as_deref().and_then(non_empty)
+}
+
+/// `var` from `env` when set, else the form's `value`, the same precedence the
+/// runtime settings use.
+fn env_or_field(env: &dyn Fn(&str) -> Option<String>, var: &str, value: &str) -> String {
+    env_value(env, var).unwrap_or_else(|| value.trim().to_string())
+}
+
+/// Turn a click on "Fetch models" into a request, or explain why it cannot be made.
+fn take_fetch_request(
+    form: SettingsForm,
+    editor: &mut ProviderEditor,
+    proxy: Option<&ProxySettings>,
+) -> Option<ModelFetchRequest> {
+    if !std::mem::take(&mut editor.fetch.requested) {
+        return None;
+    }
+    match editor_settings(
+        editor,
+        "fetch models",
+        &|name| std::env::var(name).ok(),
+        proxy,
+    ) {
+        Ok(settings) => {
+            editor.fetch.in_flight = true;
+            editor.fetch.warning = None;
+            Some(ModelFetchRequest { form, settings })
+        }
+        Err(warning) => {
+            editor.fetch.warning = Some(warning);
+            None
+        }
+    }
+}
+
+/// Turn a click on "Test connection" into a request; a form that cannot make one says
+/// why in its feedback line.
+fn take_connection_test(
+    form: SettingsForm,
+    editor: &mut ProviderEditor,
+    feedback: &mut Option<Feedback>,
+    proxy: Option<&ProxySettings>,
+) -> Option<ConnectionTestRequest> {
+    if !std::mem::take(&mut editor.test.requested) {
+        return None;
+    }
+    match connection_test_settings(editor, proxy) {
+        Ok(settings) => {
+            editor.test.in_flight = true;
+            *feedback = None;
+            Some(ConnectionTestRequest { form, settings })
+        }
+        Err(message) => {
+            *feedback = Some(Feedback::failure(message));
+            None
+        }
+    }
+}
+
+/// The form's settings with the model a test request is sent to: the first listed
+/// model for OpenAI, the deployment for Azure.
+fn connection_test_settings(
+    editor: &ProviderEditor,
+    proxy: Option<&ProxySettings>,
+) -> Result<AiRuntimeSettings, String> {
+    let mut settings = editor_settings(
+        editor,
+        "test the connection",
+        &|name| std::env::var(name).ok(),
+        proxy,
+    )?;
+    if let Some(openai) = settings.openai.as_mut() {
+        openai.model = editor.openai.models.to_models().into_iter().next();
+    }
+    if let Some(azure) = settings.azure.as_ref() {
+        if azure.deployment_name.is_empty() {
+            return Err("Enter a deployment name to test the connection".to_string());
+        }
+    }
+    Ok(settings)
+}
+
+/// Runtime settings built from the unsaved values in `editor` and the configured
+/// `proxy`, with credential overrides read through `env`; `action` completes the
+/// message shown when a required value is missing.
+fn editor_settings(
+    editor: &ProviderEditor,
+    action: &str,
+    env: &dyn Fn(&str) -> Option<String>,
+    proxy: Option<&ProxySettings>,
+) -> Result<AiRuntimeSettings, String> {
+    let mut settings = AiRuntimeSettings {
+        provider: editor.provider,
+        openai: None,
+        azure: None,
+        ollama: None,
+        model: None,
+        retry: RetryPolicy::none(),
+        proxy: proxy.cloned(),
+    };
+    match editor.provider {
+        LlmProviderKind::OpenAi => {
+            let api_key = env_or_field(env, OPENAI_API_KEY_VAR, &editor.openai.api_key);
+            if api_key.is_empty() {
+                return Err(format!("Enter an API key to {action}"));
+            }
+            let base_url = editor.openai.base_url.trim();
+            if !base_url.is_empty() && !is_http_url(base_url) {
+                return Err(format!("Enter an http:// or https:// base URL to {action}"));
+            }
+            settings.openai = Some(OpenAiSettings {
+                api_key,
+                model: None,
+                base_url: non_empty(&editor.openai.base_url),
+                organization: non_empty(&editor.openai.organization),
+                project: non_empty(&editor.openai.project),
+            });
+        }
+        LlmProviderKind::AzureOpenAi => {
+            let api_key = env_or_field(env, AZURE_OPENAI_API_KEY_VAR, &editor.azure.api_key);
+            if api_key.is_empty() {
+                return Err(format!("Enter an API key to {action}"));
+            }
+            let endpoint = env_or_field(env, AZURE_OPENAI_ENDPOINT_VAR, &editor.azure.endpoint);
+            if Url::parse(&endpoint).is_err() {
+                return Err(format!("Enter a valid endpoint to {action}"));
+            }
+            settings.azure = Some(AzureOpenAiSettings {
+                api_key,
+                endpoint,
+                api_version: env_or_field(
+                    env,
+                    AZURE_OPENAI_API_VERSION_VAR,
+                    &editor.azure.api_version,
+                ),
+                deployment_name: env_or_field(
+                    env,
+                    AZURE_OPENAI_DEPLOYMENT_VAR,
+                    &editor.azure.deployment_name,
+                ),
+            });
+        }
+        LlmProviderKind::Ollama | LlmProviderKind::Mock => {
+            return Err(format!("Only OpenAI and Azure OpenAI can {action} here"));
+        }
+    }
+    Ok(settings)
+}
+
 fn provider_before_label(provider: LlmProviderKind) -> &'static str {
     match provider {
         LlmProviderKind::OpenAi => "OpenAI",
         LlmProviderKind::AzureOpenAi => "Azure OpenAI",
+        LlmProviderKind::Ollama => "Ollama",
         LlmProviderKind::Mock => "Mock",
     }
 }
@@ -1335,8 +2085,17 @@ fn render_provider_panel(
                 LlmProviderKind::AzureOpenAi => {
                     render_azure_fields(ui, palette, &mut provider.azure, dirty);
                 }
+                LlmProviderKind::Ollama => {
+                    render_ollama_fields(ui, &mut provider.ollama, dirty);
+                }
                 LlmProviderKind::Mock => {}
             }
+            if matches!(
+                active_provider,
+                LlmProviderKind::OpenAi | LlmProviderKind::AzureOpenAi
+            ) {
+                render_provider_actions(ui, palette, &mut provider.fetch, &mut provider.test);
+            }
         });
         if disabled {
             ui.label(RichText::new("Using App settings").color(palette.text_secondary));
@@ -1345,12 +2104,13 @@ fn render_provider_panel(
     let validation = if disabled {
         ProviderValidation::default()
     } else {
-        validate_provider(active_provider, provider)
+        validate_provider(active_provider, provider, &|name| std::env::var(name).ok())
     };
     if provider.details_expanded && !disabled {
         match active_provider {
             LlmProviderKind::OpenAi => show_openai_validation(ui, palette, &validation),
             LlmProviderKind::AzureOpenAi => show_azure_validation(ui, palette, &validation),
+            LlmProviderKind::Ollama => show_ollama_validation(ui, &validation),
             LlmProviderKind::Mock => {}
         }
     }
@@ -1379,26 +2139,28 @@ fn render_openai_fields(
         }
     });
     ui.add_space(12.0);
-    ui.label(RichText::new("Available model names").strong());
-    let response = ui.add(
-        egui::TextEdit::multiline(&mut editor.models_input)
-            .desired_rows(3)
-            .hint_text("Comma or semicolon separated"),
-    );
-    if response.changed() {
+
+    ui.label(RichText::new("Base URL").strong());
+    if ui
+        .add(
+            egui::TextEdit::singleline(&mut editor.base_url)
+                .hint_text(DEFAULT_OPENAI_BASE_URL)
+                .desired_width(f32::INFINITY),
+        )
+        .on_hover_text("Host of an OpenAI-compatible gateway such as LiteLLM or vLLM")
+        .changed()
+    {
         *dirty = true;
     }
-    ui.label(
-        RichText::new("Comma or semicolon separated")
-            .color(palette.text_secondary)
-            .small(),
+    field_with_label(
+        ui,
+        "Organization (optional)",
+        &mut editor.organization,
+        dirty,
     );
-    ui.add_space(6.0);
-    ui.horizontal_wrapped(|ui| {
-        for model in parse_models_input(&editor.models_input) {
-            let _ = ui.selectable_label(false, model);
-        }
-    });
+    field_with_label(ui, "Project (optional)", &mut editor.project, dirty);
+    ui.add_space(12.0);
+    render_model_list(ui, palette, &mut editor.models, dirty);
 }
 
 fn render_azure_fields(
@@ -1431,26 +2193,158 @@ fn render_azure_fields(
     field_with_label(ui, "Deployment name", &mut editor.deployment_name, dirty);
 
     ui.add_space(12.0);
-    ui.label(RichText::new("Available model names").strong());
-    let response = ui.add(
-        egui::TextEdit::multiline(&mut editor.models_input)
-            .desired_rows(3)
-            .hint_text("Comma or semicolon separated"),
-    );
-    if response.changed() {
-        *dirty = true;
+    render_model_list(ui, palette, &mut editor.models, dirty);
+}
+
+fn render_ollama_fields(ui: &mut egui::Ui, editor: &mut OllamaEditor, dirty: &mut bool) {
+    field_with_label(ui, "Base URL", &mut editor.base_url, dirty);
+    field_with_label(ui, "Model", &mut editor.model, dirty);
+}
+
+fn render_provider_actions(
+    ui: &mut egui::Ui,
+    palette: &ThemePalette,
+    fetch: &mut ModelFetchState,
+    test: &mut ConnectionTestState,
+) {
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(!fetch.in_flight, egui::Button::new("Fetch models"))
+            .on_hover_text("Replace the list with the models the provider offers")
+            .clicked()
+        {
+            fetch.requested = true;
+        }
+        if ui
+            .add_enabled(!test.in_flight, egui::Button::new("Test connection"))
+            .on_hover_text("Send a short request with the values above, without saving them")
+            .clicked()
+        {
+            test.requested = true;
+        }
+        if fetch.in_flight || test.in_flight {
+            ui.spinner();
+        }
+    });
+    if let Some(warning) = fetch.warning.as_ref() {
+        ui.colored_label(palette.warning, warning);
     }
+}
+
+/// A `#RRGGBB` input with a swatch of the parsed color; returns whether it changed.
+fn render_color_field(ui: &mut egui::Ui, palette: &ThemePalette, hex: &mut String) -> bool {
+    ui.horizontal(|ui| {
+        let changed = ui
+            .add(
+                egui::TextEdit::singleline(hex)
+                    .hint_text("Built-in")
+                    .desired_width(90.0),
+            )
+            .changed();
+        match parse_hex_color(hex) {
+            Some(color) => {
+                let (rect, _) = ui.allocate_exact_size(Vec2::splat(16.0), egui::Sense::hover());
+                ui.painter().rect_filled(rect, 3.0, color);
+            }
+            None if !hex.trim().is_empty() => {
+                ui.colored_label(palette.warning, "Use #RRGGBB");
+            }
+            None => {}
+        }
+        changed
+    })
+    .inner
+}
+
+fn render_model_list(
+    ui: &mut egui::Ui,
+    palette: &ThemePalette,
+    editor: &mut ModelListEditor,
+    dirty: &mut bool,
+) {
+    ui.label(RichText::new("Available model names").strong());
     ui.label(
-        RichText::new("Comma or semicolon separated")
+        RichText::new("The first model is the default; order is kept in the model picker.")
             .color(palette.text_secondary)
             .small(),
     );
-    ui.add_space(6.0);
-    ui.horizontal_wrapped(|ui| {
-        for model in parse_models_input(&editor.models_input) {
-            let _ = ui.selectable_label(false, model);
+    ui.add_space(4.0);
+
+    let count = editor.entries.len();
+    let mut move_up = None;
+    let mut move_down = None;
+    let mut remove = None;
+    for (index, entry) in editor.entries.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let width = (ui.available_width() - 96.0).max(120.0);
+            if ui
+                .add_sized([width, 24.0], egui::TextEdit::singleline(entry))
+                .changed()
+            {
+                *dirty = true;
+            }
+            if ui
+                .add_enabled(index > 0, egui::Button::new("⬆").small())
+                .on_hover_text("Move up")
+                .clicked()
+            {
+                move_up = Some(index);
+            }
+            if ui
+                .add_enabled(index + 1 < count, egui::Button::new("⬇").small())
+                .on_hover_text("Move down")
+                .clicked()
+            {
+                move_down = Some(index);
+            }
+            if ui
+                .add(egui::Button::new("✕").small())
+                .on_hover_text("Remove")
+                .clicked()
+            {
+                remove = Some(index);
+            }
+        });
+    }
+    if let Some(index) = move_up {
+        editor.entries.swap(index, index - 1);
+        *dirty = true;
+    }
+    if let Some(index) = move_down {
+        editor.entries.swap(index, index + 1);
+        *dirty = true;
+    }
+    if let Some(index) = remove {
+        editor.entries.remove(index);
+        *dirty = true;
+    }
+
+    ui.horizontal(|ui| {
+        let width = (ui.available_width() - 96.0).max(120.0);
+        let response = ui.add_sized(
+            [width, 24.0],
+            egui::TextEdit::singleline(&mut editor.new_entry).hint_text("Add a model"),
+        );
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if (ui.button("Add").clicked() || submitted) && editor.push(&editor.new_entry.clone()) {
+            editor.new_entry.clear();
+            *dirty = true;
         }
     });
+
+    egui::CollapsingHeader::new("Paste a list")
+        .id_source(ui.id().with("bulk_models"))
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut editor.bulk_input)
+                    .desired_rows(3)
+                    .hint_text("Comma or semicolon separated"),
+            );
+            if ui.button("Append models").clicked() && editor.import_bulk() {
+                *dirty = true;
+            }
+        });
 }
 
 fn show_openai_validation(
@@ -1458,9 +2352,13 @@ fn show_openai_validation(
     palette: &ThemePalette,
     validation: &ProviderValidation,
 ) {
+    show_env_hints(ui, palette, validation);
     if let Some(warning) = validation.openai_key_warning.as_ref() {
         ui.colored_label(palette.warning, warning);
     }
+    if let Some(err) = validation.openai_base_url_error.as_ref() {
+        ui.colored_label(Color32::from_rgb(198, 60, 60), err);
+    }
 }
 
 fn show_azure_validation(
@@ -1468,6 +2366,7 @@ fn show_azure_validation(
     palette: &ThemePalette,
     validation: &ProviderValidation,
 ) {
+    show_env_hints(ui, palette, validation);
     let error_color = Color32::from_rgb(198, 60, 60);
     if let Some(warning) = validation.azure_key_warning.as_ref() {
         ui.colored_label(palette.warning, warning);
@@ -1483,6 +2382,22 @@ fn show_azure_validation(
     }
 }
 
+fn show_env_hints(ui: &mut egui::Ui, palette: &ThemePalette, validation: &ProviderValidation) {
+    for hint in &validation.env_hints {
+        ui.label(RichText::new(hint).color(palette.text_secondary));
+    }
+}
+
+fn show_ollama_validation(ui: &mut egui::Ui, validation: &ProviderValidation) {
+    let error_color = Color32::from_rgb(198, 60, 60);
+    if let Some(err) = validation.ollama_base_url_error.as_ref() {
+        ui.colored_label(error_color, err);
+    }
+    if let Some(err) = validation.ollama_model_error.as_ref() {
+        ui.colored_label(error_color, err);
+    }
+}
+
 fn field_with_label(ui: &mut egui::Ui, label: &str, value: &mut String, dirty: &mut bool) {
     ui.label(RichText::new(label).strong());
     if ui
@@ -1493,3 +2408,168 @@ fn field_with_label(ui: &mut egui::Ui, label: &str, value: &mut String, dirty: &
     }
     ui.add_space(10.0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    /// An environment holding only `vars`, in place of the process environment.
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    fn editor(provider: LlmProviderKind) -> ProviderEditor {
+        let mut editor = ProviderEditor::from_selection(&ProviderSelection::default());
+        editor.provider = provider;
+        editor
+    }
+
+    #[test]
+    fn the_openai_base_url_needs_an_http_scheme() {
+        let mut editor = editor(LlmProviderKind::OpenAi);
+        editor.openai.api_key = "key".to_string();
+        editor.openai.base_url = "localhost:4000".to_string();
+        let validation = validate_provider(LlmProviderKind::OpenAi, &editor, &no_env);
+        assert_eq!(
+            validation.openai_base_url_error.as_deref(),
+            Some("Base URL must start with http:// or https://")
+        );
+        assert_eq!(
+            editor_settings(&editor, "fetch models", &no_env, None).unwrap_err(),
+            "Enter an http:// or https:// base URL to fetch models"
+        );
+
+        editor.openai.base_url = "http://localhost:4000".to_string();
+        let validation = validate_provider(LlmProviderKind::OpenAi, &editor, &no_env);
+        assert!(validation.openai_base_url_error.is_none());
+        let settings = editor_settings(&editor, "fetch models", &no_env, None).unwrap();
+        assert_eq!(
+            settings.openai.unwrap().base_url.as_deref(),
+            Some("http://localhost:4000")
+        );
+    }
+
+    #[test]
+    fn an_api_key_in_the_environment_satisfies_openai() {
+        let editor = editor(LlmProviderKind::OpenAi);
+        let empty = validate_provider(LlmProviderKind::OpenAi, &editor, &no_env);
+        assert!(empty.openai_key_warning.is_some());
+        assert_eq!(
+            editor_settings(&editor, "fetch models", &no_env, None).unwrap_err(),
+            "Enter an API key to fetch models"
+        );
+
+        let vars = [(OPENAI_API_KEY_VAR, "env-key")];
+        let validation = validate_provider(LlmProviderKind::OpenAi, &editor, &env(&vars));
+        assert!(validation.openai_key_warning.is_none());
+        assert_eq!(
+            validation.env_hints,
+            ["API key from environment (OPENAI_API_KEY)"]
+        );
+        let settings =
+            editor_settings(&editor, "fetch models", &env(&vars), None).expect("settings");
+        assert_eq!(settings.openai.unwrap().api_key, "env-key");
+    }
+
+    #[test]
+    fn requests_from_the_form_use_the_configured_proxy() {
+        let document: Value = serde_yaml::from_str(
+            "app:\n  proxy:\n    url: http://proxy.corp:3128\n    username: me\n  profiles:\n    office:\n      proxy:\n        url: http://office:8080\n",
+        )
+        .unwrap();
+        let proxy = configured_proxy(&document).expect("proxy");
+        assert_eq!(proxy.url, "http://proxy.corp:3128");
+        assert_eq!(proxy.username.as_deref(), Some("me"));
+        assert_eq!(proxy.password, None);
+
+        let mut editor = editor(LlmProviderKind::OpenAi);
+        editor.openai.api_key = "key".to_string();
+        let settings =
+            editor_settings(&editor, "fetch models", &no_env, Some(&proxy)).expect("settings");
+        assert_eq!(settings.proxy.expect("proxy").url, "http://proxy.corp:3128");
+
+        let mut office = document.clone();
+        office["app"]["active_profile"] = "office".into();
+        assert_eq!(
+            configured_proxy(&office).expect("proxy").url,
+            "http://office:8080"
+        );
+        assert!(
+            configured_proxy(&serde_yaml::from_str("app:\n  provider: mock\n").unwrap()).is_none()
+        );
+    }
+
+    #[test]
+    fn the_form_edits_the_provider_settings_of_the_active_profile() {
+        let path = std::env::temp_dir().join(format!("patina-profile-{}.yaml", std::process::id()));
+        let document: Value = serde_yaml::from_str(
+            "app:\n  theme: dark\n  provider: open_ai\n  openai:\n    api_key: personal\n  active_profile: local\n  profiles:\n    local:\n      provider: ollama\n      ollama:\n        model: llama3.2\n",
+        )
+        .unwrap();
+        let mut store = GlobalSettingsStore {
+            path: path.clone(),
+            data: extract_app_settings(&document),
+            document,
+            dirty: false,
+        };
+        assert_eq!(store.active_profile(), Some("local"));
+        let selection = &store.data().provider;
+        assert_eq!(selection.provider, LlmProviderKind::Ollama);
+        assert_eq!(selection.ollama.model, "llama3.2");
+        assert!(selection.openai.api_key.is_empty());
+
+        let mut data = store.data().clone();
+        data.provider.ollama.model = "qwen2.5".to_string();
+        data.theme = ThemeMode::Light;
+        store.set(data);
+        store.persist().expect("persist");
+        let saved = load_yaml_document(&path);
+        std::fs::remove_file(&path).ok();
+        let app = &saved["app"];
+        assert_eq!(app["profiles"]["local"]["ollama"]["model"], "qwen2.5");
+        assert_eq!(app["openai"]["api_key"], "personal");
+        assert_eq!(app["provider"], "open_ai");
+        assert!(app.get("ollama").is_none());
+        assert_eq!(app["theme"], "light");
+
+        store.set_active_profile(None).expect("switch");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(store.active_profile(), None);
+        assert_eq!(store.data().provider.provider, LlmProviderKind::OpenAi);
+    }
+
+    #[test]
+    fn azure_fields_in_the_environment_are_not_required_in_the_form() {
+        let editor = editor(LlmProviderKind::AzureOpenAi);
+        let empty = validate_provider(LlmProviderKind::AzureOpenAi, &editor, &no_env);
+        assert!(empty.azure_endpoint_error.is_some());
+        assert!(empty.azure_version_error.is_some());
+
+        let vars = [
+            (AZURE_OPENAI_API_KEY_VAR, "env-key"),
+            (AZURE_OPENAI_ENDPOINT_VAR, "https://env.openai.azure.com"),
+            (AZURE_OPENAI_API_VERSION_VAR, "2024-06-01"),
+            (AZURE_OPENAI_DEPLOYMENT_VAR, "gpt-4o"),
+        ];
+        let validation = validate_provider(LlmProviderKind::AzureOpenAi, &editor, &env(&vars));
+        assert!(validation.is_valid());
+        assert!(validation.azure_key_warning.is_none());
+        assert_eq!(validation.env_hints.len(), 4);
+
+        let settings =
+            editor_settings(&editor, "test the connection", &env(&vars), None).expect("settings");
+        let azure = settings.azure.unwrap();
+        assert_eq!(azure.api_key, "env-key");
+        assert_eq!(azure.endpoint, "https://env.openai.azure.com");
+        assert_eq!(azure.api_version, "2024-06-01");
+        assert_eq!(azure.deployment_name, "gpt-4o");
+    }
+}
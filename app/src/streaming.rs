@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+/// Batches streamed deltas so the transcript is re-laid out at most once per window
+/// instead of once per token.
+#[derive(Debug)]
+pub struct StreamCoalescer {
+    window: Duration,
+    pending: String,
+    first_pending_at: Option<Instant>,
+}
+
+impl StreamCoalescer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: String::new(),
+            first_pending_at: None,
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Buffer `delta`, received at `now`.
+    pub fn push(&mut self, delta: &str, now: Instant) {
+        if delta.is_empty() {
+            return;
+        }
+        self.first_pending_at.get_or_insert(now);
+        self.pending.push_str(delta);
+    }
+
+    /// The buffered text, once the oldest buffered delta is at least a window old.
+    pub fn poll(&mut self, now: Instant) -> Option<String> {
+        let due = self
+            .first_pending_at
+            .is_some_and(|first| now.saturating_duration_since(first) >= self.window);
+        if due {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Everything buffered so far, regardless of the window.
+    pub fn flush(&mut self) -> Option<String> {
+        self.first_pending_at = None;
+        (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending))
+    }
+
+    /// How long until the buffered text becomes due, if anything is buffered.
+    pub fn next_flush(&self, now: Instant) -> Option<Duration> {
+        self.first_pending_at.map(|first| {
+            self.window
+                .saturating_sub(now.saturating_duration_since(first))
+        })
+    }
+}
@@ -1,9 +1,24 @@
-use chrono::{DateTime, Local};
+use crate::config::{NoModelsReason, Pricing, ProviderConfig, UI_SCALE_RANGE};
+use crate::highlight::{split_code_blocks, CodeHighlighter, Segment};
+use crate::images::{PastedImage, MAX_ATTACHED_IMAGE_SIDE};
+use crate::settings::ColorOverrides;
+use chrono::format::StrftimeItems;
+use chrono::{DateTime, Datelike, Local, TimeZone, Utc};
 use egui::{self, Align, Color32, Frame, Layout, Margin, RichText, ScrollArea, Sense, Vec2};
 use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
-use patina_core::state::{ChatMessage, Conversation, ConversationSummary, MessageRole};
+use patina_core::attachment::TextAttachment;
+use patina_core::llm::{ModelUsage, RateLimitSnapshot, ToolDefinition};
+use patina_core::mcp::{McpEndpoint, McpEvent, TransportSpec};
+use patina_core::state::{
+    ChatMessage, ChatParameters, Conversation, ConversationSettings, ConversationSummary,
+    MessageRole, ToolCallStatus,
+};
+use patina_core::tokens::TokenCounter;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::Level;
 use uuid::Uuid;
 
 #[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -13,23 +28,31 @@ pub enum ThemeMode {
     System,
     Light,
     Dark,
+    #[serde(rename = "high-contrast")]
+    HighContrast,
 }
 
 impl ThemeMode {
-    pub const ALL: [ThemeMode; 3] = [ThemeMode::System, ThemeMode::Light, ThemeMode::Dark];
+    pub const ALL: [ThemeMode; 4] = [
+        ThemeMode::System,
+        ThemeMode::Light,
+        ThemeMode::Dark,
+        ThemeMode::HighContrast,
+    ];
 
     pub fn label(self) -> &'static str {
         match self {
             ThemeMode::System => "System",
             ThemeMode::Light => "Light",
             ThemeMode::Dark => "Dark",
+            ThemeMode::HighContrast => "High contrast",
         }
     }
 
     pub fn fallback_theme(self) -> eframe::Theme {
         match self {
             ThemeMode::Light => eframe::Theme::Light,
-            ThemeMode::System | ThemeMode::Dark => eframe::Theme::Dark,
+            ThemeMode::System | ThemeMode::Dark | ThemeMode::HighContrast => eframe::Theme::Dark,
         }
     }
 }
@@ -82,6 +105,57 @@ impl ThemePalette {
         }
     }
 
+    /// This palette with the colors set in `overrides`; invalid ones are ignored.
+    pub fn with_overrides(mut self, overrides: &ColorOverrides) -> Self {
+        for (color, hex) in [
+            (&mut self.accent, &overrides.accent),
+            (&mut self.user_bubble, &overrides.user_bubble),
+            (&mut self.assistant_bubble, &overrides.assistant_bubble),
+        ] {
+            if let Some(parsed) = parse_hex_color(hex) {
+                *color = parsed;
+            }
+        }
+        self
+    }
+
+    /// Near-black surfaces with white text and a yellow accent, meeting WCAG AA contrast.
+    pub fn for_high_contrast() -> Self {
+        Self {
+            background: color_from_hex("#000000"),
+            sidebar_background: color_from_hex("#000000"),
+            surface: color_from_hex("#0F0F0F"),
+            user_bubble: color_from_hex("#1A1A1A"),
+            assistant_bubble: color_from_hex("#0A0A0A"),
+            accent: color_from_hex("#FFD400"),
+            text_primary: color_from_hex("#FFFFFF"),
+            text_secondary: color_from_hex("#DADADA"),
+            border: color_from_hex("#FFFFFF"),
+            warning: color_from_hex("#FF7A7A"),
+            elevated_shadow: Color32::from_rgba_unmultiplied(0, 0, 0, 0),
+        }
+    }
+
+    /// Dark visuals with thick widget borders and accent-colored hover and focus outlines.
+    pub fn high_contrast_visuals(&self) -> egui::Visuals {
+        let mut visuals = self.visuals(true);
+        let border = egui::Stroke::new(1.5, self.border);
+        let focus = egui::Stroke::new(2.5, self.accent);
+        visuals.widgets.noninteractive.bg_stroke = border;
+        visuals.widgets.inactive.bg_stroke = border;
+        visuals.widgets.hovered.bg_stroke = focus;
+        visuals.widgets.active.bg_stroke = focus;
+        visuals.widgets.open.bg_stroke = focus;
+        visuals.widgets.hovered.fg_stroke.color = self.accent;
+        visuals.selection.stroke = focus;
+        visuals.selection.bg_fill = color_from_hex("#5A4A00");
+        visuals.hyperlink_color = self.accent;
+        visuals.window_stroke = border;
+        visuals.window_shadow = egui::epaint::Shadow::NONE;
+        visuals.popup_shadow = egui::epaint::Shadow::NONE;
+        visuals
+    }
+
     pub fn visuals(&self, dark_mode: bool) -> egui::Visuals {
         let mut visuals = if dark_mode {
             egui::Visuals::dark()
@@ -101,27 +175,90 @@ impl ThemePalette {
 }
 
 fn color_from_hex(hex: &str) -> Color32 {
-    let trimmed = hex.trim_start_matches('#');
-    if trimmed.len() == 6 {
-        if let Ok(value) = u32::from_str_radix(trimmed, 16) {
-            let r = ((value >> 16) & 0xFF) as u8;
-            let g = ((value >> 8) & 0xFF) as u8;
-            let b = (value & 0xFF) as u8;
-            return Color32::from_rgb(r, g, b);
-        }
+    parse_hex_color(hex).unwrap_or(Color32::WHITE)
+}
+
+/// Parse a `#RRGGBB` color; the `#` is optional.
+pub fn parse_hex_color(hex: &str) -> Option<Color32> {
+    let trimmed = hex.trim().trim_start_matches('#');
+    if trimmed.len() != 6 || !trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
     }
-    Color32::WHITE
+    let value = u32::from_str_radix(trimmed, 16).ok()?;
+    let r = ((value >> 16) & 0xFF) as u8;
+    let g = ((value >> 8) & 0xFF) as u8;
+    let b = (value & 0xFF) as u8;
+    Some(Color32::from_rgb(r, g, b))
 }
 
 #[derive(Debug, Default)]
 pub struct MenuBarState {
     pub theme_mode: ThemeMode,
+    pub session_usage: SessionUsage,
+    /// Provider quota from the latest response; nothing is shown when absent.
+    pub rate_limits: Option<RateLimitSnapshot>,
+    pub autosave_delay_ms: u64,
+    pub stream_flush_ms: u64,
+    pub load_remote_images: bool,
+    pub regenerate_on_model_change: bool,
+    pub allow_mcp_sampling: bool,
+    pub auto_title_chats: bool,
+    pub group_chats_by_date: bool,
+    pub timestamp_format: TimestampFormat,
+    /// Provider profiles defined in patina.yaml; the switcher is hidden when empty.
+    pub profiles: Vec<String>,
+    pub active_profile: Option<String>,
+    /// Verbosity picked under Help > Diagnostics; `None` keeps the startup filter.
+    pub log_level: Option<Level>,
+    pub ui_scale: f32,
+}
+
+/// Token usage accumulated across all conversations since launch.
+#[derive(Debug, Default, Clone)]
+pub struct SessionUsage {
+    pub requests: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    /// The same usage split by model, for pricing.
+    pub by_model: BTreeMap<String, ModelUsage>,
+}
+
+impl SessionUsage {
+    pub fn record(&mut self, model: &str, usage: &ModelUsage) {
+        self.requests += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        let total = self.by_model.entry(model.to_string()).or_default();
+        total.prompt_tokens += usage.prompt_tokens;
+        total.completion_tokens += usage.completion_tokens;
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// What the session cost, when every model used has a price.
+    pub fn cost(&self, pricing: &Pricing) -> Option<f64> {
+        self.by_model
+            .iter()
+            .map(|(model, usage)| pricing.cost(model, usage))
+            .sum()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
 }
 
 #[derive(Default)]
 pub struct MenuBarOutput {
     pub new_project: bool,
     pub open_project: bool,
+    pub save_project_as: bool,
+    pub import_conversation: bool,
+    pub save_now: bool,
+    pub autosave_delay_changed: Option<u64>,
+    pub stream_flush_changed: Option<u64>,
     pub new_chat: bool,
     pub toggle_sidebar: bool,
     pub focus_search: bool,
@@ -129,7 +266,21 @@ pub struct MenuBarOutput {
     pub exit: bool,
     pub show_about: bool,
     pub show_settings: bool,
+    pub reset_session_usage: bool,
+    pub prune_empty_chats: bool,
+    pub remote_images_changed: Option<bool>,
+    pub regenerate_on_model_change_changed: Option<bool>,
+    pub mcp_sampling_changed: Option<bool>,
+    pub auto_title_changed: Option<bool>,
+    pub group_chats_by_date_changed: Option<bool>,
+    pub timestamp_format_changed: Option<TimestampFormat>,
     pub theme_changed: Option<ThemeMode>,
+    /// The provider profile picked in the switcher; `Some(None)` for the top-level
+    /// settings.
+    pub profile_changed: Option<Option<String>>,
+    /// The log level picked under Help > Diagnostics; `Some(None)` for the startup filter.
+    pub log_level_changed: Option<Option<Level>>,
+    pub ui_scale_changed: Option<f32>,
 }
 
 pub struct MenuBar;
@@ -141,6 +292,7 @@ impl MenuBar {
         logo_texture: Option<&egui::TextureHandle>,
         project_available: bool,
         project_name: Option<&str>,
+        pricing: &Pricing,
     ) -> MenuBarOutput {
         let mut output = MenuBarOutput::default();
         egui::menu::bar(ui, |ui| {
@@ -166,6 +318,37 @@ impl MenuBar {
                         output.open_project = true;
                         ui.close_menu();
                     }
+                    if ui
+                        .add_enabled(project_available, egui::Button::new("Save Project As…"))
+                        .clicked()
+                    {
+                        output.save_project_as = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(project_available, egui::Button::new("Import Conversation…"))
+                        .clicked()
+                    {
+                        output.import_conversation = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Save now\tCtrl+S").clicked() {
+                        output.save_now = true;
+                        ui.close_menu();
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Auto-save delay");
+                        let response = ui.add(
+                            egui::DragValue::new(&mut state.autosave_delay_ms)
+                                .clamp_range(0..=60_000)
+                                .speed(50.0)
+                                .suffix(" ms"),
+                        );
+                        if response.changed() {
+                            output.autosave_delay_changed = Some(state.autosave_delay_ms);
+                        }
+                    });
                     ui.separator();
                     if ui.button("Settings…").clicked() {
                         output.show_settings = true;
@@ -179,6 +362,14 @@ impl MenuBar {
                         output.new_chat = true;
                         ui.close_menu();
                     }
+                    if ui
+                        .add_enabled(project_available, egui::Button::new("Prune empty chats"))
+                        .on_hover_text("Delete chats that never received a message")
+                        .clicked()
+                    {
+                        output.prune_empty_chats = true;
+                        ui.close_menu();
+                    }
                     if ui.button("Exit").clicked() {
                         output.exit = true;
                         ui.close_menu();
@@ -192,6 +383,36 @@ impl MenuBar {
                         output.clear_input = true;
                         ui.close_menu();
                     }
+                    if ui
+                        .checkbox(
+                            &mut state.regenerate_on_model_change,
+                            "Regenerate on model change",
+                        )
+                        .on_hover_text("Re-ask the last prompt when you pick another model")
+                        .changed()
+                    {
+                        output.regenerate_on_model_change_changed =
+                            Some(state.regenerate_on_model_change);
+                    }
+                    if ui
+                        .checkbox(&mut state.allow_mcp_sampling, "Allow MCP sampling")
+                        .on_hover_text(
+                            "Let MCP servers ask the selected model for completions, \
+                             spending tokens",
+                        )
+                        .changed()
+                    {
+                        output.mcp_sampling_changed = Some(state.allow_mcp_sampling);
+                    }
+                    if ui
+                        .checkbox(&mut state.auto_title_chats, "Generate chat titles")
+                        .on_hover_text(
+                            "Ask the selected model to title each chat after its first reply",
+                        )
+                        .changed()
+                    {
+                        output.auto_title_changed = Some(state.auto_title_chats);
+                    }
                 });
                 ui.menu_button("View", |ui| {
                     if ui
@@ -204,6 +425,78 @@ impl MenuBar {
                         output.toggle_sidebar = true;
                         ui.close_menu();
                     }
+                    if ui
+                        .checkbox(&mut state.load_remote_images, "Load remote images")
+                        .on_hover_text("Fetch images linked from assistant replies")
+                        .changed()
+                    {
+                        output.remote_images_changed = Some(state.load_remote_images);
+                    }
+                    if ui
+                        .checkbox(&mut state.group_chats_by_date, "Group chats by date")
+                        .on_hover_text("Show Today, Yesterday, This week and Older headers")
+                        .changed()
+                    {
+                        output.group_chats_by_date_changed = Some(state.group_chats_by_date);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Timestamps");
+                        let formats = [
+                            TimestampFormat::Relative,
+                            TimestampFormat::Short,
+                            TimestampFormat::Custom(DEFAULT_CUSTOM_TIMESTAMP.to_string()),
+                        ];
+                        egui::ComboBox::from_id_source("timestamp_format")
+                            .selected_text(state.timestamp_format.label())
+                            .show_ui(ui, |ui| {
+                                for format in formats {
+                                    let selected = state.timestamp_format.label() == format.label();
+                                    if ui.selectable_label(selected, format.label()).clicked()
+                                        && !selected
+                                    {
+                                        state.timestamp_format = format;
+                                        output.timestamp_format_changed =
+                                            Some(state.timestamp_format.clone());
+                                    }
+                                }
+                            });
+                    });
+                    if let TimestampFormat::Custom(pattern) = &mut state.timestamp_format {
+                        let response = ui
+                            .add(egui::TextEdit::singleline(pattern).desired_width(160.0))
+                            .on_hover_text("A strftime pattern; invalid ones show the short form");
+                        if response.changed() {
+                            output.timestamp_format_changed =
+                                Some(TimestampFormat::Custom(pattern.clone()));
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Stream refresh");
+                        let response = ui
+                            .add(
+                                egui::DragValue::new(&mut state.stream_flush_ms)
+                                    .clamp_range(0..=1_000)
+                                    .speed(5.0)
+                                    .suffix(" ms"),
+                            )
+                            .on_hover_text("Batch streamed text before redrawing the reply");
+                        if response.changed() {
+                            output.stream_flush_changed = Some(state.stream_flush_ms);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Zoom");
+                        let response = ui
+                            .add(
+                                egui::Slider::new(&mut state.ui_scale, UI_SCALE_RANGE)
+                                    .step_by(0.05)
+                                    .custom_formatter(|value, _| format!("{:.0}%", value * 100.0)),
+                            )
+                            .on_hover_text("Ctrl+= zooms in, Ctrl+- out, Ctrl+0 resets");
+                        if response.changed() {
+                            output.ui_scale_changed = Some(state.ui_scale);
+                        }
+                    });
                     if ui
                         .add_enabled(project_available, egui::Button::new("Focus search\tCtrl+K"))
                         .clicked()
@@ -213,6 +506,22 @@ impl MenuBar {
                     }
                 });
                 ui.menu_button("Help", |ui| {
+                    ui.menu_button("Diagnostics", |ui| {
+                        let levels = [
+                            (None, "Default logging"),
+                            (Some(Level::DEBUG), "Debug logging"),
+                            (Some(Level::TRACE), "Trace logging"),
+                        ];
+                        for (level, label) in levels {
+                            if ui.radio(state.log_level == level, label).clicked() {
+                                if state.log_level != level {
+                                    state.log_level = level;
+                                    output.log_level_changed = Some(level);
+                                }
+                                ui.close_menu();
+                            }
+                        }
+                    });
                     if ui.button("About").clicked() {
                         output.show_about = true;
                         ui.close_menu();
@@ -235,6 +544,39 @@ impl MenuBar {
                                 }
                             }
                         });
+                    if !state.profiles.is_empty() {
+                        profile_switcher(ui, state, &mut output);
+                    }
+                    if let Some(limits) = &state.rate_limits {
+                        rate_limit_indicator(ui, limits);
+                    }
+                    let usage = &state.session_usage;
+                    if usage.requests > 0 {
+                        let cost = usage.cost(pricing);
+                        let title = match cost {
+                            Some(cost) => format!("Session: {}", format_cost(cost)),
+                            None => format!("Session: {} tokens", usage.total_tokens()),
+                        };
+                        ui.menu_button(title, |ui| {
+                            ui.label(format!("Requests: {}", usage.requests));
+                            ui.label(format!("Prompt tokens: {}", usage.prompt_tokens));
+                            ui.label(format!("Completion tokens: {}", usage.completion_tokens));
+                            match cost {
+                                Some(cost) => {
+                                    ui.label(format!("Estimated cost: {}", format_cost(cost)));
+                                }
+                                None if !pricing.is_empty() => {
+                                    ui.weak("Not every model used has a price");
+                                }
+                                None => {}
+                            }
+                            ui.separator();
+                            if ui.button("Reset counter").clicked() {
+                                output.reset_session_usage = true;
+                                ui.close_menu();
+                            }
+                        });
+                    }
                 });
             });
         });
@@ -242,6 +584,31 @@ impl MenuBar {
     }
 }
 
+/// Lets the user pick the provider profile from patina.yaml, or the top-level settings.
+fn profile_switcher(ui: &mut egui::Ui, state: &MenuBarState, output: &mut MenuBarOutput) {
+    const DEFAULT_LABEL: &str = "Default";
+    let active = state.active_profile.as_deref();
+    egui::ComboBox::from_id_source("profile_selector")
+        .selected_text(format!("Profile: {}", active.unwrap_or(DEFAULT_LABEL)))
+        .show_ui(ui, |ui| {
+            let choices = std::iter::once(None).chain(state.profiles.iter().map(Some));
+            for choice in choices {
+                let name = choice.map(String::as_str);
+                if ui
+                    .selectable_label(active == name, name.unwrap_or(DEFAULT_LABEL))
+                    .clicked()
+                {
+                    if active != name {
+                        output.profile_changed = Some(choice.cloned());
+                    }
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Provider settings from patina.yaml");
+}
+
 #[derive(Default)]
 pub struct SidebarState {
     pub collapsed: bool,
@@ -249,10 +616,20 @@ pub struct SidebarState {
     pub search_focus_requested: bool,
     pub mcp_collapsed: bool,
     pub chats_collapsed: bool,
+    pub group_by_date: bool,
+    pub timestamp_format: TimestampFormat,
     rename_editor: Option<RenameEditor>,
     pub dragging_chat: Option<Uuid>,
     pub hovered_chat: Option<Uuid>,
     pub active_mcp_popup: Option<String>,
+    /// Project root offered to MCP servers as their root and default working directory.
+    pub mcp_root: Option<PathBuf>,
+    pub read_only: bool,
+    pub merge_selection: HashSet<Uuid>,
+    /// Message matches for the search query, one per conversation.
+    pub message_hits: Vec<(Uuid, ChatMessage)>,
+    /// The arguments form of the MCP tool about to be run.
+    pub tool_form: Option<ToolForm>,
 }
 
 impl SidebarState {
@@ -284,10 +661,32 @@ impl RenameEditor {
 pub struct SidebarOutput {
     pub selected_chat: Option<Uuid>,
     pub rename: Option<(Uuid, String)>,
+    pub reset_title: Option<Uuid>,
+    pub duplicate: Option<Uuid>,
     pub delete: Option<Uuid>,
     pub pin: Option<Uuid>,
     pub unpin: Option<Uuid>,
     pub reorder: Option<(Uuid, Uuid)>,
+    pub merge: Option<(Uuid, Vec<Uuid>)>,
+    pub copy_json: Option<Uuid>,
+    pub export_json: Option<Uuid>,
+    pub open_mcp_settings: bool,
+    pub mcp_collapsed_changed: Option<bool>,
+    /// Re-read the project's MCP servers and reconnect to them.
+    pub reload_mcp: bool,
+    /// An MCP server to connect again, by endpoint id.
+    pub reconnect_mcp: Option<String>,
+    /// An MCP server to disconnect from, by endpoint id.
+    pub close_mcp: Option<String>,
+    /// An MCP server whose tools should be listed, by endpoint id.
+    pub list_mcp_tools: Option<String>,
+    /// An MCP tool to run: endpoint id, tool name and arguments.
+    pub call_mcp_tool: Option<(String, String, serde_json::Value)>,
+    /// An MCP tool call to abort: endpoint id and tool name.
+    pub cancel_mcp_tool: Option<(String, String)>,
+    pub search_changed: Option<String>,
+    /// A search result was picked: the conversation and the message to scroll to.
+    pub open_message: Option<(Uuid, Uuid)>,
 }
 
 pub struct Sidebar;
@@ -303,6 +702,8 @@ impl Sidebar {
         pinned_order: &[Uuid],
         mcp_entries: &mut [McpSidebarEntry],
         active_chat: Option<Uuid>,
+        pricing: &Pricing,
+        model: &str,
     ) -> SidebarOutput {
         let mut output = SidebarOutput::default();
         let search_frame = Frame::none()
@@ -318,22 +719,44 @@ impl Sidebar {
                     egui::TextEdit::singleline(&mut state.search_query)
                         .hint_text("Search MCPs & chats"),
                 );
+                if response.changed() {
+                    output.search_changed = Some(state.search_query.clone());
+                }
                 if state.search_focus_requested {
                     response.request_focus();
                     state.search_focus_requested = false;
                 }
+                if !state.search_query.is_empty()
+                    && ui
+                        .add(egui::Button::new("×").frame(false))
+                        .on_hover_text("Clear search")
+                        .clicked()
+                {
+                    state.search_query.clear();
+                    output.search_changed = Some(String::new());
+                }
             });
         });
 
         ui.add_space(12.0);
         let query = state.search_query.clone();
-        Self::mcp_section(ui, state, palette, mcp_entries, &query);
+        Self::mcp_section(ui, state, palette, mcp_entries, &query, &mut output);
+        Self::tool_form_window(ui, state, palette, &mut output);
         ui.add_space(10.0);
+        if !query.trim().is_empty() && !state.message_hits.is_empty() {
+            Self::message_results(ui, state, palette, summaries, &query, &mut output);
+            ui.add_space(10.0);
+        }
+        let usage: HashMap<Uuid, String> = summaries
+            .iter()
+            .filter_map(|summary| Some((summary.id, usage_label(summary, pricing, model)?)))
+            .collect();
         Self::chats_section(
             ui,
             state,
             palette,
             summaries,
+            &usage,
             pinned_lookup,
             pinned_order,
             &query,
@@ -349,47 +772,80 @@ impl Sidebar {
         palette: &ThemePalette,
         entries: &mut [McpSidebarEntry],
         query: &str,
+        output: &mut SidebarOutput,
     ) {
+        if entries.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("MCP").strong());
+                ui.label(
+                    RichText::new("No MCP servers —")
+                        .color(palette.text_secondary)
+                        .small(),
+                );
+                if ui
+                    .link(RichText::new("Add one in Settings").small())
+                    .clicked()
+                {
+                    output.open_mcp_settings = true;
+                }
+                if ui.link(RichText::new("Reload").small()).clicked() {
+                    output.reload_mcp = true;
+                }
+            });
+            return;
+        }
         let filtered_query = query.trim().to_lowercase();
-        ui.collapsing("MCP", |ui| {
-            ui.spacing_mut().item_spacing.y = 6.0;
-            for entry in entries.iter_mut().filter(|entry| {
-                filtered_query.is_empty()
-                    || entry.name.to_lowercase().contains(&filtered_query)
-                    || entry.description.to_lowercase().contains(&filtered_query)
-            }) {
-                let card = Frame::none()
-                    .fill(palette.surface)
-                    .inner_margin(Margin::symmetric(8.0, 6.0))
-                    .rounding(6.0)
-                    .stroke(egui::Stroke::new(1.0, palette.border));
-                let popup_id = ui.make_persistent_id(format!("mcp_popup_{}", entry.id));
-                let response = card.show(ui, |ui| {
-                    ui.horizontal(|ui| {
-                        let indicator_color = entry.status.color(palette);
-                        let (rect, _) = ui.allocate_exact_size(Vec2::splat(10.0), Sense::hover());
-                        ui.painter()
-                            .circle_filled(rect.center(), 5.0, indicator_color);
-                        ui.vertical(|ui| {
-                            ui.label(RichText::new(&entry.name).strong());
-                            ui.label(
-                                RichText::new(format!(
-                                    "{} • {}",
-                                    entry.description,
-                                    entry.status.label()
-                                ))
-                                .color(palette.text_secondary)
-                                .small(),
-                            );
+        let section = egui::CollapsingHeader::new("MCP")
+            .open(Some(!state.mcp_collapsed))
+            .show(ui, |ui| {
+                ui.spacing_mut().item_spacing.y = 6.0;
+                for entry in entries.iter_mut().filter(|entry| {
+                    filtered_query.is_empty()
+                        || entry.name.to_lowercase().contains(&filtered_query)
+                        || entry.description.to_lowercase().contains(&filtered_query)
+                }) {
+                    let card = Frame::none()
+                        .fill(palette.surface)
+                        .inner_margin(Margin::symmetric(8.0, 6.0))
+                        .rounding(6.0)
+                        .stroke(egui::Stroke::new(1.0, palette.border));
+                    let popup_id = ui.make_persistent_id(format!("mcp_popup_{}", entry.id));
+                    let response = card.show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            let indicator_color = entry.status.color(palette);
+                            let (rect, _) =
+                                ui.allocate_exact_size(Vec2::splat(10.0), Sense::hover());
+                            ui.painter()
+                                .circle_filled(rect.center(), 5.0, indicator_color);
+                            ui.vertical(|ui| {
+                                ui.label(RichText::new(&entry.name).strong());
+                                ui.label(
+                                    RichText::new(format!(
+                                        "{} • {}",
+                                        entry.description,
+                                        entry.status.label()
+                                    ))
+                                    .color(palette.text_secondary)
+                                    .small(),
+                                );
+                                for tool in &entry.running_tools {
+                                    ui.horizontal(|ui| {
+                                        ui.spinner();
+                                        ui.label(RichText::new(format!("Running {tool}…")).small());
+                                        if ui.small_button("Cancel").clicked() {
+                                            output.cancel_mcp_tool =
+                                                Some((entry.id.clone(), tool.clone()));
+                                        }
+                                    });
+                                }
+                            });
                         });
                     });
-                });
-                if response.response.clicked() {
-                    state.active_mcp_popup = Some(entry.id.clone());
-                    ui.memory_mut(|mem| mem.toggle_popup(popup_id));
-                }
-                if let Some(active) = &state.active_mcp_popup {
-                    if *active == entry.id {
+                    if response.response.clicked() {
+                        state.active_mcp_popup = Some(entry.id.clone());
+                        ui.memory_mut(|mem| mem.toggle_popup(popup_id));
+                    }
+                    if state.active_mcp_popup.as_deref() == Some(entry.id.as_str()) {
                         egui::popup::popup_above_or_below_widget(
                             ui,
                             popup_id,
@@ -401,13 +857,41 @@ impl Sidebar {
                                 popup_ui.separator();
                                 popup_ui.label("Status");
                                 popup_ui.label(entry.status.label());
+                                if let Some(reason) = &entry.reason {
+                                    popup_ui.label(
+                                        RichText::new(reason).color(palette.text_secondary).small(),
+                                    );
+                                }
+                                popup_ui.separator();
+                                popup_ui.label("Root");
+                                match &state.mcp_root {
+                                    Some(root) => popup_ui.label(
+                                        RichText::new(root.display().to_string())
+                                            .monospace()
+                                            .small(),
+                                    ),
+                                    None => popup_ui.label(
+                                        RichText::new("No project open")
+                                            .color(palette.text_secondary)
+                                            .small(),
+                                    ),
+                                };
+                                popup_ui.separator();
+                                Self::mcp_tools(popup_ui, palette, entry, state, output);
                                 popup_ui.separator();
                                 popup_ui.horizontal(|ui| {
                                     if ui.button("Reconnect").clicked() {
                                         entry.status = McpStatus::Connecting;
+                                        entry.reason = None;
+                                        output.reconnect_mcp = Some(entry.id.clone());
                                         ui.close_menu();
                                     }
-                                    if ui.button("Close").clicked() {
+                                    let connected = entry.status != McpStatus::Disconnected;
+                                    if ui
+                                        .add_enabled(connected, egui::Button::new("Close"))
+                                        .clicked()
+                                    {
+                                        output.close_mcp = Some(entry.id.clone());
                                         ui.close_menu();
                                     }
                                 });
@@ -415,8 +899,213 @@ impl Sidebar {
                         );
                     }
                 }
+                if ui
+                    .small_button("Reload MCP servers")
+                    .on_hover_text("Re-read the servers listed in the project file")
+                    .clicked()
+                {
+                    output.reload_mcp = true;
+                }
+            });
+        if section.header_response.clicked() {
+            state.mcp_collapsed = !state.mcp_collapsed;
+            output.mcp_collapsed_changed = Some(state.mcp_collapsed);
+        }
+    }
+
+    /// The tools of a connected server, listed the first time its popup opens.
+    fn mcp_tools(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        entry: &mut McpSidebarEntry,
+        state: &mut SidebarState,
+        output: &mut SidebarOutput,
+    ) {
+        ui.label("Tools");
+        if entry.status != McpStatus::Connected {
+            ui.label(
+                RichText::new("Connect to list tools")
+                    .color(palette.text_secondary)
+                    .small(),
+            );
+            return;
+        }
+        if matches!(entry.tools, McpToolList::NotLoaded) {
+            entry.tools = McpToolList::Loading;
+            output.list_mcp_tools = Some(entry.id.clone());
+        }
+        match &entry.tools {
+            McpToolList::NotLoaded | McpToolList::Loading => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(RichText::new("Loading tools…").small());
+                });
             }
-        });
+            McpToolList::Failed(err) => {
+                ui.label(RichText::new(err).color(palette.warning).small());
+            }
+            McpToolList::Loaded(tools) if tools.is_empty() => {
+                ui.label(
+                    RichText::new("No tools")
+                        .color(palette.text_secondary)
+                        .small(),
+                );
+            }
+            McpToolList::Loaded(tools) => {
+                ScrollArea::vertical()
+                    .id_source(("mcp_tools", &entry.id))
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for tool in tools {
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new(&tool.name).monospace());
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    if ui.small_button("Run…").clicked() {
+                                        state.tool_form = Some(ToolForm::new(&entry.id, tool));
+                                        ui.close_menu();
+                                    }
+                                });
+                            });
+                            if !tool.description.is_empty() {
+                                ui.label(
+                                    RichText::new(&tool.description)
+                                        .color(palette.text_secondary)
+                                        .small(),
+                                );
+                            }
+                        }
+                    });
+            }
+        }
+    }
+
+    fn tool_form_window(
+        ui: &mut egui::Ui,
+        state: &mut SidebarState,
+        palette: &ThemePalette,
+        output: &mut SidebarOutput,
+    ) {
+        let Some(form) = state.tool_form.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut close = false;
+        egui::Window::new(format!("Run {}", form.tool))
+            .id(egui::Id::new("mcp_tool_form"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                if !form.description.is_empty() {
+                    ui.label(
+                        RichText::new(&form.description)
+                            .color(palette.text_secondary)
+                            .small(),
+                    );
+                    ui.add_space(8.0);
+                }
+                if form.fields.is_empty() {
+                    ui.label(
+                        RichText::new("This tool takes no arguments.")
+                            .color(palette.text_secondary)
+                            .small(),
+                    );
+                }
+                egui::Grid::new("mcp_tool_form_fields")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for field in &mut form.fields {
+                            let label = if field.required {
+                                ui.label(format!("{} *", field.name))
+                            } else {
+                                ui.label(&field.name)
+                            };
+                            if !field.description.is_empty() {
+                                label.on_hover_text(&field.description);
+                            }
+                            match field.kind {
+                                ArgumentKind::Boolean => {
+                                    ui.checkbox(&mut field.checked, "");
+                                }
+                                ArgumentKind::Json => {
+                                    ui.add(
+                                        egui::TextEdit::multiline(&mut field.text)
+                                            .code_editor()
+                                            .desired_rows(2),
+                                    );
+                                }
+                                _ => {
+                                    ui.text_edit_singleline(&mut field.text);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(error) = &form.error {
+                    ui.label(RichText::new(error).color(palette.warning).small());
+                }
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Run").clicked() {
+                        match form.arguments() {
+                            Ok(arguments) => {
+                                output.call_mcp_tool =
+                                    Some((form.endpoint.clone(), form.tool.clone(), arguments));
+                                close = true;
+                            }
+                            Err(err) => form.error = Some(err),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if !open || close {
+            state.tool_form = None;
+        }
+    }
+
+    fn message_results(
+        ui: &mut egui::Ui,
+        state: &SidebarState,
+        palette: &ThemePalette,
+        summaries: &[ConversationSummary],
+        query: &str,
+        output: &mut SidebarOutput,
+    ) {
+        egui::CollapsingHeader::new("Messages")
+            .default_open(true)
+            .show(ui, |ui| {
+                ScrollArea::vertical()
+                    .id_source("sidebar_message_hits")
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        for (conversation_id, message) in &state.message_hits {
+                            let title = summaries
+                                .iter()
+                                .find(|summary| summary.id == *conversation_id)
+                                .map_or("Untitled", |summary| summary.title.as_str());
+                            let row = Frame::none()
+                                .fill(palette.surface)
+                                .inner_margin(Margin::symmetric(8.0, 6.0))
+                                .rounding(6.0)
+                                .stroke(egui::Stroke::new(1.0, palette.border))
+                                .show(ui, |ui| {
+                                    ui.set_width(ui.available_width());
+                                    ui.label(RichText::new(title).strong());
+                                    ui.label(
+                                        RichText::new(match_excerpt(&message.content, query))
+                                            .color(palette.text_secondary)
+                                            .small(),
+                                    );
+                                });
+                            if row.response.interact(Sense::click()).clicked() {
+                                output.open_message = Some((*conversation_id, message.id));
+                            }
+                        }
+                    });
+            });
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -425,6 +1114,7 @@ impl Sidebar {
         state: &mut SidebarState,
         palette: &ThemePalette,
         summaries: &[ConversationSummary],
+        usage: &HashMap<Uuid, String>,
         pinned_lookup: &HashSet<Uuid>,
         pinned_order: &[Uuid],
         query: &str,
@@ -449,6 +1139,7 @@ impl Sidebar {
                     lower_query.is_empty() || summary.title.to_lowercase().contains(&lower_query)
                 })
                 .collect();
+            Self::merge_bar(ui, state, palette, summaries, pinned_order, output);
 
             ScrollArea::vertical()
                 .id_source("sidebar_chats")
@@ -462,6 +1153,7 @@ impl Sidebar {
                                 state,
                                 palette,
                                 summary,
+                                usage.get(&summary.id).map(String::as_str),
                                 true,
                                 output,
                                 active_chat,
@@ -469,8 +1161,30 @@ impl Sidebar {
                         }
                         ui.separator();
                     }
+                    let mut current_group = None;
                     for summary in others {
-                        Self::chat_entry(ui, state, palette, summary, false, output, active_chat);
+                        if state.group_by_date {
+                            let group = date_group(
+                                &DateTime::<Local>::from(summary.updated_at),
+                                &Local::now(),
+                            );
+                            if current_group != Some(group) {
+                                ui.label(
+                                    RichText::new(group.label()).color(palette.text_secondary),
+                                );
+                                current_group = Some(group);
+                            }
+                        }
+                        Self::chat_entry(
+                            ui,
+                            state,
+                            palette,
+                            summary,
+                            usage.get(&summary.id).map(String::as_str),
+                            false,
+                            output,
+                            active_chat,
+                        );
                     }
                 });
         });
@@ -487,11 +1201,69 @@ impl Sidebar {
         }
     }
 
+    /// The chats marked with a command-click, in sidebar order: pinned chats first.
+    fn merge_order(
+        state: &SidebarState,
+        summaries: &[ConversationSummary],
+        pinned_order: &[Uuid],
+    ) -> Vec<Uuid> {
+        let pinned = pinned_order
+            .iter()
+            .filter(|id| summaries.iter().any(|summary| summary.id == **id));
+        let others = summaries
+            .iter()
+            .map(|summary| &summary.id)
+            .filter(|id| !pinned_order.contains(id));
+        pinned
+            .chain(others)
+            .filter(|id| state.merge_selection.contains(id))
+            .copied()
+            .collect()
+    }
+
+    /// Offers to merge two or more marked chats into the first of them.
+    fn merge_bar(
+        ui: &mut egui::Ui,
+        state: &mut SidebarState,
+        palette: &ThemePalette,
+        summaries: &[ConversationSummary],
+        pinned_order: &[Uuid],
+        output: &mut SidebarOutput,
+    ) {
+        let selected = Self::merge_order(state, summaries, pinned_order);
+        let Some((&into, sources)) = selected.split_first().filter(|_| selected.len() >= 2) else {
+            return;
+        };
+        let title = summaries
+            .iter()
+            .find(|summary| summary.id == into)
+            .map_or("Untitled", |summary| summary.title.as_str());
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!("{} chats selected", selected.len()))
+                    .color(palette.text_secondary)
+                    .small(),
+            );
+            if ui
+                .add_enabled(!state.read_only, egui::Button::new("Merge"))
+                .on_hover_text(format!("Merge into “{title}”"))
+                .clicked()
+            {
+                output.merge = Some((into, sources.to_vec()));
+            }
+            if ui.small_button("Cancel").clicked() {
+                state.merge_selection.clear();
+            }
+        });
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn chat_entry(
         ui: &mut egui::Ui,
         state: &mut SidebarState,
         palette: &ThemePalette,
         summary: &ConversationSummary,
+        usage: Option<&str>,
         pinned: bool,
         output: &mut SidebarOutput,
         active_chat: Option<Uuid>,
@@ -499,9 +1271,12 @@ impl Sidebar {
         let available = ui.available_width();
         let desired = Vec2::new(available, 52.0);
         let (rect, response) = ui.allocate_exact_size(desired, Sense::click_and_drag());
-        let mut frame = Frame::none()
-            .rounding(6.0)
-            .stroke(egui::Stroke::new(1.0, palette.border));
+        let marked_for_merge = state.merge_selection.contains(&summary.id);
+        let mut frame = Frame::none().rounding(6.0).stroke(if marked_for_merge {
+            egui::Stroke::new(2.0, palette.accent)
+        } else {
+            egui::Stroke::new(1.0, palette.border)
+        });
         let fill = if Some(summary.id) == active_chat {
             palette.surface.linear_multiply(1.08)
         } else {
@@ -516,19 +1291,25 @@ impl Sidebar {
                     ui.label(RichText::new("📌").color(palette.accent));
                 }
             });
-            let timestamp = format_timestamp(summary.updated_at);
-            ui.label(
-                RichText::new(format!(
-                    "{} · {} messages",
-                    timestamp, summary.message_count
-                ))
-                .color(palette.text_secondary)
-                .small(),
-            );
+            let timestamp =
+                format_timestamp(summary.updated_at, &state.timestamp_format, Utc::now());
+            let mut details = format!("{} · {} messages", timestamp, summary.message_count);
+            if let Some(usage) = usage {
+                details.push_str(" · ");
+                details.push_str(usage);
+            }
+            ui.label(RichText::new(details).color(palette.text_secondary).small());
         });
 
         if response.clicked() {
-            output.selected_chat = Some(summary.id);
+            if ui.input(|i| i.modifiers.command) {
+                if !state.merge_selection.remove(&summary.id) {
+                    state.merge_selection.insert(summary.id);
+                }
+            } else {
+                state.merge_selection.clear();
+                output.selected_chat = Some(summary.id);
+            }
         }
         if response.hovered() {
             state.hovered_chat = Some(summary.id);
@@ -536,15 +1317,27 @@ impl Sidebar {
         if response.drag_started() {
             state.dragging_chat = Some(summary.id);
         }
-        if response.double_clicked() {
+        if response.double_clicked() && !state.read_only {
             state.rename_editor = Some(RenameEditor::new(summary.id, &summary.title));
         }
 
+        let writable = !state.read_only;
         response.context_menu(|ui| {
-            if ui.button("Rename").clicked() {
+            if ui
+                .add_enabled(writable, egui::Button::new("Rename"))
+                .clicked()
+            {
                 state.rename_editor = Some(RenameEditor::new(summary.id, &summary.title));
                 ui.close_menu();
             }
+            if ui
+                .add_enabled(writable, egui::Button::new("Reset title"))
+                .on_hover_text("Use the start of the first message as the title")
+                .clicked()
+            {
+                output.reset_title = Some(summary.id);
+                ui.close_menu();
+            }
             if pinned {
                 if ui.button("Unpin").clicked() {
                     output.unpin = Some(summary.id);
@@ -554,12 +1347,31 @@ impl Sidebar {
                 output.pin = Some(summary.id);
                 ui.close_menu();
             }
-            if ui.button("Delete").clicked() {
-                output.delete = Some(summary.id);
+            if ui
+                .add_enabled(writable, egui::Button::new("Duplicate"))
+                .on_hover_text("Continue a copy without changing this chat")
+                .clicked()
+            {
+                output.duplicate = Some(summary.id);
                 ui.close_menu();
             }
-        });
-
+            if ui.button("Copy as JSON").clicked() {
+                output.copy_json = Some(summary.id);
+                ui.close_menu();
+            }
+            if ui.button("Export as JSON…").clicked() {
+                output.export_json = Some(summary.id);
+                ui.close_menu();
+            }
+            if ui
+                .add_enabled(writable, egui::Button::new("Delete"))
+                .clicked()
+            {
+                output.delete = Some(summary.id);
+                ui.close_menu();
+            }
+        });
+
         if matches!(
             state.rename_editor.as_ref().map(|e| e.id),
             Some(edit_id) if edit_id == summary.id
@@ -596,9 +1408,201 @@ impl Sidebar {
     }
 }
 
-fn format_timestamp(time: DateTime<chrono::Utc>) -> String {
+fn rate_limit_indicator(ui: &mut egui::Ui, limits: &RateLimitSnapshot) {
+    let budget = |remaining: Option<u64>, limit: Option<u64>, unit: &str| {
+        remaining.map(|remaining| match limit {
+            Some(limit) => format!("{remaining}/{limit} {unit}"),
+            None => format!("{remaining} {unit}"),
+        })
+    };
+    let parts: Vec<String> = [
+        budget(limits.remaining_requests, limits.limit_requests, "req"),
+        budget(limits.remaining_tokens, limits.limit_tokens, "tok"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if parts.is_empty() {
+        return;
+    }
+    let response = ui.label(
+        RichText::new(format!("Quota: {}", parts.join(" · ")))
+            .small()
+            .color(ui.visuals().weak_text_color()),
+    );
+    let resets: Vec<String> = [
+        limits
+            .reset_requests
+            .as_ref()
+            .map(|reset| format!("Requests reset in {reset}")),
+        limits
+            .reset_tokens
+            .as_ref()
+            .map(|reset| format!("Tokens reset in {reset}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !resets.is_empty() {
+        response.on_hover_text(resets.join("\n"));
+    }
+}
+
+/// Sidebar header a non-pinned chat is listed under when grouping by date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateGroup {
+    Today,
+    Yesterday,
+    ThisWeek,
+    Older,
+}
+
+impl DateGroup {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DateGroup::Today => "Today",
+            DateGroup::Yesterday => "Yesterday",
+            DateGroup::ThisWeek => "This week",
+            DateGroup::Older => "Older",
+        }
+    }
+}
+
+/// Bucket `time` relative to `now` by calendar day in their shared time zone.
+///
+/// Weeks start on Monday; timestamps in the future count as today.
+pub fn date_group<Tz: TimeZone>(time: &DateTime<Tz>, now: &DateTime<Tz>) -> DateGroup {
+    let day = time.date_naive();
+    let today = now.date_naive();
+    let week_start = today - chrono::Duration::days(today.weekday().num_days_from_monday().into());
+    if day >= today {
+        DateGroup::Today
+    } else if today.pred_opt() == Some(day) {
+        DateGroup::Yesterday
+    } else if day >= week_start {
+        DateGroup::ThisWeek
+    } else {
+        DateGroup::Older
+    }
+}
+
+/// A single-line excerpt of `content` around the first case-insensitive match of
+/// `query`, with ellipses where it was cut.
+pub fn match_excerpt(content: &str, query: &str) -> String {
+    const BEFORE: usize = 24;
+    const LENGTH: usize = 80;
+    let chars: Vec<char> = content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect();
+    let lowered: String = chars.iter().collect::<String>().to_lowercase();
+    let needle = query.trim().to_lowercase();
+    // Counting chars in the lowercased prefix is exact except for the few letters whose
+    // lowercase form has a different length, where the excerpt shifts slightly.
+    let position = lowered
+        .find(&needle)
+        .map_or(0, |byte| lowered[..byte].chars().count())
+        .min(chars.len());
+    let start = position.saturating_sub(BEFORE);
+    let end = (start + LENGTH).min(chars.len());
+    let mut excerpt = String::new();
+    if start > 0 {
+        excerpt.push('…');
+    }
+    excerpt.extend(&chars[start..end]);
+    if end < chars.len() {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+/// strftime pattern of [`TimestampFormat::Short`], and the fallback for custom patterns
+/// that do not parse.
+const SHORT_TIMESTAMP: &str = "%b %e, %H:%M";
+/// Pattern a newly picked custom format starts from.
+const DEFAULT_CUSTOM_TIMESTAMP: &str = "%Y-%m-%d %H:%M";
+
+/// How chat and message times are shown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    /// "5m ago", "3h ago", up to a week; the short form after that.
+    Relative,
+    /// Month, day and time, such as "Mar  4, 14:05".
+    #[default]
+    Short,
+    /// A strftime pattern, such as `%Y-%m-%d %H:%M`.
+    Custom(String),
+}
+
+impl TimestampFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimestampFormat::Relative => "Relative",
+            TimestampFormat::Short => "Short",
+            TimestampFormat::Custom(_) => "Custom",
+        }
+    }
+}
+
+/// Whether chrono can format with `pattern`; formatting with an invalid one panics.
+fn is_valid_strftime(pattern: &str) -> bool {
+    !pattern.trim().is_empty()
+        && !StrftimeItems::new(pattern).any(|item| matches!(item, chrono::format::Item::Error))
+}
+
+/// `time` in local time as `format` asks, with `now` as the reference for relative times.
+pub fn format_timestamp(
+    time: DateTime<chrono::Utc>,
+    format: &TimestampFormat,
+    now: DateTime<chrono::Utc>,
+) -> String {
     let local: DateTime<Local> = DateTime::from(time);
-    local.format("%b %e, %H:%M").to_string()
+    match format {
+        TimestampFormat::Relative => {
+            let elapsed = now.signed_duration_since(time);
+            match elapsed.num_minutes() {
+                minutes if minutes < 1 => "just now".to_string(),
+                minutes if minutes < 60 => format!("{minutes}m ago"),
+                _ if elapsed.num_hours() < 24 => format!("{}h ago", elapsed.num_hours()),
+                _ if elapsed.num_days() < 7 => format!("{}d ago", elapsed.num_days()),
+                _ => local.format(SHORT_TIMESTAMP).to_string(),
+            }
+        }
+        TimestampFormat::Custom(pattern) if is_valid_strftime(pattern) => {
+            local.format(pattern).to_string()
+        }
+        TimestampFormat::Short | TimestampFormat::Custom(_) => {
+            local.format(SHORT_TIMESTAMP).to_string()
+        }
+    }
+}
+
+/// Dollars to the cent, or to a hundredth of a cent below one so cheap replies do not
+/// read as free.
+pub fn format_cost(dollars: f64) -> String {
+    if dollars > 0.0 && dollars < 0.01 {
+        format!("${dollars:.4}")
+    } else {
+        format!("${dollars:.2}")
+    }
+}
+
+/// The cost of a chat's replies when every model used has a price, otherwise their
+/// token count; `None` before the chat's messages are loaded.
+fn usage_label(summary: &ConversationSummary, pricing: &Pricing, model: &str) -> Option<String> {
+    if summary.usage.is_empty() {
+        return None;
+    }
+    Some(match pricing.total_cost(&summary.usage, model) {
+        Some(cost) => format_cost(cost),
+        None => {
+            let tokens: usize = summary.usage.values().map(ModelUsage::total_tokens).sum();
+            format!("{tokens} tokens")
+        }
+    })
 }
 
 #[derive(Clone, Debug)]
@@ -607,9 +1611,174 @@ pub struct McpSidebarEntry {
     pub name: String,
     pub description: String,
     pub status: McpStatus,
+    /// Why the server last disconnected, when it did not close on request.
+    pub reason: Option<String>,
+    pub tools: McpToolList,
+    /// Tools with a call in flight, by name.
+    pub running_tools: Vec<String>,
+}
+
+/// The tools of a connected MCP server, as far as they are known.
+#[derive(Clone, Debug, Default)]
+pub enum McpToolList {
+    #[default]
+    NotLoaded,
+    Loading,
+    Loaded(Vec<ToolDefinition>),
+    Failed(String),
+}
+
+/// How a tool argument is entered, from the `type` in its JSON schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgumentKind {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// Objects, arrays and anything untyped, typed in as JSON.
+    Json,
+}
+
+#[derive(Clone, Debug)]
+pub struct ToolArgumentField {
+    pub name: String,
+    pub description: String,
+    pub kind: ArgumentKind,
+    pub required: bool,
+    pub text: String,
+    pub checked: bool,
+}
+
+/// A form for the arguments of an MCP tool, one field per property of its schema.
+#[derive(Clone, Debug)]
+pub struct ToolForm {
+    pub endpoint: String,
+    pub tool: String,
+    pub description: String,
+    pub fields: Vec<ToolArgumentField>,
+    pub error: Option<String>,
+}
+
+impl ToolForm {
+    pub fn new(endpoint: &str, tool: &ToolDefinition) -> Self {
+        let schema = &tool.parameters;
+        let required: HashSet<&str> = schema["required"]
+            .as_array()
+            .map(|names| names.iter().filter_map(|name| name.as_str()).collect())
+            .unwrap_or_default();
+        let fields = schema["properties"]
+            .as_object()
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(name, property)| ToolArgumentField {
+                        name: name.clone(),
+                        description: property["description"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        kind: match property["type"].as_str() {
+                            Some("string") => ArgumentKind::String,
+                            Some("integer") => ArgumentKind::Integer,
+                            Some("number") => ArgumentKind::Number,
+                            Some("boolean") => ArgumentKind::Boolean,
+                            _ => ArgumentKind::Json,
+                        },
+                        required: required.contains(name.as_str()),
+                        text: String::new(),
+                        checked: false,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            endpoint: endpoint.to_string(),
+            tool: tool.name.clone(),
+            description: tool.description.clone(),
+            fields,
+            error: None,
+        }
+    }
+
+    /// The arguments object for the call; empty optional fields are left out.
+    pub fn arguments(&self) -> Result<serde_json::Value, String> {
+        let mut arguments = serde_json::Map::new();
+        for field in &self.fields {
+            let text = field.text.trim();
+            if field.kind != ArgumentKind::Boolean && text.is_empty() {
+                if field.required {
+                    return Err(format!("`{}` is required", field.name));
+                }
+                continue;
+            }
+            let value = match field.kind {
+                ArgumentKind::String => serde_json::Value::String(field.text.clone()),
+                ArgumentKind::Boolean => serde_json::Value::Bool(field.checked),
+                ArgumentKind::Integer => text
+                    .parse::<i64>()
+                    .map(serde_json::Value::from)
+                    .map_err(|_| format!("`{}` must be a whole number", field.name))?,
+                ArgumentKind::Number => text
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(serde_json::Value::Number)
+                    .ok_or_else(|| format!("`{}` must be a number", field.name))?,
+                ArgumentKind::Json => serde_json::from_str(text)
+                    .map_err(|err| format!("`{}` is not valid JSON: {err}", field.name))?,
+            };
+            arguments.insert(field.name.clone(), value);
+        }
+        Ok(serde_json::Value::Object(arguments))
+    }
 }
 
 impl McpSidebarEntry {
+    /// A not yet connected entry for `endpoint`, described by what it runs or where.
+    pub fn for_endpoint(endpoint: &McpEndpoint) -> Self {
+        let description = match &endpoint.transport {
+            TransportSpec::ChildProcess(command) => Path::new(&command.program)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| command.program.clone()),
+            TransportSpec::Sse { url, .. } => url.clone(),
+        };
+        Self {
+            id: endpoint.id.clone(),
+            name: endpoint.id.clone(),
+            description,
+            status: McpStatus::Connecting,
+            reason: None,
+            tools: McpToolList::NotLoaded,
+            running_tools: Vec::new(),
+        }
+    }
+
+    /// Move to the status `event` leads to; no-op for events about other endpoints.
+    pub fn apply_event(&mut self, event: &McpEvent) {
+        let endpoint = match event {
+            McpEvent::Connected { endpoint, .. }
+            | McpEvent::Disconnected { endpoint, .. }
+            | McpEvent::Reconnecting { endpoint, .. }
+            | McpEvent::ToolInvoked { endpoint, .. }
+            | McpEvent::ToolFailed { endpoint, .. } => endpoint,
+        };
+        if *endpoint != self.id {
+            return;
+        }
+        if let Some(status) = McpStatus::after_event(event) {
+            self.status = status;
+            // A new connection may offer different tools.
+            self.tools = McpToolList::NotLoaded;
+            self.reason = match event {
+                McpEvent::Disconnected { reason, .. } => {
+                    reason.clone().filter(|reason| reason != "cancelled")
+                }
+                _ => None,
+            };
+        }
+    }
+
     pub fn matches(&self, query: &str) -> bool {
         let q = query.trim().to_lowercase();
         q.is_empty()
@@ -626,6 +1795,16 @@ pub enum McpStatus {
 }
 
 impl McpStatus {
+    /// The status an MCP client event moves its endpoint to, if it changes it.
+    pub fn after_event(event: &McpEvent) -> Option<Self> {
+        match event {
+            McpEvent::Connected { .. } => Some(McpStatus::Connected),
+            McpEvent::Reconnecting { .. } => Some(McpStatus::Connecting),
+            McpEvent::Disconnected { .. } => Some(McpStatus::Disconnected),
+            McpEvent::ToolInvoked { .. } | McpEvent::ToolFailed { .. } => None,
+        }
+    }
+
     pub fn label(&self) -> &'static str {
         match self {
             McpStatus::Connected => "Connected",
@@ -643,17 +1822,79 @@ impl McpStatus {
     }
 }
 
+/// Space below each message bubble.
+const BUBBLE_SPACING: f32 = 8.0;
+/// Latest messages a conversation opens with; scrolling to the top shows older ones.
+const VISIBLE_WINDOW: usize = 80;
+/// Older messages added each time the top of the history is reached.
+const LOAD_OLDER_STEP: usize = 40;
+/// Messages longer than this many lines are shown collapsed until expanded.
+const COLLAPSE_AFTER_LINES: usize = 40;
+/// Lines of a collapsed message that are still shown.
+const COLLAPSED_PREVIEW_LINES: usize = 20;
+
+/// What "Copy" puts on the clipboard for `message`: its raw markdown, followed by its
+/// tool calls as a fenced JSON block when `with_tool_calls` is set and it made any.
+pub fn message_clipboard_text(message: &ChatMessage, with_tool_calls: bool) -> String {
+    if !with_tool_calls || message.tool_calls.is_empty() {
+        return message.content.clone();
+    }
+    let calls = serde_json::to_string_pretty(&message.tool_calls).unwrap_or_default();
+    format!("{}\n\n```json\n{calls}\n```", message.content.trim_end())
+}
+
+/// The start of `content` to show while it is collapsed, or `None` when it is short
+/// enough to show in full.
+pub fn collapsed_preview(content: &str) -> Option<&str> {
+    let mut ends = content.match_indices('\n').map(|(index, _)| index);
+    let preview_end = ends.nth(COLLAPSED_PREVIEW_LINES - 1)?;
+    // The newline ending the last line may be missing; `lines()` counts that line too.
+    let remaining = content[preview_end + 1..].lines().count();
+    (COLLAPSED_PREVIEW_LINES + remaining > COLLAPSE_AFTER_LINES).then(|| &content[..preview_end])
+}
+
 #[derive(Clone)]
 pub struct ChatPanelState {
+    /// How many of the latest messages the history holds; see [`Self::request_more`].
     pub visible_limit: usize,
+    /// Messages the last [`Self::request_more`] added above the ones shown, so the view
+    /// can stay where it was instead of jumping to the top.
+    added_above: usize,
     pub last_conversation_id: Option<Uuid>,
+    /// Session-only reading mode: hides the input bar and disables edits for this view.
+    pub preview: bool,
+    settings_draft: Option<ConversationSettings>,
+    system_prompt_draft: Option<String>,
+    /// User message being edited in place, with its draft text.
+    editing: Option<(Uuid, String)>,
+    /// Message to bring into view on the next frame, e.g. a picked search result.
+    scroll_to: Option<Uuid>,
+    /// Laid-out height of each message seen so far, valid for `row_width`.
+    row_heights: HashMap<Uuid, f32>,
+    row_width: f32,
+    /// Messages laid out in the last frame, by index.
+    rendered: Range<usize>,
+    /// Long messages the user expanded in this conversation; others stay collapsed.
+    expanded: HashSet<Uuid>,
+    pub timestamp_format: TimestampFormat,
 }
 
 impl Default for ChatPanelState {
     fn default() -> Self {
         Self {
-            visible_limit: 80,
+            visible_limit: VISIBLE_WINDOW,
+            added_above: 0,
             last_conversation_id: None,
+            preview: false,
+            settings_draft: None,
+            system_prompt_draft: None,
+            editing: None,
+            scroll_to: None,
+            row_heights: HashMap::new(),
+            row_width: 0.0,
+            rendered: 0..0,
+            expanded: HashSet::new(),
+            timestamp_format: TimestampFormat::default(),
         }
     }
 }
@@ -662,25 +1903,90 @@ impl ChatPanelState {
     pub fn reset_if_needed(&mut self, conversation_id: Uuid) {
         if self.last_conversation_id != Some(conversation_id) {
             self.last_conversation_id = Some(conversation_id);
-            self.visible_limit = 80;
+            self.visible_limit = VISIBLE_WINDOW;
+            self.added_above = 0;
+            self.preview = false;
+            self.row_heights.clear();
+            self.settings_draft = None;
+            self.system_prompt_draft = None;
+            self.editing = None;
+            self.expanded.clear();
         }
     }
 
+    /// Show [`LOAD_OLDER_STEP`] more of the `total` messages, above the ones shown.
     pub fn request_more(&mut self, total: usize) {
         if self.visible_limit < total {
-            self.visible_limit = (self.visible_limit + 40).min(total);
+            let limit = (self.visible_limit + LOAD_OLDER_STEP).min(total);
+            self.added_above = limit - self.visible_limit;
+            self.visible_limit = limit;
         }
     }
+
+    /// Scroll `message_id` into view the next time its conversation is shown, expanded
+    /// so that whatever was searched for in it is visible.
+    pub fn scroll_to_message(&mut self, conversation_id: Uuid, message_id: Uuid) {
+        // Switch now, so showing the conversation does not collapse the message again.
+        self.reset_if_needed(conversation_id);
+        self.scroll_to = Some(message_id);
+        self.expanded.insert(message_id);
+    }
+
+    /// Whether `message` is shown in full rather than as a collapsed preview.
+    pub fn is_expanded(&self, message: &ChatMessage) -> bool {
+        self.expanded.contains(&message.id) || collapsed_preview(&message.content).is_none()
+    }
+
+    pub fn toggle_expanded(&mut self, message_id: Uuid) {
+        if !self.expanded.remove(&message_id) {
+            self.expanded.insert(message_id);
+        }
+    }
+
+    /// Indices of the messages laid out in the last frame; the rest of the transcript
+    /// is only accounted for by height.
+    pub fn rendered_messages(&self) -> Range<usize> {
+        self.rendered.clone()
+    }
+
+    /// Height of `message` including the spacing below it: as last laid out, or
+    /// estimated from its line count until it has been on screen.
+    fn row_height(&self, message: &ChatMessage) -> f32 {
+        if let Some(height) = self.row_heights.get(&message.id) {
+            return *height;
+        }
+        let columns = (self.row_width / 7.0).max(20.0) as usize;
+        let content = match self.expanded.contains(&message.id) {
+            true => &message.content,
+            false => collapsed_preview(&message.content).unwrap_or(&message.content),
+        };
+        let lines: usize = content
+            .lines()
+            .map(|line| line.chars().count() / columns + 1)
+            .sum();
+        72.0 + lines as f32 * 18.0 + BUBBLE_SPACING
+    }
 }
 
 #[derive(Default)]
 pub struct ChatPanelOutput {
+    pub settings_changed: Option<(Uuid, ConversationSettings)>,
+    pub system_prompt_changed: Option<(Uuid, String)>,
+    /// Assistant message to regenerate into a new variant.
+    pub regenerate: Option<Uuid>,
+    /// Assistant message and the variant index to show for it.
+    pub select_alternative: Option<(Uuid, usize)>,
+    /// User message and its new content; later messages are dropped and a new reply
+    /// is requested.
+    pub edit_message: Option<(Uuid, String)>,
+    /// The top of the history was reached while older messages are hidden.
     pub load_older: bool,
 }
 
 pub struct ChatPanel;
 
 impl ChatPanel {
+    #[allow(clippy::too_many_arguments)]
     pub fn show(
         ui: &mut egui::Ui,
         palette: &ThemePalette,
@@ -688,39 +1994,435 @@ impl ChatPanel {
         conversation: &Conversation,
         streaming_message: Option<&crate::app::StreamingMessage>,
         markdown_cache: &mut CommonMarkCache,
+        code_highlighter: &mut CodeHighlighter,
+        tokens: &TokenCounter,
+        defaults: &ChatParameters,
+        available_models: &[String],
+        pricing: &Pricing,
+        read_only: bool,
+        busy: bool,
     ) -> ChatPanelOutput {
         let mut output = ChatPanelOutput::default();
         state.reset_if_needed(conversation.id);
-        let total = conversation.messages.len();
-        let start = total.saturating_sub(state.visible_limit);
-        let messages = &conversation.messages[start..];
-        let scroll = ScrollArea::vertical()
+        Self::header(ui, palette, state, conversation);
+        let read_only = read_only || state.preview;
+        Self::system_prompt(ui, palette, state, conversation, read_only, &mut output);
+        Self::settings_window(
+            ui,
+            palette,
+            state,
+            conversation,
+            defaults,
+            available_models,
+            read_only,
+            &mut output,
+        );
+        let all = &conversation.messages;
+        // A picked search hit outside the window widens it.
+        if let Some(index) = state
+            .scroll_to
+            .and_then(|id| all.iter().position(|message| message.id == id))
+        {
+            state.visible_limit = state.visible_limit.max(all.len() - index);
+        }
+        let start = all.len().saturating_sub(state.visible_limit);
+        let messages = &all[start..];
+        let width = ui.available_width();
+        if (state.row_width - width).abs() > 1.0 {
+            state.row_heights.clear();
+            state.row_width = width;
+        }
+        // Only bubbles in the viewport are laid out; the others are stood in for by
+        // their last known (or estimated) height.
+        let mut tops = Vec::with_capacity(messages.len() + 1);
+        let mut bottom = 0.0;
+        for message in messages {
+            tops.push(bottom);
+            bottom += state.row_height(message);
+        }
+        tops.push(bottom);
+        let target = state
+            .scroll_to
+            .and_then(|id| messages.iter().position(|message| message.id == id));
+        let mut scroll = ScrollArea::vertical()
             .id_source("chat_history")
-            .stick_to_bottom(true)
-            .auto_shrink([false; 2])
+            .stick_to_bottom(target.is_none())
+            .auto_shrink([false; 2]);
+        if let Some(index) = target {
+            scroll = scroll.vertical_scroll_offset(tops[index]);
+        } else if state.added_above > 0 {
+            scroll = scroll.vertical_scroll_offset(tops[state.added_above.min(messages.len())]);
+        }
+        state.added_above = 0;
+        state.rendered = 0..0;
+        let scrolled = scroll.show_viewport(ui, |ui, viewport| {
+            let first = tops[1..].partition_point(|bottom| *bottom <= viewport.min.y);
+            let last = tops[..messages.len()]
+                .partition_point(|top| *top < viewport.max.y)
+                .max(first);
+            ui.add_space(tops[first]);
+            if messages.is_empty() && streaming_message.is_none() && !read_only {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(48.0);
+                    ui.label(
+                        RichText::new("Ask anything to get started").color(palette.text_secondary),
+                    );
+                });
+            }
+            // Replies that did not record their model are charged as the chat's model.
+            let model = conversation
+                .settings
+                .model
+                .as_deref()
+                .unwrap_or(&defaults.model);
+            for message in &messages[first..last] {
+                if state.scroll_to == Some(message.id) {
+                    ui.scroll_to_cursor(Some(Align::TOP));
+                    state.scroll_to = None;
+                }
+                let top = ui.cursor().min.y;
+                let cost = message.usage.as_ref().and_then(|usage| {
+                    pricing.cost(message.model.as_deref().unwrap_or(model), usage)
+                });
+                Self::chat_bubble(
+                    ui,
+                    palette,
+                    markdown_cache,
+                    code_highlighter,
+                    tokens,
+                    message,
+                    cost,
+                    !read_only && !busy,
+                    &mut state.editing,
+                    &mut state.expanded,
+                    &state.timestamp_format,
+                    &mut output,
+                );
+                ui.add_space(BUBBLE_SPACING);
+                state
+                    .row_heights
+                    .insert(message.id, ui.cursor().min.y - top);
+            }
+            state.rendered = start + first..start + last;
+            ui.add_space(tops[messages.len()] - tops[last]);
+
+            // Display streaming message if present
+            if let Some(streaming) = streaming_message {
+                // Only the reply's tokens are known until it finishes.
+                let readout = (!streaming.content.is_empty()).then(|| {
+                    let usage = ModelUsage {
+                        prompt_tokens: 0,
+                        completion_tokens: tokens.count(&streaming.content),
+                    };
+                    match pricing.cost(model, &usage) {
+                        Some(cost) => format!(
+                            "~{} tokens · {} so far",
+                            usage.completion_tokens,
+                            format_cost(cost)
+                        ),
+                        None => format!("~{} tokens so far", usage.completion_tokens),
+                    }
+                });
+                Self::streaming_bubble(ui, palette, markdown_cache, &streaming.content, readout);
+                ui.add_space(BUBBLE_SPACING);
+            }
+        });
+        if scrolled.state.offset.y <= 4.0 && start > 0 {
+            output.load_older = true;
+        }
+        output
+    }
+
+    fn header(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        state: &mut ChatPanelState,
+        conversation: &Conversation,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(&conversation.title)
+                    .strong()
+                    .color(palette.text_primary),
+            );
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                let label = if conversation.settings.is_empty() {
+                    "Chat settings"
+                } else {
+                    "Chat settings •"
+                };
+                if ui
+                    .button(label)
+                    .on_hover_text("Model and parameters for this chat")
+                    .clicked()
+                {
+                    state.settings_draft = match state.settings_draft {
+                        Some(_) => None,
+                        None => Some(conversation.settings.clone()),
+                    };
+                }
+                if ui
+                    .selectable_label(state.preview, "👁 Preview")
+                    .on_hover_text("Read without the input bar; nothing is saved")
+                    .clicked()
+                {
+                    state.preview = !state.preview;
+                }
+            });
+        });
+        ui.add_space(8.0);
+    }
+
+    fn system_prompt(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        state: &mut ChatPanelState,
+        conversation: &Conversation,
+        read_only: bool,
+        output: &mut ChatPanelOutput,
+    ) {
+        let saved = conversation.system_prompt.as_deref().unwrap_or_default();
+        let draft = state
+            .system_prompt_draft
+            .get_or_insert_with(|| saved.to_string());
+        let title = if saved.is_empty() {
+            "System prompt"
+        } else {
+            "System prompt •"
+        };
+        egui::CollapsingHeader::new(title)
+            .id_source(("system_prompt", conversation.id))
+            .default_open(false)
             .show(ui, |ui| {
-                for message in messages {
-                    Self::chat_bubble(ui, palette, markdown_cache, message);
-                    ui.add_space(8.0);
+                let response = ui.add_enabled(
+                    !read_only,
+                    egui::TextEdit::multiline(draft)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY)
+                        .hint_text("Instructions sent ahead of every message in this chat"),
+                );
+                ui.label(
+                    RichText::new(
+                        "Changes apply from the next reply; earlier replies stay as they are.",
+                    )
+                    .color(palette.text_secondary)
+                    .small(),
+                );
+                if response.lost_focus() && draft.trim() != saved {
+                    output.system_prompt_changed = Some((conversation.id, draft.clone()));
                 }
-                
-                // Display streaming message if present
-                if let Some(streaming) = streaming_message {
-                    Self::streaming_bubble(ui, palette, markdown_cache, &streaming.content);
-                    ui.add_space(8.0);
+            });
+        ui.add_space(4.0);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn settings_window(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        state: &mut ChatPanelState,
+        conversation: &Conversation,
+        defaults: &ChatParameters,
+        available_models: &[String],
+        read_only: bool,
+        output: &mut ChatPanelOutput,
+    ) {
+        let Some(draft) = state.settings_draft.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut close = false;
+        egui::Window::new("Chat settings")
+            .id(egui::Id::new("chat_settings").with(conversation.id))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                let hint = if conversation.settings.locked {
+                    "Parameters are locked. Unlock them from the input bar to edit."
+                } else {
+                    "Unchecked parameters inherit the global defaults."
+                };
+                ui.label(RichText::new(hint).color(palette.text_secondary).small());
+                ui.add_space(8.0);
+                override_row(
+                    ui,
+                    palette,
+                    "Model",
+                    &mut draft.model,
+                    &defaults.model,
+                    |ui, model| {
+                        egui::ComboBox::from_id_source("chat_settings_model")
+                            .selected_text(model.as_str())
+                            .show_ui(ui, |ui| {
+                                for candidate in available_models {
+                                    ui.selectable_value(model, candidate.clone(), candidate);
+                                }
+                            });
+                    },
+                );
+                override_row(
+                    ui,
+                    palette,
+                    "Temperature",
+                    &mut draft.temperature,
+                    &defaults.temperature,
+                    |ui, temperature| {
+                        ui.add(egui::Slider::new(temperature, 0.0..=2.0));
+                    },
+                );
+                override_row(
+                    ui,
+                    palette,
+                    "History",
+                    &mut draft.max_request_messages,
+                    &defaults.max_request_messages,
+                    |ui, limit| {
+                        ui.add(egui::DragValue::new(limit).clamp_range(0..=500))
+                            .on_hover_text("Prior messages sent with each request (0 = all)");
+                    },
+                );
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !read_only && !conversation.settings.locked,
+                            egui::Button::new("Save"),
+                        )
+                        .clicked()
+                    {
+                        output.settings_changed = Some((conversation.id, draft.clone()));
+                        close = true;
+                    }
+                    if ui.button("Inherit all").clicked() {
+                        *draft = ConversationSettings::default();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if close || !open {
+            state.settings_draft = None;
+        }
+    }
+
+    /// Markdown with its fenced code blocks highlighted by `code_highlighter`.
+    fn message_body(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        markdown_cache: &mut CommonMarkCache,
+        code_highlighter: &mut CodeHighlighter,
+        message: &ChatMessage,
+        content: &str,
+    ) {
+        for (index, segment) in split_code_blocks(content).into_iter().enumerate() {
+            match segment {
+                Segment::Markdown(text) if text.trim().is_empty() => {}
+                Segment::Markdown(text) => {
+                    CommonMarkViewer::new(format!("msg_{}_{index}", message.id)).show(
+                        ui,
+                        markdown_cache,
+                        text,
+                    );
+                }
+                Segment::Code { language, code } => {
+                    let code = code.strip_suffix('\n').unwrap_or(code);
+                    let job = code_highlighter.layout(ui, message.id, index, language, code);
+                    let fill = code_highlighter
+                        .background(ui.visuals().dark_mode)
+                        .unwrap_or(ui.visuals().extreme_bg_color);
+                    Frame::none()
+                        .fill(fill)
+                        .stroke(egui::Stroke::new(1.0, palette.border))
+                        .rounding(6.0)
+                        .inner_margin(Margin::symmetric(8.0, 6.0))
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    RichText::new(language)
+                                        .color(palette.text_secondary)
+                                        .small(),
+                                );
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    if ui.small_button("Copy").clicked() {
+                                        ui.ctx().output_mut(|o| o.copied_text = code.to_owned());
+                                    }
+                                });
+                            });
+                            ui.add(egui::Label::new(job).wrap(true));
+                        });
+                    ui.add_space(4.0);
+                }
+            }
+        }
+    }
+
+    /// Copies the message's markdown source; a right click offers to include its tool
+    /// calls when it made any.
+    fn copy_message_button(ui: &mut egui::Ui, message: &ChatMessage) {
+        let hover = if message.tool_calls.is_empty() {
+            "Copy the message as markdown"
+        } else {
+            "Copy the message as markdown; right-click to include its tool calls"
+        };
+        let button = ui.small_button("Copy").on_hover_text(hover);
+        if button.clicked() {
+            let text = message_clipboard_text(message, false);
+            ui.ctx().output_mut(|o| o.copied_text = text);
+        }
+        if !message.tool_calls.is_empty() {
+            button.context_menu(|ui| {
+                if ui.button("Copy with tool calls").clicked() {
+                    let text = message_clipboard_text(message, true);
+                    ui.ctx().output_mut(|o| o.copied_text = text);
+                    ui.close_menu();
                 }
             });
-        if scroll.state.offset.y <= 4.0 && start > 0 {
-            output.load_older = true;
         }
-        output
     }
 
+    /// "Show more"/"Show less" under a long message.
+    fn collapse_toggle(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        message: &ChatMessage,
+        expanded: &mut HashSet<Uuid>,
+    ) {
+        let Some(preview) = collapsed_preview(&message.content) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            if !expanded.contains(&message.id) {
+                let hidden = message.content.lines().count() - preview.lines().count();
+                ui.label(
+                    RichText::new(format!("… {hidden} more lines"))
+                        .color(palette.text_secondary)
+                        .small(),
+                );
+                if ui.link("Show more").clicked() {
+                    expanded.insert(message.id);
+                }
+            } else if ui.link("Show less").clicked() {
+                expanded.remove(&message.id);
+            }
+        });
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn chat_bubble(
         ui: &mut egui::Ui,
         palette: &ThemePalette,
         markdown_cache: &mut CommonMarkCache,
+        code_highlighter: &mut CodeHighlighter,
+        tokens: &TokenCounter,
         message: &ChatMessage,
+        cost: Option<f64>,
+        can_change: bool,
+        editing: &mut Option<(Uuid, String)>,
+        expanded: &mut HashSet<Uuid>,
+        timestamps: &TimestampFormat,
+        output: &mut ChatPanelOutput,
     ) {
         let is_user = matches!(message.role, MessageRole::User);
         let bubble_color = if is_user {
@@ -750,7 +2452,7 @@ impl ChatPanel {
                 Layout::top_down(Align::Min),
                 |ui| {
                     ui.set_width(bubble_width);
-                    Frame::none()
+                    let bubble = Frame::none()
                         .fill(bubble_color)
                         .stroke(egui::Stroke::new(1.0, palette.border))
                         .rounding(egui::Rounding::same(10.0))
@@ -759,20 +2461,74 @@ impl ChatPanel {
                             ui.horizontal(|ui| {
                                 ui.label(RichText::new(message.role_label()).strong());
                                 ui.label(
-                                    RichText::new(message.created_at.to_rfc2822())
-                                        .color(palette.text_secondary)
-                                        .small(),
+                                    RichText::new(format_timestamp(
+                                        message.created_at,
+                                        timestamps,
+                                        Utc::now(),
+                                    ))
+                                    .color(palette.text_secondary)
+                                    .small(),
                                 );
+                                if message.truncated {
+                                    ui.label(
+                                        RichText::new("Stopped").color(palette.warning).small(),
+                                    )
+                                    .on_hover_text("The reply was stopped before it finished");
+                                }
+                                if !message.images.is_empty() {
+                                    let count = message.images.len();
+                                    let noun = if count == 1 { "image" } else { "images" };
+                                    ui.label(
+                                        RichText::new(format!("🖼 {count} {noun}"))
+                                            .color(palette.text_secondary)
+                                            .small(),
+                                    )
+                                    .on_hover_text("Sent to the model with this message");
+                                }
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    Self::copy_message_button(ui, message);
+                                });
                             });
-                            CommonMarkViewer::new(format!("msg_{}", message.id)).show(
-                                ui,
-                                markdown_cache,
-                                &message.content,
-                            );
+                            match editing {
+                                Some((id, draft)) if *id == message.id => {
+                                    let (save, cancel) = Self::message_editor(ui, draft);
+                                    if save {
+                                        output.edit_message = Some((message.id, draft.clone()));
+                                    }
+                                    if save || cancel {
+                                        *editing = None;
+                                    }
+                                }
+                                _ => {
+                                    let preview = collapsed_preview(&message.content)
+                                        .filter(|_| !expanded.contains(&message.id));
+                                    Self::message_body(
+                                        ui,
+                                        palette,
+                                        markdown_cache,
+                                        code_highlighter,
+                                        message,
+                                        preview.unwrap_or(&message.content),
+                                    );
+                                    Self::collapse_toggle(ui, palette, message, expanded);
+                                }
+                            }
                             if !message.tool_calls.is_empty() {
                                 ui.collapsing("Tool calls", |ui| {
                                     for call in &message.tool_calls {
-                                        ui.label(RichText::new(&call.name).strong());
+                                        ui.horizontal(|ui| {
+                                            ui.label(RichText::new(&call.name).strong());
+                                            let status = match call.status {
+                                                ToolCallStatus::Pending => "pending",
+                                                ToolCallStatus::Completed => "completed",
+                                                ToolCallStatus::Failed => "failed",
+                                            };
+                                            ui.label(
+                                                RichText::new(status)
+                                                    .small()
+                                                    .color(palette.text_secondary),
+                                            );
+                                        });
                                         if let Ok(pretty) =
                                             serde_json::to_string_pretty(&call.arguments)
                                         {
@@ -781,13 +2537,26 @@ impl ChatPanel {
                                     }
                                 });
                             }
-                            let token_guess = (message.content.chars().count() / 4).max(1);
-                            ui.label(
-                                RichText::new(format!("~{} tokens", token_guess))
-                                    .color(palette.text_secondary)
-                                    .small(),
-                            );
+                            ui.horizontal(|ui| {
+                                Self::token_count(ui, palette, tokens, message, cost);
+                                if message.role == MessageRole::Assistant {
+                                    Self::alternative_controls(
+                                        ui, palette, message, can_change, output,
+                                    );
+                                }
+                            });
                         });
+                    if is_user
+                        && can_change
+                        && editing.is_none()
+                        && bubble
+                            .response
+                            .interact(egui::Sense::click())
+                            .on_hover_text("Double-click to edit and resend")
+                            .double_clicked()
+                    {
+                        *editing = Some((message.id, message.content.clone()));
+                    }
                 },
             );
             if trailing_pad > 0.0 {
@@ -796,11 +2565,109 @@ impl ChatPanel {
         });
     }
 
+    /// Inline editor for a user message; returns whether it was saved or cancelled.
+    fn message_editor(ui: &mut egui::Ui, draft: &mut String) -> (bool, bool) {
+        let response = ui.add(
+            egui::TextEdit::multiline(draft)
+                .desired_rows(3)
+                .desired_width(f32::INFINITY),
+        );
+        let escape = response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::Escape));
+        let mut save = false;
+        let mut cancel = escape;
+        ui.horizontal(|ui| {
+            save = ui
+                .add_enabled(!draft.trim().is_empty(), egui::Button::new("Save & resend"))
+                .on_hover_text("Replies after this message are removed")
+                .clicked();
+            cancel |= ui.button("Cancel").clicked();
+        });
+        (save, cancel)
+    }
+
+    /// The reply's reported token usage and its `cost`, if priced, or the tokenizer's
+    /// count for messages without usage.
+    fn token_count(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        tokens: &TokenCounter,
+        message: &ChatMessage,
+        cost: Option<f64>,
+    ) {
+        match &message.usage {
+            Some(usage) => {
+                let mut label = format!("{} tokens", usage.completion_tokens);
+                let mut hover = format!(
+                    "Prompt {} · completion {} · total {}",
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                    usage.total_tokens()
+                );
+                if let Some(cost) = cost {
+                    label = format!("{label} · {}", format_cost(cost));
+                    hover = format!("{hover}\nEstimated cost {}", format_cost(cost));
+                }
+                if let Some(model) = &message.model {
+                    hover = format!("{hover}\nModel {model}");
+                }
+                ui.label(RichText::new(label).color(palette.text_secondary).small())
+                    .on_hover_text(hover);
+            }
+            None => {
+                ui.label(
+                    RichText::new(format!("{} tokens", tokens.count(&message.content)))
+                        .color(palette.text_secondary)
+                        .small(),
+                );
+            }
+        }
+    }
+
+    fn alternative_controls(
+        ui: &mut egui::Ui,
+        palette: &ThemePalette,
+        message: &ChatMessage,
+        can_regenerate: bool,
+        output: &mut ChatPanelOutput,
+    ) {
+        let count = message.alternative_count();
+        let selected = message.selected_alternative;
+        if count > 1 {
+            if ui
+                .add_enabled(selected > 0, egui::Button::new("◀").small())
+                .on_hover_text("Previous variant")
+                .clicked()
+            {
+                output.select_alternative = Some((message.id, selected - 1));
+            }
+            ui.label(
+                RichText::new(format!("{}/{}", selected + 1, count))
+                    .color(palette.text_secondary)
+                    .small(),
+            );
+            if ui
+                .add_enabled(selected + 1 < count, egui::Button::new("▶").small())
+                .on_hover_text("Next variant")
+                .clicked()
+            {
+                output.select_alternative = Some((message.id, selected + 1));
+            }
+        }
+        if ui
+            .add_enabled(can_regenerate, egui::Button::new("⟳ Regenerate").small())
+            .on_hover_text("Generate another reply and keep this one")
+            .clicked()
+        {
+            output.regenerate = Some(message.id);
+        }
+    }
+
     fn streaming_bubble(
         ui: &mut egui::Ui,
         palette: &ThemePalette,
         markdown_cache: &mut CommonMarkCache,
         content: &str,
+        readout: Option<String>,
     ) {
         let bubble_color = palette.assistant_bubble;
         let total_width = ui.available_width().max(0.0);
@@ -825,8 +2692,15 @@ impl ChatPanel {
                                         .small()
                                         .italics(),
                                 );
+                                if let Some(readout) = readout {
+                                    ui.label(
+                                        RichText::new(readout)
+                                            .color(palette.text_secondary)
+                                            .small(),
+                                    );
+                                }
                             });
-                            
+
                             if !content.is_empty() {
                                 CommonMarkViewer::new("streaming_msg").show(
                                     ui,
@@ -863,7 +2737,21 @@ pub struct InputBarState {
     pub draft: String,
     pub selected_model: String,
     pub temperature: f32,
+    pub max_request_messages: usize,
+    /// Longest reply to request; 0 leaves it to the provider.
+    pub max_tokens: u32,
     pub retain_input: bool,
+    pub read_only: bool,
+    /// A reply is still streaming in; sending again is blocked until it finishes.
+    pub busy: bool,
+    /// Frozen parameters of the active conversation, shown instead of the global controls.
+    pub locked: Option<ChatParameters>,
+    /// Focus the message box on the next frame unless another widget has focus.
+    pub focus_requested: bool,
+    /// Text files sent along with the next message, shown as chips above the message box.
+    pub attachments: Vec<TextAttachment>,
+    /// Images pasted from the clipboard for the next message, shown as thumbnails.
+    pub images: Vec<PastedImage>,
     active_tools: HashSet<InputTool>,
 }
 
@@ -876,11 +2764,23 @@ impl InputBarState {
             draft: String::new(),
             selected_model: model.into(),
             temperature,
+            max_request_messages: 0,
+            max_tokens: 0,
             retain_input,
+            read_only: false,
+            busy: false,
+            locked: None,
+            focus_requested: false,
+            attachments: Vec::new(),
+            images: Vec::new(),
             active_tools,
         }
     }
 
+    pub fn request_focus(&mut self) {
+        self.focus_requested = true;
+    }
+
     pub fn toggle_tool(&mut self, tool: InputTool) {
         if !self.active_tools.insert(tool) {
             self.active_tools.remove(&tool);
@@ -910,21 +2810,63 @@ impl InputTool {
 #[derive(Default)]
 pub struct InputBarOutput {
     pub send: bool,
+    /// Stop the reply that is streaming in.
+    pub stop: bool,
     pub clear: bool,
     pub model_changed: Option<String>,
     pub temperature_changed: Option<f32>,
+    pub history_limit_changed: Option<usize>,
+    pub max_tokens_changed: Option<u32>,
+    pub attach_files: bool,
+    /// The paste shortcut was pressed; attach the clipboard image, if any.
+    pub paste_image: bool,
+    /// Lock (`true`) or unlock (`false`) the active conversation's parameters.
+    pub lock_changed: Option<bool>,
+}
+
+/// One inherit/override row of the chat settings window.
+fn override_row<T: Clone + std::fmt::Display>(
+    ui: &mut egui::Ui,
+    palette: &ThemePalette,
+    label: &str,
+    value: &mut Option<T>,
+    inherited: &T,
+    edit: impl FnOnce(&mut egui::Ui, &mut T),
+) {
+    ui.horizontal(|ui| {
+        let mut overridden = value.is_some();
+        if ui.checkbox(&mut overridden, label).changed() {
+            *value = overridden.then(|| inherited.clone());
+        }
+        match value {
+            Some(value) => edit(ui, value),
+            None => {
+                ui.label(
+                    RichText::new(format!("Inherited: {inherited}")).color(palette.text_secondary),
+                );
+            }
+        }
+    });
 }
 
 pub struct InputBar;
 
+/// Whether a [`egui::DragValue`] settled on its value this frame: a drag ended or a
+/// value was typed. The values it passes through while being dragged do not count.
+fn drag_value_committed(response: &egui::Response) -> bool {
+    response.drag_released() || (response.changed() && !response.dragged())
+}
+
 impl InputBar {
     pub fn show(
         ui: &mut egui::Ui,
         state: &mut InputBarState,
         palette: &ThemePalette,
-        available_models: &[String],
+        provider: &ProviderConfig,
         selection_valid: bool,
+        tokens: &TokenCounter,
     ) -> InputBarOutput {
+        let available_models = &provider.available_models;
         let mut output = InputBarOutput::default();
         Frame::none()
             .fill(palette.surface)
@@ -932,77 +2874,227 @@ impl InputBar {
             .stroke(egui::Stroke::new(1.0, palette.border))
             .inner_margin(Margin::symmetric(10.0, 8.0))
             .show(ui, |ui| {
+                let writable = !state.read_only;
+                if !state.images.is_empty() {
+                    let mut removed = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for (index, image) in state.images.iter_mut().enumerate() {
+                            let downscaled = image.downscaled;
+                            let texture = image.texture(ui.ctx());
+                            let response = ui.add(
+                                egui::Image::new(texture)
+                                    .max_size(Vec2::splat(48.0))
+                                    .rounding(4.0),
+                            );
+                            if downscaled {
+                                response.on_hover_text(format!(
+                                    "Scaled down to {MAX_ATTACHED_IMAGE_SIDE} pixels per side"
+                                ));
+                            }
+                            if ui.small_button("✕").on_hover_text("Remove image").clicked() {
+                                removed = Some(index);
+                            }
+                        }
+                    });
+                    if let Some(index) = removed {
+                        state.images.remove(index);
+                    }
+                }
+                if !state.attachments.is_empty() {
+                    let mut removed = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for (index, attachment) in state.attachments.iter().enumerate() {
+                            Frame::none()
+                                .fill(palette.background)
+                                .rounding(10.0)
+                                .stroke(egui::Stroke::new(1.0, palette.border))
+                                .inner_margin(Margin::symmetric(8.0, 2.0))
+                                .show(ui, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label(
+                                            RichText::new(format!("📄 {}", attachment.name))
+                                                .small(),
+                                        );
+                                        if ui
+                                            .small_button("✕")
+                                            .on_hover_text("Remove attachment")
+                                            .clicked()
+                                        {
+                                            removed = Some(index);
+                                        }
+                                    });
+                                });
+                        }
+                    });
+                    if let Some(index) = removed {
+                        state.attachments.remove(index);
+                    }
+                }
                 let textarea = egui::TextEdit::multiline(&mut state.draft)
                     .desired_rows(4)
-                    .hint_text("Message Patina…")
+                    .hint_text(if writable {
+                        "Message Patina…"
+                    } else {
+                        "This project is read-only"
+                    })
                     .lock_focus(true)
                     .frame(false);
-                let response = ui.add(textarea);
+                let response = ui.add_enabled(writable, textarea);
+                if state.focus_requested {
+                    state.focus_requested = false;
+                    if writable && ui.memory(|mem| mem.focus().is_none()) {
+                        response.request_focus();
+                    }
+                }
+                #[cfg(feature = "vision-attachments")]
+                if writable
+                    && response.has_focus()
+                    && ui.input_mut(|i| i.consume_shortcut(&crate::images::PASTE_SHORTCUT))
+                {
+                    output.paste_image = true;
+                }
                 let send_shortcut = ui.input(|i| {
                     i.key_pressed(egui::Key::Enter) && i.modifiers.command && !i.modifiers.shift
                 });
-                if send_shortcut && response.has_focus() {
+                let can_send = writable && !state.busy;
+                if send_shortcut && response.has_focus() && can_send {
                     output.send = true;
                 }
                 ui.horizontal(|ui| {
-                    if ui.button("✈ Send").clicked() {
+                    if writable && state.busy {
+                        if ui
+                            .button("■ Stop")
+                            .on_hover_text("Stop the reply and keep what has arrived")
+                            .clicked()
+                        {
+                            output.stop = true;
+                        }
+                    } else if ui
+                        .add_enabled(can_send, egui::Button::new("✈ Send"))
+                        .clicked()
+                    {
                         output.send = true;
                     }
                     if ui.button("Clear").clicked() {
                         output.clear = true;
                     }
                     ui.checkbox(&mut state.retain_input, "Retain input");
+                    if !state.draft.is_empty() {
+                        ui.label(
+                            RichText::new(format!("{} tokens", tokens.count(&state.draft)))
+                                .color(palette.text_secondary)
+                                .small(),
+                        )
+                        .on_hover_text(format!("Counted with the {} tokenizer", tokens.model()));
+                    }
                 });
                 ui.separator();
                 ui.horizontal(|ui| {
-                    ui.vertical(|ui| {
-                        let display_text = if state.selected_model.is_empty() {
-                            "Select model"
-                        } else {
-                            state.selected_model.as_str()
-                        };
-                        egui::ComboBox::from_id_source("model_selector")
-                            .selected_text(display_text)
-                            .show_ui(ui, |ui| {
-                                for model in available_models {
-                                    if ui
-                                        .selectable_label(state.selected_model == *model, model)
-                                        .clicked()
-                                        && state.selected_model != *model
-                                    {
-                                        state.selected_model = model.clone();
-                                        output.model_changed = Some(model.clone());
+                    if let Some(locked) = &state.locked {
+                        ui.label(
+                            RichText::new(format!(
+                                "🔒 {} · temperature {:.2} · history {}",
+                                locked.model, locked.temperature, locked.max_request_messages
+                            ))
+                            .color(palette.text_secondary),
+                        )
+                        .on_hover_text("This chat always uses these parameters");
+                        if ui
+                            .add_enabled(writable, egui::Button::new("Unlock"))
+                            .clicked()
+                        {
+                            output.lock_changed = Some(false);
+                        }
+                    } else {
+                        ui.vertical(|ui| {
+                            let display_text = if state.selected_model.is_empty() {
+                                "Select model"
+                            } else {
+                                state.selected_model.as_str()
+                            };
+                            egui::ComboBox::from_id_source("model_selector")
+                                .selected_text(display_text)
+                                .show_ui(ui, |ui| {
+                                    for model in available_models {
+                                        if ui
+                                            .selectable_label(state.selected_model == *model, model)
+                                            .clicked()
+                                            && state.selected_model != *model
+                                        {
+                                            state.selected_model = model.clone();
+                                            output.model_changed = Some(model.clone());
+                                        }
                                     }
+                                });
+                            if available_models.is_empty() {
+                                let hint = provider.no_models.as_ref().map_or_else(
+                                    || "No models configured".to_string(),
+                                    NoModelsReason::hint,
+                                );
+                                let label =
+                                    ui.label(RichText::new(hint).color(palette.warning).small());
+                                if let Some(reason) = &provider.no_models {
+                                    label.on_hover_text(reason.to_string());
                                 }
-                            });
-                        if available_models.is_empty() {
-                            ui.label(
-                                RichText::new("No models configured")
-                                    .color(palette.warning)
-                                    .small(),
-                            );
-                        } else if !selection_valid {
-                            ui.label(
-                                RichText::new("Model not in patina.yaml")
-                                    .color(palette.warning)
-                                    .small(),
-                            );
+                            } else if !selection_valid {
+                                ui.label(
+                                    RichText::new("Model not in patina.yaml")
+                                        .color(palette.warning)
+                                        .small(),
+                                );
+                            }
+                        });
+                        let slider = egui::Slider::new(&mut state.temperature, 0.0..=2.0)
+                            .text("Temperature");
+                        if ui.add(slider).drag_released() {
+                            output.temperature_changed = Some(state.temperature);
+                        }
+                        let history = ui
+                            .add(
+                                egui::DragValue::new(&mut state.max_request_messages)
+                                    .clamp_range(0..=500)
+                                    .prefix("History: "),
+                            )
+                            .on_hover_text("Prior messages sent with each request (0 = all)");
+                        if drag_value_committed(&history) {
+                            output.history_limit_changed = Some(state.max_request_messages);
+                        }
+                        let max_tokens = ui
+                            .add(
+                                egui::DragValue::new(&mut state.max_tokens)
+                                    .clamp_range(0..=200_000)
+                                    .speed(16.0)
+                                    .prefix("Max tokens: "),
+                            )
+                            .on_hover_text("Longest reply to request (0 = provider default)");
+                        if drag_value_committed(&max_tokens) {
+                            output.max_tokens_changed = Some(state.max_tokens);
+                        }
+                        if ui
+                            .add_enabled(writable, egui::Button::new("🔒"))
+                            .on_hover_text("Lock this chat's parameters")
+                            .clicked()
+                        {
+                            output.lock_changed = Some(true);
                         }
-                    });
-                    let slider =
-                        egui::Slider::new(&mut state.temperature, 0.0..=2.0).text("Temperature");
-                    if ui.add(slider).drag_released() {
-                        output.temperature_changed = Some(state.temperature);
                     }
                     for tool in InputTool::ALL {
-                        let active = state.active_tools.contains(&tool);
+                        let active = if tool == InputTool::Files {
+                            !state.attachments.is_empty()
+                        } else {
+                            state.active_tools.contains(&tool)
+                        };
                         let label = RichText::new(tool.label()).color(if active {
                             palette.text_primary
                         } else {
                             palette.text_secondary
                         });
                         if ui.selectable_label(active, label).clicked() {
-                            state.toggle_tool(tool);
+                            if tool == InputTool::Files {
+                                output.attach_files = true;
+                            } else {
+                                state.toggle_tool(tool);
+                            }
                         }
                     }
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
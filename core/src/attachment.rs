@@ -0,0 +1,191 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Maximum number of bytes of a single text attachment included in a message.
+pub const MAX_ATTACHMENT_BYTES: usize = 64 * 1024;
+
+/// Maximum number of bytes of all attachments of one message together.
+pub const MAX_TOTAL_ATTACHMENT_BYTES: usize = 256 * 1024;
+
+/// A plain-text file attached to an outgoing message as context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextAttachment {
+    pub name: String,
+    pub content: String,
+    pub truncated: bool,
+}
+
+impl TextAttachment {
+    /// Read a text file from disk, truncating it to [`MAX_ATTACHMENT_BYTES`]. Files that
+    /// are not UTF-8 text are rejected.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "attachment".to_string());
+        match std::str::from_utf8(&bytes) {
+            Ok(contents) if !contents.contains('\0') => Ok(Self::from_contents(name, contents)),
+            _ => bail!("{name} looks like a binary file; only text files can be attached"),
+        }
+    }
+
+    /// Build an attachment from in-memory contents, truncating on a char boundary.
+    pub fn from_contents(name: impl Into<String>, contents: &str) -> Self {
+        let mut end = contents.len().min(MAX_ATTACHMENT_BYTES);
+        while !contents.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            name: name.into(),
+            content: contents[..end].to_string(),
+            truncated: end < contents.len(),
+        }
+    }
+
+    /// Whether this attachment still fits next to `attached` under
+    /// [`MAX_TOTAL_ATTACHMENT_BYTES`].
+    pub fn fits_with(&self, attached: &[TextAttachment]) -> bool {
+        let used: usize = attached.iter().map(|a| a.content.len()).sum();
+        used + self.content.len() <= MAX_TOTAL_ATTACHMENT_BYTES
+    }
+
+    /// Render the attachment as a fenced code block headed by its file name.
+    pub fn to_markdown(&self) -> String {
+        let language = Path::new(&self.name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        let fence = fence_for(&self.content);
+        let mut output = format!("**{}**\n\n{fence}{language}\n", self.name);
+        output.push_str(&self.content);
+        if !self.content.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push_str(&fence);
+        output.push('\n');
+        if self.truncated {
+            output.push_str(&format!(
+                "\n_Note: {} was truncated to the first {} KB._\n",
+                self.name,
+                MAX_ATTACHMENT_BYTES / 1024
+            ));
+        }
+        output
+    }
+}
+
+/// An image sent along with a message to a vision model, e.g. a pasted screenshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageAttachment {
+    /// MIME type of the encoded image, such as `image/png`.
+    pub mime_type: String,
+    /// The encoded image as standard base64.
+    pub data: String,
+}
+
+impl ImageAttachment {
+    /// The image as a `data:` URL, the form OpenAI-compatible APIs accept.
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+/// The outgoing message: `text` followed by each attachment as a fenced block.
+pub fn with_attachments(text: &str, attachments: &[TextAttachment]) -> String {
+    let mut message = text.trim().to_string();
+    for attachment in attachments {
+        if !message.is_empty() {
+            message.push_str("\n\n");
+        }
+        message.push_str(attachment.to_markdown().trim_end());
+    }
+    message
+}
+
+/// Pick a backtick fence longer than any run of backticks inside the content.
+fn fence_for(content: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in content.chars() {
+        if ch == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_small_file_as_fenced_block() {
+        let attachment = TextAttachment::from_contents("main.rs", "fn main() {}");
+        assert!(!attachment.truncated);
+        assert_eq!(
+            attachment.to_markdown(),
+            "**main.rs**\n\n```rs\nfn main() {}\n```\n"
+        );
+    }
+
+    #[test]
+    fn truncates_large_file_with_note() {
+        let contents = "é".repeat(MAX_ATTACHMENT_BYTES);
+        let attachment = TextAttachment::from_contents("notes.txt", &contents);
+        assert!(attachment.truncated);
+        assert!(attachment.content.len() <= MAX_ATTACHMENT_BYTES);
+        assert!(attachment
+            .to_markdown()
+            .contains("_Note: notes.txt was truncated to the first 64 KB._"));
+    }
+
+    #[test]
+    fn rejects_binary_files() {
+        let dir = std::env::temp_dir().join(format!("patina-attach-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logo.png");
+        fs::write(&path, [0x89, b'P', b'N', b'G', 0, 0xff]).unwrap();
+        let err = TextAttachment::from_path(&path).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("logo.png looks like a binary file"));
+
+        let path = dir.join("notes.txt");
+        fs::write(&path, "plain text").unwrap();
+        assert_eq!(
+            TextAttachment::from_path(&path).unwrap().content,
+            "plain text"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn attachments_follow_the_message_under_a_total_cap() {
+        let notes = TextAttachment::from_contents("notes.txt", "remember");
+        assert_eq!(
+            with_attachments("Summarize this.\n", std::slice::from_ref(&notes)),
+            "Summarize this.\n\n**notes.txt**\n\n```txt\nremember\n```"
+        );
+        assert_eq!(
+            with_attachments("", std::slice::from_ref(&notes)),
+            "**notes.txt**\n\n```txt\nremember\n```"
+        );
+
+        let big = TextAttachment::from_contents("big.txt", &"x".repeat(MAX_ATTACHMENT_BYTES));
+        let attached = vec![big.clone(); 3];
+        assert!(big.fits_with(&attached));
+        assert!(!big.fits_with(&[attached, vec![big.clone()]].concat()));
+    }
+
+    #[test]
+    fn widens_fence_around_embedded_backticks() {
+        let attachment = TextAttachment::from_contents("README.md", "```\ncode\n```");
+        assert!(attachment.to_markdown().contains("````md\n"));
+    }
+}
@@ -1,10 +1,15 @@
-use crate::store::TranscriptStore;
-use anyhow::{anyhow, Result};
+use crate::secrets::{default_secret_store, SecretStore};
+use crate::store::{write_atomic, TranscriptStore};
+use anyhow::{anyhow, Context, Result};
 use chrono::{Duration, Utc};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 /// Authentication modes advertised by an MCP endpoint.
@@ -35,12 +40,18 @@ impl AuthState {
 
     pub fn needs_refresh(&self) -> bool {
         match (self.access_token.as_ref(), self.expires_at) {
-            (Some(_), Some(exp)) => Utc::now() + Duration::minutes(1) >= exp,
+            (Some(_), Some(_)) => self.expires_soon(),
             (Some(_), None) => false,
             _ => true,
         }
     }
 
+    /// Whether the token expires within the next minute.
+    pub fn expires_soon(&self) -> bool {
+        self.expires_at
+            .is_some_and(|exp| Utc::now() + Duration::minutes(1) >= exp)
+    }
+
     pub fn hydrate_for_testing(mode: AuthMode) -> Self {
         let mut state = Self::new(mode.clone());
         state.refresh(mode).expect("refresh for testing");
@@ -65,18 +76,80 @@ impl AuthState {
 }
 
 /// Coordinates authentication state across MCP endpoints.
+///
+/// With a state file, the mode and expiry of every endpoint survive restarts; the
+/// tokens themselves only ever go to the secret store.
 #[derive(Clone)]
 pub struct AuthCoordinator {
     store: Arc<RwLock<HashMap<String, AuthState>>>,
-    transcript_store: TranscriptStore,
+    secrets: Arc<dyn SecretStore>,
+    state_path: Option<PathBuf>,
 }
 
 impl AuthCoordinator {
+    /// Tokens go to the OS keyring, or to the project's secret files in builds
+    /// without the `keyring` feature; the rest of the state to the project's
+    /// internal directory.
     pub fn new(transcript_store: TranscriptStore) -> Self {
+        Self::with_secrets(default_secret_store(&transcript_store))
+            .with_state_file(transcript_store.auth_state_path())
+    }
+
+    /// An in-memory coordinator keeping tokens in `secrets`.
+    pub fn with_secrets(secrets: Arc<dyn SecretStore>) -> Self {
         Self {
             store: Arc::new(RwLock::new(HashMap::new())),
-            transcript_store,
+            secrets,
+            state_path: None,
+        }
+    }
+
+    /// Load state saved at `path` and save every later change there.
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_path = Some(path);
+        if let Err(err) = self.load() {
+            tracing::warn!(error = %err, "failed to load saved auth state");
         }
+        self
+    }
+
+    /// Replace the in-memory states with the saved ones, if any were saved.
+    ///
+    /// Tokens are fetched from the secret store on the next [`Self::negotiate`].
+    pub fn load(&self) -> Result<()> {
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let states: HashMap<String, AuthState> = serde_json::from_str(&contents)
+            .with_context(|| format!("invalid auth state in {}", path.display()))?;
+        *self.store.write() = states;
+        Ok(())
+    }
+
+    /// Save every state, minus its tokens, to the state file.
+    pub fn persist(&self) -> Result<()> {
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+        let states: HashMap<String, AuthState> = self
+            .store
+            .read()
+            .iter()
+            .map(|(key, state)| {
+                let state = AuthState {
+                    access_token: None,
+                    refresh_token: None,
+                    ..state.clone()
+                };
+                (key.clone(), state)
+            })
+            .collect();
+        write_atomic(path, serde_json::to_string_pretty(&states)?.as_bytes())
     }
 
     pub fn state_for(&self, key: &str) -> Option<AuthState> {
@@ -85,6 +158,9 @@ impl AuthCoordinator {
 
     pub fn upsert(&self, key: impl Into<String>, state: AuthState) {
         self.store.write().insert(key.into(), state);
+        if let Err(err) = self.persist() {
+            tracing::warn!(error = %err, "failed to save auth state");
+        }
     }
 
     /// Negotiate auth with an MCP endpoint based on the advertised mode.
@@ -92,19 +168,112 @@ impl AuthCoordinator {
         let mut state = self
             .state_for(key)
             .unwrap_or_else(|| AuthState::new(mode.clone()));
+        if state.access_token.is_none() && state.expires_at.is_some() {
+            // Reloaded from the state file; the tokens are in the secret store.
+            let secret_key = key.to_owned();
+            let (access_token, refresh_token) = self
+                .blocking_secrets(move |secrets| {
+                    Ok((
+                        secrets.load(&secret_key)?,
+                        secrets.load(&refresh_secret_key(&secret_key))?,
+                    ))
+                })
+                .await?;
+            state.access_token = access_token.filter(|token| !token.is_empty());
+            state.refresh_token = refresh_token.filter(|token| !token.is_empty());
+        }
         if state.needs_refresh() {
-            state.refresh(mode.clone())?;
-            // Persisting auth tokens to the same directory used for transcripts keeps the
-            // implementation simple while allowing tests to exercise credential reuse.
-            self.transcript_store
-                .persist_secret(key, &state.access_token.clone().unwrap_or_default())?;
+            self.refresh_and_store(key, &mut state, mode).await?;
         }
         self.upsert(key.to_owned(), state.clone());
         Ok(state)
     }
 
+    /// Refresh every state that expires within the next minute, returning the keys
+    /// that were refreshed.
+    ///
+    /// Endpoints that fail to refresh are logged and left for the next scan.
+    pub async fn refresh_expiring(&self) -> Vec<String> {
+        let expiring: Vec<(String, AuthState)> = self
+            .store
+            .read()
+            .iter()
+            .filter(|(_, state)| state.expires_soon())
+            .map(|(key, state)| (key.clone(), state.clone()))
+            .collect();
+        let mut refreshed = Vec::new();
+        for (key, mut state) in expiring {
+            let mode = state.mode.clone();
+            match self.refresh_and_store(&key, &mut state, mode).await {
+                Ok(()) => {
+                    self.upsert(key.clone(), state);
+                    refreshed.push(key);
+                }
+                Err(err) => tracing::warn!(endpoint = %key, error = %err, "token refresh failed"),
+            }
+        }
+        refreshed
+    }
+
+    /// Run [`Self::refresh_expiring`] every `interval` until `cancel` fires.
+    ///
+    /// Nothing refreshes in the background unless this is spawned, which keeps tests
+    /// deterministic.
+    pub fn spawn_refresh_task(
+        &self,
+        interval: std::time::Duration,
+        cancel: CancellationToken,
+    ) -> JoinHandle<()> {
+        let auth = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    () = cancel.cancelled() => break,
+                    () = tokio::time::sleep(interval) => {
+                        auth.refresh_expiring().await;
+                    }
+                }
+            }
+        })
+    }
+
+    async fn refresh_and_store(
+        &self,
+        key: &str,
+        state: &mut AuthState,
+        mode: AuthMode,
+    ) -> Result<()> {
+        state.refresh(mode)?;
+        let secret_key = key.to_owned();
+        let (access_token, refresh_token) =
+            (state.access_token.clone(), state.refresh_token.clone());
+        self.blocking_secrets(move |secrets| {
+            secrets.store(&secret_key, access_token.as_deref().unwrap_or_default())?;
+            secrets.store(
+                &refresh_secret_key(&secret_key),
+                refresh_token.as_deref().unwrap_or_default(),
+            )
+        })
+        .await
+    }
+
+    /// Run `f` against the secret store off the async workers: keyring backends block,
+    /// and the Secret Service one runs its own runtime.
+    async fn blocking_secrets<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&dyn SecretStore) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let secrets = self.secrets.clone();
+        tokio::task::spawn_blocking(move || f(secrets.as_ref())).await?
+    }
+
     pub fn require(&self, key: &str) -> Result<AuthState> {
         self.state_for(key)
             .ok_or_else(|| anyhow!("no auth state registered for {key}"))
     }
 }
+
+/// Secret store key for the refresh token of the endpoint `key`.
+fn refresh_secret_key(key: &str) -> String {
+    format!("{key}-refresh")
+}
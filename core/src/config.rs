@@ -1,21 +1,47 @@
-use crate::llm::LlmProviderKind;
+use crate::llm::{LlmProviderKind, RetryPolicy};
 use directories::BaseDirs;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Where OpenAI requests go unless `app.openai.base_url` says otherwise.
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com";
+
+/// Where Ollama listens unless `app.ollama.base_url` says otherwise.
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+/// Environment variables that, when set, take precedence over the matching
+/// `patina.yaml` keys, so credentials need not be written to disk.
+pub const OPENAI_API_KEY_VAR: &str = "OPENAI_API_KEY";
+pub const AZURE_OPENAI_API_KEY_VAR: &str = "AZURE_OPENAI_API_KEY";
+pub const AZURE_OPENAI_ENDPOINT_VAR: &str = "AZURE_OPENAI_ENDPOINT";
+pub const AZURE_OPENAI_API_VERSION_VAR: &str = "AZURE_OPENAI_API_VERSION";
+pub const AZURE_OPENAI_DEPLOYMENT_VAR: &str = "AZURE_OPENAI_DEPLOYMENT_NAME";
 
 #[derive(Debug, Clone)]
 pub struct AiRuntimeSettings {
     pub provider: LlmProviderKind,
     pub openai: Option<OpenAiSettings>,
     pub azure: Option<AzureOpenAiSettings>,
+    pub ollama: Option<OllamaSettings>,
     pub model: Option<String>,
+    pub retry: RetryPolicy,
+    /// Proxy from `app.proxy`; without one, `HTTPS_PROXY`/`NO_PROXY` apply.
+    pub proxy: Option<ProxySettings>,
 }
 
 #[derive(Debug, Clone)]
 pub struct OpenAiSettings {
     pub api_key: String,
     pub model: Option<String>,
+    /// Host of an OpenAI-compatible gateway used instead of [`DEFAULT_OPENAI_BASE_URL`];
+    /// the `/v1/...` paths are kept.
+    pub base_url: Option<String>,
+    /// Sent as `OpenAI-Organization` when set.
+    pub organization: Option<String>,
+    /// Sent as `OpenAI-Project` when set.
+    pub project: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +52,21 @@ pub struct AzureOpenAiSettings {
     pub deployment_name: String,
 }
 
+/// An HTTP(S) proxy for provider requests, optionally with basic-auth credentials.
+#[derive(Debug, Clone)]
+pub struct ProxySettings {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// A local Ollama server; no API key is involved.
+#[derive(Debug, Clone)]
+pub struct OllamaSettings {
+    pub base_url: String,
+    pub model: String,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AiConfigError {
     #[error("AI not configured—create patina.yaml with provider credentials.")]
@@ -49,64 +90,187 @@ impl AiConfigError {
 
 impl AiRuntimeSettings {
     pub fn load() -> Result<Self, AiConfigError> {
-        let path = locate_config_file().ok_or(AiConfigError::Missing)?;
-        let contents = fs::read_to_string(&path).map_err(|err| {
+        Self::load_layered(None)
+    }
+
+    /// Like [`Self::load`], with the patina.yaml in `project_dir`, if there is one,
+    /// layered over the user's: the keys it sets win and the rest are inherited.
+    pub fn load_layered(project_dir: Option<&Path>) -> Result<Self, AiConfigError> {
+        let mut layers = Vec::new();
+        layers.extend(locate_config_file());
+        layers.extend(project_dir.and_then(|dir| {
+            [dir.join("patina.yaml"), dir.join("patina.yml")]
+                .into_iter()
+                .find(|path| path.exists())
+        }));
+        Self::load_files(&layers, &|name| std::env::var(name).ok())
+    }
+
+    /// Resolve the patina.yaml files at `paths`, each overriding the keys it sets in
+    /// the ones before it, reading variables from `env` instead of the process.
+    pub fn load_files(
+        paths: &[PathBuf],
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self, AiConfigError> {
+        load_layers(paths, env)
+    }
+}
+
+/// Resolve the patina.yaml files at `paths`, each overriding the keys it sets in the
+/// ones before it.
+fn load_layers(
+    paths: &[PathBuf],
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<AiRuntimeSettings, AiConfigError> {
+    if paths.is_empty() {
+        return Err(AiConfigError::Missing);
+    }
+    let mut merged = serde_yaml::Value::Null;
+    for path in paths {
+        let contents = fs::read_to_string(path).map_err(|err| {
             AiConfigError::Invalid(format!("failed to read {}: {err}", path.display()))
         })?;
-        let config: PatinaConfig = serde_yaml::from_str(&contents)
+        let layer = serde_yaml::from_str(&contents)
             .map_err(|err| AiConfigError::Invalid(format!("invalid patina.yaml: {err}")))?;
-        let app = config
-            .app
-            .ok_or_else(|| AiConfigError::Invalid("missing `app` section".to_string()))?;
-        resolve_app_settings(app)
+        merge_yaml(&mut merged, layer);
     }
+    // An empty file is a null document; read it as one without an `app` section.
+    let config: PatinaConfig = if merged.is_null() {
+        PatinaConfig { app: None }
+    } else {
+        serde_yaml::from_value(merged)
+            .map_err(|err| AiConfigError::Invalid(format!("invalid patina.yaml: {err}")))?
+    };
+    let app = config
+        .app
+        .ok_or_else(|| AiConfigError::Invalid("missing `app` section".to_string()))?;
+    resolve_app_settings(app, env)
+}
+
+/// Merge `overlay` into `base` key by key: nested mappings are merged, any other value
+/// of `overlay` replaces the one in `base`, and nulls leave `base` as it is.
+pub fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match (base, overlay) {
+        (_, Value::Null) => {}
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// The environment variable `var` if set and not blank, otherwise the `patina.yaml` value.
+fn env_or_yaml(env: &dyn Fn(&str) -> Option<String>, var: &str, yaml: String) -> String {
+    env(var)
+        .and_then(non_empty)
+        .unwrap_or_else(|| yaml.trim().to_string())
 }
 
-fn resolve_app_settings(app: AppSection) -> Result<AiRuntimeSettings, AiConfigError> {
+/// The error for a required setting found neither in the environment nor in `patina.yaml`.
+fn missing(what: &str, var: &str, key: &str) -> AiConfigError {
+    AiConfigError::Invalid(format!(
+        "missing {what}: set {var} or `{key}` in patina.yaml (the environment variable takes precedence)"
+    ))
+}
+
+/// Resolve the `app` section, reading credential overrides through `env`.
+fn resolve_app_settings(
+    mut app: AppSection,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<AiRuntimeSettings, AiConfigError> {
+    if let Some(name) = app.active_profile.take().and_then(non_empty) {
+        let Some(mut profile) = app.profiles.remove(&name) else {
+            return Err(AiConfigError::Invalid(format!(
+                "active profile `{name}` is not defined under app.profiles"
+            )));
+        };
+        profile.active_profile = None;
+        profile.profiles.clear();
+        profile.retry = profile.retry.or(app.retry);
+        profile.proxy = profile.proxy.or(app.proxy);
+        app = profile;
+    }
     let provider = app.provider.unwrap_or(LlmProviderKind::OpenAi);
+    let retry = app.retry.unwrap_or_default();
+    let proxy = app.proxy.map(resolve_proxy).transpose()?;
     match provider {
         LlmProviderKind::OpenAi => {
             let section = app.openai.unwrap_or_default();
-            let api_key = section.api_key.trim().to_string();
+            let api_key = env_or_yaml(env, OPENAI_API_KEY_VAR, section.api_key);
             if api_key.is_empty() {
-                return Err(AiConfigError::Invalid(
-                    "missing OpenAI api key in patina.yaml".to_string(),
+                return Err(missing(
+                    "OpenAI api key",
+                    OPENAI_API_KEY_VAR,
+                    "app.openai.api_key",
                 ));
             }
+            let base_url = match section.base_url.trim() {
+                "" => None,
+                url if !is_http_url(url) => {
+                    return Err(AiConfigError::Invalid(format!(
+                        "invalid OpenAI base_url `{url}` in patina.yaml: use an http:// or https:// url"
+                    )));
+                }
+                url => Some(url.to_string()),
+            };
             Ok(AiRuntimeSettings {
                 provider,
                 openai: Some(OpenAiSettings {
                     api_key,
                     model: None,
+                    base_url,
+                    organization: non_empty(section.organization),
+                    project: non_empty(section.project),
                 }),
                 azure: None,
+                ollama: None,
                 model: None,
+                retry,
+                proxy,
             })
         }
         LlmProviderKind::AzureOpenAi => {
             let section = app.azure_openai.unwrap_or_default();
-            let api_key = section.api_key.trim().to_string();
+            let api_key = env_or_yaml(env, AZURE_OPENAI_API_KEY_VAR, section.api_key);
             if api_key.is_empty() {
-                return Err(AiConfigError::Invalid(
-                    "missing Azure OpenAI api key in patina.yaml".to_string(),
+                return Err(missing(
+                    "Azure OpenAI api key",
+                    AZURE_OPENAI_API_KEY_VAR,
+                    "app.azure_openai.api_key",
                 ));
             }
-            let endpoint = section.endpoint.trim().to_string();
+            let endpoint = env_or_yaml(env, AZURE_OPENAI_ENDPOINT_VAR, section.endpoint);
             if endpoint.is_empty() {
-                return Err(AiConfigError::Invalid(
-                    "missing Azure endpoint in patina.yaml".to_string(),
+                return Err(missing(
+                    "Azure endpoint",
+                    AZURE_OPENAI_ENDPOINT_VAR,
+                    "app.azure_openai.endpoint",
                 ));
             }
-            let api_version = section.api_version.trim().to_string();
+            let api_version = env_or_yaml(env, AZURE_OPENAI_API_VERSION_VAR, section.api_version);
             if api_version.is_empty() {
-                return Err(AiConfigError::Invalid(
-                    "missing Azure api version in patina.yaml".to_string(),
+                return Err(missing(
+                    "Azure api version",
+                    AZURE_OPENAI_API_VERSION_VAR,
+                    "app.azure_openai.api_version",
                 ));
             }
-            let deployment_name = section.deployment_name.trim().to_string();
+            let deployment_name =
+                env_or_yaml(env, AZURE_OPENAI_DEPLOYMENT_VAR, section.deployment_name);
             if deployment_name.is_empty() {
-                return Err(AiConfigError::Invalid(
-                    "missing Azure deployment name in patina.yaml".to_string(),
+                return Err(missing(
+                    "Azure deployment name",
+                    AZURE_OPENAI_DEPLOYMENT_VAR,
+                    "app.azure_openai.deployment_name",
                 ));
             }
             Ok(AiRuntimeSettings {
@@ -118,25 +282,96 @@ fn resolve_app_settings(app: AppSection) -> Result<AiRuntimeSettings, AiConfigEr
                     api_version,
                     deployment_name: deployment_name.clone(),
                 }),
+                ollama: None,
                 model: Some(deployment_name),
+                retry,
+                proxy,
+            })
+        }
+        LlmProviderKind::Ollama => {
+            let section = app.ollama.unwrap_or_default();
+            let base_url = match section.base_url.trim() {
+                "" => DEFAULT_OLLAMA_BASE_URL.to_string(),
+                url => url.to_string(),
+            };
+            let model = section.model.trim().to_string();
+            if model.is_empty() {
+                return Err(AiConfigError::Invalid(
+                    "missing Ollama model in patina.yaml".to_string(),
+                ));
+            }
+            Ok(AiRuntimeSettings {
+                provider,
+                openai: None,
+                azure: None,
+                ollama: Some(OllamaSettings {
+                    base_url,
+                    model: model.clone(),
+                }),
+                model: Some(model),
+                retry,
+                proxy,
             })
         }
         LlmProviderKind::Mock => Ok(AiRuntimeSettings {
             provider,
             openai: None,
             azure: None,
+            ollama: None,
             model: None,
+            retry,
+            proxy,
         }),
     }
 }
 
-fn locate_config_file() -> Option<PathBuf> {
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Whether `url` is absolute with an http or https scheme. `localhost:4000` parses as a
+/// url with the scheme `localhost`, so parsing alone is not enough.
+pub fn is_http_url(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|parsed| matches!(parsed.scheme(), "http" | "https"))
+}
+
+fn resolve_proxy(section: ProxySection) -> Result<ProxySettings, AiConfigError> {
+    let url = section.url.trim().to_string();
+    if url.is_empty() {
+        return Err(AiConfigError::Invalid(
+            "missing proxy url in patina.yaml".to_string(),
+        ));
+    }
+    match url::Url::parse(&url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        Ok(_) => {
+            return Err(AiConfigError::Invalid(format!(
+                "invalid proxy url `{url}` in patina.yaml: use an http:// or https:// url"
+            )));
+        }
+        Err(err) => {
+            return Err(AiConfigError::Invalid(format!(
+                "invalid proxy url `{url}` in patina.yaml: {err}"
+            )));
+        }
+    }
+    Ok(ProxySettings {
+        url,
+        username: non_empty(section.username),
+        password: non_empty(section.password),
+    })
+}
+
+/// The first of [`patina_yaml_candidates`] that exists.
+pub fn locate_config_file() -> Option<PathBuf> {
     patina_yaml_candidates()
         .into_iter()
         .find(|path| path.exists())
 }
 
-fn patina_yaml_candidates() -> Vec<PathBuf> {
+/// Where patina.yaml is looked for, in order.
+pub fn patina_yaml_candidates() -> Vec<PathBuf> {
     let mut paths = Vec::new();
     if let Some(base) = BaseDirs::new() {
         let config_dir = base.config_dir().join("patina");
@@ -157,18 +392,34 @@ struct PatinaConfig {
     app: Option<AppSection>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct AppSection {
+    /// Name of the entry in `profiles` to use instead of the settings below.
+    #[serde(default)]
+    active_profile: Option<String>,
+    /// Named alternatives to the provider settings below, each with its own provider,
+    /// credentials and models; `retry` and `proxy` are inherited unless a profile sets them.
+    #[serde(default)]
+    profiles: BTreeMap<String, AppSection>,
     provider: Option<LlmProviderKind>,
     openai: Option<OpenAiSection>,
     #[serde(rename = "azure_openai")]
     azure_openai: Option<AzureSection>,
+    ollama: Option<OllamaSection>,
+    retry: Option<RetryPolicy>,
+    proxy: Option<ProxySection>,
 }
 
 #[derive(Debug, Default, Deserialize)]
 struct OpenAiSection {
     #[serde(default)]
     api_key: String,
+    #[serde(default)]
+    base_url: String,
+    #[serde(default)]
+    organization: String,
+    #[serde(default)]
+    project: String,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -183,22 +434,99 @@ struct AzureSection {
     deployment_name: String,
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct OllamaSection {
+    #[serde(default)]
+    base_url: String,
+    #[serde(default)]
+    model: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProxySection {
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    /// An environment holding only `vars`, in place of the process environment.
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
     #[test]
     fn resolves_openai_settings() {
         let app = AppSection {
             provider: Some(LlmProviderKind::OpenAi),
             openai: Some(OpenAiSection {
                 api_key: "test-key".into(),
+                ..OpenAiSection::default()
             }),
             azure_openai: None,
+            ollama: None,
+            retry: None,
+            proxy: None,
+            ..AppSection::default()
         };
-        let settings = resolve_app_settings(app).expect("openai settings");
+        let settings = resolve_app_settings(app, &no_env).expect("openai settings");
         assert!(matches!(settings.provider, LlmProviderKind::OpenAi));
         assert_eq!(settings.openai.as_ref().unwrap().api_key, "test-key");
+        assert!(settings.openai.as_ref().unwrap().base_url.is_none());
+        assert!(settings.openai.as_ref().unwrap().organization.is_none());
+    }
+
+    #[test]
+    fn resolves_openai_organization_and_project() {
+        let config: PatinaConfig = serde_yaml::from_str(
+            "app:\n  openai:\n    api_key: k\n    organization: org-1\n    project: ' '\n",
+        )
+        .expect("parse yaml");
+        let openai = resolve_app_settings(config.app.unwrap(), &no_env)
+            .expect("openai settings")
+            .openai
+            .unwrap();
+        assert_eq!(openai.organization.as_deref(), Some("org-1"));
+        assert!(openai.project.is_none());
+    }
+
+    #[test]
+    fn resolves_openai_base_url() {
+        let config: PatinaConfig = serde_yaml::from_str(
+            "app:\n  openai:\n    api_key: k\n    base_url: http://localhost:4000\n",
+        )
+        .expect("parse yaml");
+        let settings = resolve_app_settings(config.app.unwrap(), &no_env).expect("openai settings");
+        assert_eq!(
+            settings.openai.unwrap().base_url.as_deref(),
+            Some("http://localhost:4000")
+        );
+
+        let config: PatinaConfig =
+            serde_yaml::from_str("app:\n  openai:\n    api_key: k\n    base_url: not a url\n")
+                .expect("parse yaml");
+        let err = resolve_app_settings(config.app.unwrap(), &no_env).unwrap_err();
+        assert!(matches!(err, AiConfigError::Invalid(_)));
+
+        let config: PatinaConfig =
+            serde_yaml::from_str("app:\n  openai:\n    api_key: k\n    base_url: localhost:4000\n")
+                .expect("parse yaml");
+        let err = resolve_app_settings(config.app.unwrap(), &no_env).unwrap_err();
+        assert!(err.to_string().contains("use an http:// or https:// url"));
     }
 
     #[test]
@@ -212,8 +540,12 @@ mod tests {
                 api_version: "2024-12-01-preview".into(),
                 deployment_name: "gpt-4o".into(),
             }),
+            ollama: None,
+            retry: None,
+            proxy: None,
+            ..AppSection::default()
         };
-        let settings = resolve_app_settings(app).expect("azure settings");
+        let settings = resolve_app_settings(app, &no_env).expect("azure settings");
         assert!(matches!(settings.provider, LlmProviderKind::AzureOpenAi));
         let azure = settings.azure.as_ref().unwrap();
         assert_eq!(azure.api_key, "azure-key");
@@ -227,11 +559,190 @@ mod tests {
         let app = AppSection {
             provider: Some(LlmProviderKind::OpenAi),
             openai: Some(OpenAiSection {
-                api_key: String::new(),
+                ..OpenAiSection::default()
             }),
             azure_openai: None,
+            ollama: None,
+            retry: None,
+            proxy: None,
+            ..AppSection::default()
         };
-        let err = resolve_app_settings(app).unwrap_err();
+        let err = resolve_app_settings(app, &no_env).unwrap_err();
         assert!(matches!(err, AiConfigError::Invalid(_)));
     }
+
+    #[test]
+    fn resolves_proxy_settings() {
+        let config: PatinaConfig = serde_yaml::from_str(
+            "app:\n  provider: mock\n  proxy:\n    url: http://proxy.corp:3128\n    username: me\n",
+        )
+        .expect("parse yaml");
+        let proxy = resolve_app_settings(config.app.unwrap(), &no_env)
+            .expect("settings")
+            .proxy
+            .expect("proxy");
+        assert_eq!(proxy.url, "http://proxy.corp:3128");
+        assert_eq!(proxy.username.as_deref(), Some("me"));
+        assert!(proxy.password.is_none());
+
+        let config: PatinaConfig =
+            serde_yaml::from_str("app:\n  provider: mock\n  proxy:\n    url: proxy.corp:3128\n")
+                .expect("parse yaml");
+        let err = resolve_app_settings(config.app.unwrap(), &no_env).unwrap_err();
+        assert!(err.to_string().contains("invalid proxy url"));
+    }
+
+    #[test]
+    fn resolves_ollama_without_api_key() {
+        let config: PatinaConfig =
+            serde_yaml::from_str("app:\n  provider: ollama\n  ollama:\n    model: llama3.2\n")
+                .expect("parse yaml");
+        let settings = resolve_app_settings(config.app.unwrap(), &no_env).expect("ollama settings");
+        assert!(matches!(settings.provider, LlmProviderKind::Ollama));
+        let ollama = settings.ollama.as_ref().unwrap();
+        assert_eq!(ollama.base_url, DEFAULT_OLLAMA_BASE_URL);
+        assert_eq!(ollama.model, "llama3.2");
+        assert_eq!(settings.model.as_deref(), Some("llama3.2"));
+
+        let config: PatinaConfig = serde_yaml::from_str(
+            "app:\n  provider: ollama\n  ollama:\n    base_url: http://gpu-box:11434\n",
+        )
+        .expect("parse yaml");
+        let err = resolve_app_settings(config.app.unwrap(), &no_env).unwrap_err();
+        assert!(matches!(err, AiConfigError::Invalid(_)));
+    }
+
+    fn openai_app(api_key: &str) -> AppSection {
+        AppSection {
+            provider: Some(LlmProviderKind::OpenAi),
+            openai: Some(OpenAiSection {
+                api_key: api_key.into(),
+                ..OpenAiSection::default()
+            }),
+            azure_openai: None,
+            ollama: None,
+            retry: None,
+            proxy: None,
+            ..AppSection::default()
+        }
+    }
+
+    #[test]
+    fn openai_key_comes_from_the_environment_when_yaml_is_blank() {
+        let vars = env(&[(OPENAI_API_KEY_VAR, "env-key")]);
+        let settings = resolve_app_settings(openai_app(""), &vars).expect("openai settings");
+        assert_eq!(settings.openai.unwrap().api_key, "env-key");
+    }
+
+    #[test]
+    fn openai_key_in_the_environment_wins_over_yaml() {
+        let vars = env(&[(OPENAI_API_KEY_VAR, "env-key")]);
+        let settings = resolve_app_settings(openai_app("yaml-key"), &vars).expect("openai");
+        assert_eq!(settings.openai.unwrap().api_key, "env-key");
+
+        let blank = env(&[(OPENAI_API_KEY_VAR, "  ")]);
+        let settings = resolve_app_settings(openai_app("yaml-key"), &blank).expect("openai");
+        assert_eq!(settings.openai.unwrap().api_key, "yaml-key");
+    }
+
+    #[test]
+    fn missing_key_error_names_the_variable_and_precedence() {
+        let err = resolve_app_settings(openai_app(""), &no_env).unwrap_err();
+        let message = err.user_message();
+        assert!(message.contains(OPENAI_API_KEY_VAR), "{message}");
+        assert!(message.contains("app.openai.api_key"), "{message}");
+        assert!(message.contains("takes precedence"), "{message}");
+    }
+
+    #[test]
+    fn azure_settings_come_from_the_environment() {
+        let app = || AppSection {
+            provider: Some(LlmProviderKind::AzureOpenAi),
+            openai: None,
+            azure_openai: Some(AzureSection {
+                api_key: "yaml-key".into(),
+                endpoint: String::new(),
+                api_version: String::new(),
+                deployment_name: "yaml-deployment".into(),
+            }),
+            ollama: None,
+            retry: None,
+            proxy: None,
+            ..AppSection::default()
+        };
+        let vars = [
+            (AZURE_OPENAI_API_KEY_VAR, "env-key"),
+            (AZURE_OPENAI_ENDPOINT_VAR, "https://env.azure.com"),
+            (AZURE_OPENAI_API_VERSION_VAR, "2024-12-01-preview"),
+            (AZURE_OPENAI_DEPLOYMENT_VAR, "env-deployment"),
+        ];
+        let settings = resolve_app_settings(app(), &env(&vars)).expect("azure settings");
+        let azure = settings.azure.unwrap();
+        assert_eq!(azure.api_key, "env-key");
+        assert_eq!(azure.endpoint, "https://env.azure.com");
+        assert_eq!(azure.api_version, "2024-12-01-preview");
+        assert_eq!(azure.deployment_name, "env-deployment");
+        assert_eq!(settings.model.as_deref(), Some("env-deployment"));
+
+        // Without the endpoint variable the blank yaml endpoint is reported.
+        let err = resolve_app_settings(app(), &env(&vars[..1])).unwrap_err();
+        assert!(err.to_string().contains(AZURE_OPENAI_ENDPOINT_VAR), "{err}");
+        let err = resolve_app_settings(app(), &env(&[vars[1]])).unwrap_err();
+        assert!(
+            err.to_string().contains(AZURE_OPENAI_API_VERSION_VAR),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn resolves_the_active_profile() {
+        let config: PatinaConfig = serde_yaml::from_str(
+            "app:\n  provider: open_ai\n  openai:\n    api_key: personal\n  active_profile: local\n  proxy:\n    url: http://proxy.corp:3128\n  profiles:\n    work:\n      provider: azure_open_ai\n    local:\n      provider: ollama\n      ollama:\n        model: llama3.2\n",
+        )
+        .expect("yaml");
+        let settings = resolve_app_settings(config.app.unwrap(), &no_env).expect("profile");
+        assert!(matches!(settings.provider, LlmProviderKind::Ollama));
+        assert_eq!(settings.model.as_deref(), Some("llama3.2"));
+        assert!(settings.openai.is_none());
+        assert_eq!(settings.proxy.unwrap().url, "http://proxy.corp:3128");
+    }
+
+    #[test]
+    fn unknown_active_profile_is_invalid() {
+        let config: PatinaConfig = serde_yaml::from_str(
+            "app:\n  provider: mock\n  active_profile: missing\n  profiles:\n    local:\n      provider: mock\n",
+        )
+        .expect("yaml");
+        let err = resolve_app_settings(config.app.unwrap(), &no_env).unwrap_err();
+        assert!(err.to_string().contains("`missing`"), "{err}");
+    }
+
+    #[test]
+    fn a_project_file_overrides_only_the_keys_it_sets() {
+        let dir = std::env::temp_dir().join(format!("patina-config-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let user = dir.join("user.yaml");
+        let project = dir.join("project.yaml");
+        fs::write(
+            &user,
+            "app:\n  provider: ollama\n  ollama:\n    base_url: http://gpu-box:11434\n    model: llama3.2\n",
+        )
+        .unwrap();
+        fs::write(&project, "app:\n  ollama:\n    model: qwen2.5-coder\n").unwrap();
+
+        let settings = load_layers(&[user.clone(), project.clone()], &no_env).expect("layers");
+        assert!(matches!(settings.provider, LlmProviderKind::Ollama));
+        let ollama = settings.ollama.expect("ollama");
+        assert_eq!(ollama.base_url, "http://gpu-box:11434");
+        assert_eq!(ollama.model, "qwen2.5-coder");
+
+        fs::write(&project, "app:\n  provider: mock\n").unwrap();
+        let settings = load_layers(&[user, project], &no_env).expect("layers");
+        assert!(matches!(settings.provider, LlmProviderKind::Mock));
+        assert!(matches!(
+            load_layers(&[], &no_env),
+            Err(AiConfigError::Missing)
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
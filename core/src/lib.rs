@@ -1,18 +1,31 @@
+pub mod attachment;
 pub mod auth;
+pub mod clock;
 pub mod config;
 pub mod llm;
 pub mod mcp;
 pub mod project;
+pub mod secrets;
 pub mod state;
 pub mod store;
 pub mod telemetry;
+pub mod tokens;
+
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 
 #[cfg(test)]
 mod llm_streaming_test;
 
+pub use attachment::{ImageAttachment, TextAttachment};
 pub use auth::{AuthCoordinator, AuthMode, AuthState};
-pub use llm::{LlmDriver, LlmProviderKind, LlmStatus, StreamChunk};
-pub use mcp::{CommandSpec, McpClient, McpEndpoint, McpEvent};
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use llm::{LlmDriver, LlmProviderKind, LlmStatus, ModelUsage, RateLimitSnapshot, StreamChunk};
+pub use mcp::{
+    CommandSpec, McpClient, McpEndpoint, McpEvent, ReconnectPolicy, SamplingModel, TransportSpec,
+};
 pub use project::{ProjectHandle, ProjectPaths};
+pub use secrets::SecretStore;
 pub use state::{AppState, ChatMessage, Conversation, MessageRole};
 pub use store::TranscriptStore;
+pub use tokens::TokenCounter;
@@ -1,11 +1,14 @@
-use crate::config::AiRuntimeSettings;
-use crate::state::{ChatMessage, MessageRole};
+use crate::config::{AiRuntimeSettings, OpenAiSettings, ProxySettings, DEFAULT_OPENAI_BASE_URL};
+use crate::state::{snippet, ChatMessage, MessageRole, ToolCall};
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use chrono::Utc;
 use futures::StreamExt;
+use parking_lot::Mutex;
+use reqwest::header::HeaderMap;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
@@ -16,6 +19,7 @@ use uuid::Uuid;
 pub enum LlmProviderKind {
     OpenAi,
     AzureOpenAi,
+    Ollama,
     Mock,
 }
 
@@ -24,6 +28,17 @@ pub struct LlmConfig {
     pub provider: LlmProviderKind,
     pub model: Option<String>,
     pub temperature: Option<f32>,
+    /// Longest reply to ask for, in tokens; `None` leaves it to the provider.
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Nucleus sampling cutoff; `None` leaves it to the provider.
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// Functions the model may ask to call; sent to OpenAI and Azure only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
 }
 
 impl LlmConfig {
@@ -32,8 +47,92 @@ impl LlmConfig {
             provider,
             model,
             temperature: None,
+            max_tokens: None,
+            top_p: None,
+            tools: Vec::new(),
+            retry: RetryPolicy::default(),
         }
     }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+/// A function the model may call, described by a JSON schema for its arguments.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// JSON schema of the arguments object.
+    pub parameters: Value,
+}
+
+/// Bounded exponential backoff for transient provider failures: 5xx gateway/server
+/// errors and dropped connections. Client errors such as 401 are never retried; a 429
+/// is retried once after the delay its `Retry-After` header asks for.
+///
+/// Configured under `app.retry` in `patina.yaml` as `max_attempts` and `base_delay_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first; 1 disables retrying.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one.
+    #[serde(rename = "base_delay_ms", with = "duration_ms")]
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+        }
+    }
+
+    /// Delay after failed attempt `attempt` (1-based), with up to 50% random jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let jitter = fastrand::u64(0..=exponential.as_millis() as u64 / 2);
+        exponential + Duration::from_millis(jitter)
+    }
+
+    pub fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+        matches!(status.as_u16(), 500 | 502 | 503 | 504)
+    }
+
+    /// Connection failures and timeouts; a request that could not be built or sent for
+    /// any other reason fails the same way on every attempt.
+    pub fn is_retryable_error(err: &reqwest::Error) -> bool {
+        err.is_connect() || err.is_timeout()
+    }
+}
+
+mod duration_ms {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +147,63 @@ pub struct ModelUsage {
     pub completion_tokens: usize,
 }
 
+impl ModelUsage {
+    /// Rough token counts (about four characters per token) for providers that
+    /// do not report usage.
+    pub fn estimate(prompt: &[ChatMessage], completion: &str) -> Self {
+        let prompt_chars: usize = prompt
+            .iter()
+            .map(|message| message.content.chars().count())
+            .sum();
+        Self {
+            prompt_tokens: prompt_chars.div_ceil(4),
+            completion_tokens: completion.chars().count().div_ceil(4),
+        }
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// Rate-limit headers from the provider's most recent response.
+///
+/// OpenAI reports limits, remaining counts and reset times; Azure OpenAI only reports
+/// the remaining counts, so the other fields stay empty there.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitSnapshot {
+    pub limit_requests: Option<u64>,
+    pub remaining_requests: Option<u64>,
+    /// Time until the request budget resets, as sent by the provider (e.g. `1s`, `6m0s`).
+    pub reset_requests: Option<String>,
+    pub limit_tokens: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub reset_tokens: Option<String>,
+}
+
+impl RateLimitSnapshot {
+    /// Read the `x-ratelimit-*` headers, or `None` when the response carries none.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let number = |name: &str| text(name).and_then(|value| value.parse().ok());
+        let snapshot = Self {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            reset_requests: text("x-ratelimit-reset-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_tokens: text("x-ratelimit-reset-tokens"),
+        };
+        (snapshot != Self::default()).then_some(snapshot)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum LlmStatus {
     Ready,
@@ -58,6 +214,10 @@ pub enum LlmStatus {
 pub struct StreamChunk {
     pub delta: String,
     pub done: bool,
+    /// Token usage, reported on the final chunk when known.
+    pub usage: Option<ModelUsage>,
+    /// Tool calls the model asked for, reported on the final chunk.
+    pub tool_calls: Vec<ToolCall>,
 }
 
 #[async_trait]
@@ -70,6 +230,11 @@ pub trait LanguageModelProvider: Send + Sync {
         messages: &[ChatMessage],
         config: &LlmConfig,
     ) -> Result<mpsc::UnboundedReceiver<Result<StreamChunk>>>;
+
+    /// Model names the provider offers with the configured credentials.
+    async fn list_models(&self) -> Result<Vec<String>> {
+        bail!("this provider cannot list its models")
+    }
 }
 
 #[derive(Clone)]
@@ -77,11 +242,18 @@ pub struct LlmDriver {
     config: Option<LlmConfig>,
     provider: Option<Arc<dyn LanguageModelProvider>>,
     status: LlmStatus,
+    rate_limits: Arc<Mutex<Option<RateLimitSnapshot>>>,
 }
 
 impl LlmDriver {
     pub async fn from_environment() -> Self {
-        match AiRuntimeSettings::load() {
+        Self::from_layered_environment(None).await
+    }
+
+    /// Like [`Self::from_environment`], with the patina.yaml in `project_dir` layered
+    /// over the user's; see [`AiRuntimeSettings::load_layered`].
+    pub async fn from_layered_environment(project_dir: Option<&std::path::Path>) -> Self {
+        match AiRuntimeSettings::load_layered(project_dir) {
             Ok(settings) => match Self::from_settings(settings).await {
                 Ok(driver) => driver,
                 Err(err) => Self::unconfigured(err.to_string()),
@@ -90,8 +262,9 @@ impl LlmDriver {
         }
     }
 
-    async fn from_settings(settings: AiRuntimeSettings) -> Result<Self> {
-        let client = Client::builder().build()?;
+    pub async fn from_settings(settings: AiRuntimeSettings) -> Result<Self> {
+        let client = http_client(settings.proxy.as_ref())?;
+        let rate_limits = Arc::new(Mutex::new(None));
         match settings.provider {
             LlmProviderKind::OpenAi => {
                 let creds = settings
@@ -101,12 +274,17 @@ impl LlmDriver {
                     .model
                     .clone()
                     .unwrap_or_else(|| "gpt-4o-mini".to_string());
-                let provider =
-                    OpenAiChatProvider::openai(client.clone(), creds.api_key, model.clone());
+                let provider = OpenAiChatProvider::openai(
+                    client.clone(),
+                    creds,
+                    model.clone(),
+                    rate_limits.clone(),
+                );
                 Ok(Self::ready(
-                    LlmConfig::new(LlmProviderKind::OpenAi, Some(model)),
+                    LlmConfig::new(LlmProviderKind::OpenAi, Some(model)).with_retry(settings.retry),
                     Arc::new(provider),
-                ))
+                )
+                .with_rate_limits(rate_limits))
             }
             LlmProviderKind::AzureOpenAi => {
                 let creds = settings
@@ -119,9 +297,24 @@ impl LlmDriver {
                     creds.api_key,
                     creds.api_version,
                     deployment.clone(),
+                    rate_limits.clone(),
                 );
                 Ok(Self::ready(
-                    LlmConfig::new(LlmProviderKind::AzureOpenAi, Some(deployment)),
+                    LlmConfig::new(LlmProviderKind::AzureOpenAi, Some(deployment))
+                        .with_retry(settings.retry),
+                    Arc::new(provider),
+                )
+                .with_rate_limits(rate_limits))
+            }
+            LlmProviderKind::Ollama => {
+                let local = settings
+                    .ollama
+                    .ok_or_else(|| anyhow!("Ollama settings missing after resolution"))?;
+                let model = local.model.clone();
+                let provider =
+                    OllamaChatProvider::new(client.clone(), local.base_url, model.clone());
+                Ok(Self::ready(
+                    LlmConfig::new(LlmProviderKind::Ollama, Some(model)).with_retry(settings.retry),
                     Arc::new(provider),
                 ))
             }
@@ -148,11 +341,18 @@ impl LlmDriver {
         self.status.clone()
     }
 
+    /// Rate-limit headers from the provider's latest response, if it sent any.
+    pub fn rate_limits(&self) -> Option<RateLimitSnapshot> {
+        self.rate_limits.lock().clone()
+    }
+
     pub async fn respond(
         &self,
         history: &[ChatMessage],
         model_override: Option<&str>,
         temperature: Option<f32>,
+        max_tokens: Option<u32>,
+        top_p: Option<f32>,
     ) -> Result<ChatResponse> {
         match (&self.provider, &self.config) {
             (Some(provider), Some(config)) => {
@@ -161,6 +361,8 @@ impl LlmDriver {
                     effective.model = Some(model.to_string());
                 }
                 effective.temperature = temperature;
+                effective.max_tokens = max_tokens;
+                effective.top_p = top_p;
                 provider.send_chat(history, &effective).await
             }
             _ => {
@@ -178,6 +380,8 @@ impl LlmDriver {
         history: &[ChatMessage],
         model_override: Option<&str>,
         temperature: Option<f32>,
+        max_tokens: Option<u32>,
+        top_p: Option<f32>,
     ) -> Result<mpsc::UnboundedReceiver<Result<StreamChunk>>> {
         match (&self.provider, &self.config) {
             (Some(provider), Some(config)) => {
@@ -186,6 +390,8 @@ impl LlmDriver {
                     effective.model = Some(model.to_string());
                 }
                 effective.temperature = temperature;
+                effective.max_tokens = max_tokens;
+                effective.top_p = top_p;
                 provider.send_chat_stream(history, &effective).await
             }
             _ => {
@@ -198,22 +404,75 @@ impl LlmDriver {
         }
     }
 
+    /// Ask the provider which models it offers, sorted by name.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        match &self.provider {
+            Some(provider) => {
+                let mut models = provider.list_models().await?;
+                models.sort();
+                models.dedup();
+                Ok(models)
+            }
+            None => {
+                let message = match &self.status {
+                    LlmStatus::Ready => "AI driver not initialized".to_string(),
+                    LlmStatus::Unconfigured(msg) => msg.clone(),
+                };
+                bail!(message);
+            }
+        }
+    }
+
     fn ready(config: LlmConfig, provider: Arc<dyn LanguageModelProvider>) -> Self {
         Self {
             config: Some(config),
             provider: Some(provider),
             status: LlmStatus::Ready,
+            rate_limits: Arc::default(),
         }
     }
 
+    /// Send a one-line prompt and return how long the provider took to answer it.
+    pub async fn check_connection(&self) -> Result<Duration> {
+        let prompt = [ChatMessage::new(MessageRole::User, "Reply with OK.")];
+        let started = std::time::Instant::now();
+        self.respond(&prompt, None, None, None, None).await?;
+        Ok(started.elapsed())
+    }
+
+    /// Ask `model` for a short title for a conversation that opened with `exchange`.
+    pub async fn suggest_title(
+        &self,
+        exchange: &[ChatMessage],
+        model: Option<&str>,
+    ) -> Result<String> {
+        let mut prompt: Vec<ChatMessage> = exchange
+            .iter()
+            .map(|message| {
+                ChatMessage::new(message.role.clone(), snippet_for_title(&message.content))
+            })
+            .collect();
+        prompt.push(ChatMessage::new(MessageRole::User, TITLE_PROMPT));
+        let response = self
+            .respond(&prompt, model, Some(0.2), Some(TITLE_MAX_TOKENS), None)
+            .await?;
+        clean_title(&response.message.content).context("the model did not suggest a title")
+    }
+
     fn unconfigured(message: impl Into<String>) -> Self {
         Self {
             config: None,
             provider: None,
             status: LlmStatus::Unconfigured(message.into()),
+            rate_limits: Arc::default(),
         }
     }
 
+    fn with_rate_limits(mut self, rate_limits: Arc<Mutex<Option<RateLimitSnapshot>>>) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
     fn configured_mock(model: Option<String>) -> Self {
         Self::ready(
             LlmConfig::new(LlmProviderKind::Mock, model),
@@ -222,16 +481,50 @@ impl LlmDriver {
     }
 }
 
+/// The client shared by every request of a driver, streaming or not, and by other
+/// outgoing requests such as remote images.
+///
+/// Without a configured proxy, reqwest picks up `HTTPS_PROXY`/`HTTP_PROXY` and
+/// `NO_PROXY` from the environment; a configured one still honours `NO_PROXY`.
+pub fn http_client(proxy: Option<&ProxySettings>) -> Result<Client> {
+    let mut builder = Client::builder();
+    if let Some(settings) = proxy {
+        let mut proxy = reqwest::Proxy::all(settings.url.as_str())
+            .with_context(|| format!("invalid proxy url `{}`", settings.url))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        if let Some(username) = &settings.username {
+            proxy = proxy.basic_auth(username, settings.password.as_deref().unwrap_or_default());
+        }
+        builder = builder.proxy(proxy);
+    }
+    builder.build().context("failed to build the HTTP client")
+}
+
 struct OpenAiChatProvider {
     client: Client,
     backend: OpenAiBackend,
+    rate_limits: Arc<Mutex<Option<RateLimitSnapshot>>>,
 }
 
 impl OpenAiChatProvider {
-    fn openai(client: Client, api_key: String, model: String) -> Self {
+    fn openai(
+        client: Client,
+        settings: OpenAiSettings,
+        model: String,
+        rate_limits: Arc<Mutex<Option<RateLimitSnapshot>>>,
+    ) -> Self {
         Self {
             client,
-            backend: OpenAiBackend::OpenAi { api_key, model },
+            backend: OpenAiBackend::OpenAi {
+                base_url: settings
+                    .base_url
+                    .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string()),
+                api_key: settings.api_key,
+                model,
+                organization: settings.organization,
+                project: settings.project,
+            },
+            rate_limits,
         }
     }
 
@@ -241,6 +534,7 @@ impl OpenAiChatProvider {
         api_key: String,
         api_version: String,
         deployment: String,
+        rate_limits: Arc<Mutex<Option<RateLimitSnapshot>>>,
     ) -> Self {
         Self {
             client,
@@ -250,14 +544,237 @@ impl OpenAiChatProvider {
                 api_version,
                 deployment,
             },
+            rate_limits,
+        }
+    }
+
+    /// Post `payload`, retrying transient failures according to `policy`.
+    ///
+    /// Non-retryable error statuses (every 4xx) fail on the first attempt.
+    async fn post_with_retry<T: Serialize + Sync>(
+        &self,
+        payload: &T,
+        policy: &RetryPolicy,
+        what: &str,
+    ) -> Result<reqwest::Response> {
+        let label = self.backend.label();
+        let response = send_with_retry(
+            || self.backend.request_builder(&self.client).json(payload),
+            policy,
+            label,
+            what,
+        )
+        .await?;
+        self.record_rate_limits(&response);
+        check_status(response, label).await
+    }
+
+    /// Remember the rate-limit headers of `response`, keeping the previous snapshot
+    /// when it has none.
+    fn record_rate_limits(&self, response: &reqwest::Response) {
+        if let Some(snapshot) = RateLimitSnapshot::from_headers(response.headers()) {
+            *self.rate_limits.lock() = Some(snapshot);
+        }
+    }
+}
+
+/// Splits a streamed body into lines, keeping raw bytes until a line is complete so a
+/// multi-byte character split across network chunks still decodes intact.
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    /// The next complete line, without its newline.
+    fn next_line(&mut self) -> Option<String> {
+        let end = self.pending.iter().position(|&byte| byte == b'\n')?;
+        let line: Vec<u8> = self.pending.drain(..=end).collect();
+        Some(String::from_utf8_lossy(&line[..end]).into_owned())
+    }
+
+    /// Whatever followed the last newline.
+    fn finish(self) -> String {
+        String::from_utf8_lossy(&self.pending).into_owned()
+    }
+}
+
+/// Send the request built by `build`, rebuilding and resending it while the failure is
+/// transient and `policy` allows another attempt.
+///
+/// The last response is returned as-is, error status included.
+async fn send_with_retry(
+    build: impl Fn() -> reqwest::RequestBuilder,
+    policy: &RetryPolicy,
+    label: &str,
+    what: &str,
+) -> Result<reqwest::Response> {
+    let mut attempt = 1;
+    let mut waited_for_rate_limit = false;
+    loop {
+        let result = build().send().await;
+        if let Ok(response) = &result {
+            if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS && !waited_for_rate_limit
+            {
+                let delay = retry_after(response.headers(), Utc::now())
+                    .unwrap_or(DEFAULT_RATE_LIMIT_DELAY)
+                    .min(MAX_RATE_LIMIT_DELAY);
+                tracing::warn!(?delay, "{label} {what} was rate limited, retrying");
+                sleep(delay).await;
+                waited_for_rate_limit = true;
+                continue;
+            }
+        }
+        let transient = match &result {
+            Ok(response) => RetryPolicy::is_retryable_status(response.status()),
+            Err(err) => RetryPolicy::is_retryable_error(err),
+        };
+        if transient && attempt < policy.max_attempts {
+            let delay = policy.backoff(attempt);
+            match &result {
+                Ok(response) => tracing::warn!(
+                    status = %response.status(),
+                    attempt,
+                    ?delay,
+                    "{label} {what} failed, retrying"
+                ),
+                Err(err) => tracing::warn!(
+                    error = %err,
+                    attempt,
+                    ?delay,
+                    "{label} {what} failed, retrying"
+                ),
+            }
+            sleep(delay).await;
+            attempt += 1;
+            continue;
+        }
+        return result.with_context(|| format!("{label} {what} failed"));
+    }
+}
+
+/// How long to wait after a 429 that carries no `Retry-After` header.
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(2);
+/// Upper bound on a `Retry-After` wait, so a far-off date cannot stall a reply indefinitely.
+const MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+
+/// The wait requested by a `Retry-After` header, given either as seconds or as an HTTP
+/// date relative to `now`.
+fn retry_after(headers: &HeaderMap, now: chrono::DateTime<Utc>) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Asked after a conversation's first exchange when generated titles are enabled.
+const TITLE_PROMPT: &str =
+    "Write a title of 3 to 5 words for this conversation. Reply with the title only.";
+/// Reply budget of a title request; a title needs a handful of tokens.
+const TITLE_MAX_TOKENS: u32 = 16;
+/// Characters of each message sent with a title request, to keep it cheap.
+const TITLE_CONTEXT_CHARS: usize = 2_000;
+
+fn snippet_for_title(content: &str) -> &str {
+    match content.char_indices().nth(TITLE_CONTEXT_CHARS) {
+        Some((cut, _)) => &content[..cut],
+        None => content,
+    }
+}
+
+/// The first line of a suggested title without quotes, markdown or a "Title:" label.
+fn clean_title(reply: &str) -> Option<String> {
+    let decoration = |c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '*' | '#' | '`');
+    let line = reply
+        .lines()
+        .map(|line| line.trim_matches(decoration))
+        .find(|line| !line.is_empty())?;
+    let line = match line.get(..6) {
+        Some(label) if label.eq_ignore_ascii_case("title:") => line[6..].trim_matches(decoration),
+        _ => line,
+    };
+    let title = line.trim_end_matches('.').trim_end();
+    (!title.is_empty()).then(|| snippet(title))
+}
+
+/// Pass successful responses through; otherwise fail with the status and whatever
+/// explanation the provider put in the body.
+async fn check_status(response: reqwest::Response, label: &str) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let detail = describe_error_body(&body)
+            .map(|detail| format!(": {detail}"))
+            .unwrap_or_default();
+        bail!(
+            "{label} is rate limiting requests ({status}); slow down and try again shortly{detail}"
+        );
+    }
+    match describe_error_body(&body) {
+        Some(detail) => bail!("{label} returned {status}: {detail}"),
+        None => bail!("{label} returned an error status ({status})"),
+    }
+}
+
+/// The human-readable part of an error body.
+///
+/// OpenAI sends `{"error": {"message", "type", "code"}}`, Azure `{"error": {"code",
+/// "message"}}` and Ollama `{"error": "..."}`; anything else is shown as raw text.
+fn describe_error_body(body: &str) -> Option<String> {
+    const MAX_RAW_CHARS: usize = 300;
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+    let error = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value.get("error").cloned());
+    match error {
+        Some(serde_json::Value::String(message)) => Some(message),
+        Some(serde_json::Value::Object(fields)) => {
+            let text = |name: &str| match fields.get(name) {
+                Some(serde_json::Value::String(value)) if !value.is_empty() => Some(value.clone()),
+                Some(serde_json::Value::Number(value)) => Some(value.to_string()),
+                _ => None,
+            };
+            match (text("message"), text("code")) {
+                (Some(message), Some(code)) => Some(format!("{message} ({code})")),
+                (Some(message), None) => Some(message),
+                (None, Some(code)) => Some(code),
+                (None, None) => None,
+            }
         }
+        _ => Some(body.chars().take(MAX_RAW_CHARS).collect()),
     }
 }
 
+const AZURE_DEPLOYMENTS_API_VERSION: &str = "2022-12-01";
+
 enum OpenAiBackend {
     OpenAi {
+        /// `https://api.openai.com` or the host of an OpenAI-compatible gateway.
+        base_url: String,
         api_key: String,
         model: String,
+        organization: Option<String>,
+        project: Option<String>,
     },
     Azure {
         api_key: String,
@@ -277,9 +794,10 @@ impl OpenAiBackend {
 
     fn request_builder(&self, client: &Client) -> reqwest::RequestBuilder {
         match self {
-            Self::OpenAi { api_key, .. } => client
-                .post("https://api.openai.com/v1/chat/completions")
-                .bearer_auth(api_key),
+            Self::OpenAi { base_url, .. } => self.openai_auth(client.post(format!(
+                "{}/v1/chat/completions",
+                base_url.trim_end_matches('/')
+            ))),
             Self::Azure {
                 api_key,
                 endpoint,
@@ -298,9 +816,54 @@ impl OpenAiBackend {
         }
     }
 
-    fn request_model(&self) -> Option<&str> {
+    /// Request listing the available models; Azure lists its deployments instead.
+    fn models_request(&self, client: &Client) -> reqwest::RequestBuilder {
+        match self {
+            Self::OpenAi { base_url, .. } => self
+                .openai_auth(client.get(format!("{}/v1/models", base_url.trim_end_matches('/')))),
+            Self::Azure {
+                api_key, endpoint, ..
+            } => {
+                let base = endpoint.trim_end_matches('/');
+                // Deployment listing was dropped from newer api versions, so pin one that has it
+                client
+                    .get(format!(
+                        "{base}/openai/deployments?api-version={AZURE_DEPLOYMENTS_API_VERSION}"
+                    ))
+                    .header("api-key", api_key)
+            }
+        }
+    }
+
+    /// Bearer auth plus the optional organization and project headers OpenAI uses to
+    /// route billing; requests without them are sent exactly as before.
+    fn openai_auth(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let Self::OpenAi {
+            api_key,
+            organization,
+            project,
+            ..
+        } = self
+        else {
+            return builder;
+        };
+        let mut builder = builder.bearer_auth(api_key);
+        if let Some(organization) = organization {
+            builder = builder.header("OpenAI-Organization", organization);
+        }
+        if let Some(project) = project {
+            builder = builder.header("OpenAI-Project", project);
+        }
+        builder
+    }
+
+    /// The `model` field for a request body.
+    ///
+    /// Azure selects the model through the deployment in the URL, so the field is always
+    /// omitted there, even when the caller supplies an override.
+    fn request_model(&self, override_model: Option<&str>) -> Option<String> {
         match self {
-            Self::OpenAi { model, .. } => Some(model.as_str()),
+            Self::OpenAi { model, .. } => Some(override_model.unwrap_or(model).to_string()),
             Self::Azure { .. } => None,
         }
     }
@@ -314,22 +877,16 @@ impl LanguageModelProvider for OpenAiChatProvider {
         config: &LlmConfig,
     ) -> Result<ChatResponse> {
         let payload = ChatCompletionRequest {
-            model: config
-                .model
-                .clone()
-                .or_else(|| self.backend.request_model().map(|model| model.to_string())),
+            model: self.backend.request_model(config.model.as_deref()),
             temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            top_p: config.top_p,
             messages: map_messages(messages),
+            tools: api_tools(&config.tools),
         };
         let response = self
-            .backend
-            .request_builder(&self.client)
-            .json(&payload)
-            .send()
-            .await
-            .with_context(|| format!("{} request failed", self.backend.label()))?
-            .error_for_status()
-            .with_context(|| format!("{} returned an error status", self.backend.label()))?;
+            .post_with_retry(&payload, &config.retry, "request")
+            .await?;
         let payload: ChatCompletionResponse = response
             .json()
             .await
@@ -345,40 +902,41 @@ impl LanguageModelProvider for OpenAiChatProvider {
         let (tx, rx) = mpsc::unbounded_channel();
 
         let payload = ChatCompletionStreamRequest {
-            model: config
-                .model
-                .clone()
-                .or_else(|| self.backend.request_model().map(|model| model.to_string())),
+            model: self.backend.request_model(config.model.as_deref()),
             temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            top_p: config.top_p,
             messages: map_messages(messages),
+            tools: api_tools(&config.tools),
             stream: true,
         };
 
         let response = self
-            .backend
-            .request_builder(&self.client)
-            .json(&payload)
-            .send()
-            .await
-            .with_context(|| format!("{} streaming request failed", self.backend.label()))?
-            .error_for_status()
-            .with_context(|| format!("{} returned an error status", self.backend.label()))?;
+            .post_with_retry(&payload, &config.retry, "streaming request")
+            .await?;
 
         let backend_label = self.backend.label();
         tokio::spawn(async move {
             let mut stream = response.bytes_stream();
-            let mut buffer = String::new();
+            let mut buffer = LineBuffer::default();
+            let mut tool_calls = ToolCallAccumulator::default();
 
-            while let Some(chunk) = stream.next().await {
+            loop {
+                let chunk = tokio::select! {
+                    chunk = stream.next() => chunk,
+                    // The receiver was dropped: the reply was stopped
+                    () = tx.closed() => return,
+                };
+                let Some(chunk) = chunk else {
+                    break;
+                };
                 match chunk {
                     Ok(bytes) => {
-                        buffer.push_str(&String::from_utf8_lossy(&bytes));
+                        buffer.push(&bytes);
 
                         // Process complete lines from buffer
-                        while let Some(line_end) = buffer.find('\n') {
-                            let line = buffer[..line_end].trim().to_string();
-                            buffer = buffer[line_end + 1..].to_string();
-
+                        while let Some(line) = buffer.next_line() {
+                            let line = line.trim();
                             if line.is_empty() {
                                 continue;
                             }
@@ -388,6 +946,8 @@ impl LanguageModelProvider for OpenAiChatProvider {
                                     let _ = tx.send(Ok(StreamChunk {
                                         delta: String::new(),
                                         done: true,
+                                        usage: None,
+                                        tool_calls: tool_calls.finish(),
                                     }));
                                     return;
                                 }
@@ -400,12 +960,17 @@ impl LanguageModelProvider for OpenAiChatProvider {
                                                 let _ = tx.send(Ok(StreamChunk {
                                                     delta: content.clone(),
                                                     done: false,
+                                                    usage: None,
+                                                    tool_calls: Vec::new(),
                                                 }));
                                             }
+                                            tool_calls.push(&choice.delta.tool_calls);
                                             if choice.finish_reason.is_some() {
                                                 let _ = tx.send(Ok(StreamChunk {
                                                     delta: String::new(),
                                                     done: true,
+                                                    usage: None,
+                                                    tool_calls: tool_calls.finish(),
                                                 }));
                                                 return;
                                             }
@@ -434,11 +999,229 @@ impl LanguageModelProvider for OpenAiChatProvider {
             let _ = tx.send(Ok(StreamChunk {
                 delta: String::new(),
                 done: true,
+                usage: None,
+                tool_calls: tool_calls.finish(),
+            }));
+        });
+
+        Ok(rx)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let label = self.backend.label();
+        let response = self
+            .backend
+            .models_request(&self.client)
+            .send()
+            .await
+            .with_context(|| format!("{label} model listing failed"))?;
+        let listing: ModelListResponse = check_status(response, label)
+            .await?
+            .json()
+            .await
+            .with_context(|| format!("{label} model listing decoding failed"))?;
+        Ok(listing.data.into_iter().map(|model| model.id).collect())
+    }
+}
+
+/// A local Ollama server, spoken to through its native `/api/chat` endpoint.
+struct OllamaChatProvider {
+    client: Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaChatProvider {
+    fn new(client: Client, base_url: String, model: String) -> Self {
+        Self {
+            client,
+            base_url,
+            model,
+        }
+    }
+
+    fn chat_url(&self) -> String {
+        format!("{}/api/chat", self.base_url.trim_end_matches('/'))
+    }
+
+    fn request(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+        stream: bool,
+    ) -> OllamaChatRequest {
+        OllamaChatRequest {
+            model: config.model.clone().unwrap_or_else(|| self.model.clone()),
+            messages: map_ollama_messages(messages),
+            stream,
+            options: OllamaOptions::from_config(config),
+        }
+    }
+
+    async fn post(
+        &self,
+        payload: &OllamaChatRequest,
+        policy: &RetryPolicy,
+        what: &str,
+    ) -> Result<reqwest::Response> {
+        let url = self.chat_url();
+        let response = send_with_retry(
+            || self.client.post(&url).json(payload),
+            policy,
+            "Ollama",
+            what,
+        )
+        .await?;
+        check_status(response, "Ollama").await
+    }
+}
+
+#[async_trait]
+impl LanguageModelProvider for OllamaChatProvider {
+    async fn send_chat(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<ChatResponse> {
+        let payload = self.request(messages, config, false);
+        let response = self.post(&payload, &config.retry, "request").await?;
+        let payload: OllamaChatResponse = response
+            .json()
+            .await
+            .context("Ollama response decoding failed")?;
+        if let Some(error) = payload.error {
+            bail!("Ollama error: {error}");
+        }
+        let usage = payload.usage();
+        let content = payload
+            .message
+            .map(|message| message.content)
+            .filter(|content| !content.is_empty())
+            .unwrap_or_else(|| "[empty response]".to_string());
+        Ok(ChatResponse {
+            usage,
+            message: ChatMessage {
+                id: Uuid::new_v4(),
+                role: MessageRole::Assistant,
+                content,
+                created_at: Utc::now(),
+                tool_calls: Vec::new(),
+                alternatives: Vec::new(),
+                selected_alternative: 0,
+                truncated: false,
+                usage: None,
+                model: None,
+                images: Vec::new(),
+            },
+        })
+    }
+
+    async fn send_chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<mpsc::UnboundedReceiver<Result<StreamChunk>>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let payload = self.request(messages, config, true);
+        let response = self
+            .post(&payload, &config.retry, "streaming request")
+            .await?;
+
+        tokio::spawn(async move {
+            let mut stream = response.bytes_stream();
+            let mut buffer = LineBuffer::default();
+            // Forward one NDJSON line; true once the stream is finished or abandoned.
+            let forward = |line: &str| match parse_ollama_line(line) {
+                Ok(chunk) => {
+                    let done = chunk.done;
+                    tx.send(Ok(chunk)).is_err() || done
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    true
+                }
+            };
+
+            loop {
+                let chunk = tokio::select! {
+                    chunk = stream.next() => chunk,
+                    // The receiver was dropped: the reply was stopped
+                    () = tx.closed() => return,
+                };
+                let Some(chunk) = chunk else {
+                    break;
+                };
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.push(&bytes);
+
+                        // Ollama streams one JSON object per line
+                        while let Some(line) = buffer.next_line() {
+                            let line = line.trim();
+                            if !line.is_empty() && forward(line) {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(anyhow!("Ollama stream error: {}", e)));
+                        return;
+                    }
+                }
+            }
+
+            // The final line may arrive without a trailing newline
+            let rest = buffer.finish();
+            let rest = rest.trim();
+            if !rest.is_empty() && forward(rest) {
+                return;
+            }
+
+            // Stream ended without a `done` line
+            let _ = tx.send(Ok(StreamChunk {
+                delta: String::new(),
+                done: true,
+                usage: None,
+                tool_calls: Vec::new(),
             }));
         });
 
         Ok(rx)
     }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let url = format!("{}/api/tags", self.base_url.trim_end_matches('/'));
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("Ollama model listing failed")?;
+        let listing: OllamaTagsResponse = check_status(response, "Ollama")
+            .await?
+            .json()
+            .await
+            .context("Ollama model listing decoding failed")?;
+        Ok(listing.models.into_iter().map(|model| model.name).collect())
+    }
+}
+
+/// Map one line of Ollama's NDJSON stream to a chunk; usage rides on the final line.
+fn parse_ollama_line(line: &str) -> Result<StreamChunk> {
+    let response: OllamaChatResponse = serde_json::from_str(line)
+        .with_context(|| format!("Ollama sent an unreadable stream line: {line}"))?;
+    if let Some(error) = response.error {
+        bail!("Ollama error: {error}");
+    }
+    Ok(StreamChunk {
+        usage: response.done.then(|| response.usage()).flatten(),
+        delta: response
+            .message
+            .map(|message| message.content)
+            .unwrap_or_default(),
+        done: response.done,
+        tool_calls: Vec::new(),
+    })
 }
 
 #[derive(Default)]
@@ -454,6 +1237,10 @@ impl LanguageModelProvider for MockProvider {
         synthetic_response("Mock", messages, config).await
     }
 
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(vec!["mock".to_string()])
+    }
+
     async fn send_chat_stream(
         &self,
         messages: &[ChatMessage],
@@ -475,6 +1262,7 @@ impl LanguageModelProvider for MockProvider {
             prompt
         );
 
+        let usage = ModelUsage::estimate(messages, &reply);
         tokio::spawn(async move {
             // Simulate streaming by sending chunks character by character
             for chunk in reply.chars().collect::<Vec<_>>().chunks(5) {
@@ -484,6 +1272,8 @@ impl LanguageModelProvider for MockProvider {
                     .send(Ok(StreamChunk {
                         delta,
                         done: false,
+                        usage: None,
+                        tool_calls: Vec::new(),
                     }))
                     .is_err()
                 {
@@ -495,6 +1285,8 @@ impl LanguageModelProvider for MockProvider {
             let _ = tx.send(Ok(StreamChunk {
                 delta: String::new(),
                 done: true,
+                usage: Some(usage),
+                tool_calls: Vec::new(),
             }));
         });
 
@@ -508,13 +1300,55 @@ struct ChatCompletionRequest {
     model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
     messages: Vec<CompletionRequestMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ApiTool>,
+}
+
+#[derive(Serialize)]
+struct ApiTool {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    function: ToolDefinition,
 }
 
 #[derive(Serialize)]
 struct CompletionRequestMessage {
+    role: String,
+    content: RequestContent,
+}
+
+/// Plain text, or the text followed by the images of a message for vision models.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum RequestContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Serialize)]
+struct ImageUrl {
+    url: String,
+}
+
+/// Ollama takes images as a list of base64 strings next to the text.
+#[derive(Serialize)]
+struct OllamaRequestMessage {
     role: String,
     content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -533,6 +1367,36 @@ struct CompletionResponseMessage {
     #[allow(dead_code)]
     role: String,
     content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ApiToolCall>,
+}
+
+#[derive(Deserialize)]
+struct ApiToolCall {
+    #[serde(default)]
+    id: String,
+    function: ApiFunctionCall,
+}
+
+#[derive(Deserialize)]
+struct ApiFunctionCall {
+    name: String,
+    /// JSON-encoded arguments, as the model wrote them.
+    #[serde(default)]
+    arguments: String,
+}
+
+impl From<ApiToolCall> for ToolCall {
+    fn from(call: ApiToolCall) -> Self {
+        // Models occasionally emit arguments that are not valid JSON; keep the raw
+        // text rather than dropping the call.
+        let arguments = serde_json::from_str(&call.function.arguments)
+            .unwrap_or(Value::String(call.function.arguments));
+        ToolCall {
+            call_id: (!call.id.is_empty()).then_some(call.id),
+            ..ToolCall::new(call.function.name, arguments)
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -547,7 +1411,13 @@ struct ChatCompletionStreamRequest {
     model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
     messages: Vec<CompletionRequestMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ApiTool>,
     stream: bool,
 }
 
@@ -565,14 +1435,195 @@ struct StreamChoice {
 #[derive(Deserialize)]
 struct StreamDelta {
     content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<StreamToolCallDelta>,
+}
+
+/// A fragment of a streamed tool call; fragments sharing an `index` belong together.
+#[derive(Deserialize)]
+struct StreamToolCallDelta {
+    index: usize,
+    id: Option<String>,
+    function: Option<StreamFunctionDelta>,
+}
+
+#[derive(Deserialize)]
+struct StreamFunctionDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+/// Reassembles streamed tool calls: the id and name arrive once, the arguments
+/// in pieces.
+#[derive(Default)]
+struct ToolCallAccumulator {
+    calls: Vec<ApiToolCall>,
+}
+
+impl ToolCallAccumulator {
+    fn push(&mut self, deltas: &[StreamToolCallDelta]) {
+        for delta in deltas {
+            while self.calls.len() <= delta.index {
+                self.calls.push(ApiToolCall {
+                    id: String::new(),
+                    function: ApiFunctionCall {
+                        name: String::new(),
+                        arguments: String::new(),
+                    },
+                });
+            }
+            let call = &mut self.calls[delta.index];
+            if let Some(id) = &delta.id {
+                call.id.clone_from(id);
+            }
+            if let Some(function) = &delta.function {
+                if let Some(name) = &function.name {
+                    call.function.name.push_str(name);
+                }
+                if let Some(arguments) = &function.arguments {
+                    call.function.arguments.push_str(arguments);
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Vec<ToolCall> {
+        std::mem::take(&mut self.calls)
+            .into_iter()
+            .filter(|call| !call.function.name.is_empty())
+            .map(ToolCall::from)
+            .collect()
+    }
+}
+
+/// `GET /v1/models` on OpenAI, `GET /openai/deployments` on Azure.
+#[derive(Deserialize)]
+struct ModelListResponse {
+    data: Vec<ModelListEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelListEntry {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaTagsResponse {
+    #[serde(default)]
+    models: Vec<OllamaTag>,
+}
+
+#[derive(Deserialize)]
+struct OllamaTag {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaRequestMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<OllamaOptions>,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+impl OllamaOptions {
+    /// The sampling options set in `config`, or `None` when all are left to the model.
+    fn from_config(config: &LlmConfig) -> Option<Self> {
+        let options = Self {
+            temperature: config.temperature,
+            num_predict: config.max_tokens,
+            top_p: config.top_p,
+        };
+        (options.temperature.is_some() || options.num_predict.is_some() || options.top_p.is_some())
+            .then_some(options)
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaChatResponse {
+    message: Option<OllamaResponseMessage>,
+    #[serde(default)]
+    done: bool,
+    prompt_eval_count: Option<usize>,
+    eval_count: Option<usize>,
+    error: Option<String>,
+}
+
+impl OllamaChatResponse {
+    fn usage(&self) -> Option<ModelUsage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+        Some(ModelUsage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or(0),
+            completion_tokens: self.eval_count.unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaResponseMessage {
+    #[serde(default)]
+    content: String,
 }
 
 fn map_messages(messages: &[ChatMessage]) -> Vec<CompletionRequestMessage> {
     messages
         .iter()
         .map(|message| CompletionRequestMessage {
+            role: api_role(&message.role),
+            content: request_content(message),
+        })
+        .collect()
+}
+
+fn request_content(message: &ChatMessage) -> RequestContent {
+    if message.images.is_empty() {
+        return RequestContent::Text(message.content.clone());
+    }
+    let text = (!message.content.is_empty()).then(|| ContentPart::Text {
+        text: message.content.clone(),
+    });
+    let images = message.images.iter().map(|image| ContentPart::ImageUrl {
+        image_url: ImageUrl {
+            url: image.data_url(),
+        },
+    });
+    RequestContent::Parts(text.into_iter().chain(images).collect())
+}
+
+fn map_ollama_messages(messages: &[ChatMessage]) -> Vec<OllamaRequestMessage> {
+    messages
+        .iter()
+        .map(|message| OllamaRequestMessage {
             role: api_role(&message.role),
             content: message.content.clone(),
+            images: message
+                .images
+                .iter()
+                .map(|image| image.data.clone())
+                .collect(),
+        })
+        .collect()
+}
+
+fn api_tools(tools: &[ToolDefinition]) -> Vec<ApiTool> {
+    tools
+        .iter()
+        .map(|tool| ApiTool {
+            kind: "function",
+            function: tool.clone(),
         })
         .collect()
 }
@@ -596,16 +1647,32 @@ fn completion_to_chat(
         .into_iter()
         .next()
         .ok_or_else(|| anyhow!("response contained no choices"))?;
-    let content = choice
+    let tool_calls: Vec<ToolCall> = choice
         .message
-        .content
-        .unwrap_or_else(|| "[empty response]".to_string());
+        .tool_calls
+        .into_iter()
+        .map(ToolCall::from)
+        .collect();
+    // A reply that only asks for tools legitimately has no text.
+    let content = choice.message.content.unwrap_or_else(|| {
+        if tool_calls.is_empty() {
+            "[empty response]".to_string()
+        } else {
+            String::new()
+        }
+    });
     let reply = ChatMessage {
         id: Uuid::new_v4(),
         role: MessageRole::Assistant,
         content,
         created_at: Utc::now(),
-        tool_calls: Vec::new(),
+        tool_calls,
+        alternatives: Vec::new(),
+        selected_alternative: 0,
+        truncated: false,
+        usage: None,
+        model: None,
+        images: Vec::new(),
     };
     let usage = payload.usage.map(|usage| ModelUsage {
         prompt_tokens: usage.prompt_tokens.unwrap_or(0),
@@ -641,6 +1708,12 @@ async fn synthetic_response(
         content: reply,
         created_at: Utc::now(),
         tool_calls: Vec::new(),
+        alternatives: Vec::new(),
+        selected_alternative: 0,
+        truncated: false,
+        usage: None,
+        model: None,
+        images: Vec::new(),
     };
     Ok(ChatResponse {
         message,
@@ -650,3 +1723,694 @@ async fn synthetic_response(
         }),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_server_with, ReceivedRequests};
+
+    fn request_body(backend: &OpenAiBackend, config: &LlmConfig) -> serde_json::Value {
+        let payload = ChatCompletionRequest {
+            model: backend.request_model(config.model.as_deref()),
+            temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            top_p: config.top_p,
+            messages: map_messages(&[ChatMessage::new(MessageRole::User, "hi")]),
+            tools: api_tools(&config.tools),
+        };
+        serde_json::to_value(payload).expect("serialize request")
+    }
+
+    fn screenshot() -> ChatMessage {
+        ChatMessage {
+            images: vec![crate::attachment::ImageAttachment {
+                mime_type: "image/png".into(),
+                data: "iVBORw0KGgo=".into(),
+            }],
+            ..ChatMessage::new(MessageRole::User, "What is on screen?")
+        }
+    }
+
+    #[test]
+    fn images_are_sent_as_content_parts_after_the_text() {
+        let messages = serde_json::to_value(map_messages(&[
+            ChatMessage::new(MessageRole::User, "hi"),
+            screenshot(),
+        ]))
+        .expect("serialize messages");
+        assert_eq!(messages[0]["content"], "hi");
+        assert_eq!(
+            messages[1]["content"],
+            serde_json::json!([
+                {"type": "text", "text": "What is on screen?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}},
+            ])
+        );
+
+        let ollama =
+            serde_json::to_value(map_ollama_messages(&[screenshot()])).expect("serialize messages");
+        assert_eq!(ollama[0]["content"], "What is on screen?");
+        assert_eq!(ollama[0]["images"], serde_json::json!(["iVBORw0KGgo="]));
+    }
+
+    #[tokio::test]
+    async fn azure_requests_omit_model_even_with_override() {
+        let (endpoint, hits) = fake_server(vec![200]);
+        let mut config = LlmConfig::new(LlmProviderKind::AzureOpenAi, None);
+        config.model = Some("gpt-4o".into());
+        local_provider(endpoint)
+            .send_chat(&prompt(), &config)
+            .await
+            .expect("azure request");
+        let body = hits.json(0);
+        assert!(body.get("model").is_none());
+        assert_eq!(body["messages"][0]["content"], "hi");
+    }
+
+    #[test]
+    fn openai_base_url_replaces_only_the_host() {
+        let client = Client::new();
+        let url = |base_url: &str| {
+            let backend = OpenAiBackend::OpenAi {
+                base_url: base_url.into(),
+                api_key: "key".into(),
+                model: "gpt-4o".into(),
+                organization: None,
+                project: None,
+            };
+            let chat = backend.request_builder(&client).build().unwrap();
+            let models = backend.models_request(&client).build().unwrap();
+            (chat.url().to_string(), models.url().to_string())
+        };
+        assert_eq!(
+            url(DEFAULT_OPENAI_BASE_URL),
+            (
+                "https://api.openai.com/v1/chat/completions".to_string(),
+                "https://api.openai.com/v1/models".to_string()
+            )
+        );
+        assert_eq!(
+            url("http://localhost:4000/"),
+            (
+                "http://localhost:4000/v1/chat/completions".to_string(),
+                "http://localhost:4000/v1/models".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn organization_and_project_headers_are_sent_only_when_set() {
+        let client = Client::new();
+        let backend = |organization: Option<&str>, project: Option<&str>| OpenAiBackend::OpenAi {
+            base_url: DEFAULT_OPENAI_BASE_URL.into(),
+            api_key: "key".into(),
+            model: "gpt-4o".into(),
+            organization: organization.map(Into::into),
+            project: project.map(Into::into),
+        };
+
+        let request = backend(Some("org-1"), Some("proj-1"))
+            .request_builder(&client)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["OpenAI-Organization"], "org-1");
+        assert_eq!(request.headers()["OpenAI-Project"], "proj-1");
+        let models = backend(Some("org-1"), None)
+            .models_request(&client)
+            .build()
+            .unwrap();
+        assert_eq!(models.headers()["OpenAI-Organization"], "org-1");
+        assert!(models.headers().get("OpenAI-Project").is_none());
+
+        let request = backend(None, None)
+            .request_builder(&client)
+            .build()
+            .unwrap();
+        assert!(request.headers().get("OpenAI-Organization").is_none());
+        assert!(request.headers().get("OpenAI-Project").is_none());
+        assert_eq!(request.headers()["authorization"], "Bearer key");
+    }
+
+    #[test]
+    fn openai_requests_prefer_the_override() {
+        let backend = OpenAiBackend::OpenAi {
+            base_url: DEFAULT_OPENAI_BASE_URL.into(),
+            api_key: "key".into(),
+            model: "gpt-4o".into(),
+            organization: None,
+            project: None,
+        };
+        let config = LlmConfig::new(LlmProviderKind::OpenAi, Some("gpt-4o-mini".into()));
+        assert_eq!(request_body(&backend, &config)["model"], "gpt-4o-mini");
+
+        let config = LlmConfig::new(LlmProviderKind::OpenAi, None);
+        assert_eq!(request_body(&backend, &config)["model"], "gpt-4o");
+    }
+
+    #[test]
+    fn sampling_limits_are_sent_only_when_set() {
+        let backend = OpenAiBackend::OpenAi {
+            base_url: DEFAULT_OPENAI_BASE_URL.into(),
+            api_key: "key".into(),
+            model: "gpt-4o".into(),
+            organization: None,
+            project: None,
+        };
+        let mut config = LlmConfig::new(LlmProviderKind::OpenAi, None);
+        let body = request_body(&backend, &config);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("top_p").is_none());
+
+        config.max_tokens = Some(256);
+        config.top_p = Some(0.5);
+        let body = request_body(&backend, &config);
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["top_p"], 0.5);
+
+        let ollama = OllamaChatProvider::new(Client::new(), "http://localhost".into(), "m".into());
+        let body = serde_json::to_value(ollama.request(&prompt(), &config, false)).unwrap();
+        assert_eq!(body["options"]["num_predict"], 256);
+        assert!(body["options"].get("temperature").is_none());
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().expect("header value"));
+        }
+        map
+    }
+
+    #[test]
+    fn rate_limit_snapshot_parses_openai_headers() {
+        let recorded = headers(&[
+            ("x-ratelimit-limit-requests", "5000"),
+            ("x-ratelimit-remaining-requests", "4999"),
+            ("x-ratelimit-reset-requests", "12ms"),
+            ("x-ratelimit-limit-tokens", "800000"),
+            ("x-ratelimit-remaining-tokens", "799976"),
+            ("x-ratelimit-reset-tokens", "1ms"),
+            ("content-type", "application/json"),
+        ]);
+        assert_eq!(
+            RateLimitSnapshot::from_headers(&recorded),
+            Some(RateLimitSnapshot {
+                limit_requests: Some(5000),
+                remaining_requests: Some(4999),
+                reset_requests: Some("12ms".into()),
+                limit_tokens: Some(800_000),
+                remaining_tokens: Some(799_976),
+                reset_tokens: Some("1ms".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn rate_limit_snapshot_handles_partial_and_missing_headers() {
+        let azure = headers(&[
+            ("x-ratelimit-remaining-requests", "119"),
+            ("x-ratelimit-remaining-tokens", "119000"),
+        ]);
+        let snapshot = RateLimitSnapshot::from_headers(&azure).expect("snapshot");
+        assert_eq!(snapshot.remaining_requests, Some(119));
+        assert_eq!(snapshot.remaining_tokens, Some(119_000));
+        assert_eq!(snapshot.limit_requests, None);
+        assert_eq!(snapshot.reset_tokens, None);
+
+        let none = headers(&[("content-type", "application/json")]);
+        assert_eq!(RateLimitSnapshot::from_headers(&none), None);
+    }
+
+    #[test]
+    fn retry_backoff_doubles_with_bounded_jitter() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+        };
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(100) && first <= Duration::from_millis(150));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_millis(400) && third <= Duration::from_millis(600));
+        }
+        assert_eq!(RetryPolicy::none().backoff(1), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn only_connection_failures_and_timeouts_are_retried() {
+        let client = reqwest::Client::new();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = closed.local_addr().unwrap();
+        drop(closed);
+        let refused = client.get(format!("http://{address}")).send().await.unwrap_err();
+        assert!(RetryPolicy::is_retryable_error(&refused));
+
+        // A server that answers with something other than HTTP fails the same way
+        // however often the request is sent.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            use std::io::{Read, Write};
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            let _ = stream.write_all(b"not http at all\r\n\r\n");
+        });
+        let garbled = client.get(format!("http://{address}")).send().await.unwrap_err();
+        assert!(garbled.is_request());
+        assert!(!RetryPolicy::is_retryable_error(&garbled));
+    }
+
+    /// Answer one request per connection with the next canned status, counting requests.
+    fn fake_server(statuses: Vec<u16>) -> (String, ReceivedRequests) {
+        let responses = statuses
+            .into_iter()
+            .map(|status| {
+                let body = if status == 200 {
+                    r#"{"choices":[{"message":{"role":"assistant","content":"recovered"}}]}"#
+                } else {
+                    r#"{"error":{"message":"nope"}}"#
+                };
+                (status, "", body.to_string())
+            })
+            .collect();
+        fake_server_with(responses)
+    }
+
+    fn local_provider(endpoint: String) -> OpenAiChatProvider {
+        OpenAiChatProvider::azure(
+            Client::new(),
+            endpoint,
+            "key".into(),
+            "2024-02-01".into(),
+            "chat".into(),
+            Arc::default(),
+        )
+    }
+
+    fn fast_retries() -> LlmConfig {
+        LlmConfig::new(LlmProviderKind::AzureOpenAi, None).with_retry(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+        })
+    }
+
+    fn prompt() -> Vec<ChatMessage> {
+        vec![ChatMessage::new(MessageRole::User, "hi")]
+    }
+
+    #[tokio::test]
+    async fn requests_go_through_the_configured_proxy() {
+        let (proxy_url, hits) = fake_server(vec![200]);
+        let client = http_client(Some(&ProxySettings {
+            url: proxy_url,
+            username: Some("me".into()),
+            password: Some("secret".into()),
+        }))
+        .expect("client");
+        let provider = OpenAiChatProvider::openai(
+            client,
+            OpenAiSettings {
+                api_key: "key".into(),
+                model: None,
+                base_url: Some("http://llm.invalid".into()),
+                organization: None,
+                project: None,
+            },
+            "gpt-4o".into(),
+            Arc::default(),
+        );
+        let response = provider
+            .send_chat(&prompt(), &fast_retries())
+            .await
+            .expect("request through the proxy");
+        assert_eq!(response.message.content, "recovered");
+        assert_eq!(hits.len(), 1);
+
+        let err = http_client(Some(&ProxySettings {
+            url: "http://proxy corp:3128".into(),
+            username: None,
+            password: None,
+        }))
+        .expect_err("malformed proxy url");
+        assert!(err.to_string().contains("invalid proxy url"));
+    }
+
+    #[tokio::test]
+    async fn transient_server_errors_are_retried() {
+        let (endpoint, hits) = fake_server(vec![503, 502, 200]);
+        let response = local_provider(endpoint)
+            .send_chat(&prompt(), &fast_retries())
+            .await
+            .expect("third attempt succeeds");
+        assert_eq!(response.message.content, "recovered");
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_max_attempts() {
+        let (endpoint, hits) = fake_server(vec![500, 500, 500, 200]);
+        let result = local_provider(endpoint)
+            .send_chat(&prompt(), &fast_retries())
+            .await;
+        assert!(result.is_err());
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_fails_fast() {
+        let (endpoint, hits) = fake_server(vec![401, 200]);
+        let err = local_provider(endpoint)
+            .send_chat(&prompt(), &fast_retries())
+            .await
+            .expect_err("401 is not retried");
+        assert!(format!("{err:#}").contains("401"));
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn ollama_lines_map_to_stream_chunks() {
+        let chunk = parse_ollama_line(
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+        )
+        .expect("delta line");
+        assert_eq!(chunk.delta, "Hel");
+        assert!(!chunk.done);
+        assert!(chunk.usage.is_none());
+
+        let last = parse_ollama_line(
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":12,"eval_count":7}"#,
+        )
+        .expect("final line");
+        assert!(last.done);
+        let usage = last.usage.expect("usage on final line");
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 7));
+
+        let err = parse_ollama_line(r#"{"error":"model 'nope' not found"}"#).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn ollama_streams_ndjson_without_api_key() {
+        let body = [
+            r#"{"message":{"role":"assistant","content":"Hello"},"done":false}"#,
+            r#"{"message":{"role":"assistant","content":" there"},"done":false}"#,
+            r#"{"message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":3,"eval_count":2}"#,
+        ]
+        .join("\n");
+        let (base_url, hits) = fake_server_with(vec![(200, "", body)]);
+        let provider = OllamaChatProvider::new(Client::new(), base_url, "llama3.2".into());
+        let config = LlmConfig::new(LlmProviderKind::Ollama, None);
+        let mut rx = provider
+            .send_chat_stream(&prompt(), &config)
+            .await
+            .expect("stream starts");
+        let mut text = String::new();
+        let mut usage = None;
+        while let Some(chunk) = rx.recv().await {
+            let chunk = chunk.expect("chunk");
+            text.push_str(&chunk.delta);
+            if chunk.done {
+                usage = chunk.usage;
+                break;
+            }
+        }
+        assert_eq!(text, "Hello there");
+        assert_eq!(usage.map(|usage| usage.total_tokens()), Some(5));
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn line_buffer_keeps_codepoints_split_across_chunks() {
+        let text = "data: {\"content\":\"日本語\"}\n";
+        // Cut inside the three-byte encoding of 本
+        let split = text.find('本').unwrap() + 1;
+        let line = text.as_bytes();
+        let mut buffer = LineBuffer::default();
+        buffer.push(&line[..split]);
+        assert_eq!(buffer.next_line(), None);
+        buffer.push(&line[split..]);
+        assert_eq!(
+            buffer.next_line().as_deref(),
+            Some("data: {\"content\":\"日本語\"}")
+        );
+        assert_eq!(buffer.next_line(), None);
+        assert_eq!(buffer.finish(), "");
+    }
+
+    #[test]
+    fn error_bodies_are_described() {
+        let openai = r#"{"error":{"message":"The model `gpt-9` does not exist","type":"invalid_request_error","param":null,"code":"model_not_found"}}"#;
+        assert_eq!(
+            describe_error_body(openai).as_deref(),
+            Some("The model `gpt-9` does not exist (model_not_found)")
+        );
+        let azure = r#"{"error":{"code":"DeploymentNotFound","message":"The API deployment for this resource does not exist."}}"#;
+        assert_eq!(
+            describe_error_body(azure).as_deref(),
+            Some("The API deployment for this resource does not exist. (DeploymentNotFound)")
+        );
+        let ollama = r#"{"error":"model \"llama9\" not found, try pulling it first"}"#;
+        assert_eq!(
+            describe_error_body(ollama).as_deref(),
+            Some("model \"llama9\" not found, try pulling it first")
+        );
+        assert_eq!(
+            describe_error_body("Bad Gateway").as_deref(),
+            Some("Bad Gateway")
+        );
+        assert_eq!(describe_error_body("  "), None);
+    }
+
+    #[tokio::test]
+    async fn error_status_includes_provider_message() {
+        let body =
+            r#"{"error":{"message":"The model `gpt-9` does not exist","code":"model_not_found"}}"#;
+        let (endpoint, _) = fake_server_with(vec![
+            (404, "", body.to_string()),
+            (404, "", body.to_string()),
+        ]);
+        let provider = local_provider(endpoint);
+        let err = provider
+            .send_chat(&prompt(), &fast_retries())
+            .await
+            .expect_err("unknown models fail");
+        let message = format!("{err:#}");
+        assert!(message.contains("404"), "{message}");
+        assert!(message.contains("The model `gpt-9` does not exist (model_not_found)"));
+
+        let err = provider
+            .send_chat_stream(&prompt(), &fast_retries())
+            .await
+            .expect_err("streaming request fails too");
+        assert!(format!("{err:#}").contains("model_not_found"));
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let now = chrono::DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        let seconds = headers(&[("retry-after", "7")]);
+        assert_eq!(retry_after(&seconds, now), Some(Duration::from_secs(7)));
+        let date = headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:30 GMT")]);
+        assert_eq!(retry_after(&date, now), Some(Duration::from_secs(30)));
+        let past = headers(&[("retry-after", "Wed, 21 Oct 2015 07:27:00 GMT")]);
+        assert_eq!(retry_after(&past, now), Some(Duration::ZERO));
+        assert_eq!(retry_after(&headers(&[]), now), None);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_wait_and_retry_once() {
+        let limited = r#"{"error":{"message":"Rate limit reached","code":"rate_limit_exceeded"}}"#;
+        let (endpoint, hits) = fake_server_with(vec![
+            (429, "retry-after: 0\r\n", limited.to_string()),
+            (
+                200,
+                "",
+                r#"{"choices":[{"message":{"role":"assistant","content":"recovered"}}]}"#
+                    .to_string(),
+            ),
+        ]);
+        let response = local_provider(endpoint)
+            .send_chat(&prompt(), &fast_retries())
+            .await
+            .expect("retry after the wait succeeds");
+        assert_eq!(response.message.content, "recovered");
+        assert_eq!(hits.len(), 2);
+
+        let (endpoint, hits) = fake_server_with(vec![
+            (429, "retry-after: 0\r\n", limited.to_string()),
+            (429, "retry-after: 0\r\n", limited.to_string()),
+            (200, "", String::new()),
+        ]);
+        let err = local_provider(endpoint)
+            .send_chat(&prompt(), &fast_retries())
+            .await
+            .expect_err("second 429 gives up");
+        let message = format!("{err:#}");
+        assert!(message.contains("rate limiting"), "{message}");
+        assert!(message.contains("Rate limit reached"), "{message}");
+        assert_eq!(hits.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn azure_lists_deployments() {
+        let listing = r#"{"data":[{"id":"gpt-4o","model":"gpt-4o","object":"deployment"},{"id":"chat","model":"gpt-35-turbo","object":"deployment"}],"object":"list"}"#;
+        let (endpoint, _) = fake_server_with(vec![(200, "", listing.to_string())]);
+        let provider = local_provider(endpoint);
+        let mut models = provider.list_models().await.expect("deployments");
+        models.sort();
+        assert_eq!(models, vec!["chat".to_string(), "gpt-4o".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn invalid_key_fails_model_listing() {
+        let body = r#"{"error":{"message":"Incorrect API key provided","code":"invalid_api_key"}}"#;
+        let (endpoint, _) = fake_server_with(vec![(401, "", body.to_string())]);
+        let err = local_provider(endpoint)
+            .list_models()
+            .await
+            .expect_err("401 fails");
+        assert!(format!("{err:#}").contains("Incorrect API key provided"));
+    }
+
+    #[tokio::test]
+    async fn connection_check_reports_provider_errors() {
+        let body = r#"{"error":{"message":"Incorrect API key provided","code":"invalid_api_key"}}"#;
+        let (endpoint, _) = fake_server_with(vec![(401, "", body.to_string())]);
+        let driver = LlmDriver::ready(fast_retries(), Arc::new(local_provider(endpoint)));
+        let err = driver.check_connection().await.expect_err("401 fails");
+        assert!(format!("{err:#}").contains("Incorrect API key provided"));
+
+        LlmDriver::fake()
+            .await
+            .check_connection()
+            .await
+            .expect("mock answers");
+        let err = LlmDriver::unconfigured("missing OpenAI API key")
+            .check_connection()
+            .await
+            .expect_err("unconfigured");
+        assert!(err.to_string().contains("missing OpenAI API key"));
+    }
+
+    #[test]
+    fn suggested_titles_are_cleaned_up() {
+        assert_eq!(
+            clean_title("\"Rust Lifetimes Explained.\"").as_deref(),
+            Some("Rust Lifetimes Explained")
+        );
+        assert_eq!(
+            clean_title("\n**Title:** Trip to Lisbon\nmore").as_deref(),
+            Some("Trip to Lisbon")
+        );
+        assert_eq!(clean_title(" \n \"\" "), None);
+    }
+
+    #[tokio::test]
+    async fn titles_are_suggested_by_the_model() {
+        let body = r#"{"choices":[{"message":{"role":"assistant","content":"Weather in Porto"}}]}"#;
+        let (endpoint, _) = fake_server_with(vec![(200, "", body.to_string())]);
+        let driver = LlmDriver::ready(fast_retries(), Arc::new(local_provider(endpoint)));
+        let exchange = [
+            ChatMessage::new(MessageRole::User, "hi, what's it like in Porto?"),
+            ChatMessage::new(MessageRole::Assistant, "Sunny and 24°C."),
+        ];
+        let title = driver.suggest_title(&exchange, None).await.expect("title");
+        assert_eq!(title, "Weather in Porto");
+    }
+
+    fn weather_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "get_weather".into(),
+            description: "Current weather for a city".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "city": { "type": "string" } },
+                "required": ["city"]
+            }),
+        }
+    }
+
+    #[test]
+    fn requests_carry_tools_only_when_configured() {
+        let backend = OpenAiBackend::Azure {
+            api_key: "key".into(),
+            endpoint: "https://example.openai.azure.com".into(),
+            api_version: "2024-02-01".into(),
+            deployment: "chat".into(),
+        };
+        let mut config = LlmConfig::new(LlmProviderKind::AzureOpenAi, None);
+        assert!(request_body(&backend, &config).get("tools").is_none());
+
+        config.tools.push(weather_tool());
+        let body = request_body(&backend, &config);
+        assert_eq!(body["tools"][0]["type"], "function");
+        assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+        assert_eq!(
+            body["tools"][0]["function"]["parameters"]["required"][0],
+            "city"
+        );
+    }
+
+    #[tokio::test]
+    async fn tool_calls_in_a_reply_become_pending_calls() {
+        let body = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "get_weather", "arguments": "{\"city\":\"Lisbon\"}" }
+                    }]
+                }
+            }]
+        });
+        let (endpoint, _) = fake_server_with(vec![(200, "", body.to_string())]);
+        let mut config = fast_retries();
+        config.tools.push(weather_tool());
+        let response = local_provider(endpoint)
+            .send_chat(&prompt(), &config)
+            .await
+            .expect("reply");
+        let message = response.message;
+        assert_eq!(message.content, "");
+        assert_eq!(message.tool_calls.len(), 1);
+        let call = &message.tool_calls[0];
+        assert_eq!(call.name, "get_weather");
+        assert_eq!(call.call_id.as_deref(), Some("call_1"));
+        assert_eq!(call.arguments["city"], "Lisbon");
+        assert_eq!(call.status, crate::state::ToolCallStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn streamed_tool_calls_are_reassembled_on_the_final_chunk() {
+        let body = [
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"get_weather","arguments":""}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Porto\"}"}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+        ]
+        .iter()
+        .map(|line| format!("data: {line}\n\n"))
+        .collect::<String>();
+        let (endpoint, _) = fake_server_with(vec![(200, "", body)]);
+        let mut rx = local_provider(endpoint)
+            .send_chat_stream(&prompt(), &fast_retries())
+            .await
+            .expect("stream starts");
+        let mut tool_calls = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            let chunk = chunk.expect("chunk");
+            if chunk.done {
+                tool_calls = chunk.tool_calls;
+                break;
+            }
+        }
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].name, "get_weather");
+        assert_eq!(tool_calls[0].arguments["city"], "Porto");
+    }
+}
@@ -12,7 +12,7 @@ mod streaming_tests {
         ];
 
         let mut stream = driver
-            .respond_streaming(&messages, Some("mock"), Some(0.7))
+            .respond_streaming(&messages, Some("mock"), Some(0.7), None, None)
             .await
             .expect("Failed to start streaming");
 
@@ -47,7 +47,7 @@ mod streaming_tests {
         let messages: Vec<ChatMessage> = vec![];
 
         let mut stream = driver
-            .respond_streaming(&messages, Some("mock"), Some(0.7))
+            .respond_streaming(&messages, Some("mock"), Some(0.7), None, None)
             .await
             .expect("Failed to start streaming");
 
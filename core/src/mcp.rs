@@ -1,23 +1,32 @@
 use crate::auth::{AuthCoordinator, AuthMode, AuthState};
-use anyhow::{anyhow, Context, Result};
+use crate::llm::{LlmDriver, ToolDefinition};
+use crate::state::{ChatMessage, MessageRole};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
 use rmcp::model::{
-    CallToolRequestParam, CallToolResult, ClientCapabilities, ClientInfo,
-    CreateMessageRequestMethod, ElicitationCreateRequestMethod, InitializeResult, JsonObject,
-    ListRootsResult, ServerNotification, ServerRequest, Tool,
+    CallToolRequestParam, CallToolResult, ClientCapabilities, ClientInfo, ClientResult, Content,
+    CreateMessageRequestParam, CreateMessageResult, ElicitationCreateRequestMethod,
+    InitializeResult, JsonObject, ListRootsResult, Role, Root, SamplingMessage, ServerNotification,
+    ServerRequest, Tool,
 };
 use rmcp::service::QuitReason;
 use rmcp::service::{self, Peer, RoleClient, RunningServiceCancellationToken};
 use rmcp::transport::child_process::TokioChildProcess;
+use rmcp::transport::sse_client::SseClientConfig;
+use rmcp::transport::SseClientTransport;
 use rmcp::ErrorData;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::Command;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,12 +43,73 @@ pub enum McpEvent {
         #[serde(skip_serializing_if = "Option::is_none")]
         reason: Option<String>,
     },
+    /// The connection dropped and attempt `attempt` to restore it starts after `delay_ms`.
+    Reconnecting {
+        endpoint: String,
+        attempt: u32,
+        delay_ms: u64,
+    },
     ToolInvoked {
         endpoint: String,
         tool: String,
         arguments: Value,
         result: Value,
     },
+    /// A tool call that errored, timed out or was cancelled.
+    ToolFailed {
+        endpoint: String,
+        tool: String,
+        arguments: Value,
+        error: String,
+    },
+}
+
+/// How long a tool call may run when its endpoint sets no `tool_timeout_secs`.
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often [`McpRegistry::start_background_refresh`] looks for tokens that expire
+/// within the next minute.
+pub const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The model that answers servers' `sampling/createMessage` requests.
+#[derive(Clone)]
+pub struct SamplingModel {
+    pub llm: LlmDriver,
+    pub model: String,
+    pub temperature: f32,
+}
+
+/// Shared by a registry and its clients; `None` declines sampling requests.
+type SharedSampling = Arc<parking_lot::RwLock<Option<SamplingModel>>>;
+
+/// Exponential backoff for restoring a dropped MCP connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Reconnect attempts before giving up.
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled for each further one.
+    pub base_delay: Duration,
+    /// Longest delay between attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before attempt `attempt` (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,24 +157,119 @@ impl CommandSpec {
         self
     }
 
-    pub fn to_command(&self) -> Command {
-        let mut cmd = Command::new(&self.program);
-        cmd.args(&self.args);
+    /// The command to spawn, with `${VAR}` references in the program, arguments, env
+    /// values and working directory taken from the current environment.
+    ///
+    /// Fails if a referenced variable is unset.
+    pub fn to_command(&self) -> Result<Command> {
+        let mut cmd = Command::new(expand_env(&self.program)?);
+        for arg in &self.args {
+            cmd.arg(expand_env(arg)?);
+        }
         for (key, value) in &self.env {
-            cmd.env(key, value);
+            cmd.env(key, expand_env(value)?);
         }
         if let Some(dir) = &self.current_dir {
-            cmd.current_dir(dir);
+            match dir.to_str() {
+                Some(dir) => cmd.current_dir(expand_env(dir)?),
+                None => cmd.current_dir(dir),
+            };
+        }
+        Ok(cmd)
+    }
+}
+
+/// Check that every endpoint has a unique, non-empty id and something to connect to.
+pub fn validate_endpoints(endpoints: &[McpEndpoint]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for endpoint in endpoints {
+        let id = endpoint.id.trim();
+        if id.is_empty() {
+            bail!("an MCP server has an empty id");
+        }
+        if !seen.insert(id) {
+            bail!("MCP server id `{id}` is used more than once");
+        }
+        match &endpoint.transport {
+            TransportSpec::ChildProcess(command) if command.program.trim().is_empty() => {
+                bail!("MCP server `{id}` has an empty command")
+            }
+            TransportSpec::Sse { url, .. } if url.trim().is_empty() => {
+                bail!("MCP server `{id}` has an empty url")
+            }
+            _ => {}
+        }
+        if endpoint.tool_timeout_secs == Some(0) {
+            bail!("MCP server `{id}` has a tool timeout of zero seconds");
         }
-        cmd
     }
+    Ok(())
+}
+
+/// Replace `${VAR}` in `value` with the variable from the process environment; `$$`
+/// stands for a literal `$` and any other `$` is kept as is. No shell is involved.
+pub fn expand_env(value: &str) -> Result<String> {
+    expand_with(value, |name| std::env::var(name).ok())
+}
+
+fn expand_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some(after) = after.strip_prefix('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| anyhow!("unterminated `${{` in `{value}`"))?;
+            let name = &after[..end];
+            let resolved = lookup(name).ok_or_else(|| {
+                anyhow!("environment variable `{name}` used in `{value}` is not set")
+            })?;
+            expanded.push_str(&resolved);
+            rest = &after[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = after;
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// How to reach an MCP server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransportSpec {
+    /// Spawn a local server and talk to it over stdio.
+    ChildProcess(CommandSpec),
+    /// Connect to a remote server's SSE endpoint, e.g. `https://tools.example.com/sse`.
+    Sse {
+        url: String,
+        /// Sent with every request; an `Authorization` header here takes precedence
+        /// over the negotiated access token.
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpEndpoint {
     pub id: String,
     pub mode: AuthMode,
-    pub command: CommandSpec,
+    pub transport: TransportSpec,
+    /// Project directory advertised to the server as its MCP root.
+    #[serde(default)]
+    pub root: Option<PathBuf>,
+    /// Seconds a tool call may run before it is abandoned; see [`DEFAULT_TOOL_TIMEOUT`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_timeout_secs: Option<u64>,
+    /// Reconnect with the default [`ReconnectPolicy`] when the connection drops.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reconnect: bool,
 }
 
 impl McpEndpoint {
@@ -112,8 +277,71 @@ impl McpEndpoint {
         Self {
             id: id.into(),
             mode,
-            command,
+            transport: TransportSpec::ChildProcess(command),
+            root: None,
+            tool_timeout_secs: None,
+            reconnect: false,
+        }
+    }
+
+    pub fn sse(
+        id: impl Into<String>,
+        mode: AuthMode,
+        url: impl Into<String>,
+        headers: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            mode,
+            transport: TransportSpec::Sse {
+                url: url.into(),
+                headers,
+            },
+            root: None,
+            tool_timeout_secs: None,
+            reconnect: false,
+        }
+    }
+
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn with_tool_timeout(mut self, timeout: Duration) -> Self {
+        self.tool_timeout_secs = Some(timeout.as_secs());
+        self
+    }
+
+    pub fn tool_timeout(&self) -> Duration {
+        self.tool_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOOL_TIMEOUT)
+    }
+
+    pub fn with_reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// How a dropped connection is restored, if it is.
+    pub fn reconnect_policy(&self) -> Option<ReconnectPolicy> {
+        self.reconnect.then(ReconnectPolicy::default)
+    }
+
+    /// Command to spawn; servers without an explicit working directory start in the root.
+    fn to_command(&self, command: &CommandSpec) -> Result<Command> {
+        let mut cmd = command.to_command()?;
+        if command.current_dir.is_none() {
+            if let Some(root) = &self.root {
+                cmd.current_dir(root);
+            }
         }
+        Ok(cmd)
     }
 
     pub fn id(&self) -> &str {
@@ -154,12 +382,28 @@ impl ClientConnectionState {
     async fn take(&self) -> Option<ConnectedState> {
         self.inner.write().await.take()
     }
+
+    async fn tools(&self) -> Option<Vec<Tool>> {
+        self.inner
+            .read()
+            .await
+            .as_ref()
+            .and_then(|state| state.tools.clone())
+    }
+
+    async fn set_tools(&self, tools: Vec<Tool>) {
+        if let Some(state) = self.inner.write().await.as_mut() {
+            state.tools = Some(tools);
+        }
+    }
 }
 
 struct ConnectedState {
     peer: Peer<RoleClient>,
     cancel: RunningServiceCancellationToken,
     server_info: InitializeResult,
+    /// The server's tools, listed once per connection.
+    tools: Option<Vec<Tool>>,
 }
 
 #[derive(Clone)]
@@ -169,6 +413,10 @@ pub struct McpClient {
     events_tx: UnboundedSender<McpEvent>,
     state: Arc<ClientConnectionState>,
     connect_lock: Arc<Mutex<()>>,
+    reconnect: Option<ReconnectPolicy>,
+    /// Cancelled by [`Self::disconnect`] to stop a reconnect in progress.
+    reconnect_cancel: Arc<parking_lot::Mutex<CancellationToken>>,
+    sampling: SharedSampling,
 }
 
 impl McpClient {
@@ -184,11 +432,26 @@ impl McpClient {
                 events_tx,
                 state: Arc::new(ClientConnectionState::default()),
                 connect_lock: Arc::new(Mutex::new(())),
+                reconnect: None,
+                reconnect_cancel: Arc::default(),
+                sampling: SharedSampling::default(),
             },
             events_rx,
         )
     }
 
+    /// Reconnect with `policy` when the connection drops, instead of waiting for
+    /// [`Self::handshake`] to be called again. A [`Self::disconnect`] is never undone.
+    pub fn with_auto_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    fn with_sampling(mut self, sampling: SharedSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
     pub async fn handshake(&self) -> Result<AuthState> {
         let auth_state = self
             .auth
@@ -208,6 +471,7 @@ impl McpClient {
     }
 
     pub async fn disconnect(&self) -> Result<()> {
+        std::mem::take(&mut *self.reconnect_cancel.lock()).cancel();
         if let Some(state) = self.state.take().await {
             state.cancel.cancel();
         }
@@ -215,6 +479,18 @@ impl McpClient {
     }
 
     pub async fn call_tool(&self, tool: &str, arguments: Option<Value>) -> Result<CallToolResult> {
+        self.call_tool_cancellable(tool, arguments, &CancellationToken::new())
+            .await
+    }
+
+    /// Call `tool`, giving up when the endpoint's tool timeout expires or `cancel` fires.
+    /// Failures are reported as [`McpEvent::ToolFailed`] as well as returned.
+    pub async fn call_tool_cancellable(
+        &self,
+        tool: &str,
+        arguments: Option<Value>,
+        cancel: &CancellationToken,
+    ) -> Result<CallToolResult> {
         let peer = self
             .state
             .peer()
@@ -233,13 +509,35 @@ impl McpClient {
             }
         };
 
-        let result = peer
-            .call_tool(CallToolRequestParam {
-                name: Cow::Owned(tool.to_owned()),
-                arguments: arguments_map,
-            })
-            .await
-            .with_context(|| format!("failed to call tool '{tool}'"))?;
+        let timeout = self.endpoint.tool_timeout();
+        let call = peer.call_tool(CallToolRequestParam {
+            name: Cow::Owned(tool.to_owned()),
+            arguments: arguments_map,
+        });
+        let outcome = tokio::select! {
+            result = tokio::time::timeout(timeout, call) => match result {
+                Ok(result) => result.with_context(|| format!("failed to call tool '{tool}'")),
+                Err(_) => Err(anyhow!(
+                    "tool '{tool}' on MCP server '{}' did not answer within {timeout:?}",
+                    self.endpoint.id
+                )),
+            },
+            _ = cancel.cancelled() => Err(anyhow!("call to tool '{tool}' was cancelled")),
+        };
+        let result = match outcome {
+            Ok(result) => result,
+            Err(err) => {
+                self.events_tx
+                    .send(McpEvent::ToolFailed {
+                        endpoint: self.endpoint.id.clone(),
+                        tool: tool.to_owned(),
+                        arguments: args_value,
+                        error: format!("{err:#}"),
+                    })
+                    .ok();
+                return Err(err);
+            }
+        };
 
         let result_json =
             serde_json::to_value(&result).context("serialize tool result for event dispatch")?;
@@ -254,15 +552,23 @@ impl McpClient {
         Ok(result)
     }
 
+    /// The server's tools; listed on the first call after connecting, then cached
+    /// until the connection is replaced.
     pub async fn list_tools(&self) -> Result<Vec<Tool>> {
+        if let Some(tools) = self.state.tools().await {
+            return Ok(tools);
+        }
         let peer = self
             .state
             .peer()
             .await
             .ok_or_else(|| anyhow!("MCP client is not connected"))?;
-        peer.list_all_tools()
+        let tools = peer
+            .list_all_tools()
             .await
-            .map_err(|err| anyhow!("failed to list tools: {err}"))
+            .map_err(|err| anyhow!("failed to list tools: {err}"))?;
+        self.state.set_tools(tools.clone()).await;
+        Ok(tools)
     }
 
     pub fn endpoint(&self) -> &str {
@@ -273,6 +579,62 @@ impl McpClient {
         self.endpoint.mode()
     }
 
+    /// Retry the handshake with backoff after the connection dropped for `reason`,
+    /// ending in a final [`McpEvent::Disconnected`] if every attempt fails or `cancel`
+    /// fires.
+    ///
+    /// Boxed because a successful handshake spawns the task that calls this again.
+    fn reconnect_with(
+        self,
+        policy: ReconnectPolicy,
+        reason: Option<String>,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'static, ()> {
+        Box::pin(async move { self.reconnect_loop(policy, reason, cancel).await })
+    }
+
+    async fn reconnect_loop(
+        &self,
+        policy: ReconnectPolicy,
+        reason: Option<String>,
+        cancel: CancellationToken,
+    ) {
+        let mut last_error = reason.unwrap_or_else(|| "connection closed".to_string());
+        for attempt in 1..=policy.max_attempts {
+            let delay = policy.delay(attempt);
+            let _ = self.events_tx.send(McpEvent::Reconnecting {
+                endpoint: self.endpoint.id.clone(),
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+            });
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = cancel.cancelled() => {}
+            }
+            if cancel.is_cancelled() {
+                let _ = self.events_tx.send(McpEvent::Disconnected {
+                    endpoint: self.endpoint.id.clone(),
+                    reason: format_quit_reason(QuitReason::Cancelled),
+                });
+                return;
+            }
+            match self.handshake().await {
+                Ok(_) => return,
+                Err(err) => {
+                    warn!(endpoint = %self.endpoint.id, attempt, error = %err, "MCP reconnect failed");
+                    last_error = format!("{err:#}");
+                }
+            }
+        }
+        let _ = self.events_tx.send(McpEvent::Disconnected {
+            endpoint: self.endpoint.id.clone(),
+            reason: Some(format!(
+                "gave up after {} reconnect attempts: {last_error}",
+                policy.max_attempts
+            )),
+        });
+    }
+
     async fn ensure_connected(&self, auth_state: AuthState) -> Result<InitializeResult> {
         if let Some(info) = self.state.server_info().await {
             return Ok(info);
@@ -284,18 +646,52 @@ impl McpClient {
         self.establish_connection(auth_state).await
     }
 
-    async fn establish_connection(&self, auth_state: AuthState) -> Result<InitializeResult> {
-        let handler = PatinaClientHandler::new(
+    /// What answers the server's requests on a connection to this endpoint.
+    fn handler(&self, auth_state: AuthState) -> PatinaClientHandler {
+        PatinaClientHandler::new(
             self.endpoint.id.clone(),
             self.endpoint.mode.clone(),
             auth_state,
-        );
+            self.endpoint.root.clone(),
+            self.sampling.clone(),
+        )
+    }
 
-        let transport = TokioChildProcess::new(self.endpoint.command.to_command())
-            .with_context(|| format!("failed to spawn MCP transport for '{}'", self.endpoint.id))?;
-        let service = service::serve_client(handler, transport)
-            .await
-            .with_context(|| format!("failed to initialize MCP client '{}':", self.endpoint.id))?;
+    async fn establish_connection(&self, auth_state: AuthState) -> Result<InitializeResult> {
+        let access_token = auth_state.access_token.clone();
+        let handler = self.handler(auth_state);
+
+        let service = match &self.endpoint.transport {
+            TransportSpec::ChildProcess(command) => {
+                let command = self.endpoint.to_command(command).with_context(|| {
+                    format!("invalid command for MCP server '{}'", self.endpoint.id)
+                })?;
+                let transport = TokioChildProcess::new(command).with_context(|| {
+                    format!("failed to spawn MCP transport for '{}'", self.endpoint.id)
+                })?;
+                service::serve_client(handler, transport).await
+            }
+            TransportSpec::Sse { url, headers } => {
+                let client = reqwest::Client::builder()
+                    .default_headers(sse_headers(headers, access_token.as_deref())?)
+                    .build()
+                    .context("failed to build MCP HTTP client")?;
+                let config = SseClientConfig {
+                    sse_endpoint: url.as_str().into(),
+                    ..Default::default()
+                };
+                let transport = SseClientTransport::start_with_client(client, config)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to connect to MCP server '{}' at {url}",
+                            self.endpoint.id
+                        )
+                    })?;
+                service::serve_client(handler, transport).await
+            }
+        }
+        .with_context(|| format!("failed to initialize MCP client '{}':", self.endpoint.id))?;
 
         let peer = service.peer().clone();
         let server_info = peer
@@ -303,14 +699,28 @@ impl McpClient {
             .cloned()
             .unwrap_or_else(InitializeResult::default);
         let cancel = service.cancellation_token();
-        let endpoint = self.endpoint.id.clone();
-        let events = self.events_tx.clone();
+        let client = self.clone();
+        let stop_reconnect = self.reconnect_cancel.lock().clone();
         tokio::spawn(async move {
-            let reason = match service.waiting().await {
-                Ok(reason) => format_quit_reason(reason),
-                Err(err) => Some(format!("task join error: {err}")),
+            let (reason, cancelled) = match service.waiting().await {
+                Ok(reason) => {
+                    let cancelled = matches!(reason, QuitReason::Cancelled);
+                    (format_quit_reason(reason), cancelled)
+                }
+                Err(err) => (Some(format!("task join error: {err}")), false),
             };
-            let _ = events.send(McpEvent::Disconnected { endpoint, reason });
+            match client.reconnect {
+                Some(policy) if !cancelled => {
+                    client.state.take().await;
+                    client.reconnect_with(policy, reason, stop_reconnect).await;
+                }
+                _ => {
+                    let _ = client.events_tx.send(McpEvent::Disconnected {
+                        endpoint: client.endpoint.id.clone(),
+                        reason,
+                    });
+                }
+            }
         });
 
         self.state
@@ -318,56 +728,164 @@ impl McpClient {
                 peer,
                 cancel,
                 server_info: server_info.clone(),
+                tools: None,
             })
             .await;
         Ok(server_info)
     }
 }
 
+impl From<Tool> for ToolDefinition {
+    fn from(tool: Tool) -> Self {
+        Self {
+            name: tool.name.into_owned(),
+            description: tool.description.map(Cow::into_owned).unwrap_or_default(),
+            parameters: Value::Object((*tool.input_schema).clone()),
+        }
+    }
+}
+
+/// Headers for an SSE connection: the configured ones, plus the access token as a
+/// bearer token unless an `Authorization` header was configured.
+fn sse_headers(
+    configured: &HashMap<String, String>,
+    access_token: Option<&str>,
+) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in configured {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("invalid MCP header name `{name}`"))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("invalid value for MCP header `{name}`"))?;
+        headers.insert(name, value);
+    }
+    if let Some(token) = access_token.filter(|token| !token.is_empty()) {
+        if !headers.contains_key(AUTHORIZATION) {
+            let mut value = HeaderValue::from_str(&format!("Bearer {token}"))
+                .context("access token is not a valid header value")?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+    }
+    Ok(headers)
+}
+
 struct PatinaClientHandler {
     endpoint_id: String,
     #[allow(dead_code)]
     mode: AuthMode,
     #[allow(dead_code)]
     auth_state: AuthState,
+    root: Option<PathBuf>,
+    sampling: SharedSampling,
     client_info: ClientInfo,
 }
 
 impl PatinaClientHandler {
-    fn new(endpoint_id: String, mode: AuthMode, auth_state: AuthState) -> Self {
+    fn new(
+        endpoint_id: String,
+        mode: AuthMode,
+        auth_state: AuthState,
+        root: Option<PathBuf>,
+        sampling: SharedSampling,
+    ) -> Self {
         let mut client_info = ClientInfo::default();
         client_info.client_info.name = "patina-desktop".to_string();
         client_info.client_info.title = Some("Patina Desktop Client".to_string());
         client_info.client_info.version = env!("CARGO_PKG_VERSION").to_string();
-        client_info.capabilities = ClientCapabilities::builder().build();
+        // Advertised even while sampling is turned off, so turning it on needs no
+        // reconnect; requests are declined until then.
+        client_info.capabilities = ClientCapabilities::builder()
+            .enable_roots()
+            .enable_sampling()
+            .build();
         Self {
             endpoint_id: endpoint_id.clone(),
             mode,
             auth_state,
+            root,
+            sampling,
             client_info,
         }
     }
-}
 
-impl service::Service<RoleClient> for PatinaClientHandler {
-    async fn handle_request(
+    fn list_roots(&self) -> ListRootsResult {
+        let roots = self
+            .root
+            .iter()
+            .filter_map(|path| {
+                let uri = url::Url::from_directory_path(path).ok()?;
+                Some(Root {
+                    uri: uri.to_string(),
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned()),
+                })
+            })
+            .collect();
+        ListRootsResult { roots }
+    }
+
+    /// Answer a sampling request with the user's model, if they allowed sampling.
+    async fn create_message(
         &self,
-        request: ServerRequest,
-        _context: service::RequestContext<RoleClient>,
-    ) -> Result<rmcp::model::ClientResult, ErrorData> {
+        request: CreateMessageRequestParam,
+    ) -> Result<CreateMessageResult, ErrorData> {
+        let Some(sampling) = self.sampling.read().clone() else {
+            return Err(ErrorData::invalid_request(
+                "sampling is turned off in Patina",
+                None,
+            ));
+        };
+        info!(endpoint = %self.endpoint_id, model = %sampling.model, "Answering MCP sampling request");
+        let history = sampling_history(&request);
+        let max_tokens = (request.max_tokens > 0).then_some(request.max_tokens);
+        let response = sampling
+            .llm
+            .respond(
+                &history,
+                Some(sampling.model.as_str()),
+                Some(sampling.temperature),
+                max_tokens,
+                None,
+            )
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{err:#}"), None))?;
+        Ok(CreateMessageResult {
+            model: sampling.model,
+            stop_reason: Some(CreateMessageResult::STOP_REASON_END_TURN.to_string()),
+            message: SamplingMessage {
+                role: Role::Assistant,
+                content: Content::text(response.message.content),
+            },
+        })
+    }
+
+    async fn respond(&self, request: ServerRequest) -> Result<ClientResult, ErrorData> {
         match request {
-            ServerRequest::PingRequest(_) => Ok(rmcp::model::ClientResult::empty(())),
-            ServerRequest::ListRootsRequest(_) => Ok(rmcp::model::ClientResult::ListRootsResult(
-                ListRootsResult::default(),
-            )),
-            ServerRequest::CreateMessageRequest(_) => {
-                Err(ErrorData::method_not_found::<CreateMessageRequestMethod>())
+            ServerRequest::PingRequest(_) => Ok(ClientResult::empty(())),
+            ServerRequest::ListRootsRequest(_) => {
+                Ok(ClientResult::ListRootsResult(self.list_roots()))
             }
+            ServerRequest::CreateMessageRequest(request) => self
+                .create_message(request.params)
+                .await
+                .map(|result| ClientResult::CreateMessageResult(Box::new(result))),
             ServerRequest::CreateElicitationRequest(_) => {
                 Err(ErrorData::method_not_found::<ElicitationCreateRequestMethod>())
             }
         }
     }
+}
+
+impl service::Service<RoleClient> for PatinaClientHandler {
+    async fn handle_request(
+        &self,
+        request: ServerRequest,
+        _context: service::RequestContext<RoleClient>,
+    ) -> Result<ClientResult, ErrorData> {
+        self.respond(request).await
+    }
 
     async fn handle_notification(
         &self,
@@ -392,6 +910,28 @@ impl service::Service<RoleClient> for PatinaClientHandler {
     }
 }
 
+/// The conversation a sampling request asks to continue, as chat messages. Content
+/// other than text is replaced by a note, since providers here only take text.
+fn sampling_history(request: &CreateMessageRequestParam) -> Vec<ChatMessage> {
+    let system = request
+        .system_prompt
+        .as_deref()
+        .filter(|prompt| !prompt.trim().is_empty())
+        .map(|prompt| ChatMessage::new(MessageRole::System, prompt));
+    let messages = request.messages.iter().map(|message| {
+        let role = match message.role {
+            Role::User => MessageRole::User,
+            Role::Assistant => MessageRole::Assistant,
+        };
+        let content = match message.content.as_text() {
+            Some(text) => text.text.clone(),
+            None => "[non-text content omitted]".to_string(),
+        };
+        ChatMessage::new(role, content)
+    });
+    system.into_iter().chain(messages).collect()
+}
+
 fn format_quit_reason(reason: QuitReason) -> Option<String> {
     match reason {
         QuitReason::Cancelled => Some("cancelled".to_string()),
@@ -403,6 +943,9 @@ fn format_quit_reason(reason: QuitReason) -> Option<String> {
 pub struct McpRegistry {
     auth: AuthCoordinator,
     clients: Vec<Arc<McpClient>>,
+    /// Stops the background token refresh, when one was started.
+    refresh_cancel: Option<CancellationToken>,
+    sampling: SharedSampling,
 }
 
 impl McpRegistry {
@@ -410,24 +953,558 @@ impl McpRegistry {
         Self {
             auth,
             clients: Vec::new(),
+            refresh_cancel: None,
+            sampling: SharedSampling::default(),
         }
     }
 
+    /// Refresh tokens that are about to expire every `interval`, so a long session does
+    /// not hit an expired token mid-call. Stops on [`Self::shutdown`] or when dropped.
+    /// Must be called within a Tokio runtime; a refresh already running is replaced.
+    pub fn start_background_refresh(&mut self, interval: Duration) {
+        self.shutdown();
+        let cancel = CancellationToken::new();
+        self.auth.spawn_refresh_task(interval, cancel.clone());
+        self.refresh_cancel = Some(cancel);
+    }
+
+    pub fn auth(&self) -> &AuthCoordinator {
+        &self.auth
+    }
+
+    pub fn shutdown(&mut self) {
+        if let Some(cancel) = self.refresh_cancel.take() {
+            cancel.cancel();
+        }
+    }
+
+    /// Answer sampling requests from every client's server with `model`, or decline
+    /// them when `None`.
+    pub fn set_sampling(&self, model: Option<SamplingModel>) {
+        *self.sampling.write() = model;
+    }
+
     pub async fn register(
         &mut self,
         endpoint: McpEndpoint,
     ) -> Result<(Arc<McpClient>, UnboundedReceiver<McpEvent>)> {
         let mode = endpoint.mode.clone();
         let id = endpoint.id.clone();
-        let (client, rx) = McpClient::new(endpoint, self.auth.clone());
-        let client = Arc::new(client);
+        let (client, rx) = self.client_for(endpoint);
         client.handshake().await?;
         info!(endpoint = %id, mode = ?mode, "Registered MCP client");
         self.clients.push(client.clone());
         Ok((client, rx))
     }
 
+    /// Stop tracking every client, returning them so they can be disconnected.
+    pub fn take_clients(&mut self) -> Vec<Arc<McpClient>> {
+        std::mem::take(&mut self.clients)
+    }
+
+    /// Track a client for `endpoint` without connecting it; see [`McpClient::handshake`].
+    pub fn add(&mut self, endpoint: McpEndpoint) -> (Arc<McpClient>, UnboundedReceiver<McpEvent>) {
+        let (client, rx) = self.client_for(endpoint);
+        self.clients.push(client.clone());
+        (client, rx)
+    }
+
+    /// A client sharing this registry's auth and sampling model, reconnecting as the
+    /// endpoint asks.
+    fn client_for(&self, endpoint: McpEndpoint) -> (Arc<McpClient>, UnboundedReceiver<McpEvent>) {
+        let policy = endpoint.reconnect_policy();
+        let (client, rx) = McpClient::new(endpoint, self.auth.clone());
+        let mut client = client.with_sampling(self.sampling.clone());
+        if let Some(policy) = policy {
+            client = client.with_auto_reconnect(policy);
+        }
+        (Arc::new(client), rx)
+    }
+
     pub fn clients(&self) -> &[Arc<McpClient>] {
         &self.clients
     }
+
+    pub fn client(&self, endpoint: &str) -> Option<Arc<McpClient>> {
+        self.clients
+            .iter()
+            .find(|client| client.endpoint() == endpoint)
+            .cloned()
+    }
+}
+
+impl Drop for McpRegistry {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::{CreateMessageRequest, ListRootsRequest};
+
+    fn handler(root: Option<PathBuf>) -> PatinaClientHandler {
+        PatinaClientHandler::new(
+            "test".to_string(),
+            AuthMode::ServerManaged,
+            AuthState::new(AuthMode::ServerManaged),
+            root,
+            SharedSampling::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn list_roots_returns_the_project_root() {
+        let root = std::env::temp_dir().join("patina-mcp-root");
+        let handler = handler(Some(root.clone()));
+        assert!(handler.client_info.capabilities.roots.is_some());
+
+        let result = handler
+            .respond(ServerRequest::ListRootsRequest(ListRootsRequest::default()))
+            .await
+            .expect("roots/list succeeds");
+        let ClientResult::ListRootsResult(result) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert_eq!(result.roots.len(), 1);
+        let expected = url::Url::from_directory_path(&root).unwrap().to_string();
+        assert_eq!(result.roots[0].uri, expected);
+        assert!(result.roots[0].uri.starts_with("file://"));
+        assert_eq!(result.roots[0].name.as_deref(), Some("patina-mcp-root"));
+    }
+
+    #[tokio::test]
+    async fn project_endpoints_list_the_project_root() {
+        let dir = std::env::temp_dir().join(format!("patina-mcp-roots-{}", std::process::id()));
+        let project = crate::project::ProjectHandle::create(&dir, "Roots").unwrap();
+        let manifest = std::fs::read_to_string(&project.paths().pat_file).unwrap();
+        std::fs::write(
+            &project.paths().pat_file,
+            format!(
+                "{manifest}\n[[mcp.endpoints]]\nid = \"files\"\nmode = \"ServerManaged\"\n\n\
+                 [mcp.endpoints.transport]\ntype = \"child_process\"\nprogram = \"files-mcp\"\n"
+            ),
+        )
+        .unwrap();
+        let project = crate::project::ProjectHandle::open(&project.paths().root).unwrap();
+        let endpoint = project.mcp_endpoints().unwrap().remove(0);
+
+        let auth = AuthCoordinator::with_secrets(Arc::new(MemorySecrets::default()));
+        let (client, _events) = McpClient::new(endpoint, auth);
+        let result = client
+            .handler(AuthState::new(AuthMode::ServerManaged))
+            .respond(ServerRequest::ListRootsRequest(ListRootsRequest::default()))
+            .await
+            .expect("roots/list succeeds");
+        let ClientResult::ListRootsResult(result) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        let expected = url::Url::from_directory_path(&project.paths().root)
+            .unwrap()
+            .to_string();
+        assert_eq!(result.roots.len(), 1);
+        assert_eq!(result.roots[0].uri, expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn list_roots_is_empty_without_a_project() {
+        let result = handler(None)
+            .respond(ServerRequest::ListRootsRequest(ListRootsRequest::default()))
+            .await
+            .expect("roots/list succeeds");
+        let ClientResult::ListRootsResult(result) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert!(result.roots.is_empty());
+    }
+
+    fn sampling_request() -> ServerRequest {
+        ServerRequest::CreateMessageRequest(CreateMessageRequest::new(CreateMessageRequestParam {
+            messages: vec![SamplingMessage {
+                role: Role::User,
+                content: Content::text("Summarize the diff"),
+            }],
+            model_preferences: None,
+            system_prompt: Some("Be brief.".to_string()),
+            include_context: None,
+            temperature: Some(1.0),
+            max_tokens: 200,
+            stop_sequences: None,
+            metadata: None,
+        }))
+    }
+
+    #[test]
+    fn sampling_requests_become_chat_history() {
+        let ServerRequest::CreateMessageRequest(request) = sampling_request() else {
+            unreachable!();
+        };
+        let history = sampling_history(&request.params);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].role, MessageRole::System);
+        assert_eq!(history[0].content, "Be brief.");
+        assert_eq!(history[1].role, MessageRole::User);
+        assert_eq!(history[1].content, "Summarize the diff");
+    }
+
+    #[tokio::test]
+    async fn sampling_is_declined_until_allowed() {
+        let handler = handler(None);
+        assert!(handler.client_info.capabilities.sampling.is_some());
+        let err = handler
+            .respond(sampling_request())
+            .await
+            .expect_err("sampling is off");
+        assert!(err.message.contains("turned off"), "{}", err.message);
+
+        *handler.sampling.write() = Some(SamplingModel {
+            llm: LlmDriver::fake().await,
+            model: "mock".to_string(),
+            temperature: 0.2,
+        });
+        let result = handler
+            .respond(sampling_request())
+            .await
+            .expect("sampling answered");
+        let ClientResult::CreateMessageResult(result) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert_eq!(result.model, "mock");
+        assert_eq!(result.message.role, Role::Assistant);
+        assert!(result.message.content.as_text().is_some());
+    }
+
+    #[test]
+    fn sse_headers_carry_the_access_token_unless_overridden() {
+        let mut configured = HashMap::new();
+        configured.insert("X-Team".to_string(), "tools".to_string());
+        let headers = sse_headers(&configured, Some("abc")).expect("headers");
+        assert_eq!(headers["x-team"], "tools");
+        assert_eq!(headers[AUTHORIZATION], "Bearer abc");
+
+        configured.insert("Authorization".to_string(), "Basic xyz".to_string());
+        let headers = sse_headers(&configured, Some("abc")).expect("headers");
+        assert_eq!(headers[AUTHORIZATION], "Basic xyz");
+
+        let headers = sse_headers(&HashMap::new(), None).expect("headers");
+        assert!(headers.is_empty());
+
+        configured.insert("bad header".to_string(), "x".to_string());
+        assert!(sse_headers(&configured, None).is_err());
+    }
+
+    #[test]
+    fn env_references_expand_without_a_shell() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/ada".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_with("${HOME}/bin/server", lookup).unwrap(),
+            "/home/ada/bin/server"
+        );
+        assert_eq!(expand_with("a${EMPTY}b", lookup).unwrap(), "ab");
+        assert_eq!(
+            expand_with("$$HOME costs $5", lookup).unwrap(),
+            "$HOME costs $5"
+        );
+        assert_eq!(expand_with("$${HOME}", lookup).unwrap(), "${HOME}");
+        assert_eq!(expand_with("plain", lookup).unwrap(), "plain");
+
+        let err = expand_with("${MISSING}/x", lookup).unwrap_err().to_string();
+        assert!(err.contains("MISSING"), "{err}");
+        assert!(expand_with("${HOME", lookup).is_err());
+    }
+
+    #[test]
+    fn commands_fail_on_unset_variables() {
+        let command = CommandSpec::new("server").push_arg("${PATINA_TEST_SURELY_UNSET_VARIABLE}");
+        assert!(command.to_command().is_err());
+        assert!(CommandSpec::new("server")
+            .push_arg("$$1")
+            .to_command()
+            .is_ok());
+    }
+
+    #[test]
+    fn tools_convert_to_definitions() {
+        let tool: Tool = serde_json::from_value(serde_json::json!({
+            "name": "search",
+            "description": "Search the docs",
+            "inputSchema": { "type": "object", "properties": { "query": { "type": "string" } } }
+        }))
+        .expect("tool");
+        let definition = ToolDefinition::from(tool);
+        assert_eq!(definition.name, "search");
+        assert_eq!(definition.description, "Search the docs");
+        assert_eq!(
+            definition.parameters["properties"]["query"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn transports_serialize_with_a_type_tag() {
+        let endpoint = McpEndpoint::sse(
+            "remote",
+            AuthMode::ServerManaged,
+            "https://tools.example.com/sse",
+            HashMap::new(),
+        );
+        let json = serde_json::to_value(&endpoint).expect("serialize");
+        assert_eq!(json["transport"]["type"], "sse");
+        assert_eq!(json["transport"]["url"], "https://tools.example.com/sse");
+
+        let local: McpEndpoint = serde_json::from_value(serde_json::json!({
+            "id": "local",
+            "mode": "ServerManaged",
+            "transport": { "type": "child_process", "program": "mcp-server", "args": ["--stdio"] }
+        }))
+        .expect("deserialize");
+        let TransportSpec::ChildProcess(command) = local.transport else {
+            panic!("expected a child process");
+        };
+        assert_eq!(command.args, ["--stdio"]);
+    }
+
+    #[test]
+    fn tool_timeouts_default_and_can_be_set_per_endpoint() {
+        let endpoint: McpEndpoint = serde_json::from_value(serde_json::json!({
+            "id": "slow",
+            "mode": "ServerManaged",
+            "transport": { "type": "child_process", "program": "mcp-server" }
+        }))
+        .expect("deserialize");
+        assert_eq!(endpoint.tool_timeout(), DEFAULT_TOOL_TIMEOUT);
+
+        let endpoint = endpoint.with_tool_timeout(Duration::from_secs(120));
+        assert_eq!(endpoint.tool_timeout(), Duration::from_secs(120));
+        let json = serde_json::to_value(&endpoint).expect("serialize");
+        assert_eq!(json["tool_timeout_secs"], 120);
+
+        let err = validate_endpoints(&[endpoint.with_tool_timeout(Duration::ZERO)]).unwrap_err();
+        assert!(err.to_string().contains("tool timeout of zero"), "{err}");
+    }
+
+    #[test]
+    fn registered_clients_reconnect_when_their_endpoint_asks() {
+        let endpoint: McpEndpoint = serde_json::from_value(serde_json::json!({
+            "id": "flaky",
+            "mode": "ServerManaged",
+            "transport": { "type": "child_process", "program": "mcp-server" },
+            "reconnect": true
+        }))
+        .expect("deserialize");
+        assert_eq!(
+            endpoint.reconnect_policy(),
+            Some(ReconnectPolicy::default())
+        );
+
+        let auth = AuthCoordinator::with_secrets(Arc::new(MemorySecrets::default()));
+        let mut registry = McpRegistry::new(auth);
+        let (client, _events) = registry.add(endpoint.clone());
+        assert_eq!(client.reconnect, Some(ReconnectPolicy::default()));
+        let (client, _events) = registry.add(endpoint.with_reconnect(false));
+        assert_eq!(client.reconnect, None);
+    }
+
+    #[test]
+    fn reconnect_delays_double_up_to_the_cap() {
+        let policy = ReconnectPolicy {
+            max_attempts: 6,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        let delays: Vec<u128> = (1..=5).map(|n| policy.delay(n).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+    }
+
+    #[derive(Default)]
+    struct MemorySecrets(parking_lot::Mutex<HashMap<String, String>>);
+
+    impl crate::secrets::SecretStore for MemorySecrets {
+        fn store(&self, key: &str, secret: &str) -> Result<()> {
+            self.0.lock().insert(key.to_owned(), secret.to_owned());
+            Ok(())
+        }
+
+        fn load(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.0.lock().get(key).cloned())
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnect_reports_each_attempt_and_gives_up() {
+        let endpoint = McpEndpoint::child_process(
+            "gone",
+            AuthMode::ServerManaged,
+            CommandSpec::new("patina-test-no-such-mcp-server"),
+        );
+        let auth = AuthCoordinator::with_secrets(Arc::new(MemorySecrets::default()));
+        let (client, mut events) = McpClient::new(endpoint, auth);
+        let policy = ReconnectPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        client
+            .with_auto_reconnect(policy)
+            .reconnect_with(
+                policy,
+                Some("transport closed".into()),
+                CancellationToken::new(),
+            )
+            .await;
+
+        let mut attempts = Vec::new();
+        let reason = loop {
+            match events.recv().await.expect("event") {
+                McpEvent::Reconnecting { attempt, .. } => attempts.push(attempt),
+                McpEvent::Disconnected { reason, .. } => break reason.expect("reason"),
+                other => panic!("unexpected event: {other:?}"),
+            }
+        };
+        assert_eq!(attempts, [1, 2]);
+        assert!(
+            reason.starts_with("gave up after 2 reconnect attempts"),
+            "{reason}"
+        );
+        assert!(
+            reason.contains("patina-test-no-such-mcp-server") || reason.contains("spawn"),
+            "{reason}"
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnecting_stops_a_reconnect_waiting_out_its_backoff() {
+        let endpoint = McpEndpoint::child_process(
+            "gone",
+            AuthMode::ServerManaged,
+            CommandSpec::new("patina-test-no-such-mcp-server"),
+        );
+        let auth = AuthCoordinator::with_secrets(Arc::new(MemorySecrets::default()));
+        let (client, mut events) = McpClient::new(endpoint, auth);
+        let policy = ReconnectPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(600),
+            max_delay: Duration::from_secs(600),
+        };
+        let client = client.with_auto_reconnect(policy);
+        let cancel = client.reconnect_cancel.lock().clone();
+        let reconnect = tokio::spawn(client.clone().reconnect_with(policy, None, cancel));
+
+        assert!(matches!(
+            events.recv().await,
+            Some(McpEvent::Reconnecting { attempt: 1, .. })
+        ));
+        client.disconnect().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), reconnect)
+            .await
+            .expect("the reconnect stops without waiting out its delay")
+            .unwrap();
+        match events.recv().await {
+            Some(McpEvent::Disconnected { reason, .. }) => {
+                assert_eq!(reason.as_deref(), Some("cancelled"))
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(events.try_recv().is_err());
+        assert!(!client.reconnect_cancel.lock().is_cancelled());
+    }
+
+    /// A stdio MCP server that completes the handshake but never answers a tool call.
+    #[cfg(unix)]
+    fn silent_tool_server(timeout: Duration) -> McpEndpoint {
+        let script = r#"while IFS= read -r line; do
+  case "$line" in
+    *'"method":"initialize"'*)
+      id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"silent","version":"1"}}}\n' "$id"
+      ;;
+  esac
+done"#;
+        McpEndpoint::child_process(
+            "silent",
+            AuthMode::ServerManaged,
+            CommandSpec::new("sh").with_args(["-c", script]),
+        )
+        .with_tool_timeout(timeout)
+    }
+
+    #[cfg(unix)]
+    async fn connected(endpoint: McpEndpoint) -> (McpClient, UnboundedReceiver<McpEvent>) {
+        let auth = AuthCoordinator::with_secrets(Arc::new(MemorySecrets::default()));
+        let (client, mut events) = McpClient::new(endpoint, auth);
+        client.handshake().await.expect("handshake");
+        assert!(matches!(
+            events.recv().await,
+            Some(McpEvent::Connected { .. })
+        ));
+        (client, events)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_tool_that_does_not_answer_times_out() {
+        let (client, mut events) = connected(silent_tool_server(Duration::from_secs(1))).await;
+        let started = std::time::Instant::now();
+        let err = client
+            .call_tool_cancellable("slow", None, &CancellationToken::new())
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert!(
+            err.to_string().contains("did not answer within 1s"),
+            "{err}"
+        );
+        match events.recv().await {
+            Some(McpEvent::ToolFailed { tool, error, .. }) => {
+                assert_eq!(tool, "slow");
+                assert!(error.contains("did not answer"), "{error}");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        client.disconnect().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cancelling_a_tool_call_reports_and_returns_the_failure() {
+        let (client, mut events) = connected(silent_tool_server(Duration::from_secs(600))).await;
+        let cancel = CancellationToken::new();
+        let call = {
+            let client = client.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                client
+                    .call_tool_cancellable("slow", Some(serde_json::json!({ "n": 1 })), &cancel)
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+        let err = tokio::time::timeout(Duration::from_secs(5), call)
+            .await
+            .expect("cancelling ends the call")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "call to tool 'slow' was cancelled");
+        match events.recv().await {
+            Some(McpEvent::ToolFailed {
+                tool,
+                arguments,
+                error,
+                ..
+            }) => {
+                assert_eq!(tool, "slow");
+                assert_eq!(arguments, serde_json::json!({ "n": 1 }));
+                assert_eq!(error, "call to tool 'slow' was cancelled");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        client.disconnect().await.unwrap();
+    }
 }
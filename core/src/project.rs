@@ -1,3 +1,4 @@
+use crate::mcp::{validate_endpoints, McpEndpoint};
 use crate::store::TranscriptStore;
 use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
@@ -5,10 +6,14 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::{self, Read, Seek, Write};
 use std::path::{Component, Path, PathBuf};
-use walkdir::WalkDir;
+use uuid::Uuid;
+use walkdir::{DirEntry, WalkDir};
 use zip::write::FileOptions;
 use zip::{CompressionMethod, ZipArchive, ZipWriter};
 
+/// Path components never exported or imported, wherever they appear.
+const ALWAYS_IGNORED: &[&str] = &["secrets"];
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProjectPaths {
     pub root: PathBuf,
@@ -44,13 +49,31 @@ struct ProjectManifest {
     version: u32,
     name: String,
     created_utc: DateTime<Utc>,
+    /// Extra path component names left out of exported archives, e.g. `drafts`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    export_ignore: Vec<String>,
     paths: ProjectManifestPaths,
 }
 
+/// The `[mcp]` table of the manifest: the MCP servers the project uses. Read on its
+/// own, so a broken server entry does not keep the project from opening.
+#[derive(Debug, Default, Deserialize)]
+struct ProjectMcpManifest {
+    #[serde(default)]
+    mcp: ProjectMcpSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProjectMcpSection {
+    #[serde(default)]
+    endpoints: Vec<McpEndpoint>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ProjectHandle {
     manifest: ProjectManifest,
     paths: ProjectPaths,
+    read_only: bool,
 }
 
 impl ProjectHandle {
@@ -109,6 +132,7 @@ impl ProjectHandle {
             version: 1,
             name: manifest_name.clone(),
             created_utc: Utc::now(),
+            export_ignore: Vec::new(),
             paths: ProjectManifestPaths {
                 internal: ".patina".to_string(),
                 conversations: ".patina/conversations".to_string(),
@@ -136,7 +160,11 @@ impl ProjectHandle {
             .with_context(|| format!("failed to write manifest at {}", pat_path.display()))?;
 
         let paths = ProjectPaths::new(root.clone(), pat_path, internal_dir, conversations_dir);
-        Ok(Self { manifest, paths })
+        Ok(Self {
+            manifest,
+            paths,
+            read_only: false,
+        })
     }
 
     pub fn open(from: &Path) -> Result<Self> {
@@ -194,31 +222,55 @@ impl ProjectHandle {
             )
         })?;
 
+        let read_only = !probe_writable(&internal);
+        if read_only {
+            tracing::warn!(
+                path = %internal.display(),
+                "project data directory is not writable; opening read-only"
+            );
+        }
         let paths = ProjectPaths::new(root.clone(), pat_file, internal, conversations);
 
-        Ok(Self { manifest, paths })
+        Ok(Self {
+            manifest,
+            paths,
+            read_only,
+        })
     }
 
+    /// Unpack an exported project into the empty directory `into_dir`.
+    ///
+    /// Entries under an always-ignored directory such as `secrets/` are skipped, so an
+    /// archive cannot plant credentials. Archives with absolute entries or entries that
+    /// climb out of `into_dir` are rejected before anything is written.
     pub fn import_zip<R: Read + Seek>(reader: R, into_dir: &Path) -> Result<Self> {
-        if into_dir.exists() {
-            if !into_dir.is_dir() {
-                return Err(anyhow!("import destination is not a directory"));
-            }
-            if into_dir.read_dir()?.next().is_some() {
-                return Err(anyhow!("import destination must be empty"));
-            }
-        } else {
-            fs::create_dir_all(into_dir).with_context(|| {
-                format!(
-                    "failed to create destination directory at {}",
-                    into_dir.display()
-                )
-            })?;
-        }
+        Self::import_zip_with_progress(reader, into_dir, |_, _| {})
+    }
+
+    /// [`ProjectHandle::import_zip`], calling `progress` with the number of archive
+    /// entries handled so far and the total: once before the first entry and again
+    /// after each one is unpacked, so the last call is always `(total, total)`.
+    pub fn import_zip_with_progress<R: Read + Seek>(
+        reader: R,
+        into_dir: &Path,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<Self> {
+        prepare_empty_destination(into_dir, "import")?;
 
         let mut archive = ZipArchive::new(reader)?;
         let mut root_component: Option<PathBuf> = None;
+        let total = archive.len() as u64;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if Path::new(file.name()).has_root() || file.enclosed_name().is_none() {
+                return Err(anyhow!(
+                    "archive entry '{}' points outside the import directory",
+                    file.name()
+                ));
+            }
+        }
 
+        progress(0, total);
         for i in 0..archive.len() {
             let mut file = archive.by_index(i)?;
             let name = file.mangled_name();
@@ -237,16 +289,27 @@ impl ProjectHandle {
                 }
             }
 
-            let out_path = into_dir.join(&name);
-            if file.is_dir() {
-                fs::create_dir_all(&out_path)?;
+            if has_ignored_component(&name, &[]) {
+                tracing::warn!(entry = %name.display(), "skipping ignored archive entry");
             } else {
-                if let Some(parent) = out_path.parent() {
-                    fs::create_dir_all(parent)?;
+                let out_path = into_dir.join(&name);
+                if !resolves_within(&out_path, into_dir)? {
+                    return Err(anyhow!(
+                        "archive entry '{}' points outside the import directory",
+                        name.display()
+                    ));
+                }
+                if file.is_dir() {
+                    fs::create_dir_all(&out_path)?;
+                } else {
+                    if let Some(parent) = out_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let mut output = fs::File::create(&out_path)?;
+                    io::copy(&mut file, &mut output)?;
                 }
-                let mut output = fs::File::create(&out_path)?;
-                io::copy(&mut file, &mut output)?;
             }
+            progress(i as u64 + 1, total);
         }
 
         let root_name =
@@ -255,7 +318,20 @@ impl ProjectHandle {
         Self::open(&project_root)
     }
 
+    /// Zip the project directory, leaving out stored secrets and any path with a
+    /// component listed under `export_ignore` in the manifest.
     pub fn export_zip<W: Write + Seek>(&self, writer: W) -> Result<()> {
+        self.export_zip_with_progress(writer, |_, _| {})
+    }
+
+    /// [`ProjectHandle::export_zip`], calling `progress` with the number of files and
+    /// directories written so far and the total: once before the first entry and again
+    /// after each one is written, so the last call is always `(total, total)`.
+    pub fn export_zip_with_progress<W: Write + Seek>(
+        &self,
+        writer: W,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<()> {
         let mut zip = ZipWriter::new(writer);
         let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
         let root_name = self
@@ -268,12 +344,11 @@ impl ProjectHandle {
 
         zip.add_directory(format!("{}/", root_name), options)?;
 
-        for entry in WalkDir::new(&self.paths.root).into_iter() {
-            let entry = entry?;
+        let entries = self.exported_entries(None)?;
+        let total = entries.len() as u64;
+        progress(0, total);
+        for (index, entry) in entries.iter().enumerate() {
             let path = entry.path();
-            if path == self.paths.root {
-                continue;
-            }
             let relative = path.strip_prefix(&self.paths.root)?;
             let mut zip_path = PathBuf::from(&root_name);
             if !relative.as_os_str().is_empty() {
@@ -291,12 +366,102 @@ impl ProjectHandle {
                 zip.start_file(zip_path.to_string_lossy().replace("\\", "/"), options)?;
                 io::copy(&mut file, &mut zip)?;
             }
+            progress(index as u64 + 1, total);
         }
 
         zip.finish()?;
         Ok(())
     }
 
+    /// Copy the project tree into the empty directory `dest`, leaving out the same
+    /// paths as [`ProjectHandle::export_zip`].
+    ///
+    /// Unlike the archive, `dest` itself becomes the project root, so it can be
+    /// opened, diffed or synced directly.
+    pub fn export_to_dir(&self, dest: &Path) -> Result<()> {
+        prepare_empty_destination(dest, "export")?;
+        for entry in self.exported_entries(Some(dest))? {
+            let path = entry.path();
+            let target = dest.join(path.strip_prefix(&self.paths.root)?);
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target)?;
+            } else {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(path, &target).with_context(|| {
+                    format!("failed to copy {} to {}", path.display(), target.display())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Everything under the project root that exports include, parents before children.
+    ///
+    /// Stored secrets, `export_ignore` entries and `skip`, an export destination that
+    /// may lie inside the project, are left out.
+    fn exported_entries(&self, skip: Option<&Path>) -> Result<Vec<DirEntry>> {
+        let root = &self.paths.root;
+        let ignored = &self.manifest.export_ignore;
+        WalkDir::new(root)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| {
+                let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                !has_ignored_component(relative, ignored)
+                    && !skip.is_some_and(|skip| entry.path().starts_with(skip))
+            })
+            .map(|entry| entry.map_err(Into::into))
+            .collect()
+    }
+
+    /// Copy this project into a fresh project at `new_root` named `new_name`.
+    ///
+    /// The destination is validated like [`ProjectHandle::create`]. Stored secrets are
+    /// only copied when `include_secrets` is set.
+    pub fn save_as(&self, new_root: &Path, new_name: &str, include_secrets: bool) -> Result<Self> {
+        let copy = Self::create(new_root, new_name)?;
+        let secrets = self.paths.internal.join("secrets");
+
+        // The copy may live inside the source tree; never recurse into it.
+        let walker = WalkDir::new(&self.paths.root)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| !entry.path().starts_with(&copy.paths.root));
+        for entry in walker {
+            let entry = entry?;
+            let path = entry.path();
+            if path == self.paths.pat_file {
+                continue;
+            }
+            if !include_secrets && path.starts_with(&secrets) {
+                continue;
+            }
+
+            let target = if let Ok(relative) = path.strip_prefix(&self.paths.conversations) {
+                copy.paths.conversations.join(relative)
+            } else if let Ok(relative) = path.strip_prefix(&self.paths.internal) {
+                copy.paths.internal.join(relative)
+            } else {
+                copy.paths.root.join(path.strip_prefix(&self.paths.root)?)
+            };
+
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target)?;
+            } else {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(path, &target).with_context(|| {
+                    format!("failed to copy {} to {}", path.display(), target.display())
+                })?;
+            }
+        }
+
+        Self::open(&copy.paths.root)
+    }
+
     pub fn name(&self) -> &str {
         &self.manifest.name
     }
@@ -309,6 +474,18 @@ impl ProjectHandle {
         &self.paths
     }
 
+    /// Whether the project's `.patina` directory rejected a probe write when opened, or
+    /// the project was opened with [`Self::with_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Treat the project as read-only whether or not its files can be written.
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
     pub fn transcript_store(&self) -> TranscriptStore {
         TranscriptStore::new(self.paths.internal.clone())
     }
@@ -316,6 +493,78 @@ impl ProjectHandle {
     pub fn metadata_path(&self) -> &Path {
         &self.paths.pat_file
     }
+
+    /// The MCP servers listed in the manifest, read from disk so edits made since the
+    /// project opened are picked up. Servers without a root of their own get the
+    /// project directory.
+    pub fn mcp_endpoints(&self) -> Result<Vec<McpEndpoint>> {
+        let pat_file = &self.paths.pat_file;
+        let contents = fs::read_to_string(pat_file).with_context(|| {
+            format!("failed to read project manifest at {}", pat_file.display())
+        })?;
+        let manifest: ProjectMcpManifest = toml::from_str(&contents)
+            .with_context(|| format!("invalid MCP servers in {}", pat_file.display()))?;
+        let endpoints = manifest.mcp.endpoints;
+        validate_endpoints(&endpoints)
+            .with_context(|| format!("invalid MCP servers in {}", pat_file.display()))?;
+        Ok(endpoints
+            .into_iter()
+            .map(|endpoint| match endpoint.root {
+                Some(_) => endpoint,
+                None => endpoint.with_root(&self.paths.root),
+            })
+            .collect())
+    }
+}
+
+fn probe_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".write-probe-{}", Uuid::new_v4()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether any component of `relative` is always ignored or listed in `extra`.
+fn has_ignored_component(relative: &Path, extra: &[String]) -> bool {
+    relative.components().any(|component| match component {
+        Component::Normal(name) => name.to_str().is_some_and(|name| {
+            ALWAYS_IGNORED.contains(&name) || extra.iter().any(|ignored| ignored == name)
+        }),
+        _ => false,
+    })
+}
+
+/// Check that `dir` is an empty directory, creating it if it does not exist yet.
+fn prepare_empty_destination(dir: &Path, purpose: &str) -> Result<()> {
+    if dir.exists() {
+        if !dir.is_dir() {
+            return Err(anyhow!("{purpose} destination is not a directory"));
+        }
+        if dir.read_dir()?.next().is_some() {
+            return Err(anyhow!("{purpose} destination must be empty"));
+        }
+        return Ok(());
+    }
+    fs::create_dir_all(dir).with_context(|| {
+        format!(
+            "failed to create destination directory at {}",
+            dir.display()
+        )
+    })
+}
+
+/// Whether `path` ends up under `root` once the part of it that already exists is
+/// resolved, symlinks included.
+fn resolves_within(path: &Path, root: &Path) -> Result<bool> {
+    let root = root.canonicalize()?;
+    let Some(existing) = path.ancestors().find(|ancestor| ancestor.exists()) else {
+        return Ok(false);
+    };
+    Ok(existing.canonicalize()?.starts_with(root))
 }
 
 fn normalize_relative_path(root: &Path, relative: &str) -> Result<PathBuf> {
@@ -0,0 +1,172 @@
+use crate::store::TranscriptStore;
+use anyhow::Result;
+#[cfg(feature = "keyring")]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Where access tokens are kept, keyed by endpoint id.
+pub trait SecretStore: Send + Sync {
+    fn store(&self, key: &str, secret: &str) -> Result<()>;
+    fn load(&self, key: &str) -> Result<Option<String>>;
+}
+
+/// Service name secrets are filed under in the OS keyring.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "patina";
+
+/// The OS keyring: Keychain on macOS, Credential Manager on Windows and the Secret
+/// Service elsewhere.
+#[cfg(feature = "keyring")]
+#[derive(Debug, Clone)]
+pub struct KeyringSecretStore {
+    project: String,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringSecretStore {
+    /// Secrets of the project whose internal directory is `root`, kept apart from
+    /// other projects' secrets for endpoints with the same id.
+    pub fn for_project(root: &Path) -> Self {
+        Self {
+            project: root.display().to_string(),
+        }
+    }
+
+    /// The keyring account `key` is filed under.
+    pub fn account(&self, key: &str) -> String {
+        format!("{key}@{}", self.project)
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry> {
+        Ok(keyring::Entry::new(KEYRING_SERVICE, &self.account(key))?)
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl SecretStore for KeyringSecretStore {
+    fn store(&self, key: &str, secret: &str) -> Result<()> {
+        self.entry(key)?.set_password(secret)?;
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>> {
+        match self.entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Plain files under the project's `secrets/` directory, for builds without the
+/// `keyring` feature. These are never exported with the project.
+#[derive(Clone)]
+pub struct FileSecretStore {
+    store: TranscriptStore,
+}
+
+impl FileSecretStore {
+    pub fn new(store: TranscriptStore) -> Self {
+        Self { store }
+    }
+
+    /// Delete the file holding `key`, if there is one.
+    pub fn remove(&self, key: &str) -> Result<()> {
+        self.store.delete_secret(key)
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn store(&self, key: &str, secret: &str) -> Result<()> {
+        self.store.persist_secret(key, secret)
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>> {
+        self.store.load_secret(key)
+    }
+}
+
+/// `primary` until it fails, then `fallback` for the rest of the session: a keyring
+/// build on a machine without a Secret Service, or with a locked keychain, keeps its
+/// tokens in the project's secret files instead of losing them.
+pub struct FallbackSecretStore {
+    primary: Arc<dyn SecretStore>,
+    fallback: FileSecretStore,
+    failed: AtomicBool,
+}
+
+impl FallbackSecretStore {
+    pub fn new(primary: Arc<dyn SecretStore>, fallback: FileSecretStore) -> Self {
+        Self {
+            primary,
+            fallback,
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether `primary` failed and secrets now go to the files.
+    pub fn is_falling_back(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    fn primary_failed(&self, err: &anyhow::Error) {
+        if !self.failed.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                error = %err,
+                "secret store unavailable; keeping access tokens in the project's secret files"
+            );
+        }
+    }
+}
+
+impl SecretStore for FallbackSecretStore {
+    fn store(&self, key: &str, secret: &str) -> Result<()> {
+        if !self.is_falling_back() {
+            match self.primary.store(key, secret) {
+                Ok(()) => return Ok(()),
+                Err(err) => self.primary_failed(&err),
+            }
+        }
+        self.fallback.store(key, secret)
+    }
+
+    /// Secrets missing from `primary` are looked up in the files too, where an earlier
+    /// session or an older version may have put them. One found there is moved into
+    /// `primary` and its file deleted.
+    fn load(&self, key: &str) -> Result<Option<String>> {
+        if !self.is_falling_back() {
+            match self.primary.load(key) {
+                Ok(Some(secret)) => return Ok(Some(secret)),
+                Ok(None) => {}
+                Err(err) => self.primary_failed(&err),
+            }
+        }
+        let secret = self.fallback.load(key)?;
+        if let Some(secret) = &secret {
+            if !self.is_falling_back() {
+                match self.primary.store(key, secret) {
+                    Ok(()) => self.fallback.remove(key)?,
+                    Err(err) => self.primary_failed(&err),
+                }
+            }
+        }
+        Ok(secret)
+    }
+}
+
+/// The keyring when built with it, falling back to the project's secret files when it
+/// is unavailable; otherwise just the files.
+pub fn default_secret_store(store: &TranscriptStore) -> Arc<dyn SecretStore> {
+    #[cfg(feature = "keyring")]
+    {
+        Arc::new(FallbackSecretStore::new(
+            Arc::new(KeyringSecretStore::for_project(store.root())),
+            FileSecretStore::new(store.clone()),
+        ))
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        Arc::new(FileSecretStore::new(store.clone()))
+    }
+}
@@ -1,13 +1,24 @@
-use crate::llm::{LlmDriver, LlmStatus, StreamChunk};
+use crate::attachment::ImageAttachment;
+use crate::auth::AuthCoordinator;
+use crate::clock::{Clock, SystemClock};
+use crate::llm::{LlmDriver, LlmProviderKind, LlmStatus, ModelUsage, StreamChunk, ToolDefinition};
+use crate::mcp::{
+    McpClient, McpEndpoint, McpEvent, McpRegistry, SamplingModel, TOKEN_REFRESH_INTERVAL,
+};
 use crate::project::ProjectHandle;
 use crate::store::TranscriptStore;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use chrono::{DateTime, Utc};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -30,6 +41,9 @@ pub enum ToolCallStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: Uuid,
+    /// The provider's id for the call, needed to send its result back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>,
     pub name: String,
     #[serde(default)]
     pub arguments: Value,
@@ -42,6 +56,7 @@ impl ToolCall {
     pub fn new(name: impl Into<String>, arguments: serde_json::Value) -> Self {
         Self {
             id: Uuid::new_v4(),
+            call_id: None,
             name: name.into(),
             arguments,
             status: ToolCallStatus::Pending,
@@ -58,18 +73,83 @@ pub struct ChatMessage {
     pub created_at: DateTime<Utc>,
     #[serde(default)]
     pub tool_calls: Vec<ToolCall>,
+    /// Every generated variant of this turn once it has been regenerated; empty otherwise.
+    ///
+    /// `content` always mirrors the selected variant, so readers that ignore these
+    /// fields still see the reply the user picked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub selected_alternative: usize,
+    /// The reply was stopped before the provider finished it.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub truncated: bool,
+    /// Tokens the provider reported for generating this reply; for a regenerated reply,
+    /// those of its latest variant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ModelUsage>,
+    /// The model asked for this reply; `None` for replies saved before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Images sent after `content` as further parts of a user message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageAttachment>,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 impl ChatMessage {
     pub fn new(role: MessageRole, content: impl Into<String>) -> Self {
+        Self::new_at(role, content, &SystemClock)
+    }
+
+    pub fn new_at(role: MessageRole, content: impl Into<String>, clock: &dyn Clock) -> Self {
         Self {
             id: Uuid::new_v4(),
             role,
             content: content.into(),
-            created_at: Utc::now(),
+            created_at: clock.now(),
             tool_calls: Vec::new(),
+            alternatives: Vec::new(),
+            selected_alternative: 0,
+            truncated: false,
+            usage: None,
+            model: None,
+            images: Vec::new(),
         }
     }
+
+    /// Number of generated variants, counting a never-regenerated reply as one.
+    pub fn alternative_count(&self) -> usize {
+        self.alternatives.len().max(1)
+    }
+
+    /// Add a new variant and select it.
+    pub fn push_alternative(&mut self, content: impl Into<String>) {
+        if self.alternatives.is_empty() {
+            self.alternatives.push(self.content.clone());
+        }
+        self.alternatives.push(content.into());
+        self.selected_alternative = self.alternatives.len() - 1;
+        self.content = self.alternatives[self.selected_alternative].clone();
+        self.truncated = false;
+    }
+
+    /// Select the variant at `index`; returns `false` if there is no such variant.
+    pub fn select_alternative(&mut self, index: usize) -> bool {
+        let Some(content) = self.alternatives.get(index) else {
+            return index == 0 && self.alternatives.is_empty();
+        };
+        self.content = content.clone();
+        self.selected_alternative = index;
+        true
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,32 +159,179 @@ pub struct Conversation {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub settings: ConversationSettings,
+    /// Instructions sent ahead of the history with every request; never stored as a message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// [`Conversation::usage_by_model`] as of the last change to the messages; see
+    /// [`Conversation::usage`].
+    #[serde(skip)]
+    usage: BTreeMap<Option<String>, ModelUsage>,
 }
 
-impl Conversation {
-    pub fn new() -> Self {
-        let now = Utc::now();
+/// Chat parameters used for a request once defaults and overrides are combined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatParameters {
+    pub model: String,
+    pub temperature: f32,
+    pub max_request_messages: usize,
+}
+
+/// Per-conversation overrides of the global chat parameters; `None` inherits the default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_request_messages: Option<usize>,
+    /// Every parameter is frozen and edits are refused until the conversation is unlocked.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
+    /// The overrides in place before the lock, restored when it is dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before_lock: Option<Box<ConversationSettings>>,
+}
+
+impl ConversationSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Settings that pin every parameter to `params` and lock them.
+    pub fn locked_to(params: &ChatParameters) -> Self {
         Self {
-            id: Uuid::new_v4(),
-            title: "New chat".to_string(),
-            created_at: now,
-            updated_at: now,
-            messages: Vec::new(),
+            model: Some(params.model.clone()),
+            temperature: Some(params.temperature),
+            max_request_messages: Some(params.max_request_messages),
+            locked: true,
+            before_lock: None,
+        }
+    }
+
+    pub fn resolve(&self, defaults: &ChatParameters) -> ChatParameters {
+        ChatParameters {
+            model: self.model.clone().unwrap_or_else(|| defaults.model.clone()),
+            temperature: self.temperature.unwrap_or(defaults.temperature),
+            max_request_messages: self
+                .max_request_messages
+                .unwrap_or(defaults.max_request_messages),
         }
     }
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::new_at(&SystemClock)
+    }
+
+    pub fn new_at(clock: &dyn Clock) -> Self {
+        Self::with_id_at(Uuid::new_v4(), "New chat", clock)
+    }
 
     pub fn with_id(id: Uuid, title: impl Into<String>) -> Self {
-        let now = Utc::now();
+        Self::with_id_at(id, title, &SystemClock)
+    }
+
+    pub fn with_id_at(id: Uuid, title: impl Into<String>, clock: &dyn Clock) -> Self {
+        let now = clock.now();
         Self {
             id,
             title: title.into(),
             created_at: now,
             updated_at: now,
             messages: Vec::new(),
+            settings: ConversationSettings::default(),
+            system_prompt: None,
+            usage: BTreeMap::new(),
+        }
+    }
+
+    /// The system prompt as the message that leads each request, if one is set.
+    pub fn system_message(&self) -> Option<ChatMessage> {
+        self.system_prompt
+            .as_deref()
+            .filter(|prompt| !prompt.trim().is_empty())
+            .map(|prompt| ChatMessage::new(MessageRole::System, prompt))
+    }
+
+    pub fn add_message(&mut self, message: ChatMessage) -> bool {
+        self.add_message_at(message, &SystemClock)
+    }
+
+    /// The snippet title for the current first user message, or the default title if none.
+    pub fn content_title(&self) -> String {
+        self.messages
+            .iter()
+            .find(|message| message.role == MessageRole::User)
+            .map(|message| snippet(&message.content))
+            .unwrap_or_else(|| "New chat".to_string())
+    }
+
+    /// The final assistant reply, if the conversation ends with a completed turn.
+    pub fn last_completed_reply(&self) -> Option<&ChatMessage> {
+        self.messages
+            .last()
+            .filter(|message| message.role == MessageRole::Assistant)
+    }
+
+    /// Tokens reported for the replies, summed per model. Replies that did not record
+    /// their model count towards the chat's model override, or `None` without one.
+    pub fn usage_by_model(&self) -> BTreeMap<Option<String>, ModelUsage> {
+        let mut totals: BTreeMap<Option<String>, ModelUsage> = BTreeMap::new();
+        for message in &self.messages {
+            let Some(usage) = &message.usage else {
+                continue;
+            };
+            let model = message
+                .model
+                .clone()
+                .or_else(|| self.settings.model.clone());
+            let total = totals.entry(model).or_default();
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+        }
+        totals
+    }
+
+    /// The totals of [`Conversation::usage_by_model`] without walking the messages. For a
+    /// conversation whose messages are not loaded, the totals stored with its metadata.
+    pub fn usage(&self) -> &BTreeMap<Option<String>, ModelUsage> {
+        &self.usage
+    }
+
+    /// Recompute [`Conversation::usage`] after the messages were replaced or rewritten.
+    pub fn refresh_usage(&mut self) {
+        self.usage = self.usage_by_model();
+    }
+
+    pub(crate) fn set_usage(&mut self, usage: BTreeMap<Option<String>, ModelUsage>) {
+        self.usage = usage;
+    }
+
+    /// The first question and its answer, while that answer is the only reply.
+    fn first_exchange(&self) -> Option<Vec<ChatMessage>> {
+        let reply = self
+            .last_completed_reply()
+            .filter(|reply| !reply.truncated && !reply.content.trim().is_empty())?;
+        let replies = self
+            .messages
+            .iter()
+            .filter(|message| message.role == MessageRole::Assistant)
+            .count();
+        if replies != 1 {
+            return None;
         }
+        let question = self
+            .messages
+            .iter()
+            .find(|message| message.role == MessageRole::User)?;
+        Some(vec![question.clone(), reply.clone()])
     }
 
-    pub fn add_message(&mut self, mut message: ChatMessage) -> bool {
+    pub fn add_message_at(&mut self, mut message: ChatMessage, clock: &dyn Clock) -> bool {
         let mut title_changed = false;
         if self.messages.is_empty() && message.role == MessageRole::User {
             self.title = snippet(&message.content);
@@ -113,8 +340,12 @@ impl Conversation {
         if message.tool_calls.is_empty() {
             message.tool_calls = Vec::new();
         }
+        let counted = message.usage.is_some();
         self.messages.push(message);
-        self.updated_at = Utc::now();
+        if counted {
+            self.refresh_usage();
+        }
+        self.updated_at = clock.now();
         title_changed
     }
 }
@@ -131,20 +362,57 @@ pub struct ConversationSummary {
     pub title: String,
     pub updated_at: DateTime<Utc>,
     pub message_count: usize,
+    /// See [`Conversation::usage`]; empty for a chat saved before totals were stored,
+    /// until its messages are loaded.
+    pub usage: BTreeMap<Option<String>, ModelUsage>,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     inner: Arc<RwLock<InnerState>>,
     store: TranscriptStore,
-    llm: LlmDriver,
+    llm: Arc<RwLock<LlmDriver>>,
     project: ProjectHandle,
+    /// Lowercased message bodies for [`AppState::search_messages`], keyed by message id
+    /// and checked against a hash of the current content.
+    search_cache: Arc<Mutex<HashMap<Uuid, (u64, String)>>>,
+    /// Clients for the project's MCP servers; see [`AppState::connect_mcp_servers`].
+    mcp: Arc<Mutex<McpRegistry>>,
+    /// Events of every MCP client, merged; see [`AppState::take_mcp_events`].
+    mcp_events: mpsc::UnboundedSender<McpEvent>,
+    mcp_events_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<McpEvent>>>>,
+    /// The tasks passing each client's events on to `mcp_events`.
+    mcp_forwarders: Arc<Mutex<Vec<tokio::task::JoinHandle<()>>>>,
 }
 
 #[derive(Default)]
 struct InnerState {
     conversations: Vec<Conversation>,
     current_session: Option<Uuid>,
+    max_request_messages: usize,
+    max_tokens: Option<u32>,
+    top_p: Option<f32>,
+    /// Conversations with a reply currently being generated.
+    generating: HashSet<Uuid>,
+    /// Stops the streamed reply of a conversation; see [`AppState::stop_generating`].
+    cancellations: HashMap<Uuid, CancellationToken>,
+    /// Conversations whose messages are still only on disk, with their message count.
+    unloaded: HashMap<Uuid, usize>,
+    /// Ask the model for a title after the first exchange; see [`AppState::set_auto_title`].
+    auto_title: bool,
+    /// Conversations with a title request in flight.
+    titling: HashSet<Uuid>,
+    /// Usage of the replies finished since [`AppState::take_usage`] last ran.
+    finished_usage: Vec<(String, ModelUsage)>,
+}
+
+impl InnerState {
+    fn message_count(&self, conversation: &Conversation) -> usize {
+        self.unloaded
+            .get(&conversation.id)
+            .copied()
+            .unwrap_or(conversation.messages.len())
+    }
 }
 
 impl AppState {
@@ -154,23 +422,109 @@ impl AppState {
     }
 
     pub fn with_store(project: ProjectHandle, store: TranscriptStore, llm: LlmDriver) -> Self {
-        let conversations = store.load_conversations().unwrap_or_default();
+        let (conversations, counts): (Vec<_>, Vec<_>) =
+            store.load_conversation_headers().into_iter().unzip();
+        let unloaded = conversations.iter().map(|c| c.id).zip(counts).collect();
         let current_session = conversations.first().map(|c| c.id);
+        let mcp = McpRegistry::new(AuthCoordinator::new(store.clone()));
+        let (mcp_events, mcp_events_rx) = mpsc::unbounded_channel();
         Self {
             inner: Arc::new(RwLock::new(InnerState {
                 conversations,
                 current_session,
+                max_request_messages: 0,
+                max_tokens: None,
+                top_p: None,
+                generating: HashSet::new(),
+                cancellations: HashMap::new(),
+                unloaded,
+                auto_title: false,
+                titling: HashSet::new(),
+                finished_usage: Vec::new(),
             })),
             store,
-            llm,
+            llm: Arc::new(RwLock::new(llm)),
             project,
+            search_cache: Arc::default(),
+            mcp: Arc::new(Mutex::new(mcp)),
+            mcp_events,
+            mcp_events_rx: Arc::new(Mutex::new(Some(mcp_events_rx))),
+            mcp_forwarders: Arc::default(),
+        }
+    }
+
+    /// Refresh this project's MCP tokens before they expire when `enabled`, instead of
+    /// only when they are used. Starting the refresh needs a Tokio runtime.
+    pub fn with_background_refresh(self, enabled: bool) -> Self {
+        if enabled {
+            self.mcp
+                .lock()
+                .start_background_refresh(TOKEN_REFRESH_INTERVAL);
+        }
+        self
+    }
+
+    /// Read the messages of conversation `id` from disk unless they are already loaded.
+    ///
+    /// Conversations start out with only their metadata; their transcripts are read the
+    /// first time something needs the messages.
+    fn ensure_loaded(&self, id: Uuid) {
+        if self.inner.read().unloaded.contains_key(&id) {
+            Self::load_messages(&mut self.inner.write(), &self.store, id);
+        }
+    }
+
+    fn load_messages(inner: &mut InnerState, store: &TranscriptStore, id: Uuid) {
+        if inner.unloaded.remove(&id).is_none() {
+            return;
+        }
+        match store.load_conversation(id) {
+            Ok(loaded) => {
+                if let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) {
+                    conversation.messages = loaded.messages;
+                    conversation.refresh_usage();
+                }
+            }
+            Err(err) => {
+                tracing::warn!(conversation = %id, error = %err, "failed to load conversation");
+            }
+        }
+    }
+
+    /// Load every conversation that is still only on disk.
+    fn load_all(&self) {
+        let mut inner = self.inner.write();
+        let ids: Vec<Uuid> = inner.unloaded.keys().copied().collect();
+        for id in ids {
+            Self::load_messages(&mut inner, &self.store, id);
         }
     }
 
+    fn current_id(&self) -> Option<Uuid> {
+        let inner = self.inner.read();
+        inner
+            .current_session
+            .or_else(|| inner.conversations.first().map(|c| c.id))
+    }
+
     pub fn project(&self) -> &ProjectHandle {
         &self.project
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.project.is_read_only()
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            bail!(
+                "project '{}' is read-only; changes cannot be saved",
+                self.project.name()
+            );
+        }
+        Ok(())
+    }
+
     pub fn conversation_summaries(&self) -> Vec<ConversationSummary> {
         let inner = self.inner.read();
         inner
@@ -180,12 +534,61 @@ impl AppState {
                 id: c.id,
                 title: c.title.clone(),
                 updated_at: c.updated_at,
-                message_count: c.messages.len(),
+                message_count: inner.message_count(c),
+                usage: c.usage().clone(),
             })
             .collect()
     }
 
+    /// Conversations whose title or messages contain `query`, ignoring case, one per
+    /// conversation with its first matching message.
+    ///
+    /// A conversation matched only by its title is returned with its first message;
+    /// empty conversations are left to the title filter.
+    pub fn search_messages(&self, query: &str) -> Vec<(Uuid, ChatMessage)> {
+        let needle = query.trim().to_lowercase();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        self.load_all();
+        let inner = self.inner.read();
+        let mut cache = self.search_cache.lock();
+        // Rebuilt on every search so entries for deleted or edited messages do not pile up.
+        let mut previous = std::mem::take(&mut *cache);
+        let mut results = Vec::new();
+        for conversation in &inner.conversations {
+            let mut hit = None;
+            for message in &conversation.messages {
+                let mut hasher = DefaultHasher::new();
+                message.content.hash(&mut hasher);
+                let key = hasher.finish();
+                let lowered = previous
+                    .remove(&message.id)
+                    .filter(|(cached_key, _)| *cached_key == key)
+                    .map(|(_, lowered)| lowered)
+                    .unwrap_or_else(|| message.content.to_lowercase());
+                if hit.is_none() && lowered.contains(&needle) {
+                    hit = Some(message);
+                }
+                cache.insert(message.id, (key, lowered));
+            }
+            let hit = hit.or_else(|| {
+                conversation
+                    .title
+                    .to_lowercase()
+                    .contains(&needle)
+                    .then(|| conversation.messages.first())
+                    .flatten()
+            });
+            if let Some(message) = hit {
+                results.push((conversation.id, message.clone()));
+            }
+        }
+        results
+    }
+
     pub fn active_conversation(&self) -> Option<Conversation> {
+        self.ensure_loaded(self.current_id()?);
         let inner = self.inner.read();
         match inner.current_session {
             Some(id) => inner.conversations.iter().find(|c| c.id == id).cloned(),
@@ -197,6 +600,7 @@ impl AppState {
         let mut inner = self.inner.write();
         if inner.conversations.iter().any(|c| c.id == id) {
             inner.current_session = Some(id);
+            Self::load_messages(&mut inner, &self.store, id);
         }
     }
 
@@ -221,26 +625,39 @@ impl AppState {
         if content.trim().is_empty() {
             return Ok(());
         }
+        self.ensure_writable()?;
         let model = model.into();
 
         let message = ChatMessage::new(MessageRole::User, content.clone());
-        let conversation_id = {
-            let mut inner = self.inner.write();
-            let conversation = Self::ensure_conversation(&mut inner);
-            let title_changed = conversation.add_message(message.clone());
-            self.store.append_message(conversation.id, &message)?;
-            if title_changed {
-                self.store.persist_metadata(conversation)?;
-            }
-            conversation.id
-        };
+        let conversation_id = self.begin_user_turn(&message)?;
 
-        let history = self.conversation_history(conversation_id);
+        let history = self.request_history(conversation_id);
+        let (max_tokens, top_p) = self.sampling_limits();
         let response = self
-            .llm
-            .respond(&history, Some(model.as_str()), Some(temperature))
-            .await?;
-        let assistant_message = response.message;
+            .llm()
+            .respond(
+                &history,
+                Some(model.as_str()),
+                Some(temperature),
+                max_tokens,
+                top_p,
+            )
+            .await;
+        self.inner.write().generating.remove(&conversation_id);
+        let response = response?;
+        let usage = response
+            .usage
+            .clone()
+            .unwrap_or_else(|| ModelUsage::estimate(&history, &response.message.content));
+        self.inner
+            .write()
+            .finished_usage
+            .push((model.clone(), usage));
+        let assistant_message = ChatMessage {
+            usage: response.usage,
+            model: Some(model.clone()),
+            ..response.message
+        };
         {
             let mut inner = self.inner.write();
             if let Some(conversation) = inner
@@ -251,11 +668,12 @@ impl AppState {
                 let title_changed = conversation.add_message(assistant_message.clone());
                 self.store
                     .append_message(conversation.id, &assistant_message)?;
-                if title_changed {
+                if title_changed || assistant_message.usage.is_some() {
                     self.store.persist_metadata(conversation)?;
                 }
             }
         }
+        self.request_title(conversation_id, &model);
         Ok(())
     }
 
@@ -264,81 +682,266 @@ impl AppState {
         content: impl Into<String>,
         model: impl Into<String>,
         temperature: f32,
+    ) -> Result<(Uuid, mpsc::UnboundedReceiver<Result<StreamChunk>>)> {
+        self.send_user_message_with_images_streaming(content, Vec::new(), model, temperature)
+            .await
+    }
+
+    /// Like [`AppState::send_user_message_streaming`], with `images` sent after the text
+    /// for a vision model. Either may be empty, but not both.
+    pub async fn send_user_message_with_images_streaming(
+        &self,
+        content: impl Into<String>,
+        images: Vec<ImageAttachment>,
+        model: impl Into<String>,
+        temperature: f32,
     ) -> Result<(Uuid, mpsc::UnboundedReceiver<Result<StreamChunk>>)> {
         let content = content.into();
-        if content.trim().is_empty() {
+        if content.trim().is_empty() && images.is_empty() {
             let (tx, rx) = mpsc::unbounded_channel();
             let _ = tx.send(Ok(StreamChunk {
                 delta: String::new(),
                 done: true,
+                usage: None,
+                tool_calls: Vec::new(),
             }));
             return Ok((Uuid::new_v4(), rx));
         }
+        self.ensure_writable()?;
         let model = model.into();
 
-        let message = ChatMessage::new(MessageRole::User, content.clone());
-        let conversation_id = {
-            let mut inner = self.inner.write();
-            let conversation = Self::ensure_conversation(&mut inner);
-            let title_changed = conversation.add_message(message.clone());
-            self.store.append_message(conversation.id, &message)?;
+        let message = ChatMessage {
+            images,
+            ..ChatMessage::new(MessageRole::User, content)
+        };
+        let conversation_id = self.begin_user_turn(&message)?;
+
+        self.stream_assistant_reply(conversation_id, &model, temperature)
+            .await
+    }
+
+    /// Append a user message to the active conversation and mark it as generating.
+    ///
+    /// Fails without touching the conversation if a reply is already in flight for it.
+    fn begin_user_turn(&self, message: &ChatMessage) -> Result<Uuid> {
+        let mut inner = self.inner.write();
+        let id = Self::ensure_conversation(&mut inner).id;
+        Self::load_messages(&mut inner, &self.store, id);
+        if !inner.generating.insert(id) {
+            bail!("a reply is already being generated for this conversation");
+        }
+        let conversation = Self::ensure_conversation(&mut inner);
+        let title_changed = conversation.add_message(message.clone());
+        let persisted = self.store.append_message(id, message).and_then(|()| {
             if title_changed {
-                self.store.persist_metadata(conversation)?;
+                self.store.persist_metadata(conversation)
+            } else {
+                Ok(())
             }
-            conversation.id
-        };
+        });
+        if let Err(err) = persisted {
+            inner.generating.remove(&id);
+            return Err(err);
+        }
+        Ok(id)
+    }
 
-        let history = self.conversation_history(conversation_id);
-        let stream_rx = self
-            .llm
-            .respond_streaming(&history, Some(model.as_str()), Some(temperature))
-            .await?;
+    /// Whether a reply is currently being generated for the conversation.
+    pub fn is_generating(&self, id: Uuid) -> bool {
+        self.inner.read().generating.contains(&id)
+    }
+
+    /// The model and usage of every reply finished since the last call, whether sent,
+    /// streamed, stopped or regenerated; usage a provider did not report is estimated.
+    pub fn take_usage(&self) -> Vec<(String, ModelUsage)> {
+        std::mem::take(&mut self.inner.write().finished_usage)
+    }
+
+    /// Stop the reply streaming into the conversation.
+    ///
+    /// Text received so far is kept as a reply marked truncated; the stream then ends
+    /// with its usual `done` chunk. Returns `false` if nothing was streaming.
+    pub fn stop_generating(&self, id: Uuid) -> bool {
+        match self.inner.read().cancellations.get(&id) {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Generate a new assistant reply for a conversation whose last message is an
+    /// unanswered user turn, typically after a failed send. The user turn is not re-added.
+    pub async fn retry_last_turn_streaming(
+        &self,
+        conversation_id: Uuid,
+        model: impl Into<String>,
+        temperature: f32,
+    ) -> Result<(Uuid, mpsc::UnboundedReceiver<Result<StreamChunk>>)> {
+        self.ensure_writable()?;
+        self.ensure_loaded(conversation_id);
+        {
+            let mut inner = self.inner.write();
+            let Some(conversation) = inner.conversations.iter().find(|c| c.id == conversation_id)
+            else {
+                bail!("conversation {conversation_id} not found");
+            };
+            if conversation.messages.last().map(|m| &m.role) != Some(&MessageRole::User) {
+                bail!("the last message has already been answered");
+            }
+            if !inner.generating.insert(conversation_id) {
+                bail!("a reply is already being generated for this conversation");
+            }
+            inner.current_session = Some(conversation_id);
+        }
+        let model = model.into();
+        self.stream_assistant_reply(conversation_id, &model, temperature)
+            .await
+    }
+
+    /// Replace the content of an earlier user message, drop everything after it and
+    /// stream a new reply to it. The message keeps its id and `created_at`.
+    pub async fn edit_message_and_truncate(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        new_content: impl Into<String>,
+        model: impl Into<String>,
+        temperature: f32,
+    ) -> Result<(Uuid, mpsc::UnboundedReceiver<Result<StreamChunk>>)> {
+        self.ensure_writable()?;
+        let new_content = new_content.into();
+        if new_content.trim().is_empty() {
+            bail!("an edited message cannot be empty");
+        }
+        self.ensure_loaded(conversation_id);
+        {
+            let mut inner = self.inner.write();
+            let Some(index) = inner
+                .conversations
+                .iter()
+                .position(|c| c.id == conversation_id)
+            else {
+                bail!("conversation {conversation_id} not found");
+            };
+            let Some(position) = inner.conversations[index]
+                .messages
+                .iter()
+                .position(|m| m.id == message_id)
+            else {
+                bail!("message {message_id} not found");
+            };
+            if inner.conversations[index].messages[position].role != MessageRole::User {
+                bail!("only user messages can be edited");
+            }
+            if !inner.generating.insert(conversation_id) {
+                bail!("a reply is already being generated for this conversation");
+            }
+            inner.current_session = Some(conversation_id);
+            let conversation = &mut inner.conversations[index];
+            conversation.messages.truncate(position + 1);
+            conversation.refresh_usage();
+            let edited = &mut conversation.messages[position];
+            edited.content = new_content;
+            edited.alternatives.clear();
+            edited.selected_alternative = 0;
+            let edited = edited.clone();
+            conversation.updated_at = Utc::now();
+            let persisted = match self.store.truncate_at(conversation_id, message_id) {
+                Ok(true) => self.store.append_message(conversation_id, &edited),
+                Ok(false) => self
+                    .store
+                    .rewrite_transcript(conversation_id, &conversation.messages),
+                Err(err) => Err(err),
+            }
+            .and_then(|()| self.store.persist_metadata(conversation));
+            if let Err(err) = persisted {
+                inner.generating.remove(&conversation_id);
+                return Err(err);
+            }
+        }
+        let model = model.into();
+        self.stream_assistant_reply(conversation_id, &model, temperature)
+            .await
+    }
+
+    async fn stream_assistant_reply(
+        &self,
+        conversation_id: Uuid,
+        model: &str,
+        temperature: f32,
+    ) -> Result<(Uuid, mpsc::UnboundedReceiver<Result<StreamChunk>>)> {
+        let history = self.request_history(conversation_id);
+        let (max_tokens, top_p) = self.sampling_limits();
+        let cancel = CancellationToken::new();
+        self.inner
+            .write()
+            .cancellations
+            .insert(conversation_id, cancel.clone());
+        let stream_rx = match self
+            .llm()
+            .respond_streaming(&history, Some(model), Some(temperature), max_tokens, top_p)
+            .await
+        {
+            Ok(stream_rx) => stream_rx,
+            Err(err) => {
+                let mut inner = self.inner.write();
+                inner.generating.remove(&conversation_id);
+                inner.cancellations.remove(&conversation_id);
+                return Err(err);
+            }
+        };
 
         let (tx, rx) = mpsc::unbounded_channel();
         let assistant_id = Uuid::new_v4();
         let store = self.store.clone();
         let inner = self.inner.clone();
+        let state = self.clone();
+        let model = model.to_string();
 
         tokio::spawn(async move {
             let mut accumulated_content = String::new();
             let mut stream = stream_rx;
+            let mut stopped = false;
 
-            while let Some(result) = stream.recv().await {
+            loop {
+                let result = tokio::select! {
+                    result = stream.recv() => result,
+                    () = cancel.cancelled() => {
+                        stopped = true;
+                        None
+                    }
+                };
+                let Some(result) = result else {
+                    break;
+                };
                 match result {
                     Ok(chunk) => {
                         if chunk.done {
-                            // Save complete assistant message
                             let assistant_message = ChatMessage {
                                 id: assistant_id,
-                                role: MessageRole::Assistant,
                                 content: accumulated_content.clone(),
-                                created_at: Utc::now(),
-                                tool_calls: Vec::new(),
+                                tool_calls: chunk.tool_calls,
+                                usage: chunk.usage.clone(),
+                                model: Some(model.clone()),
+                                ..ChatMessage::new(MessageRole::Assistant, "")
                             };
+                            Self::record_reply(&inner, &store, conversation_id, assistant_message);
+                            state.request_title(conversation_id, &model);
 
-                            let mut inner_guard = inner.write();
-                            if let Some(conversation) = inner_guard
-                                .conversations
-                                .iter_mut()
-                                .find(|c| c.id == conversation_id)
-                            {
-                                let title_changed =
-                                    conversation.add_message(assistant_message.clone());
-                                if let Err(err) =
-                                    store.append_message(conversation.id, &assistant_message)
-                                {
-                                    tracing::error!(%err, "failed to persist assistant message");
-                                }
-                                if title_changed {
-                                    if let Err(err) = store.persist_metadata(conversation) {
-                                        tracing::error!(%err, "failed to persist metadata");
-                                    }
-                                }
-                            }
-
+                            let usage = chunk.usage.unwrap_or_else(|| {
+                                ModelUsage::estimate(&history, &accumulated_content)
+                            });
+                            inner
+                                .write()
+                                .finished_usage
+                                .push((model.clone(), usage.clone()));
                             let _ = tx.send(Ok(StreamChunk {
                                 delta: String::new(),
                                 done: true,
+                                usage: Some(usage),
+                                tool_calls: Vec::new(),
                             }));
                             break;
                         } else {
@@ -352,12 +955,265 @@ impl AppState {
                     }
                 }
             }
+            // Dropping the receiver tells the provider to stop reading the response body.
+            drop(stream);
+
+            if stopped {
+                if !accumulated_content.is_empty() {
+                    let assistant_message = ChatMessage {
+                        id: assistant_id,
+                        content: accumulated_content.clone(),
+                        truncated: true,
+                        model: Some(model.clone()),
+                        ..ChatMessage::new(MessageRole::Assistant, "")
+                    };
+                    Self::record_reply(&inner, &store, conversation_id, assistant_message);
+                }
+                let usage = ModelUsage::estimate(&history, &accumulated_content);
+                let mut inner = inner.write();
+                inner.generating.remove(&conversation_id);
+                inner.cancellations.remove(&conversation_id);
+                inner.finished_usage.push((model.clone(), usage.clone()));
+                drop(inner);
+                let _ = tx.send(Ok(StreamChunk {
+                    delta: String::new(),
+                    done: true,
+                    usage: Some(usage),
+                    tool_calls: Vec::new(),
+                }));
+            }
+            let mut inner = inner.write();
+            inner.generating.remove(&conversation_id);
+            inner.cancellations.remove(&conversation_id);
         });
 
         Ok((assistant_id, rx))
     }
 
+    /// Add a finished (or stopped) streamed reply to its conversation and persist it.
+    fn record_reply(
+        inner: &RwLock<InnerState>,
+        store: &TranscriptStore,
+        conversation_id: Uuid,
+        message: ChatMessage,
+    ) {
+        let mut inner = inner.write();
+        if let Some(conversation) = inner
+            .conversations
+            .iter_mut()
+            .find(|c| c.id == conversation_id)
+        {
+            let title_changed = conversation.add_message(message.clone());
+            if let Err(err) = store.append_message(conversation.id, &message) {
+                tracing::error!(%err, "failed to persist assistant message");
+            }
+            if title_changed || message.usage.is_some() {
+                if let Err(err) = store.persist_metadata(conversation) {
+                    tracing::error!(%err, "failed to persist metadata");
+                }
+            }
+        }
+        inner.generating.remove(&conversation_id);
+        inner.cancellations.remove(&conversation_id);
+    }
+
+    /// Regenerate an assistant reply into a new, selected variant, keeping the old ones.
+    ///
+    /// The request history is everything before `message_id`, using the selected
+    /// variant of each earlier reply.
+    pub async fn add_alternative(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        model: impl Into<String>,
+        temperature: f32,
+    ) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_loaded(conversation_id);
+        let history = {
+            let mut inner = self.inner.write();
+            let limit = inner.max_request_messages;
+            let Some(conversation) = inner.conversations.iter().find(|c| c.id == conversation_id)
+            else {
+                bail!("conversation {conversation_id} not found");
+            };
+            let Some(position) = conversation
+                .messages
+                .iter()
+                .position(|m| m.id == message_id)
+            else {
+                bail!("message {message_id} not found");
+            };
+            if conversation.messages[position].role != MessageRole::Assistant {
+                bail!("only assistant replies can be regenerated");
+            }
+            let limit = conversation.settings.max_request_messages.unwrap_or(limit);
+            let mut history = build_request_history(&conversation.messages[..position], limit);
+            history.splice(0..0, conversation.system_message());
+            if !inner.generating.insert(conversation_id) {
+                bail!("a reply is already being generated for this conversation");
+            }
+            history
+        };
+
+        let model = model.into();
+        let (max_tokens, top_p) = self.sampling_limits();
+        let response = self
+            .llm()
+            .respond(
+                &history,
+                Some(model.as_str()),
+                Some(temperature),
+                max_tokens,
+                top_p,
+            )
+            .await;
+        let mut inner = self.inner.write();
+        inner.generating.remove(&conversation_id);
+        let response = response?;
+        let usage = response
+            .usage
+            .clone()
+            .unwrap_or_else(|| ModelUsage::estimate(&history, &response.message.content));
+        inner.finished_usage.push((model.clone(), usage));
+        let Some(conversation) = inner
+            .conversations
+            .iter_mut()
+            .find(|c| c.id == conversation_id)
+        else {
+            bail!("conversation {conversation_id} was removed while regenerating");
+        };
+        let Some(message) = conversation
+            .messages
+            .iter_mut()
+            .find(|m| m.id == message_id)
+        else {
+            bail!("message {message_id} was removed while regenerating");
+        };
+        message.push_alternative(response.message.content);
+        message.usage = response.usage;
+        message.model = Some(model);
+        conversation.updated_at = Utc::now();
+        conversation.refresh_usage();
+        self.store
+            .rewrite_transcript(conversation_id, &conversation.messages)?;
+        self.store.persist_metadata(conversation)
+    }
+
+    /// Show the variant at `index` of an assistant reply and use it as history from now on.
+    pub fn select_alternative(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        index: usize,
+    ) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_loaded(conversation_id);
+        let mut inner = self.inner.write();
+        let Some(conversation) = inner
+            .conversations
+            .iter_mut()
+            .find(|c| c.id == conversation_id)
+        else {
+            bail!("conversation {conversation_id} not found");
+        };
+        let Some(message) = conversation
+            .messages
+            .iter_mut()
+            .find(|m| m.id == message_id)
+        else {
+            bail!("message {message_id} not found");
+        };
+        if message.selected_alternative == index {
+            return Ok(());
+        }
+        if !message.select_alternative(index) {
+            bail!("reply has no variant {index}");
+        }
+        self.store
+            .rewrite_transcript(conversation_id, &conversation.messages)
+    }
+
+    /// Reset a conversation's title to the snippet of its current first user message.
+    pub fn retitle_from_content(&self, id: Uuid) -> Result<String> {
+        self.ensure_writable()?;
+        self.ensure_loaded(id);
+        let mut inner = self.inner.write();
+        let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        conversation.title = conversation.content_title();
+        self.store.persist_metadata(conversation)?;
+        Ok(conversation.title.clone())
+    }
+
+    /// Generate a title with the model after each conversation's first exchange, in
+    /// place of the snippet of the first message. Never done with the mock provider.
+    pub fn set_auto_title(&self, enabled: bool) {
+        self.inner.write().auto_title = enabled;
+    }
+
+    /// Whether a title is being generated for any conversation.
+    pub fn is_generating_title(&self) -> bool {
+        !self.inner.read().titling.is_empty()
+    }
+
+    /// Ask `model` for a title in the background if auto titles are on and the
+    /// conversation just had its first exchange under the snippet title.
+    ///
+    /// A failed request leaves the snippet title in place, as does a rename made while
+    /// the request was running.
+    fn request_title(&self, conversation_id: Uuid, model: &str) {
+        let llm = self.llm();
+        if llm.provider_kind() == Some(LlmProviderKind::Mock) {
+            return;
+        }
+        let (exchange, snippet_title) = {
+            let mut inner = self.inner.write();
+            if !inner.auto_title || inner.titling.contains(&conversation_id) {
+                return;
+            }
+            let Some(conversation) = inner.conversations.iter().find(|c| c.id == conversation_id)
+            else {
+                return;
+            };
+            if conversation.title != conversation.content_title() {
+                return;
+            }
+            let Some(exchange) = conversation.first_exchange() else {
+                return;
+            };
+            let snippet_title = conversation.title.clone();
+            inner.titling.insert(conversation_id);
+            (exchange, snippet_title)
+        };
+        let state = self.clone();
+        let model = model.to_string();
+        tokio::spawn(async move {
+            match llm.suggest_title(&exchange, Some(&model)).await {
+                Ok(title) => {
+                    let unchanged = state
+                        .inner
+                        .read()
+                        .conversations
+                        .iter()
+                        .any(|c| c.id == conversation_id && c.title == snippet_title);
+                    if unchanged {
+                        if let Err(err) = state.rename_conversation(conversation_id, title) {
+                            tracing::error!(%err, "failed to save the generated title");
+                        }
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(%err, "title generation failed; keeping the snippet title")
+                }
+            }
+            state.inner.write().titling.remove(&conversation_id);
+        });
+    }
+
     pub fn rename_conversation(&self, id: Uuid, title: impl Into<String>) -> Result<()> {
+        self.ensure_writable()?;
         let mut inner = self.inner.write();
         if let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) {
             conversation.title = title.into();
@@ -367,9 +1223,11 @@ impl AppState {
     }
 
     pub fn delete_conversation(&self, id: Uuid) -> Result<bool> {
+        self.ensure_writable()?;
         let mut inner = self.inner.write();
         if let Some(position) = inner.conversations.iter().position(|c| c.id == id) {
             inner.conversations.remove(position);
+            inner.unloaded.remove(&id);
             if inner.current_session == Some(id) {
                 inner.current_session = inner.conversations.first().map(|c| c.id);
             }
@@ -379,6 +1237,223 @@ impl AppState {
         Ok(false)
     }
 
+    /// Delete every conversation without messages, except the selected one.
+    ///
+    /// This includes empty chats left on disk by earlier sessions, which are never
+    /// loaded. Returns how many conversations were removed.
+    pub fn prune_empty_conversations(&self) -> Result<usize> {
+        self.ensure_writable()?;
+        let mut inner = self.inner.write();
+        let current = inner.current_session;
+        let mut empty: Vec<Uuid> = inner
+            .conversations
+            .iter()
+            .filter(|c| inner.message_count(c) == 0 && Some(c.id) != current)
+            .map(|c| c.id)
+            .collect();
+        for id in self.store.metadata_only_ids() {
+            if Some(id) != current && !empty.contains(&id) {
+                empty.push(id);
+            }
+        }
+        for id in &empty {
+            self.store.delete_conversation(*id)?;
+            inner.conversations.retain(|c| c.id != *id);
+        }
+        Ok(empty.len())
+    }
+
+    /// Write the messages added since the last incremental export as flattened NDJSON.
+    ///
+    /// See [`TranscriptStore::export_incremental`]; the first export includes everything.
+    pub fn export_incremental<W: Write>(&self, writer: W) -> Result<usize> {
+        self.load_all();
+        let conversations = self.inner.read().conversations.clone();
+        self.store.export_incremental(&conversations, writer)
+    }
+
+    /// Merge the messages of `from` into `into`, ordered by creation time, and delete `from`.
+    ///
+    /// Messages with identical timestamps keep `into`'s messages first. The merged
+    /// conversation keeps the title of `into`.
+    pub fn merge_conversations(&self, into: Uuid, from: Uuid) -> Result<()> {
+        if into == from {
+            bail!("cannot merge a conversation into itself");
+        }
+        self.ensure_writable()?;
+        self.ensure_loaded(into);
+        self.ensure_loaded(from);
+        let mut inner = self.inner.write();
+        let Some(from_idx) = inner.conversations.iter().position(|c| c.id == from) else {
+            bail!("conversation {from} not found");
+        };
+        let Some(into_idx) = inner.conversations.iter().position(|c| c.id == into) else {
+            bail!("conversation {into} not found");
+        };
+
+        let source = &inner.conversations[from_idx];
+        let mut merged = inner.conversations[into_idx].clone();
+        merged.messages.extend(source.messages.iter().cloned());
+        merged.messages.sort_by_key(|message| message.created_at);
+        merged.refresh_usage();
+        merged.updated_at = merged.updated_at.max(source.updated_at);
+
+        self.store.rewrite_transcript(into, &merged.messages)?;
+        self.store.persist_metadata(&merged)?;
+        self.store.delete_conversation(from)?;
+
+        inner.conversations[into_idx] = merged;
+        inner.conversations.remove(from_idx);
+        if inner.current_session == Some(from) {
+            inner.current_session = Some(into);
+        }
+        Ok(())
+    }
+
+    /// Replace the per-conversation parameter overrides and persist them.
+    pub fn set_conversation_settings(
+        &self,
+        id: Uuid,
+        settings: ConversationSettings,
+    ) -> Result<()> {
+        self.ensure_writable()?;
+        let mut inner = self.inner.write();
+        let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        if conversation.settings.locked {
+            bail!("this conversation's parameters are locked");
+        }
+        conversation.settings = settings;
+        self.store.persist_metadata(conversation)?;
+        Ok(())
+    }
+
+    /// Set the conversation's system prompt and persist it; blank text clears it.
+    ///
+    /// Only requests made afterwards see the new prompt.
+    pub fn set_system_prompt(&self, id: Uuid, prompt: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let mut inner = self.inner.write();
+        let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        let prompt = prompt.trim();
+        conversation.system_prompt = (!prompt.is_empty()).then(|| prompt.to_string());
+        self.store.persist_metadata(conversation)?;
+        Ok(())
+    }
+
+    /// Freeze the conversation's effective parameters so later global changes don't apply.
+    pub fn lock_parameters(&self, id: Uuid, defaults: &ChatParameters) -> Result<()> {
+        self.ensure_writable()?;
+        let mut inner = self.inner.write();
+        let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        if conversation.settings.locked {
+            return Ok(());
+        }
+        let previous = std::mem::take(&mut conversation.settings);
+        conversation.settings = ConversationSettings {
+            before_lock: Some(Box::new(previous.clone())),
+            ..ConversationSettings::locked_to(&previous.resolve(defaults))
+        };
+        self.store.persist_metadata(conversation)?;
+        Ok(())
+    }
+
+    /// Drop a lock along with the frozen values, restoring the overrides the conversation
+    /// had before it was locked; the rest are inherited from the globals again.
+    pub fn unlock_parameters(&self, id: Uuid) -> Result<()> {
+        self.ensure_writable()?;
+        let mut inner = self.inner.write();
+        let Some(conversation) = inner.conversations.iter_mut().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        if !conversation.settings.locked {
+            return Ok(());
+        }
+        conversation.settings = conversation
+            .settings
+            .before_lock
+            .take()
+            .map(|previous| *previous)
+            .unwrap_or_default();
+        self.store.persist_metadata(conversation)?;
+        Ok(())
+    }
+
+    /// Serialize a conversation, including every message and tool call, as pretty JSON.
+    ///
+    /// The output is unredacted and may contain sensitive content. It is the format
+    /// accepted by [`AppState::import_conversation_json`].
+    pub fn conversation_json(&self, id: Uuid) -> Result<String> {
+        self.ensure_loaded(id);
+        let inner = self.inner.read();
+        let Some(conversation) = inner.conversations.iter().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        Ok(serde_json::to_string_pretty(conversation)?)
+    }
+
+    /// Import a conversation produced by [`AppState::conversation_json`] and select it.
+    ///
+    /// A fresh id is assigned when the conversation already exists in this project.
+    pub fn import_conversation_json(&self, json: &str) -> Result<Uuid> {
+        self.ensure_writable()?;
+        let mut conversation: Conversation = serde_json::from_str(json)?;
+        conversation.refresh_usage();
+        let mut inner = self.inner.write();
+        if inner.conversations.iter().any(|c| c.id == conversation.id) {
+            conversation.id = Uuid::new_v4();
+        }
+        self.store
+            .rewrite_transcript(conversation.id, &conversation.messages)?;
+        self.store.persist_metadata(&conversation)?;
+        let id = conversation.id;
+        inner.conversations.insert(0, conversation);
+        inner.current_session = Some(id);
+        Ok(id)
+    }
+
+    /// Copy a conversation into a new one at the top of the list and select it.
+    ///
+    /// Messages get fresh ids but keep their timestamps, so the copy can be continued
+    /// without touching the original thread.
+    pub fn duplicate_conversation(&self, id: Uuid) -> Result<Uuid> {
+        self.ensure_writable()?;
+        self.ensure_loaded(id);
+        let mut inner = self.inner.write();
+        let Some(source) = inner.conversations.iter().find(|c| c.id == id) else {
+            bail!("conversation {id} not found");
+        };
+        let now = Utc::now();
+        let copy = Conversation {
+            id: Uuid::new_v4(),
+            title: format!("Copy of {}", source.title),
+            created_at: now,
+            updated_at: now,
+            messages: source
+                .messages
+                .iter()
+                .map(|message| ChatMessage {
+                    id: Uuid::new_v4(),
+                    ..message.clone()
+                })
+                .collect(),
+            ..source.clone()
+        };
+        self.store.rewrite_transcript(copy.id, &copy.messages)?;
+        self.store.persist_metadata(&copy)?;
+        let copy_id = copy.id;
+        inner.conversations.insert(0, copy);
+        inner.current_session = Some(copy_id);
+        Ok(copy_id)
+    }
+
+    /// Move `dragged` to where `target` is listed and save the new order, which later
+    /// loads keep instead of sorting by time. Read-only projects reorder only in memory.
     pub fn reorder_conversations(&self, dragged: Uuid, target: Uuid) -> Result<()> {
         let mut inner = self.inner.write();
         let from_idx = inner.conversations.iter().position(|c| c.id == dragged);
@@ -392,20 +1467,73 @@ impl AppState {
                 to -= 1;
             }
             inner.conversations.insert(to, conversation);
+            if !self.is_read_only() {
+                let ids: Vec<Uuid> = inner.conversations.iter().map(|c| c.id).collect();
+                self.store.persist_order(&ids)?;
+            }
         }
         Ok(())
     }
 
-    fn conversation_history(&self, id: Uuid) -> Vec<ChatMessage> {
+    /// Cap how many prior messages are sent with each request (0 sends everything).
+    pub fn set_max_request_messages(&self, limit: usize) {
+        self.inner.write().max_request_messages = limit;
+    }
+
+    pub fn max_request_messages(&self) -> usize {
+        self.inner.read().max_request_messages
+    }
+
+    /// Cap the length of each reply in tokens (`None` leaves it to the provider).
+    pub fn set_max_tokens(&self, max_tokens: Option<u32>) {
+        self.inner.write().max_tokens = max_tokens;
+    }
+
+    /// Nucleus sampling cutoff sent with each request (`None` leaves it to the provider).
+    pub fn set_top_p(&self, top_p: Option<f32>) {
+        self.inner.write().top_p = top_p;
+    }
+
+    fn sampling_limits(&self) -> (Option<u32>, Option<f32>) {
+        let inner = self.inner.read();
+        (inner.max_tokens, inner.top_p)
+    }
+
+    /// The messages sent to the model for the conversation's next reply: its system
+    /// prompt, if any, followed by the capped history.
+    ///
+    /// Results of tools run from the sidebar are left out; they answer no tool call of
+    /// the model's, and providers reject such messages.
+    pub fn request_history(&self, id: Uuid) -> Vec<ChatMessage> {
+        self.ensure_loaded(id);
         let inner = self.inner.read();
         inner
             .conversations
             .iter()
             .find(|c| c.id == id)
-            .map(|c| c.messages.clone())
+            .map(|c| {
+                let limit = c
+                    .settings
+                    .max_request_messages
+                    .unwrap_or(inner.max_request_messages);
+                let messages: Vec<ChatMessage> = c
+                    .messages
+                    .iter()
+                    .filter(|message| message.role != MessageRole::Tool)
+                    .cloned()
+                    .collect();
+                let mut history = build_request_history(&messages, limit);
+                history.splice(0..0, c.system_message());
+                history
+            })
             .unwrap_or_default()
     }
 
+    /// The id of the active conversation, starting one when there is none.
+    pub fn ensure_active_conversation(&self) -> Uuid {
+        Self::ensure_conversation(&mut self.inner.write()).id
+    }
+
     fn ensure_conversation(inner: &mut InnerState) -> &mut Conversation {
         if let Some(id) = inner.current_session {
             if let Some(position) = inner.conversations.iter().position(|c| c.id == id) {
@@ -421,22 +1549,222 @@ impl AppState {
     }
 
     pub fn llm_status(&self) -> LlmStatus {
-        self.llm.status()
+        self.llm.read().status()
+    }
+
+    fn llm(&self) -> LlmDriver {
+        self.llm.read().clone()
+    }
+
+    /// Send later requests through `llm`, e.g. after the provider settings changed.
+    ///
+    /// Replies already streaming finish on the previous driver.
+    pub fn set_llm(&self, llm: LlmDriver) {
+        *self.llm.write() = llm;
+    }
+
+    /// The receiving end of the merged MCP client events; `None` once taken.
+    pub fn take_mcp_events(&self) -> Option<mpsc::UnboundedReceiver<McpEvent>> {
+        self.mcp_events_rx.lock().take()
+    }
+
+    /// Register `endpoints`, usually [`ProjectHandle::mcp_endpoints`], and connect to
+    /// each, replacing the servers registered before.
+    ///
+    /// A server that fails to connect is reported as [`McpEvent::Disconnected`] with
+    /// the error as reason, so one bad entry does not keep the others from starting.
+    /// Replaced servers are closed without reporting further events.
+    pub async fn connect_mcp_servers(&self, endpoints: Vec<McpEndpoint>) {
+        let previous = {
+            for forwarder in self.mcp_forwarders.lock().drain(..) {
+                forwarder.abort();
+            }
+            self.mcp.lock().take_clients()
+        };
+        for client in previous {
+            if let Err(err) = client.disconnect().await {
+                tracing::warn!(endpoint = %client.endpoint(), error = %err, "failed to close MCP connection");
+            }
+        }
+        let mut clients = Vec::new();
+        {
+            let mut registry = self.mcp.lock();
+            let mut forwarders = self.mcp_forwarders.lock();
+            for endpoint in endpoints {
+                let (client, mut rx) = registry.add(endpoint);
+                let events = self.mcp_events.clone();
+                forwarders.push(tokio::spawn(async move {
+                    while let Some(event) = rx.recv().await {
+                        if events.send(event).is_err() {
+                            break;
+                        }
+                    }
+                }));
+                clients.push(client);
+            }
+        }
+        let handshakes = clients.into_iter().map(|client| {
+            let events = self.mcp_events.clone();
+            async move {
+                if let Err(err) = client.handshake().await {
+                    tracing::warn!(endpoint = %client.endpoint(), error = %err, "MCP server failed to connect");
+                    let _ = events.send(McpEvent::Disconnected {
+                        endpoint: client.endpoint().to_string(),
+                        reason: Some(format!("{err:#}")),
+                    });
+                }
+            }
+        });
+        futures::future::join_all(handshakes).await;
+    }
+
+    /// Connect the MCP server `endpoint` again, e.g. after it was closed or dropped.
+    pub async fn reconnect_mcp_server(&self, endpoint: &str) -> Result<()> {
+        let client = self.mcp_client(endpoint)?;
+        if let Err(err) = client.handshake().await {
+            let _ = self.mcp_events.send(McpEvent::Disconnected {
+                endpoint: endpoint.to_string(),
+                reason: Some(format!("{err:#}")),
+            });
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Let MCP servers sample from the model with `params`, or decline their sampling
+    /// requests when `None`.
+    pub fn set_mcp_sampling(&self, params: Option<&ChatParameters>) {
+        self.mcp
+            .lock()
+            .set_sampling(params.map(|params| SamplingModel {
+                llm: self.llm(),
+                model: params.model.clone(),
+                temperature: params.temperature,
+            }));
+    }
+
+    /// The tools the MCP server `endpoint` offers.
+    pub async fn list_mcp_tools(&self, endpoint: &str) -> Result<Vec<ToolDefinition>> {
+        let tools = self.mcp_client(endpoint)?.list_tools().await?;
+        Ok(tools.into_iter().map(ToolDefinition::from).collect())
+    }
+
+    /// Invoke `tool` on the MCP server `endpoint` and record the result in conversation
+    /// `conversation_id`, the one it was started from, as a [`MessageRole::Tool`]
+    /// message, which is returned. Nothing is recorded when the call times out,
+    /// `cancel` fires first or the conversation was deleted meanwhile.
+    pub async fn call_mcp_tool(
+        &self,
+        conversation_id: Uuid,
+        endpoint: &str,
+        tool: &str,
+        arguments: Value,
+        cancel: &CancellationToken,
+    ) -> Result<ChatMessage> {
+        self.ensure_writable()?;
+        let client = self.mcp_client(endpoint)?;
+        let result = client
+            .call_tool_cancellable(tool, Some(arguments.clone()), cancel)
+            .await?;
+        let response = serde_json::to_value(&result)?;
+        let mut call = ToolCall::new(tool, arguments);
+        call.status = if result.is_error == Some(true) {
+            ToolCallStatus::Failed
+        } else {
+            ToolCallStatus::Completed
+        };
+        let mut message = ChatMessage::new(
+            MessageRole::Tool,
+            format!("```json\n{}\n```", serde_json::to_string_pretty(&response)?),
+        );
+        call.response = Some(response);
+        message.tool_calls.push(call);
+
+        let mut inner = self.inner.write();
+        Self::load_messages(&mut inner, &self.store, conversation_id);
+        let Some(conversation) = inner
+            .conversations
+            .iter_mut()
+            .find(|c| c.id == conversation_id)
+        else {
+            bail!("conversation {conversation_id} was removed while `{tool}` ran");
+        };
+        let title_changed = conversation.add_message(message.clone());
+        self.store.append_message(conversation.id, &message)?;
+        if title_changed {
+            self.store.persist_metadata(conversation)?;
+        }
+        Ok(message)
+    }
+
+    /// Close the connection to the MCP server `endpoint`.
+    pub async fn disconnect_mcp_server(&self, endpoint: &str) -> Result<()> {
+        self.mcp_client(endpoint)?.disconnect().await
+    }
+
+    /// Close every MCP connection, e.g. before switching projects.
+    pub async fn disconnect_mcp_servers(&self) {
+        let clients = self.mcp.lock().clients().to_vec();
+        for client in clients {
+            if let Err(err) = client.disconnect().await {
+                tracing::warn!(endpoint = %client.endpoint(), error = %err, "failed to close MCP connection");
+            }
+        }
+    }
+
+    /// The auth state of this project's MCP endpoints, kept fresh in the background.
+    pub fn mcp_auth(&self) -> AuthCoordinator {
+        self.mcp.lock().auth().clone()
+    }
+
+    fn mcp_client(&self, endpoint: &str) -> Result<Arc<McpClient>> {
+        self.mcp
+            .lock()
+            .client(endpoint)
+            .ok_or_else(|| anyhow!("unknown MCP server `{endpoint}`"))
     }
 }
 
-fn snippet(content: &str) -> String {
-    let trimmed = content.trim();
+/// Select the messages sent to the model: every system message plus the latest
+/// `max_messages` other messages, in their original order. A limit of 0 keeps everything.
+pub fn build_request_history(messages: &[ChatMessage], max_messages: usize) -> Vec<ChatMessage> {
+    let turns = messages
+        .iter()
+        .filter(|message| message.role != MessageRole::System)
+        .count();
+    if max_messages == 0 || turns <= max_messages {
+        return messages.to_vec();
+    }
+    let mut skip = turns - max_messages;
+    messages
+        .iter()
+        .filter(|message| {
+            if message.role == MessageRole::System {
+                return true;
+            }
+            if skip > 0 {
+                skip -= 1;
+                return false;
+            }
+            true
+        })
+        .cloned()
+        .collect()
+}
+
+/// A title from `content`: at most 42 characters, cut after the last whole word when
+/// there is one, with an ellipsis only when something was left out.
+pub(crate) fn snippet(content: &str) -> String {
     const MAX: usize = 42;
-    let mut chars = trimmed.chars();
-    let mut acc = String::new();
-    for _ in 0..MAX {
-        if let Some(ch) = chars.next() {
-            acc.push(ch);
-        } else {
-            return trimmed.to_string();
+    let trimmed = content.trim();
+    let Some((cut, _)) = trimmed.char_indices().nth(MAX) else {
+        return trimmed.to_string();
+    };
+    let mut head = &trimmed[..cut];
+    if !trimmed[cut..].starts_with(char::is_whitespace) {
+        if let Some(space) = head.rfind(char::is_whitespace) {
+            head = &head[..space];
         }
     }
-    acc.push('…');
-    acc
+    format!("{}…", head.trim_end())
 }
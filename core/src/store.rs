@@ -1,6 +1,8 @@
-use crate::state::{ChatMessage, Conversation};
-use anyhow::Result;
+use crate::llm::ModelUsage;
+use crate::state::{snippet, ChatMessage, Conversation, ConversationSettings, MessageRole};
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
@@ -14,6 +16,69 @@ pub struct TranscriptStore {
 #[derive(Serialize, Deserialize)]
 struct ConversationMetadata {
     title: String,
+    #[serde(default, skip_serializing_if = "ConversationSettings::is_empty")]
+    settings: ConversationSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    system_prompt: Option<String>,
+    /// [`Conversation::usage`], so the sidebar can show a chat's total before its
+    /// transcript is read.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    usage: Vec<UsageRecord>,
+}
+
+/// The tokens of one model in [`ConversationMetadata::usage`].
+#[derive(Serialize, Deserialize)]
+struct UsageRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(flatten)]
+    usage: ModelUsage,
+}
+
+/// Just enough of a transcript line to find a message without decoding all of it.
+#[derive(Deserialize)]
+struct MessageId {
+    id: Uuid,
+}
+
+/// One message of a flattened NDJSON export.
+#[derive(Serialize)]
+struct FlatMessageRecord<'a> {
+    conversation_id: Uuid,
+    conversation_title: &'a str,
+    #[serde(flatten)]
+    message: &'a ChatMessage,
+}
+
+/// Replace `path` with `contents` so readers see either the old file or the new one.
+///
+/// The data goes to a hidden sibling first, is synced, and is then renamed over `path`.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp"));
+    let written = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp_path, path)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// The snippet title of the first user message in a transcript, read line by line.
+fn first_user_title(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str::<ChatMessage>(&line).ok())
+        .find(|message| message.role == MessageRole::User)
+        .map(|message| snippet(&message.content))
 }
 
 impl TranscriptStore {
@@ -48,52 +113,274 @@ impl TranscriptStore {
     }
 
     pub fn load_conversations(&self) -> Result<Vec<Conversation>> {
+        let mut conversations = self
+            .transcript_files()
+            .iter()
+            .map(|path| self.load_transcript(path))
+            .collect::<Result<Vec<_>>>()?;
+        self.sort_conversations(&mut conversations, |c| c);
+        Ok(conversations)
+    }
+
+    /// Every conversation with its metadata but without messages, newest first, paired
+    /// with its message count.
+    ///
+    /// Transcripts are only read as far as needed to count lines and, for conversations
+    /// without metadata, to title them after the first user message. Use
+    /// [`TranscriptStore::load_conversation`] for the messages.
+    pub fn load_conversation_headers(&self) -> Vec<(Conversation, usize)> {
+        let mut headers: Vec<(Conversation, usize)> = self
+            .transcript_files()
+            .iter()
+            .filter_map(|path| {
+                let id = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|s| Uuid::parse_str(s).ok())?;
+                let mut conversation = Conversation::with_id(id, "Restored conversation");
+                if let Ok(modified) = fs::metadata(path).and_then(|meta| meta.modified()) {
+                    conversation.created_at = modified.into();
+                    conversation.updated_at = modified.into();
+                }
+                match self.read_metadata(id) {
+                    Some(meta) => {
+                        conversation.title = meta.title;
+                        conversation.settings = meta.settings;
+                        conversation.system_prompt = meta.system_prompt;
+                        conversation.set_usage(
+                            meta.usage
+                                .into_iter()
+                                .map(|record| (record.model, record.usage))
+                                .collect::<BTreeMap<_, _>>(),
+                        );
+                    }
+                    None => {
+                        if let Some(title) = first_user_title(path) {
+                            conversation.title = title;
+                        }
+                    }
+                }
+                Some((conversation, self.message_count(id)))
+            })
+            .collect();
+        self.sort_conversations(&mut headers, |(c, _)| c);
+        headers
+    }
+
+    /// Read the full transcript of one conversation.
+    pub fn load_conversation(&self, id: Uuid) -> Result<Conversation> {
+        self.load_transcript(&self.transcript_path(id))
+    }
+
+    /// The number of messages in a conversation's transcript, counted by non-blank lines
+    /// without decoding them.
+    pub fn message_count(&self, id: Uuid) -> usize {
+        let Ok(file) = File::open(self.transcript_path(id)) else {
+            return 0;
+        };
+        BufReader::new(file)
+            .split(b'\n')
+            .filter_map(|line| line.ok())
+            .filter(|line| line.iter().any(|byte| !byte.is_ascii_whitespace()))
+            .count()
+    }
+
+    /// Like [`TranscriptStore::load_conversations`], but transcripts that fail to load
+    /// are logged and skipped instead of failing the whole load.
+    pub fn load_conversations_skipping_invalid(&self) -> Vec<Conversation> {
         let mut conversations = Vec::new();
+        for path in self.transcript_files() {
+            match self.load_transcript(&path) {
+                Ok(conversation) => conversations.push(conversation),
+                Err(err) => {
+                    tracing::warn!(
+                        path = %path.display(),
+                        error = %err,
+                        "skipping unreadable conversation"
+                    );
+                }
+            }
+        }
+        self.sort_conversations(&mut conversations, |c| c);
+        conversations
+    }
+
+    /// Write every readable conversation as newline-delimited JSON, returning the record count.
+    ///
+    /// Each line is a whole conversation, or with `flatten_messages` a single message
+    /// tagged with its conversation's id and title.
+    pub fn export_ndjson<W: Write>(&self, mut writer: W, flatten_messages: bool) -> Result<usize> {
+        let mut records = 0;
+        for conversation in self.load_conversations_skipping_invalid() {
+            if flatten_messages {
+                for message in &conversation.messages {
+                    let record = FlatMessageRecord {
+                        conversation_id: conversation.id,
+                        conversation_title: &conversation.title,
+                        message,
+                    };
+                    serde_json::to_writer(&mut writer, &record)?;
+                    writer.write_all(b"\n")?;
+                    records += 1;
+                }
+            } else {
+                serde_json::to_writer(&mut writer, &conversation)?;
+                writer.write_all(b"\n")?;
+                records += 1;
+            }
+        }
+        writer.flush()?;
+        Ok(records)
+    }
+
+    fn order_path(&self) -> PathBuf {
+        self.root.join("conversation_order.json")
+    }
+
+    /// The conversation order saved by [`TranscriptStore::persist_order`], if any.
+    pub fn conversation_order(&self) -> Vec<Uuid> {
+        fs::read_to_string(self.order_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Remember the order conversations are listed in, e.g. after dragging one.
+    pub fn persist_order(&self, ids: &[Uuid]) -> Result<()> {
+        write_atomic(&self.order_path(), &serde_json::to_vec_pretty(ids)?)
+    }
+
+    /// Put conversations in their saved order. Those missing from it, i.e. created since
+    /// it was saved, come first, newest first.
+    fn sort_conversations<T>(&self, items: &mut [T], conversation: impl Fn(&T) -> &Conversation) {
+        let order: HashMap<Uuid, usize> = self
+            .conversation_order()
+            .into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index))
+            .collect();
+        items.sort_by_key(|item| {
+            let c = conversation(item);
+            (order.get(&c.id).copied(), std::cmp::Reverse(c.updated_at))
+        });
+    }
+
+    fn export_markers_path(&self) -> PathBuf {
+        self.root.join("export_markers.json")
+    }
+
+    /// Last message exported per conversation by [`TranscriptStore::export_incremental`].
+    pub fn export_markers(&self) -> HashMap<Uuid, Uuid> {
+        fs::read_to_string(self.export_markers_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the messages of `conversations` appended since the previous incremental
+    /// export as flattened NDJSON records, returning the record count.
+    ///
+    /// Conversations without a marker, or whose marked message no longer exists, are
+    /// exported in full. Markers are only advanced after the writer has been flushed,
+    /// and those of conversations missing from `conversations` are kept.
+    pub fn export_incremental<W: Write>(
+        &self,
+        conversations: &[Conversation],
+        mut writer: W,
+    ) -> Result<usize> {
+        let previous = self.export_markers();
+        let mut markers = previous.clone();
+        let mut records = 0;
+        for conversation in conversations {
+            let start = previous
+                .get(&conversation.id)
+                .and_then(|last| conversation.messages.iter().position(|m| m.id == *last))
+                .map_or(0, |index| index + 1);
+            for message in &conversation.messages[start..] {
+                let record = FlatMessageRecord {
+                    conversation_id: conversation.id,
+                    conversation_title: &conversation.title,
+                    message,
+                };
+                serde_json::to_writer(&mut writer, &record)?;
+                writer.write_all(b"\n")?;
+                records += 1;
+            }
+            if let Some(last) = conversation.messages.last() {
+                markers.insert(conversation.id, last.id);
+            }
+        }
+        writer.flush()?;
+        write_atomic(
+            &self.export_markers_path(),
+            &serde_json::to_vec_pretty(&markers)?,
+        )?;
+        Ok(records)
+    }
+
+    fn transcript_files(&self) -> Vec<PathBuf> {
         let path = self.conversation_dir();
         if !path.exists() {
-            return Ok(conversations);
+            return Vec::new();
         }
-        for entry in walkdir::WalkDir::new(path)
+        walkdir::WalkDir::new(path)
             .into_iter()
             .filter_map(|entry| entry.ok())
-        {
-            if !entry.file_type().is_file() {
-                continue;
-            }
-            let file_path = entry.into_path();
-            if file_path.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("jsonl"))
+            .collect()
+    }
+
+    fn load_transcript(&self, file_path: &Path) -> Result<Conversation> {
+        let id = file_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .unwrap_or_else(Uuid::new_v4);
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+        let mut conversation = Conversation::with_id(id, "Restored conversation");
+        let mut skipped = 0;
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
                 continue;
             }
-            let id = file_path
-                .file_stem()
-                .and_then(|stem| stem.to_str())
-                .and_then(|s| Uuid::parse_str(s).ok())
-                .unwrap_or_else(Uuid::new_v4);
-            let file = File::open(&file_path)?;
-            let reader = BufReader::new(file);
-            let mut conversation = Conversation::with_id(id, "Restored conversation");
-            for line in reader.lines() {
-                let line = line?;
-                if line.trim().is_empty() {
-                    continue;
+            // A crash mid-append can leave a partial line; keep the messages around it.
+            match serde_json::from_str::<ChatMessage>(&line) {
+                Ok(message) => {
+                    let _ = conversation.add_message(message);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        path = %file_path.display(),
+                        line = index + 1,
+                        error = %err,
+                        "skipping unreadable transcript line"
+                    );
+                    skipped += 1;
                 }
-                let message: ChatMessage = serde_json::from_str(&line)?;
-                let _ = conversation.add_message(message);
-            }
-            if let Some(meta) = self.read_metadata(id) {
-                conversation.title = meta.title;
             }
-            conversations.push(conversation);
         }
-        conversations.sort_by_key(|c| c.updated_at);
-        conversations.reverse();
-        Ok(conversations)
+        if skipped > 0 && conversation.messages.is_empty() {
+            bail!("{} has no readable messages", file_path.display());
+        }
+        if let Some(meta) = self.read_metadata(id) {
+            conversation.title = meta.title;
+            conversation.settings = meta.settings;
+            conversation.system_prompt = meta.system_prompt;
+        }
+        conversation.refresh_usage();
+        Ok(conversation)
+    }
+
+    fn transcript_path(&self, id: Uuid) -> PathBuf {
+        self.conversation_dir().join(format!("{}.jsonl", id))
     }
 
     pub fn append_message(&self, conversation_id: Uuid, message: &ChatMessage) -> Result<()> {
-        let path = self
-            .conversation_dir()
-            .join(format!("{}.jsonl", conversation_id));
+        let path = self.transcript_path(conversation_id);
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).ok();
         }
@@ -101,33 +388,118 @@ impl TranscriptStore {
         let serialized = serde_json::to_vec(message)?;
         file.write_all(&serialized)?;
         file.write_all(b"\n")?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Replace a conversation's transcript with the given messages.
+    pub fn rewrite_transcript(
+        &self,
+        conversation_id: Uuid,
+        messages: &[ChatMessage],
+    ) -> Result<()> {
+        let path = self.transcript_path(conversation_id);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        let mut serialized = Vec::new();
+        for message in messages {
+            serde_json::to_writer(&mut serialized, message)?;
+            serialized.push(b'\n');
+        }
+        write_atomic(&path, &serialized)?;
         Ok(())
     }
 
+    /// Drop the message `message_id` and every line after it from a conversation's
+    /// transcript. Returns `false`, leaving the file alone, if the message is not in it.
+    pub fn truncate_at(&self, conversation_id: Uuid, message_id: Uuid) -> Result<bool> {
+        let path = self.transcript_path(conversation_id);
+        let contents = fs::read_to_string(&path)?;
+        let mut kept = 0;
+        for line in contents.split_inclusive('\n') {
+            let matches = serde_json::from_str::<MessageId>(line.trim())
+                .is_ok_and(|message| message.id == message_id);
+            if matches {
+                write_atomic(&path, &contents.as_bytes()[..kept])?;
+                return Ok(true);
+            }
+            kept += line.len();
+        }
+        Ok(false)
+    }
+
     pub fn persist_metadata(&self, conversation: &Conversation) -> Result<()> {
         let meta = ConversationMetadata {
             title: conversation.title.clone(),
+            settings: conversation.settings.clone(),
+            system_prompt: conversation.system_prompt.clone(),
+            usage: conversation
+                .usage()
+                .iter()
+                .map(|(model, usage)| UsageRecord {
+                    model: model.clone(),
+                    usage: usage.clone(),
+                })
+                .collect(),
         };
         let path = self.metadata_path(conversation.id);
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).ok();
         }
         let serialized = serde_json::to_vec_pretty(&meta)?;
-        fs::write(path, serialized)?;
+        write_atomic(&path, &serialized)?;
         Ok(())
     }
 
+    /// Conversations that have metadata on disk but no transcript, i.e. were never used.
+    pub fn metadata_only_ids(&self) -> Vec<Uuid> {
+        let Ok(entries) = fs::read_dir(self.conversation_dir()) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let id = name.to_str()?.strip_suffix(".meta.json")?;
+                Uuid::parse_str(id).ok()
+            })
+            .filter(|id| !self.transcript_path(*id).exists())
+            .collect()
+    }
+
     pub fn delete_conversation(&self, id: Uuid) -> Result<()> {
-        let transcript_path = self.conversation_dir().join(format!("{}.jsonl", id));
-        let _ = fs::remove_file(transcript_path);
+        let _ = fs::remove_file(self.transcript_path(id));
         let _ = fs::remove_file(self.metadata_path(id));
         Ok(())
     }
 
     pub fn persist_secret(&self, key: &str, secret: &str) -> Result<()> {
-        let path = self.root.join("secrets").join(format!("{}.txt", key));
-        let mut file = File::create(path)?;
-        file.write_all(secret.as_bytes())?;
-        Ok(())
+        write_atomic(&self.secret_path(key), secret.as_bytes())
+    }
+
+    pub fn load_secret(&self, key: &str) -> Result<Option<String>> {
+        match fs::read_to_string(self.secret_path(key)) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn delete_secret(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.secret_path(key)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Where [`crate::auth::AuthCoordinator`] keeps endpoint auth state, without tokens.
+    pub fn auth_state_path(&self) -> PathBuf {
+        self.root.join("auth.json")
+    }
+
+    fn secret_path(&self, key: &str) -> PathBuf {
+        self.root.join("secrets").join(format!("{}.txt", key))
     }
 }
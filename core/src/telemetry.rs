@@ -1,21 +1,106 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::path::PathBuf;
 use std::sync::OnceLock;
-use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter, Registry};
+use tracing::Level;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, EnvFilter, Registry};
 
 static SUBSCRIBER_GUARD: OnceLock<()> = OnceLock::new();
 
+/// Swaps the installed filter, with the directives the process started with.
+static FILTER_RELOAD: OnceLock<(reload::Handle<EnvFilter, Registry>, String)> = OnceLock::new();
+
+/// Set to anything but `0` or `false` to also write logs to [`LogFile::default_dir`].
+pub const LOG_FILE_VAR: &str = "PATINA_LOG_FILE";
+
+/// Daily log files kept before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+
+/// A log file rotated daily, written alongside the stderr output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFile {
+    pub directory: PathBuf,
+    /// Files kept in `directory`; older ones are deleted on rotation.
+    pub max_files: usize,
+}
+
+impl LogFile {
+    /// The platform log directory when [`LOG_FILE_VAR`] asks for a log file.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(LOG_FILE_VAR).ok()?;
+        let value = value.trim();
+        if value.is_empty() || value == "0" || value.eq_ignore_ascii_case("false") {
+            return None;
+        }
+        Some(Self {
+            directory: Self::default_dir()?,
+            max_files: MAX_LOG_FILES,
+        })
+    }
+
+    /// `logs/` under Patina's local data directory.
+    pub fn default_dir() -> Option<PathBuf> {
+        ProjectDirs::from("com", "Patina", "Patina").map(|dirs| dirs.data_local_dir().join("logs"))
+    }
+}
+
 /// Initialize the global tracing subscriber for the Patina workspace.
 ///
-/// The initialization is idempotent so that unit tests and binaries can call it
-/// multiple times without panicking.
-pub fn init_tracing(filter: EnvFilter) -> Result<()> {
+/// Logs always go to stderr, and also to `log_file` when given; [`set_log_level`] can
+/// change `filter` later. The initialization is
+/// idempotent so that unit tests and binaries can call it multiple times without
+/// panicking.
+pub fn init_tracing(filter: EnvFilter, log_file: Option<LogFile>) -> Result<()> {
     if SUBSCRIBER_GUARD.get().is_some() {
         return Ok(());
     }
 
-    let subscriber = Registry::default().with(filter).with(fmt::layer());
+    let file_layer = match &log_file {
+        Some(log_file) => {
+            std::fs::create_dir_all(&log_file.directory)
+                .with_context(|| format!("failed to create {}", log_file.directory.display()))?;
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix("patina")
+                .filename_suffix("log")
+                .max_log_files(log_file.max_files)
+                .build(&log_file.directory)
+                .context("failed to open the log file")?;
+            Some(fmt::layer().with_ansi(false).with_writer(appender))
+        }
+        None => None,
+    };
+    let startup = filter.to_string();
+    let (filter, handle) = reload::Layer::new(filter);
+    let subscriber = Registry::default()
+        .with(filter)
+        .with(fmt::layer())
+        .with(file_layer);
     tracing::subscriber::set_global_default(subscriber)?;
     SUBSCRIBER_GUARD.set(()).ok();
+    FILTER_RELOAD.set((handle, startup)).ok();
 
+    if let Some(log_file) = log_file {
+        tracing::info!(directory = %log_file.directory.display(), "writing logs to patina.*.log");
+    }
+    Ok(())
+}
+
+/// Log everything at `level` and above, from every target, or go back to the filter
+/// given to [`init_tracing`] with `None`. Does nothing before tracing is initialized.
+pub fn set_log_level(level: Option<Level>) -> Result<()> {
+    let Some((handle, startup)) = FILTER_RELOAD.get() else {
+        return Ok(());
+    };
+    let filter = match level {
+        Some(level) => EnvFilter::default().add_directive(LevelFilter::from_level(level).into()),
+        None => EnvFilter::new(startup),
+    };
+    handle
+        .reload(filter)
+        .context("failed to change the log filter")?;
+    tracing::info!(level = ?level, "log level changed");
     Ok(())
 }
@@ -0,0 +1,77 @@
+use parking_lot::Mutex;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+
+/// The bodies of the requests a [`fake_server_with`] answered, in order.
+#[derive(Debug, Clone, Default)]
+pub struct ReceivedRequests(Arc<Mutex<Vec<String>>>);
+
+impl ReceivedRequests {
+    pub fn len(&self) -> usize {
+        self.0.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().is_empty()
+    }
+
+    pub fn bodies(&self) -> Vec<String> {
+        self.0.lock().clone()
+    }
+
+    /// The body of request `index`, decoded as JSON.
+    pub fn json(&self, index: usize) -> serde_json::Value {
+        serde_json::from_str(&self.0.lock()[index]).expect("request body is JSON")
+    }
+}
+
+/// A local HTTP server answering one request per connection with the next canned
+/// status, extra header lines (each ending in `\r\n`) and body, then stopping.
+///
+/// Returns the server's `http://` address and the requests it receives.
+pub fn fake_server_with(responses: Vec<(u16, &'static str, String)>) -> (String, ReceivedRequests) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let address = listener.local_addr().expect("address");
+    let requests = ReceivedRequests::default();
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for (status, headers, body) in responses {
+            let Ok((stream, _)) = listener.accept() else {
+                return;
+            };
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+            }
+            let mut request = vec![0; content_length];
+            let _ = reader.read_exact(&mut request);
+            received
+                .0
+                .lock()
+                .push(String::from_utf8_lossy(&request).into_owned());
+            let reply = format!(
+                "HTTP/1.1 {status} Status\r\ncontent-type: application/json\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = reader.get_mut().write_all(reply.as_bytes());
+        }
+    });
+    (format!("http://{address}"), requests)
+}
+
+/// A chat completion body replying `content`, as OpenAI-compatible APIs send it.
+pub fn chat_completion(content: &str) -> String {
+    serde_json::json!({
+        "choices": [{ "message": { "role": "assistant", "content": content } }]
+    })
+    .to_string()
+}
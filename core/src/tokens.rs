@@ -0,0 +1,82 @@
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use tiktoken_rs::CoreBPE;
+
+/// Counts above this many remembered texts are dropped and counted afresh.
+const MAX_CACHED_COUNTS: usize = 4096;
+
+/// Counts tokens with the BPE encoding of a model.
+///
+/// Models tiktoken does not know, such as Azure deployment names or local Ollama
+/// models, are counted with `o200k_base`, which is close enough for a readout. The
+/// encoder is built once per process; counts are remembered per text so redrawing
+/// the same transcript every frame does not re-encode it.
+pub struct TokenCounter {
+    model: String,
+    bpe: &'static CoreBPE,
+    counts: Mutex<HashMap<u64, usize>>,
+}
+
+impl TokenCounter {
+    pub fn for_model(model: impl Into<String>) -> Self {
+        let model = model.into();
+        let bpe = tiktoken_rs::bpe_for_model(&model)
+            .unwrap_or_else(|_| tiktoken_rs::o200k_base_singleton());
+        Self {
+            model,
+            bpe,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The model whose encoding this counter uses.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        if text.is_empty() {
+            return 0;
+        }
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let key = hasher.finish();
+        if let Some(count) = self.counts.lock().get(&key) {
+            return *count;
+        }
+        let count = self.bpe.encode_with_special_tokens(text).len();
+        let mut counts = self.counts.lock();
+        if counts.len() >= MAX_CACHED_COUNTS {
+            counts.clear();
+        }
+        counts.insert(key, count);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_with_the_model_encoding() {
+        let counter = TokenCounter::for_model("gpt-4o");
+        assert_eq!(counter.count(""), 0);
+        assert_eq!(counter.count("hello world"), 2);
+        // Cached counts match fresh ones
+        assert_eq!(counter.count("hello world"), 2);
+        assert!(counter.count("fn main() { println!(\"{}\", 1 + 2); }") > 8);
+    }
+
+    #[test]
+    fn unknown_models_fall_back_to_o200k() {
+        let counter = TokenCounter::for_model("llama3.2");
+        assert_eq!(counter.model(), "llama3.2");
+        assert_eq!(
+            counter.count("日本語のテキスト"),
+            TokenCounter::for_model("gpt-4o").count("日本語のテキスト")
+        );
+    }
+}
@@ -31,10 +31,13 @@ fn build_app(theme: ThemeMode) -> PatinaEguiApp {
             .block_on(state.send_user_message("Seed snapshot conversation", "mock", 0.6))
             .expect("seed message");
     }
-    let mut settings = UiSettings::default();
-    settings.theme_mode = theme;
+    let settings = UiSettings {
+        theme_mode: theme,
+        ..UiSettings::default()
+    };
     let provider_config = ProviderConfig {
         available_models: vec!["gpt-4o".to_string()],
+        ..ProviderConfig::default()
     };
     PatinaEguiApp::new(
         Some(project),
@@ -0,0 +1,250 @@
+use chrono::{Duration, Utc};
+use patina_core::auth::{AuthCoordinator, AuthMode};
+use patina_core::llm::LlmDriver;
+use patina_core::mcp::TOKEN_REFRESH_INTERVAL;
+use patina_core::project::ProjectHandle;
+use patina_core::secrets::{FallbackSecretStore, FileSecretStore, KeyringSecretStore, SecretStore};
+use patina_core::state::AppState;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+
+fn test_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime")
+}
+
+fn coordinator(project: &ProjectHandle) -> AuthCoordinator {
+    let store = project.transcript_store();
+    AuthCoordinator::with_secrets(Arc::new(FileSecretStore::new(store.clone())))
+        .with_state_file(store.auth_state_path())
+}
+
+#[test]
+fn auth_state_survives_a_restart_without_tokens_on_disk() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Auth").expect("project");
+
+    let first = runtime
+        .block_on(coordinator(&project).negotiate("endpoint", AuthMode::ClientManaged))
+        .expect("negotiate");
+    let token = first.access_token.clone().expect("token");
+    let saved = fs::read_to_string(project.transcript_store().auth_state_path()).expect("saved");
+    assert!(saved.contains("endpoint"));
+    assert!(!saved.contains(&token), "{saved}");
+
+    let restarted = coordinator(&project);
+    assert_eq!(
+        restarted.require("endpoint").expect("reloaded").expires_at,
+        first.expires_at
+    );
+    let reused = runtime
+        .block_on(restarted.negotiate("endpoint", AuthMode::ClientManaged))
+        .expect("negotiate");
+    assert_eq!(reused.access_token, first.access_token);
+    assert_eq!(reused.refresh_token, first.refresh_token);
+}
+
+#[test]
+fn expired_saved_state_is_refreshed() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Expired").expect("project");
+    let auth = coordinator(&project);
+    let mut state = runtime
+        .block_on(auth.negotiate("endpoint", AuthMode::ServerManaged))
+        .expect("negotiate");
+    let stale = state.access_token.clone();
+    state.expires_at = Some(Utc::now() - Duration::minutes(5));
+    auth.upsert("endpoint", state);
+
+    let refreshed = runtime
+        .block_on(coordinator(&project).negotiate("endpoint", AuthMode::ServerManaged))
+        .expect("negotiate");
+    assert_ne!(refreshed.access_token, stale);
+    assert!(refreshed.expires_at.expect("expiry") > Utc::now());
+}
+
+#[test]
+fn background_refresh_renews_only_expiring_tokens_and_stops_on_cancel() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Refresh").expect("project");
+    let store = project.transcript_store();
+    let secrets = Arc::new(FileSecretStore::new(store.clone()));
+    let auth = AuthCoordinator::with_secrets(secrets.clone());
+    let fresh = runtime
+        .block_on(auth.negotiate("fresh", AuthMode::ServerManaged))
+        .expect("negotiate");
+    let mut expiring = runtime
+        .block_on(auth.negotiate("expiring", AuthMode::ClientManaged))
+        .expect("negotiate");
+    let stale = expiring.access_token.clone();
+    expiring.expires_at = Some(Utc::now() + Duration::seconds(30));
+    auth.upsert("expiring", expiring);
+
+    assert_eq!(
+        runtime.block_on(auth.refresh_expiring()),
+        vec!["expiring".to_string()]
+    );
+    let renewed = auth.require("expiring").expect("state");
+    assert_ne!(renewed.access_token, stale);
+    assert_eq!(
+        secrets.load("expiring").expect("load"),
+        renewed.access_token
+    );
+    assert_eq!(
+        auth.require("fresh").expect("state").access_token,
+        fresh.access_token
+    );
+
+    let cancel = CancellationToken::new();
+    runtime.block_on(async {
+        let task = auth.spawn_refresh_task(std::time::Duration::from_millis(5), cancel.clone());
+        let mut state = auth.require("fresh").expect("state");
+        state.expires_at = Some(Utc::now());
+        auth.upsert("fresh", state);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        cancel.cancel();
+        task.await.expect("task stops");
+    });
+    assert_ne!(
+        auth.require("fresh").expect("state").access_token,
+        fresh.access_token
+    );
+}
+
+/// A keyring whose service is missing: every call fails.
+struct UnavailableKeyring;
+
+impl SecretStore for UnavailableKeyring {
+    fn store(&self, _: &str, _: &str) -> anyhow::Result<()> {
+        anyhow::bail!("platform secure storage failure: no Secret Service")
+    }
+
+    fn load(&self, _: &str) -> anyhow::Result<Option<String>> {
+        anyhow::bail!("platform secure storage failure: no Secret Service")
+    }
+}
+
+#[test]
+fn tokens_go_to_secret_files_when_the_keyring_is_unavailable() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "NoKeyring").expect("project");
+    let files = FileSecretStore::new(project.transcript_store());
+    let secrets = FallbackSecretStore::new(Arc::new(UnavailableKeyring), files.clone());
+    assert!(!secrets.is_falling_back());
+
+    secrets
+        .store("endpoint", "token")
+        .expect("store falls back");
+    assert!(secrets.is_falling_back());
+    assert_eq!(
+        files.load("endpoint").expect("load").as_deref(),
+        Some("token")
+    );
+    assert_eq!(
+        secrets.load("endpoint").expect("load").as_deref(),
+        Some("token")
+    );
+}
+
+/// A working keyring kept in memory.
+#[derive(Default)]
+struct MemoryKeyring(Mutex<HashMap<String, String>>);
+
+impl SecretStore for MemoryKeyring {
+    fn store(&self, key: &str, secret: &str) -> anyhow::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.0.lock().unwrap().get(key).cloned())
+    }
+}
+
+#[test]
+fn secret_files_are_moved_into_a_working_keyring() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Legacy").expect("project");
+    let files = FileSecretStore::new(project.transcript_store());
+    files.store("endpoint", "token").expect("legacy file");
+    let keyring = Arc::new(MemoryKeyring::default());
+    let secrets = FallbackSecretStore::new(keyring.clone(), files.clone());
+
+    assert_eq!(
+        secrets.load("endpoint").expect("load").as_deref(),
+        Some("token")
+    );
+    assert!(!secrets.is_falling_back());
+    assert_eq!(
+        keyring.load("endpoint").expect("keyring").as_deref(),
+        Some("token")
+    );
+    assert_eq!(files.load("endpoint").expect("load"), None);
+    assert!(!project
+        .transcript_store()
+        .root()
+        .join("secrets")
+        .join("endpoint.txt")
+        .exists());
+}
+
+#[test]
+fn keyring_accounts_are_scoped_to_the_project() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let first = ProjectHandle::create(&temp_dir.path().join("first"), "First").expect("project");
+    let second = ProjectHandle::create(&temp_dir.path().join("second"), "Second").expect("project");
+    let account = |project: &ProjectHandle| {
+        KeyringSecretStore::for_project(project.transcript_store().root()).account("files")
+    };
+    assert_ne!(account(&first), account(&second));
+    assert!(account(&first).starts_with("files@"), "{}", account(&first));
+}
+
+/// Whether a token expiring in 30 seconds was renewed two refresh intervals later by
+/// a project state built with `background_refresh`.
+fn expiring_token_renewed(background_refresh: bool) -> bool {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .expect("runtime");
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Refresh").expect("project");
+    runtime.block_on(async {
+        let driver = LlmDriver::fake().await;
+        let state = AppState::new(project, driver).with_background_refresh(background_refresh);
+        let auth = state.mcp_auth();
+        let mut expiring = auth
+            .negotiate("expiring", AuthMode::ClientManaged)
+            .await
+            .expect("negotiate");
+        let stale = expiring.access_token.clone();
+        expiring.expires_at = Some(Utc::now() + Duration::seconds(30));
+        auth.upsert("expiring", expiring);
+
+        tokio::time::sleep(TOKEN_REFRESH_INTERVAL * 2).await;
+        let current = auth.require("expiring").expect("state");
+        current.access_token != stale && !current.expires_soon()
+    })
+}
+
+#[test]
+fn project_state_refreshes_near_expiry_tokens_in_the_background() {
+    assert!(expiring_token_renewed(true));
+}
+
+#[test]
+fn background_refresh_is_off_unless_asked_for() {
+    assert!(!expiring_token_renewed(false));
+}
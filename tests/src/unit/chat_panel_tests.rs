@@ -0,0 +1,242 @@
+use egui::{CentralPanel, Context, Pos2, RawInput, Rect, Vec2};
+use egui_commonmark::CommonMarkCache;
+use patina::config::Pricing;
+use patina::highlight::CodeHighlighter;
+use patina::ui::{
+    collapsed_preview, message_clipboard_text, ChatPanel, ChatPanelState, ThemePalette,
+};
+use patina_core::state::{
+    ChatMessage, ChatParameters, Conversation, MessageRole, ToolCall, ToolCallStatus,
+};
+use patina_core::TokenCounter;
+
+fn long_conversation(len: usize) -> Conversation {
+    let mut conversation = Conversation::new();
+    conversation.messages = (0..len)
+        .map(|index| {
+            let role = if index % 2 == 0 {
+                MessageRole::User
+            } else {
+                MessageRole::Assistant
+            };
+            ChatMessage::new(role, format!("Message {index}\n\nSome **markdown** body."))
+        })
+        .collect();
+    conversation
+}
+
+#[test]
+fn only_visible_messages_are_laid_out_in_long_conversations() {
+    let conversation = long_conversation(5_000);
+    let ctx = Context::default();
+    let palette = ThemePalette::for_dark();
+    let mut state = ChatPanelState::default();
+    let mut markdown_cache = CommonMarkCache::default();
+    let mut highlighter = CodeHighlighter::default();
+    let tokens = TokenCounter::for_model("gpt-4o");
+    let defaults = ChatParameters {
+        model: "gpt-4o".into(),
+        temperature: 0.7,
+        max_request_messages: 0,
+    };
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(1024.0, 768.0))),
+        ..RawInput::default()
+    };
+
+    // Every message is in the history, as after scrolling all the way up.
+    state.reset_if_needed(conversation.id);
+    state.visible_limit = conversation.messages.len();
+    for _ in 0..3 {
+        let _ = ctx.run(input.clone(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                ChatPanel::show(
+                    ui,
+                    &palette,
+                    &mut state,
+                    &conversation,
+                    None,
+                    &mut markdown_cache,
+                    &mut highlighter,
+                    &tokens,
+                    &defaults,
+                    &[],
+                    &Pricing::default(),
+                    false,
+                    false,
+                );
+            });
+        });
+        let rendered = state.rendered_messages();
+        assert!(rendered.len() <= 20, "laid out {rendered:?}");
+    }
+    // Stuck to the bottom: the newest messages are the ones on screen.
+    assert_eq!(state.rendered_messages().end, 5_000);
+}
+
+#[test]
+fn reaching_the_top_of_the_history_asks_for_older_messages() {
+    let conversation = long_conversation(100);
+    let ctx = Context::default();
+    let palette = ThemePalette::for_dark();
+    let mut state = ChatPanelState::default();
+    let mut markdown_cache = CommonMarkCache::default();
+    let mut highlighter = CodeHighlighter::default();
+    let tokens = TokenCounter::for_model("gpt-4o");
+    let defaults = ChatParameters {
+        model: "gpt-4o".into(),
+        temperature: 0.7,
+        max_request_messages: 0,
+    };
+    let mut show = |state: &mut ChatPanelState| {
+        let mut output = None;
+        let _ = ctx.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                output = Some(ChatPanel::show(
+                    ui,
+                    &palette,
+                    state,
+                    &conversation,
+                    None,
+                    &mut markdown_cache,
+                    &mut highlighter,
+                    &tokens,
+                    &defaults,
+                    &[],
+                    &Pricing::default(),
+                    false,
+                    false,
+                ));
+            });
+        });
+        output.expect("shown")
+    };
+
+    state.reset_if_needed(conversation.id);
+    state.visible_limit = 2;
+    // Two short messages fit on screen, so the history is at its top.
+    assert!(show(&mut state).load_older);
+    state.request_more(conversation.messages.len());
+    assert_eq!(state.visible_limit, 42);
+    state.request_more(conversation.messages.len());
+    state.request_more(conversation.messages.len());
+    assert_eq!(state.visible_limit, 100);
+
+    state.visible_limit = 100;
+    assert!(!show(&mut state).load_older);
+}
+
+#[test]
+fn long_messages_collapse_until_expanded_in_their_conversation() {
+    let numbered = |count: usize| {
+        (1..=count)
+            .map(|line| format!("line {line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(collapsed_preview(&numbered(40)), None);
+    assert_eq!(collapsed_preview("short"), None);
+    let long = numbered(41);
+    let preview = collapsed_preview(&long).expect("collapsed");
+    assert_eq!(preview.lines().count(), 20);
+    assert!(preview.ends_with("line 20"));
+
+    let mut conversation = Conversation::new();
+    conversation.add_message(ChatMessage::new(MessageRole::Assistant, long));
+    conversation.add_message(ChatMessage::new(MessageRole::User, "short"));
+    let (long, short) = (&conversation.messages[0], &conversation.messages[1]);
+    let mut state = ChatPanelState::default();
+    state.reset_if_needed(conversation.id);
+    assert!(!state.is_expanded(long));
+    assert!(state.is_expanded(short));
+
+    state.toggle_expanded(long.id);
+    assert!(state.is_expanded(long));
+    state.toggle_expanded(long.id);
+    assert!(!state.is_expanded(long));
+
+    // A search result is expanded so the match is visible, until the chat is left.
+    state.scroll_to_message(conversation.id, long.id);
+    assert!(state.is_expanded(long));
+    state.reset_if_needed(Conversation::new().id);
+    assert!(!state.is_expanded(long));
+}
+
+#[test]
+fn a_search_hit_in_another_conversation_opens_expanded() {
+    let long = (1..=60)
+        .map(|line| format!("line {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let current = Conversation::new();
+    let mut other = Conversation::new();
+    other.add_message(ChatMessage::new(MessageRole::Assistant, long));
+    let hit = &other.messages[0];
+    assert!(collapsed_preview(&hit.content).is_some());
+
+    let ctx = Context::default();
+    let palette = ThemePalette::for_dark();
+    let mut state = ChatPanelState::default();
+    let mut markdown_cache = CommonMarkCache::default();
+    let mut highlighter = CodeHighlighter::default();
+    let tokens = TokenCounter::for_model("gpt-4o");
+    let defaults = ChatParameters {
+        model: "gpt-4o".into(),
+        temperature: 0.7,
+        max_request_messages: 0,
+    };
+    let mut show = |state: &mut ChatPanelState, conversation: &Conversation| {
+        let _ = ctx.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                ChatPanel::show(
+                    ui,
+                    &palette,
+                    state,
+                    conversation,
+                    None,
+                    &mut markdown_cache,
+                    &mut highlighter,
+                    &tokens,
+                    &defaults,
+                    &[],
+                    &Pricing::default(),
+                    false,
+                    false,
+                );
+            });
+        });
+    };
+    show(&mut state, &current);
+
+    // As the app does when a message hit in the sidebar is picked.
+    state.scroll_to_message(other.id, hit.id);
+    show(&mut state, &other);
+    assert!(state.is_expanded(hit));
+}
+
+#[test]
+fn copied_messages_keep_markdown_and_optionally_tool_calls() {
+    let mut message = ChatMessage::new(MessageRole::Assistant, "Use **bold** and `code`.\n");
+    assert_eq!(
+        message_clipboard_text(&message, true),
+        "Use **bold** and `code`.\n"
+    );
+
+    message.tool_calls.push(ToolCall {
+        id: uuid::Uuid::new_v4(),
+        call_id: None,
+        name: "get_weather".into(),
+        arguments: serde_json::json!({ "city": "Lisbon" }),
+        status: ToolCallStatus::Completed,
+        response: None,
+    });
+    assert_eq!(
+        message_clipboard_text(&message, false),
+        "Use **bold** and `code`.\n"
+    );
+    let with_calls = message_clipboard_text(&message, true);
+    assert!(with_calls.starts_with("Use **bold** and `code`.\n\n```json\n["));
+    assert!(with_calls.contains("\"get_weather\""));
+    assert!(with_calls.contains("\"Lisbon\""));
+    assert!(with_calls.ends_with("]\n```"));
+}
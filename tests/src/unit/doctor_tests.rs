@@ -0,0 +1,30 @@
+use patina::doctor::{Check, Report, Status};
+
+#[test]
+fn reports_tag_each_check_and_count_problems() {
+    let report = Report {
+        checks: vec![
+            Check {
+                status: Status::Ok,
+                label: "patina.yaml",
+                detail: "/home/me/.config/patina/patina.yaml".into(),
+            },
+            Check {
+                status: Status::Problem,
+                label: "API key",
+                detail: "missing; set OPENAI_API_KEY or add it to patina.yaml".into(),
+            },
+            Check {
+                status: Status::Info,
+                label: "connection",
+                detail: "not checked".into(),
+            },
+        ],
+    };
+    assert_eq!(report.problems(), 1);
+    let text = report.to_string();
+    assert!(text.starts_with("Patina "), "{text}");
+    assert!(text.contains("[ok]   patina.yaml: /home/me/.config/patina/patina.yaml\n"));
+    assert!(text.contains("[FAIL] API key: missing;"));
+    assert!(text.contains("[info] connection: not checked\n"));
+}
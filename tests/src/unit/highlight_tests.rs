@@ -0,0 +1,58 @@
+use egui::{Color32, FontId, TextFormat};
+use patina::highlight::{split_code_blocks, CodeHighlighter, Segment};
+
+#[test]
+fn messages_split_at_fenced_code_blocks() {
+    let content =
+        "Try this:\n\n```rust title=main\nfn main() {}\n```\nThen ~~~\n~~~~\nplain\n~~~~\n";
+    assert_eq!(
+        split_code_blocks(content),
+        vec![
+            Segment::Markdown("Try this:\n\n"),
+            Segment::Code {
+                language: "rust",
+                code: "fn main() {}\n",
+            },
+            Segment::Markdown("Then ~~~\n"),
+            Segment::Code {
+                language: "",
+                code: "plain\n",
+            },
+        ]
+    );
+}
+
+#[test]
+fn unclosed_fences_run_to_the_end() {
+    assert_eq!(
+        split_code_blocks("```py\nprint(1)\n``` not a close\n"),
+        vec![Segment::Code {
+            language: "py",
+            code: "print(1)\n``` not a close\n",
+        }]
+    );
+    assert_eq!(
+        split_code_blocks("no code"),
+        vec![Segment::Markdown("no code")]
+    );
+}
+
+#[test]
+fn known_languages_are_colored_and_unknown_ones_stay_plain() {
+    let highlighter = CodeHighlighter::default();
+    let plain = TextFormat::simple(FontId::monospace(12.0), Color32::WHITE);
+    let code = "fn main() { let x = 1; }";
+
+    let job = highlighter.highlight("rust", code, true, plain.clone());
+    assert_eq!(job.text, code);
+    let colors: std::collections::HashSet<_> = job
+        .sections
+        .iter()
+        .map(|section| section.format.color)
+        .collect();
+    assert!(colors.len() > 1, "expected several colors, got {colors:?}");
+
+    let job = highlighter.highlight("no-such-language", code, true, plain);
+    assert_eq!(job.sections.len(), 1);
+    assert_eq!(job.sections[0].format.color, Color32::WHITE);
+}
@@ -0,0 +1,92 @@
+use egui::{Context, Event, Key, Modifiers, RawInput};
+use patina::images::{
+    decode_data_url, PasteShortcutBridge, PastedImage, MAX_ATTACHED_IMAGE_SIDE,
+    MAX_PASTED_IMAGE_SIDE, PASTE_SHORTCUT,
+};
+
+#[test]
+fn decodes_base64_data_urls() {
+    let (bytes, mime) = decode_data_url("data:image/png;base64,iVBORw0KGgo=").expect("decode");
+    assert_eq!(bytes, b"\x89PNG\r\n\x1a\n");
+    assert_eq!(mime.as_deref(), Some("image/png"));
+
+    assert!(decode_data_url("data:image/svg+xml,<svg/>").is_err());
+    assert!(decode_data_url("data:image/png;base64,not base64!").is_err());
+}
+
+fn decoded_size(image: &PastedImage) -> (u32, u32) {
+    let (bytes, mime) = decode_data_url(&image.attachment.data_url()).expect("data URL");
+    assert_eq!(mime.as_deref(), Some("image/png"));
+    // The IHDR chunk right after the signature holds the width and height.
+    assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    let field = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+    (field(16), field(20))
+}
+
+#[test]
+fn pasted_images_are_encoded_as_png_with_a_thumbnail() {
+    let image = PastedImage::from_rgba(300, 200, vec![200; 300 * 200 * 4]).expect("small image");
+    assert!(!image.downscaled);
+    assert_eq!(decoded_size(&image), (300, 200));
+    assert_eq!(image.thumbnail.size, [96, 64]);
+}
+
+#[test]
+fn oversized_pasted_images_are_scaled_down_or_rejected() {
+    let wide = PastedImage::from_rgba(4096, 1024, vec![0; 4096 * 1024 * 4]).expect("wide image");
+    assert!(wide.downscaled);
+    assert_eq!(decoded_size(&wide), (MAX_ATTACHED_IMAGE_SIDE, 512));
+
+    let huge = MAX_PASTED_IMAGE_SIDE + 1;
+    let err = PastedImage::from_rgba(huge, 1, vec![0; huge * 4])
+        .err()
+        .expect("too large");
+    assert!(err.contains("too large"), "{err}");
+    assert!(PastedImage::from_rgba(10, 10, vec![0; 12]).is_err());
+    assert!(PastedImage::from_rgba(0, 10, Vec::new()).is_err());
+}
+
+fn paste_key(pressed: bool) -> Event {
+    Event::Key {
+        key: Key::V,
+        physical_key: None,
+        pressed,
+        repeat: false,
+        modifiers: Modifiers::COMMAND,
+    }
+}
+
+/// Run one frame through the bridge and report whether the paste shortcut was pressed.
+fn shortcut_pressed(ctx: &Context, bridge: &mut PasteShortcutBridge, events: Vec<Event>) -> bool {
+    let mut pressed = false;
+    let input = RawInput {
+        events,
+        ..RawInput::default()
+    };
+    let _ = ctx.run(input, |ctx| {
+        bridge.restore(ctx);
+        pressed = ctx.input_mut(|i| i.consume_shortcut(&PASTE_SHORTCUT));
+    });
+    pressed
+}
+
+#[test]
+fn the_swallowed_paste_press_is_restored_only_without_a_text_paste() {
+    let ctx = Context::default();
+    let mut bridge = PasteShortcutBridge::default();
+
+    // The native backend drops the press when the clipboard holds no text.
+    assert!(shortcut_pressed(&ctx, &mut bridge, vec![paste_key(false)]));
+
+    // With text, the press became a paste and must not be repeated on release.
+    assert!(!shortcut_pressed(
+        &ctx,
+        &mut bridge,
+        vec![Event::Paste("notes".into())]
+    ));
+    assert!(!shortcut_pressed(&ctx, &mut bridge, vec![paste_key(false)]));
+
+    // A backend that delivers the press is left alone.
+    assert!(shortcut_pressed(&ctx, &mut bridge, vec![paste_key(true)]));
+    assert!(!shortcut_pressed(&ctx, &mut bridge, vec![paste_key(false)]));
+}
@@ -0,0 +1,135 @@
+use egui::{
+    CentralPanel, Context, CursorIcon, Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect,
+    Vec2,
+};
+use patina::config::ProviderConfig;
+use patina::ui::{InputBar, InputBarOutput, InputBarState, ThemePalette};
+use patina_core::TokenCounter;
+
+/// Show the input bar for one frame with `events` and return what it asked for.
+fn frame(ctx: &Context, state: &mut InputBarState, events: Vec<Event>) -> InputBarOutput {
+    frame_with_cursor(ctx, state, events).0
+}
+
+/// Like [`frame`], also returning the mouse cursor the frame asked for.
+fn frame_with_cursor(
+    ctx: &Context,
+    state: &mut InputBarState,
+    events: Vec<Event>,
+) -> (InputBarOutput, CursorIcon) {
+    let palette = ThemePalette::for_dark();
+    let provider = ProviderConfig {
+        available_models: vec!["gpt-4o".to_string()],
+        ..ProviderConfig::default()
+    };
+    let tokens = TokenCounter::for_model("gpt-4o");
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(1024.0, 768.0))),
+        events,
+        ..RawInput::default()
+    };
+    let mut output = InputBarOutput::default();
+    let full_output = ctx.run(input, |ctx| {
+        CentralPanel::default().show(ctx, |ui| {
+            output = InputBar::show(ui, state, &palette, &provider, true, &tokens);
+        });
+    });
+    (output, full_output.platform_output.cursor_icon)
+}
+
+/// Click into the message box at the top of the bar.
+fn focus_message_box(ctx: &Context, state: &mut InputBarState) {
+    let at = Pos2::new(200.0, 40.0);
+    let button = |pressed| Event::PointerButton {
+        pos: at,
+        button: PointerButton::Primary,
+        pressed,
+        modifiers: Modifiers::NONE,
+    };
+    frame(ctx, state, vec![Event::PointerMoved(at)]);
+    frame(ctx, state, vec![button(true)]);
+    frame(ctx, state, vec![button(false)]);
+}
+
+fn paste_shortcut() -> Event {
+    Event::Key {
+        key: Key::V,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers: Modifiers::COMMAND,
+    }
+}
+
+#[test]
+fn the_paste_shortcut_asks_for_the_clipboard_image() {
+    let ctx = Context::default();
+    let mut state = InputBarState::new("gpt-4o", 0.7, false);
+    frame(&ctx, &mut state, Vec::new());
+    assert!(!frame(&ctx, &mut state, vec![paste_shortcut()]).paste_image);
+
+    focus_message_box(&ctx, &mut state);
+    let output = frame(&ctx, &mut state, vec![paste_shortcut()]);
+    assert!(output.paste_image);
+    assert!(state.draft.is_empty());
+
+    // A text paste without the shortcut is left to the message box.
+    let output = frame(&ctx, &mut state, vec![Event::Paste("notes".into())]);
+    assert!(!output.paste_image);
+    assert_eq!(state.draft, "notes");
+
+    state.read_only = true;
+    assert!(!frame(&ctx, &mut state, vec![paste_shortcut()]).paste_image);
+}
+
+#[test]
+fn the_history_limit_is_reported_once_a_drag_ends() {
+    let ctx = Context::default();
+    let mut state = InputBarState::new("gpt-4o", 0.7, false);
+    frame(&ctx, &mut state, Vec::new());
+    // The parameter row shows the drag cursor over the temperature slider, then over
+    // the history and token limits side by side: take the start of the second stretch.
+    let history = (0..192)
+        .find_map(|row| {
+            let y = row as f32 * 4.0;
+            let mut stretches: Vec<(f32, f32)> = Vec::new();
+            for column in 0..256 {
+                let at = Pos2::new(column as f32 * 4.0, y);
+                let cursor = frame_with_cursor(&ctx, &mut state, vec![Event::PointerMoved(at)]).1;
+                if cursor != CursorIcon::ResizeHorizontal {
+                    continue;
+                }
+                match stretches.last_mut() {
+                    Some((_, end)) if *end == at.x - 4.0 => *end = at.x,
+                    _ => stretches.push((at.x, at.x)),
+                }
+            }
+            let (start, _) = *stretches.get(1)?;
+            Some(Pos2::new(start + 8.0, y + 4.0))
+        })
+        .expect("history limit");
+    let button = |at, pressed| Event::PointerButton {
+        pos: at,
+        button: PointerButton::Primary,
+        pressed,
+        modifiers: Modifiers::NONE,
+    };
+
+    let output = frame(&ctx, &mut state, vec![button(history, true)]);
+    assert_eq!(output.history_limit_changed, None);
+    let mut at = history;
+    for _ in 0..5 {
+        at.x += 20.0;
+        let output = frame(&ctx, &mut state, vec![Event::PointerMoved(at)]);
+        assert_eq!(output.history_limit_changed, None);
+    }
+    assert!(state.max_request_messages > 0);
+    assert_eq!(state.max_tokens, 0);
+    let output = frame(&ctx, &mut state, vec![button(at, false)]);
+    assert_eq!(
+        output.history_limit_changed,
+        Some(state.max_request_messages)
+    );
+    let output = frame(&ctx, &mut state, Vec::new());
+    assert_eq!(output.history_limit_changed, None);
+}
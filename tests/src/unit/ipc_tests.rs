@@ -0,0 +1,34 @@
+use patina::ipc::{encode_command, parse_command, IpcCommand};
+use std::path::PathBuf;
+
+#[test]
+fn parses_supported_commands() {
+    assert_eq!(
+        parse_command(r#"{"command":"open-project","path":"/work/Notes"}"#).expect("open"),
+        IpcCommand::OpenProject {
+            path: PathBuf::from("/work/Notes")
+        }
+    );
+    assert_eq!(
+        parse_command("  {\"command\":\"new-chat\"}\n").expect("new chat"),
+        IpcCommand::NewChat
+    );
+}
+
+#[test]
+fn rejects_malformed_commands() {
+    assert!(parse_command(r#"{"command":"open-project"}"#).is_err());
+    assert!(parse_command(r#"{"command":"self-destruct"}"#).is_err());
+    assert!(parse_command("new-chat").is_err());
+}
+
+#[test]
+fn encoded_commands_parse_back() {
+    let command = IpcCommand::OpenProject {
+        path: PathBuf::from("/tmp/Project.pat"),
+    };
+    let line = encode_command(&command);
+    assert!(line.ends_with('\n'));
+    assert_eq!(line.matches('\n').count(), 1);
+    assert_eq!(parse_command(&line).expect("round trip"), command);
+}
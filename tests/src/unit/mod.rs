@@ -1 +1,17 @@
+mod auth_tests;
+mod chat_panel_tests;
+mod doctor_tests;
+mod highlight_tests;
+mod images_tests;
+mod input_bar_tests;
+mod ipc_tests;
+mod notify_tests;
+mod palette_tests;
+mod project_tests;
+mod settings_tests;
+mod sidebar_tests;
 mod state_tests;
+mod streaming_tests;
+mod telemetry_tests;
+mod theme_tests;
+mod ui_settings_tests;
@@ -0,0 +1,15 @@
+use patina::notify::reply_excerpt;
+
+#[test]
+fn reply_excerpts_use_the_first_line_and_are_capped() {
+    assert_eq!(
+        reply_excerpt("\n\n  Done: 3 files changed.\nDetails follow."),
+        "Done: 3 files changed."
+    );
+    assert_eq!(reply_excerpt(""), "");
+
+    let long = "word ".repeat(60);
+    let excerpt = reply_excerpt(&long);
+    assert_eq!(excerpt.chars().count(), 120);
+    assert!(excerpt.ends_with('…'));
+}
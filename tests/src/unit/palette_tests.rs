@@ -0,0 +1,66 @@
+use patina::palette::{fuzzy_score, matching_actions, PaletteAction, PaletteContext};
+
+#[test]
+fn fuzzy_score_matches_subsequences_case_insensitively() {
+    assert!(fuzzy_score("nc", "New chat").is_some());
+    assert!(fuzzy_score("NEW", "New chat").is_some());
+    assert!(fuzzy_score("set", "Open settings").is_some());
+    assert_eq!(fuzzy_score("xyz", "New chat"), None);
+    // Order matters: characters must appear in sequence.
+    assert_eq!(fuzzy_score("tn", "New chat"), None);
+    assert_eq!(fuzzy_score("", "Anything"), Some(0));
+}
+
+#[test]
+fn fuzzy_score_prefers_contiguous_and_word_start_matches() {
+    let contiguous = fuzzy_score("theme", "Theme: Dark").unwrap();
+    let scattered = fuzzy_score("theme", "Toggle the remote images").unwrap();
+    assert!(contiguous > scattered);
+
+    let word_start = fuzzy_score("chat", "New chat").unwrap();
+    let mid_word = fuzzy_score("chat", "Nochat").unwrap();
+    assert!(word_start > mid_word);
+}
+
+#[test]
+fn matching_actions_rank_best_match_first() {
+    let context = PaletteContext {
+        project_open: true,
+        read_only: false,
+        chat_active: true,
+    };
+    let results = matching_actions("new chat", context);
+    assert_eq!(results[0].action, PaletteAction::NewChat);
+
+    let results = matching_actions("dark", context);
+    assert_eq!(
+        results[0].action,
+        PaletteAction::Theme(patina::ui::ThemeMode::Dark)
+    );
+}
+
+#[test]
+fn matching_actions_respect_context() {
+    let no_project = PaletteContext::default();
+    let offered: Vec<_> = matching_actions("", no_project)
+        .iter()
+        .map(|entry| entry.action)
+        .collect();
+    assert!(offered.contains(&PaletteAction::OpenProject));
+    assert!(!offered.contains(&PaletteAction::NewChat));
+    assert!(!offered.contains(&PaletteAction::TogglePinChat));
+    assert!(!offered.contains(&PaletteAction::FocusSearch));
+
+    let read_only = PaletteContext {
+        project_open: true,
+        read_only: true,
+        chat_active: true,
+    };
+    let offered: Vec<_> = matching_actions("", read_only)
+        .iter()
+        .map(|entry| entry.action)
+        .collect();
+    assert!(!offered.contains(&PaletteAction::NewChat));
+    assert!(!offered.contains(&PaletteAction::PruneEmptyChats));
+    assert!(offered.contains(&PaletteAction::CopyChatJson));
+}
@@ -0,0 +1,503 @@
+use patina_core::auth::{AuthCoordinator, AuthMode};
+use patina_core::llm::LlmDriver;
+use patina_core::project::ProjectHandle;
+use patina_core::secrets::{FileSecretStore, SecretStore};
+use patina_core::state::{AppState, ChatMessage, Conversation, MessageRole};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn test_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime")
+}
+
+#[cfg(unix)]
+#[test]
+fn opens_unwritable_project_read_only() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let temp_dir = TempDir::new().expect("temp dir");
+    if fs::metadata(temp_dir.path()).expect("metadata").uid() == 0 {
+        // Root bypasses permission bits, so an unwritable directory cannot be made.
+        eprintln!("skipped: running as root");
+        return;
+    }
+    let project = ProjectHandle::create(temp_dir.path(), "ReadOnly").expect("project");
+    let internal = project.paths().internal.clone();
+    fs::set_permissions(&internal, fs::Permissions::from_mode(0o555)).expect("chmod");
+
+    let reopened = ProjectHandle::open(&project.paths().root).expect("open read-only");
+    fs::set_permissions(&internal, fs::Permissions::from_mode(0o755)).expect("chmod");
+    assert!(reopened.is_read_only());
+}
+
+#[test]
+fn read_only_projects_refuse_changes() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "ReadOnly").expect("project");
+    let runtime = test_runtime();
+    let driver = runtime.block_on(LlmDriver::fake());
+    let writable = AppState::new(project.clone(), driver.clone());
+    runtime
+        .block_on(writable.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    let conversation = writable.active_conversation().expect("conversation").id;
+
+    let reopened = ProjectHandle::open(&project.paths().root)
+        .expect("open")
+        .with_read_only();
+    assert!(reopened.is_read_only());
+    let state = AppState::new(reopened, driver.clone());
+    assert!(state.is_read_only());
+    let result = runtime.block_on(state.send_user_message("again", "mock", 0.6));
+    assert!(result.is_err());
+    assert!(state.rename_conversation(conversation, "Renamed").is_err());
+    assert!(!state.delete_conversation(conversation).unwrap_or(false));
+
+    let after = AppState::new(
+        ProjectHandle::open(&project.paths().root).expect("open"),
+        driver,
+    );
+    let summaries = after.conversation_summaries();
+    assert_eq!(summaries.len(), 1);
+    assert_ne!(summaries[0].title, "Renamed");
+}
+
+#[test]
+fn opens_writable_project_normally() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Writable").expect("project");
+    let reopened = ProjectHandle::open(&project.paths().root).expect("open");
+    assert!(!reopened.is_read_only());
+}
+
+#[test]
+fn save_as_copies_project_and_skips_secrets() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Original").expect("project");
+    let runtime = test_runtime();
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    project
+        .transcript_store()
+        .persist_secret("token", "hunter2")
+        .expect("secret");
+    fs::write(project.paths().root.join("notes.md"), "# notes").expect("notes");
+
+    let copy = project
+        .save_as(&temp_dir.path().join("Branch"), "Branch", false)
+        .expect("save as");
+    assert_eq!(copy.name(), "Branch");
+    assert!(copy.paths().pat_file.ends_with("Branch.pat"));
+    assert!(!copy.paths().root.join("Original.pat").exists());
+    assert!(copy.paths().root.join("notes.md").exists());
+    assert!(!copy.paths().internal.join("secrets/token.txt").exists());
+
+    let reopened = ProjectHandle::open(&copy.paths().root).expect("reopen");
+    let conversations = reopened
+        .transcript_store()
+        .load_conversations()
+        .expect("load conversations");
+    assert_eq!(conversations.len(), 1);
+    assert_eq!(conversations[0].messages.len(), 2);
+
+    let again = project.save_as(&temp_dir.path().join("Branch"), "Branch", false);
+    assert!(again.is_err());
+}
+
+#[test]
+fn exports_conversations_as_ndjson_and_skips_malformed_ones() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Analysis").expect("project");
+    let runtime = test_runtime();
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    for prompt in ["first", "second"] {
+        state.start_new_conversation();
+        runtime
+            .block_on(state.send_user_message(prompt, "mock", 0.6))
+            .expect("send");
+    }
+    fs::write(
+        project
+            .paths()
+            .conversations
+            .join(format!("{}.jsonl", uuid::Uuid::new_v4())),
+        "not json\n",
+    )
+    .expect("malformed transcript");
+
+    let store = project.transcript_store();
+    let mut out = Vec::new();
+    assert_eq!(store.export_ndjson(&mut out, false).expect("export"), 2);
+    let lines: Vec<serde_json::Value> = String::from_utf8(out)
+        .expect("utf8")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("valid json line"))
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines
+        .iter()
+        .all(|record| record["messages"].as_array().map(Vec::len) == Some(2)));
+
+    let mut out = Vec::new();
+    assert_eq!(store.export_ndjson(&mut out, true).expect("export"), 4);
+    for line in String::from_utf8(out).expect("utf8").lines() {
+        let record: serde_json::Value = serde_json::from_str(line).expect("valid json line");
+        assert!(record["conversation_id"].is_string());
+        assert!(record["role"].is_string());
+    }
+}
+
+#[test]
+fn corrupt_transcript_line_keeps_the_other_messages() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Corrupt").expect("project");
+    let good = |content: &str| {
+        serde_json::to_string(&ChatMessage::new(MessageRole::User, content)).expect("json")
+    };
+    let transcript = format!(
+        "{}\n{{\"id\":\"3f0c\",\"role\":\"assis\n{}\n",
+        good("first"),
+        good("second")
+    );
+    fs::write(
+        project
+            .paths()
+            .conversations
+            .join(format!("{}.jsonl", uuid::Uuid::new_v4())),
+        transcript,
+    )
+    .expect("transcript");
+
+    let conversations = project
+        .transcript_store()
+        .load_conversations()
+        .expect("load");
+    assert_eq!(conversations.len(), 1);
+    let contents: Vec<_> = conversations[0]
+        .messages
+        .iter()
+        .map(|message| message.content.as_str())
+        .collect();
+    assert_eq!(contents, ["first", "second"]);
+}
+
+#[test]
+fn rewrites_leave_no_temporary_files_behind() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Atomic").expect("project");
+    let store = project.transcript_store();
+    let mut conversation = Conversation::new();
+    conversation.add_message(ChatMessage::new(MessageRole::User, "hello"));
+    store
+        .append_message(conversation.id, &conversation.messages[0])
+        .expect("append");
+    for title in ["First", "Second"] {
+        conversation.title = title.to_string();
+        store.persist_metadata(&conversation).expect("metadata");
+    }
+    store
+        .rewrite_transcript(conversation.id, &conversation.messages)
+        .expect("rewrite");
+
+    let mut names: Vec<_> = fs::read_dir(&project.paths().conversations)
+        .expect("read dir")
+        .map(|entry| {
+            entry
+                .expect("entry")
+                .file_name()
+                .into_string()
+                .expect("name")
+        })
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            format!("{}.jsonl", conversation.id),
+            format!("{}.meta.json", conversation.id)
+        ]
+    );
+    let reloaded = store.load_conversations().expect("reload");
+    assert_eq!(reloaded[0].title, "Second");
+}
+
+#[test]
+fn exported_archives_leave_out_secrets() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Exported").expect("project");
+    let store = project.transcript_store();
+    let conversation = Conversation::new();
+    store
+        .append_message(
+            conversation.id,
+            &ChatMessage::new(MessageRole::User, "keep me"),
+        )
+        .expect("append");
+    store.persist_secret("endpoint", "hunter2").expect("secret");
+
+    let mut archive = std::io::Cursor::new(Vec::new());
+    project.export_zip(&mut archive).expect("export");
+    let archive = zip::ZipArchive::new(archive).expect("read archive");
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(
+        names.iter().any(|name| name.ends_with(".jsonl")),
+        "{names:?}"
+    );
+    assert!(
+        !names.iter().any(|name| name.contains("secrets/")),
+        "{names:?}"
+    );
+}
+
+#[test]
+fn secrets_round_trip_through_the_file_store() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Secrets").expect("project");
+    let secrets = Arc::new(FileSecretStore::new(project.transcript_store()));
+    assert_eq!(secrets.load("endpoint").expect("load"), None);
+
+    let auth = AuthCoordinator::with_secrets(secrets.clone());
+    let state = runtime
+        .block_on(auth.negotiate("endpoint", AuthMode::ServerManaged))
+        .expect("negotiate");
+    assert_eq!(secrets.load("endpoint").expect("load"), state.access_token);
+}
+
+#[test]
+fn export_ignore_in_the_manifest_leaves_out_matching_paths() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Ignoring").expect("project");
+    let manifest = fs::read_to_string(&project.paths().pat_file).expect("manifest");
+    fs::write(
+        &project.paths().pat_file,
+        manifest.replace("[paths]", "export_ignore = [\"drafts\"]\n\n[paths]"),
+    )
+    .expect("write manifest");
+    let project = ProjectHandle::open(&project.paths().root).expect("reopen");
+    fs::create_dir_all(project.paths().root.join("notes/drafts")).expect("drafts");
+    fs::write(project.paths().root.join("notes/drafts/todo.md"), "wip").expect("draft");
+    fs::write(project.paths().root.join("notes/final.md"), "done").expect("final");
+
+    let mut archive = std::io::Cursor::new(Vec::new());
+    project.export_zip(&mut archive).expect("export");
+    let archive = zip::ZipArchive::new(archive).expect("read archive");
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(names.contains(&"Ignoring/notes/final.md"), "{names:?}");
+    assert!(
+        !names.iter().any(|name| name.contains("drafts")),
+        "{names:?}"
+    );
+}
+
+#[test]
+fn import_skips_entries_under_secrets() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Planted").expect("project");
+    let mut archive = std::io::Cursor::new(Vec::new());
+    project.export_zip(&mut archive).expect("export");
+
+    let mut writer = zip::ZipWriter::new_append(archive).expect("append");
+    writer
+        .start_file(
+            "Planted/.patina/secrets/endpoint.txt",
+            zip::write::FileOptions::default(),
+        )
+        .expect("start file");
+    std::io::Write::write_all(&mut writer, b"attacker-token").expect("write");
+    let archive = writer.finish().expect("finish");
+
+    let into = temp_dir.path().join("imported");
+    let imported = ProjectHandle::import_zip(archive, &into).expect("import");
+    assert!(!imported
+        .paths()
+        .internal
+        .join("secrets/endpoint.txt")
+        .exists());
+}
+
+#[test]
+fn mcp_endpoints_are_read_from_the_manifest() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Tools").expect("project");
+    let manifest = fs::read_to_string(&project.paths().pat_file).expect("manifest");
+    fs::write(
+        &project.paths().pat_file,
+        format!(
+            "{manifest}\n[[mcp.endpoints]]\nid = \"files\"\nmode = \"ServerManaged\"\n\n\
+             [mcp.endpoints.transport]\ntype = \"child_process\"\nprogram = \"files-mcp\"\n\
+             args = [\"--stdio\"]\n"
+        ),
+    )
+    .expect("write manifest");
+    let project = ProjectHandle::open(&project.paths().root).expect("reopen");
+
+    let endpoints = project.mcp_endpoints().expect("endpoints");
+    assert_eq!(endpoints.len(), 1);
+    assert_eq!(endpoints[0].id(), "files");
+    assert_eq!(endpoints[0].root(), Some(project.paths().root.as_path()));
+}
+
+#[test]
+fn invalid_mcp_servers_are_rejected_without_blocking_the_project() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Broken").expect("project");
+    let manifest = fs::read_to_string(&project.paths().pat_file).expect("manifest");
+    let server = |id: &str, program: &str| {
+        format!(
+            "\n[[mcp.endpoints]]\nid = \"{id}\"\nmode = \"ServerManaged\"\n\n\
+             [mcp.endpoints.transport]\ntype = \"child_process\"\nprogram = \"{program}\"\n"
+        )
+    };
+
+    fs::write(
+        &project.paths().pat_file,
+        format!("{manifest}{}{}", server("files", "a"), server("files", "b")),
+    )
+    .expect("write manifest");
+    let reopened = ProjectHandle::open(&project.paths().root).expect("project still opens");
+    let err = format!("{:#}", reopened.mcp_endpoints().unwrap_err());
+    assert!(err.contains("`files` is used more than once"), "{err}");
+
+    fs::write(
+        &project.paths().pat_file,
+        format!("{manifest}{}", server("files", " ")),
+    )
+    .expect("write manifest");
+    let err = format!("{:#}", reopened.mcp_endpoints().unwrap_err());
+    assert!(err.contains("empty command"), "{err}");
+
+    fs::write(
+        &project.paths().pat_file,
+        format!("{manifest}{}", server("files", "a")),
+    )
+    .expect("write manifest");
+    assert_eq!(reopened.mcp_endpoints().expect("reloaded").len(), 1);
+}
+
+#[test]
+fn headers_count_messages_without_loading_them() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Headers").expect("project");
+    let store = project.transcript_store();
+    let mut conversation = Conversation::new();
+    conversation.add_message(ChatMessage::new(MessageRole::User, "first question"));
+    conversation.add_message(ChatMessage::new(MessageRole::Assistant, "answer"));
+    store
+        .rewrite_transcript(conversation.id, &conversation.messages)
+        .expect("transcript");
+
+    assert_eq!(store.message_count(conversation.id), 2);
+    assert_eq!(store.message_count(uuid::Uuid::new_v4()), 0);
+    let headers = store.load_conversation_headers();
+    assert_eq!(headers.len(), 1);
+    let (header, count) = &headers[0];
+    assert_eq!(header.id, conversation.id);
+    assert_eq!(header.title, "first question");
+    assert!(header.messages.is_empty());
+    assert_eq!(*count, 2);
+    let loaded = store.load_conversation(conversation.id).expect("load");
+    assert_eq!(loaded.messages.len(), 2);
+}
+
+#[test]
+fn directory_exports_copy_the_project_without_secrets() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Mirrored").expect("project");
+    let store = project.transcript_store();
+    let conversation = Conversation::new();
+    store
+        .append_message(
+            conversation.id,
+            &ChatMessage::new(MessageRole::User, "keep me"),
+        )
+        .expect("append");
+    store.persist_secret("endpoint", "hunter2").expect("secret");
+
+    let dest = temp_dir.path().join("backup");
+    project.export_to_dir(&dest).expect("export");
+    let copy = ProjectHandle::open(&dest.join("Mirrored.pat")).expect("open copy");
+    assert_eq!(copy.name(), "Mirrored");
+    let conversations = copy.transcript_store().load_conversations().expect("load");
+    assert_eq!(conversations[0].messages[0].content, "keep me");
+    assert!(!copy
+        .paths()
+        .internal
+        .join("secrets")
+        .join("endpoint")
+        .exists());
+
+    let error = project.export_to_dir(&dest).expect_err("not empty");
+    assert!(error.to_string().contains("must be empty"), "{error}");
+}
+
+#[test]
+fn zip_export_and_import_report_progress() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Progress").expect("project");
+    let conversation = Conversation::new();
+    project
+        .transcript_store()
+        .append_message(conversation.id, &ChatMessage::new(MessageRole::User, "hi"))
+        .expect("append");
+
+    let mut exported = Vec::new();
+    let mut archive = std::io::Cursor::new(Vec::new());
+    project
+        .export_zip_with_progress(&mut archive, |done, total| exported.push((done, total)))
+        .expect("export");
+    let total = exported.last().expect("progress").1;
+    assert!(total > 0);
+    assert_eq!(exported.first(), Some(&(0, total)));
+    assert_eq!(exported.last(), Some(&(total, total)));
+    // One call up front, then one after each entry is written.
+    assert_eq!(exported.len() as u64, total + 1);
+
+    let mut imported = Vec::new();
+    archive.set_position(0);
+    ProjectHandle::import_zip_with_progress(
+        archive,
+        &temp_dir.path().join("restored"),
+        |done, total| imported.push((done, total)),
+    )
+    .expect("import");
+    let (done, total) = *imported.last().expect("progress");
+    assert_eq!(done, total);
+    // The archive also holds the root directory entry the export adds up front.
+    assert_eq!(total, exported.last().expect("progress").1 + 1);
+}
+
+#[test]
+fn imports_reject_entries_that_escape_the_destination() {
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    for evil in ["../evil", "Project/../../evil", "/tmp/evil"] {
+        let temp_dir = TempDir::new().expect("temp dir");
+        let mut archive = std::io::Cursor::new(Vec::new());
+        let mut zip = zip::ZipWriter::new(&mut archive);
+        zip.start_file("Project/Project.pat", FileOptions::default())
+            .expect("manifest entry");
+        zip.write_all(b"name = \"Project\"").expect("manifest");
+        zip.start_file(evil, FileOptions::default())
+            .expect("evil entry");
+        zip.write_all(b"gotcha").expect("evil");
+        zip.finish().expect("finish");
+        drop(zip);
+        archive.set_position(0);
+
+        let dest = temp_dir.path().join("dest");
+        let error = ProjectHandle::import_zip(archive, &dest).expect_err(evil);
+        assert!(error.to_string().contains("outside"), "{evil}: {error}");
+        assert!(!temp_dir.path().join("evil").exists(), "{evil}");
+        assert!(!dest.join("Project").exists(), "{evil}");
+    }
+}
@@ -0,0 +1,33 @@
+use patina::settings::{ProjectSettingsData, ProjectSettingsStore};
+use tempfile::TempDir;
+
+#[test]
+fn openai_gateway_fields_round_trip_through_project_settings() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let path = temp_dir.path().join("Project.pat");
+    let mut data = ProjectSettingsData {
+        inherit_app: false,
+        ..ProjectSettingsData::default()
+    };
+    data.provider.openai.api_key = "sk-test".into();
+    data.provider.openai.base_url = "http://localhost:4000".into();
+    data.provider.openai.organization = "org-1".into();
+    data.provider.openai.project = "proj-1".into();
+
+    let mut store = ProjectSettingsStore::load(path.clone());
+    store.set(data.clone());
+    store.persist().expect("persist");
+
+    let reloaded = ProjectSettingsStore::load(path.clone());
+    assert_eq!(reloaded.data().provider.openai, data.provider.openai);
+
+    // Unset fields are left out of the file entirely
+    data.provider.openai.organization.clear();
+    data.provider.openai.project.clear();
+    let mut store = ProjectSettingsStore::load(path.clone());
+    store.set(data);
+    store.persist().expect("persist");
+    let contents = std::fs::read_to_string(&path).expect("manifest");
+    assert!(!contents.contains("organization"));
+    assert!(!contents.contains("project ="));
+}
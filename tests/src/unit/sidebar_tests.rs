@@ -0,0 +1,338 @@
+use chrono::{DateTime, FixedOffset, TimeZone};
+use egui::output::OutputEvent;
+use egui::{CentralPanel, Context, Event, Key, Modifiers, Pos2, RawInput, Rect, Vec2};
+use patina::config::Pricing;
+use patina::ui::{
+    date_group, format_timestamp, match_excerpt, ArgumentKind, DateGroup, McpSidebarEntry,
+    McpStatus, Sidebar, SidebarOutput, SidebarState, ThemePalette, TimestampFormat, ToolForm,
+};
+use patina_core::llm::ToolDefinition;
+use patina_core::mcp::{CommandSpec, McpEndpoint, McpEvent};
+use patina_core::state::ConversationSummary;
+use patina_core::AuthMode;
+use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+fn at(offset: &FixedOffset, y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<FixedOffset> {
+    offset.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+}
+
+#[test]
+fn date_groups_split_at_local_midnight() {
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+    // Wednesday 2024-05-15, just after midnight.
+    let now = at(&tz, 2024, 5, 15, 0, 5);
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 15, 0, 0), &now),
+        DateGroup::Today
+    );
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 14, 23, 59), &now),
+        DateGroup::Yesterday
+    );
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 14, 0, 0), &now),
+        DateGroup::Yesterday
+    );
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 13, 23, 59), &now),
+        DateGroup::ThisWeek
+    );
+    // Later today (e.g. clock skew) still counts as today.
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 15, 9, 0), &now),
+        DateGroup::Today
+    );
+}
+
+#[test]
+fn relative_timestamps_count_back_from_now() {
+    let now = chrono::Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+    let ago = |minutes: i64| {
+        format_timestamp(
+            now - chrono::Duration::minutes(minutes),
+            &TimestampFormat::Relative,
+            now,
+        )
+    };
+    assert_eq!(ago(0), "just now");
+    assert_eq!(ago(5), "5m ago");
+    assert_eq!(ago(59), "59m ago");
+    assert_eq!(ago(60), "1h ago");
+    assert_eq!(ago(23 * 60 + 59), "23h ago");
+    assert_eq!(ago(3 * 24 * 60), "3d ago");
+    let old = now - chrono::Duration::days(30);
+    assert_eq!(
+        format_timestamp(old, &TimestampFormat::Relative, now),
+        format_timestamp(old, &TimestampFormat::Short, now)
+    );
+}
+
+#[test]
+fn invalid_custom_timestamp_formats_fall_back_to_short() {
+    let now = chrono::Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+    let short = format_timestamp(now, &TimestampFormat::Short, now);
+    for pattern in ["%Q", "%", "  "] {
+        let custom = TimestampFormat::Custom(pattern.to_string());
+        assert_eq!(format_timestamp(now, &custom, now), short, "{pattern:?}");
+    }
+    let year = TimestampFormat::Custom("year %Y".to_string());
+    assert!(format_timestamp(now, &year, now).starts_with("year 20"));
+}
+
+#[test]
+fn date_groups_use_the_given_time_zone() {
+    let tz = FixedOffset::west_opt(5 * 3600).unwrap();
+    let now = at(&tz, 2024, 5, 15, 12, 0);
+    // 02:00 UTC on the 15th is still the 14th at UTC-5.
+    let utc = chrono::Utc.with_ymd_and_hms(2024, 5, 15, 2, 0, 0).unwrap();
+    assert_eq!(
+        date_group(&utc.with_timezone(&tz), &now),
+        DateGroup::Yesterday
+    );
+}
+
+#[test]
+fn date_groups_respect_week_edges() {
+    let tz = FixedOffset::east_opt(0).unwrap();
+    // Sunday 2024-05-19: Monday the 13th is still this week, Sunday the 12th is not.
+    let sunday = at(&tz, 2024, 5, 19, 18, 0);
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 13, 0, 0), &sunday),
+        DateGroup::ThisWeek
+    );
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 12, 23, 59), &sunday),
+        DateGroup::Older
+    );
+
+    // On Monday, yesterday wins over the previous week.
+    let monday = at(&tz, 2024, 5, 20, 8, 0);
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 19, 12, 0), &monday),
+        DateGroup::Yesterday
+    );
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 18, 12, 0), &monday),
+        DateGroup::Older
+    );
+
+    // On Tuesday, the Sunday before yesterday is already last week.
+    let tuesday = at(&tz, 2024, 5, 21, 8, 0);
+    assert_eq!(
+        date_group(&at(&tz, 2024, 5, 19, 23, 0), &tuesday),
+        DateGroup::Older
+    );
+}
+
+#[test]
+fn match_excerpts_centre_on_the_match() {
+    assert_eq!(match_excerpt("short\n\nanswer", "ANSWER"), "short answer");
+    let long = format!("{} needle {}", "a".repeat(100), "b".repeat(100));
+    let excerpt = match_excerpt(&long, "Needle");
+    assert!(
+        excerpt.starts_with('…') && excerpt.ends_with('…'),
+        "{excerpt}"
+    );
+    assert!(excerpt.contains("needle"));
+    assert_eq!(excerpt.chars().count(), 82);
+    // No match falls back to the start
+    assert!(match_excerpt(&long, "missing").starts_with("aaa"));
+}
+
+#[test]
+fn mcp_entries_follow_events_for_their_endpoint() {
+    let endpoint = McpEndpoint::child_process(
+        "files",
+        AuthMode::ServerManaged,
+        CommandSpec::new("/usr/local/bin/files-mcp"),
+    );
+    let mut entry = McpSidebarEntry::for_endpoint(&endpoint);
+    assert_eq!(entry.description, "files-mcp");
+    assert_eq!(entry.status, McpStatus::Connecting);
+
+    entry.apply_event(&McpEvent::Connected {
+        endpoint: "other".into(),
+        mode: AuthMode::ServerManaged,
+        server_info: None,
+    });
+    assert_eq!(entry.status, McpStatus::Connecting);
+
+    entry.apply_event(&McpEvent::Disconnected {
+        endpoint: "files".into(),
+        reason: Some("transport closed".into()),
+    });
+    assert_eq!(entry.status, McpStatus::Disconnected);
+    assert_eq!(entry.reason.as_deref(), Some("transport closed"));
+
+    entry.apply_event(&McpEvent::Connected {
+        endpoint: "files".into(),
+        mode: AuthMode::ServerManaged,
+        server_info: None,
+    });
+    assert_eq!(entry.status, McpStatus::Connected);
+    assert_eq!(entry.reason, None);
+
+    // A timed-out tool call leaves the connection as it is.
+    entry.apply_event(&McpEvent::ToolFailed {
+        endpoint: "files".into(),
+        tool: "search".into(),
+        arguments: json!({}),
+        error: "tool 'search' on MCP server 'files' did not answer within 30s".into(),
+    });
+    assert_eq!(entry.status, McpStatus::Connected);
+}
+
+#[test]
+fn tool_forms_follow_the_schema_and_type_their_arguments() {
+    let tool = ToolDefinition {
+        name: "search".into(),
+        description: "Search the docs".into(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "What to look for" },
+                "limit": { "type": "integer" },
+                "exact": { "type": "boolean" },
+                "filters": { "type": "object" }
+            },
+            "required": ["query"]
+        }),
+    };
+    let mut form = ToolForm::new("docs", &tool);
+    let field = |form: &ToolForm, name: &str| -> usize {
+        form.fields
+            .iter()
+            .position(|field| field.name == name)
+            .expect("field")
+    };
+    let query = field(&form, "query");
+    assert!(form.fields[query].required);
+    assert_eq!(form.fields[query].kind, ArgumentKind::String);
+    assert_eq!(form.fields[query].description, "What to look for");
+    assert_eq!(
+        form.fields[field(&form, "filters")].kind,
+        ArgumentKind::Json
+    );
+
+    assert!(form.arguments().unwrap_err().contains("query"));
+    form.fields[query].text = "borrowing".into();
+    assert_eq!(
+        form.arguments().unwrap(),
+        json!({ "query": "borrowing", "exact": false })
+    );
+
+    let limit = field(&form, "limit");
+    form.fields[limit].text = "ten".into();
+    assert!(form.arguments().is_err());
+    form.fields[limit].text = "10".into();
+    let filters = field(&form, "filters");
+    form.fields[filters].text = r#"{"lang": "en"}"#.into();
+    let exact = field(&form, "exact");
+    form.fields[exact].checked = true;
+    assert_eq!(
+        form.arguments().unwrap(),
+        json!({ "query": "borrowing", "limit": 10, "exact": true, "filters": { "lang": "en" } })
+    );
+}
+
+fn summary(title: &str) -> ConversationSummary {
+    ConversationSummary {
+        id: Uuid::new_v4(),
+        title: title.to_string(),
+        updated_at: chrono::Utc::now(),
+        message_count: 2,
+        usage: Default::default(),
+    }
+}
+
+fn press(key: Key) -> Event {
+    Event::Key {
+        key,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers: Modifiers::NONE,
+    }
+}
+
+/// Drives the sidebar from the keyboard, tabbing to widgets by their label.
+struct SidebarHarness {
+    ctx: Context,
+    state: SidebarState,
+    summaries: Vec<ConversationSummary>,
+    pinned: Vec<Uuid>,
+}
+
+impl SidebarHarness {
+    /// Show the sidebar for one frame; returns its output and the label that took focus.
+    fn frame(&mut self, events: Vec<Event>) -> (SidebarOutput, Option<String>) {
+        let palette = ThemePalette::for_dark();
+        let pinned_lookup: HashSet<Uuid> = self.pinned.iter().copied().collect();
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(400.0, 900.0))),
+            events,
+            ..RawInput::default()
+        };
+        let mut output = SidebarOutput::default();
+        let full = self.ctx.run(input, |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                output = Sidebar::show(
+                    ui,
+                    &mut self.state,
+                    &palette,
+                    &self.summaries,
+                    &pinned_lookup,
+                    &self.pinned,
+                    &mut [],
+                    None,
+                    &Pricing::default(),
+                    "gpt-4o",
+                );
+            });
+        });
+        let focused = full
+            .platform_output
+            .events
+            .iter()
+            .find_map(|event| match event {
+                OutputEvent::FocusGained(info) => info.label.clone(),
+                _ => None,
+            });
+        (output, focused)
+    }
+
+    /// Tab to the widget labelled `label` and press Enter on it.
+    fn activate(&mut self, label: &str) -> SidebarOutput {
+        for _ in 0..40 {
+            if self.frame(vec![press(Key::Tab)]).1.as_deref() == Some(label) {
+                return self.frame(vec![press(Key::Enter)]).0;
+            }
+        }
+        panic!("no focusable widget labelled {label:?}");
+    }
+}
+
+#[test]
+fn marked_chats_are_merged_into_the_first_in_sidebar_order() {
+    let (alpha, beta, gamma) = (summary("Alpha"), summary("Beta"), summary("Gamma"));
+    let mut sidebar = SidebarHarness {
+        ctx: Context::default(),
+        state: SidebarState::new(),
+        pinned: vec![gamma.id],
+        summaries: vec![alpha.clone(), beta.clone(), gamma.clone()],
+    };
+    sidebar.frame(Vec::new());
+    sidebar.activate("Chats");
+
+    // One marked chat is not enough to merge.
+    sidebar.state.merge_selection.insert(alpha.id);
+    let (output, _) = sidebar.frame(Vec::new());
+    assert!(output.merge.is_none());
+
+    // The pinned chat comes first, so the others are merged into it.
+    sidebar.state.merge_selection.insert(gamma.id);
+    let output = sidebar.activate("Merge");
+    assert_eq!(output.merge, Some((gamma.id, vec![alpha.id])));
+}
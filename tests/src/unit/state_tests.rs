@@ -1,8 +1,19 @@
+use chrono::{TimeZone, Utc};
+use patina_core::attachment::ImageAttachment;
+use patina_core::clock::FixedClock;
+use patina_core::config::{AiRuntimeSettings, OpenAiSettings};
+use patina_core::llm::{LlmProviderKind, RetryPolicy};
+use patina_core::mcp::McpEvent;
 use patina_core::project::ProjectHandle;
-use patina_core::state::AppState;
+use patina_core::state::{
+    build_request_history, AppState, ChatMessage, ChatParameters, Conversation,
+    ConversationSettings,
+};
+use patina_core::test_support::{chat_completion, fake_server_with, ReceivedRequests};
 use patina_core::{llm::LlmDriver, state::MessageRole};
 use std::sync::Arc;
 use tempfile::TempDir;
+use uuid::Uuid;
 
 fn test_runtime() -> tokio::runtime::Runtime {
     tokio::runtime::Builder::new_current_thread()
@@ -34,3 +45,1187 @@ fn app_state_records_messages() {
         .iter()
         .any(|msg| msg.role == MessageRole::User));
 }
+
+fn message_at(
+    clock: &FixedClock,
+    role: MessageRole,
+    content: &str,
+    offset_secs: i64,
+) -> ChatMessage {
+    clock.set(Utc.timestamp_opt(1_700_000_000 + offset_secs, 0).unwrap());
+    ChatMessage::new_at(role, content, clock)
+}
+
+#[test]
+fn merge_interleaves_messages_and_removes_source() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "MergeProject").expect("project");
+    let store = project.transcript_store();
+
+    let clock = FixedClock::new(Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+    let into = Conversation::with_id_at(Uuid::new_v4(), "Into", &clock);
+    let from = Conversation::with_id_at(Uuid::new_v4(), "From", &clock);
+    for (id, message) in [
+        (into.id, message_at(&clock, MessageRole::User, "a0", 0)),
+        (
+            into.id,
+            message_at(&clock, MessageRole::Assistant, "a2", 20),
+        ),
+        (from.id, message_at(&clock, MessageRole::User, "b1", 10)),
+        (
+            from.id,
+            message_at(&clock, MessageRole::Assistant, "b2", 20),
+        ),
+        (from.id, message_at(&clock, MessageRole::User, "b3", 30)),
+    ] {
+        store.append_message(id, &message).expect("append");
+    }
+    store.persist_metadata(&into).expect("metadata");
+    store.persist_metadata(&from).expect("metadata");
+
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::with_store(project.clone(), store, driver);
+    state.select_conversation(from.id);
+
+    assert!(state.merge_conversations(into.id, into.id).is_err());
+    state
+        .merge_conversations(into.id, from.id)
+        .expect("merge conversations");
+
+    let merged = state.active_conversation().expect("merged conversation");
+    assert_eq!(merged.id, into.id);
+    assert_eq!(merged.title, "Into");
+    let contents: Vec<_> = merged.messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["a0", "b1", "a2", "b2", "b3"]);
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded[0].id, into.id);
+    assert_eq!(reloaded[0].messages.len(), 5);
+}
+
+#[test]
+fn request_history_keeps_system_and_latest_messages() {
+    let messages = vec![
+        ChatMessage::new(MessageRole::System, "be brief"),
+        ChatMessage::new(MessageRole::User, "one"),
+        ChatMessage::new(MessageRole::Assistant, "two"),
+        ChatMessage::new(MessageRole::User, "three"),
+        ChatMessage::new(MessageRole::Assistant, "four"),
+        ChatMessage::new(MessageRole::User, "five"),
+    ];
+
+    let capped = build_request_history(&messages, 2);
+    let contents: Vec<_> = capped.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["be brief", "four", "five"]);
+
+    assert_eq!(build_request_history(&messages, 0).len(), messages.len());
+    assert_eq!(build_request_history(&messages, 10).len(), messages.len());
+}
+
+#[test]
+fn streaming_reports_usage_on_final_chunk() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Usage").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+
+    let usage = runtime.block_on(async {
+        let (_, mut stream) = state
+            .send_user_message_streaming("count my tokens", "mock", 0.6)
+            .await
+            .expect("stream");
+        let mut usage = None;
+        while let Some(chunk) = stream.recv().await {
+            let chunk = chunk.expect("chunk");
+            if chunk.done {
+                usage = chunk.usage;
+                break;
+            }
+        }
+        usage
+    });
+
+    let usage = usage.expect("usage on final chunk");
+    assert!(usage.prompt_tokens > 0);
+    assert!(usage.completion_tokens > 0);
+}
+
+#[test]
+fn stopping_a_stream_keeps_the_partial_reply() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Stop").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+
+    let (received, id) = runtime.block_on(async {
+        let (_, mut stream) = state
+            .send_user_message_streaming("tell me a long story", "mock", 0.6)
+            .await
+            .expect("stream");
+        let id = state.active_conversation().expect("conversation").id;
+        let mut received = String::new();
+        while let Some(chunk) = stream.recv().await {
+            let chunk = chunk.expect("chunk");
+            if chunk.done {
+                break;
+            }
+            received.push_str(&chunk.delta);
+            if !received.is_empty() {
+                assert!(state.stop_generating(id));
+            }
+        }
+        (received, id)
+    });
+
+    assert!(!state.is_generating(id));
+    assert!(!state.stop_generating(id));
+    let reply = state
+        .active_conversation()
+        .expect("conversation")
+        .messages
+        .pop()
+        .expect("reply");
+    assert_eq!(reply.role, MessageRole::Assistant);
+    assert!(reply.truncated);
+    assert!(reply.content.starts_with(&received));
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert!(reloaded[0].messages[1].truncated);
+}
+
+#[test]
+fn editing_a_user_message_drops_later_turns_and_replies_again() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Edit").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    for prompt in ["helo", "and another thing"] {
+        runtime
+            .block_on(state.send_user_message(prompt, "mock", 0.6))
+            .expect("send");
+    }
+    let conversation = state.active_conversation().expect("conversation");
+    assert_eq!(conversation.messages.len(), 4);
+    let original = conversation.messages[0].clone();
+
+    runtime.block_on(async {
+        let (_, mut stream) = state
+            .edit_message_and_truncate(conversation.id, original.id, "hello", "mock", 0.6)
+            .await
+            .expect("edit");
+        while let Some(chunk) = stream.recv().await {
+            if chunk.expect("chunk").done {
+                break;
+            }
+        }
+    });
+
+    let messages = state.active_conversation().expect("conversation").messages;
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].id, original.id);
+    assert_eq!(messages[0].content, "hello");
+    assert_eq!(messages[0].created_at, original.created_at);
+    assert!(messages[1].content.contains("'hello'"));
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    let contents: Vec<_> = reloaded[0]
+        .messages
+        .iter()
+        .map(|message| message.content.as_str())
+        .collect();
+    assert_eq!(
+        contents,
+        [messages[0].content.as_str(), messages[1].content.as_str()]
+    );
+
+    let reply = messages[1].id;
+    let err = runtime
+        .block_on(state.edit_message_and_truncate(conversation.id, reply, "x", "mock", 0.6))
+        .expect_err("assistant replies are not editable");
+    assert!(err.to_string().contains("only user messages"));
+}
+
+#[test]
+fn replies_persist_their_token_usage() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Spend").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    runtime.block_on(async {
+        let (_, mut stream) = state
+            .send_user_message_streaming("and again", "mock", 0.6)
+            .await
+            .expect("stream");
+        while let Some(chunk) = stream.recv().await {
+            if chunk.expect("chunk").done {
+                break;
+            }
+        }
+    });
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    let messages = &reloaded[0].messages;
+    assert!(messages[0].usage.is_none());
+    for reply in [&messages[1], &messages[3]] {
+        let usage = reply.usage.as_ref().expect("usage on reply");
+        assert!(usage.prompt_tokens > 0);
+        assert!(usage.completion_tokens > 0);
+        assert_eq!(reply.model.as_deref(), Some("mock"));
+    }
+    let by_model = reloaded[0].usage_by_model();
+    assert_eq!(by_model.len(), 1);
+    let mock = &by_model[&Some("mock".to_string())];
+    let replies = [&messages[1], &messages[3]].map(|reply| reply.usage.clone().expect("usage"));
+    assert_eq!(
+        mock.completion_tokens,
+        replies[0].completion_tokens + replies[1].completion_tokens
+    );
+
+    let legacy: ChatMessage = serde_json::from_str(
+        r#"{"id":"5d3c6f4e-8a1b-4c2d-9e0f-1a2b3c4d5e6f","role":"assistant","content":"hi","created_at":"2024-01-01T00:00:00Z"}"#,
+    )
+    .expect("message without usage");
+    assert!(legacy.usage.is_none());
+    assert!(legacy.model.is_none());
+}
+
+#[test]
+fn chat_usage_is_summarised_before_its_messages_load() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Spend").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver.clone());
+    runtime
+        .block_on(state.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    let usage = state.conversation_summaries()[0].usage.clone();
+    let mock = &usage[&Some("mock".to_string())];
+    assert!(mock.completion_tokens > 0);
+
+    // A fresh start reads only the headers; the total comes from the metadata file.
+    let reopened = AppState::new(project, driver);
+    let summary = &reopened.conversation_summaries()[0];
+    let stored = &summary.usage[&Some("mock".to_string())];
+    assert_eq!(stored.prompt_tokens, mock.prompt_tokens);
+    assert_eq!(stored.completion_tokens, mock.completion_tokens);
+}
+
+#[test]
+fn pasted_images_are_kept_with_the_user_message() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Vision").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    let screenshot = ImageAttachment {
+        mime_type: "image/png".into(),
+        data: "iVBORw0KGgo=".into(),
+    };
+    runtime.block_on(async {
+        let (_, mut stream) = state
+            .send_user_message_with_images_streaming("", vec![screenshot.clone()], "mock", 0.6)
+            .await
+            .expect("stream");
+        while let Some(chunk) = stream.recv().await {
+            if chunk.expect("chunk").done {
+                break;
+            }
+        }
+    });
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    let question = &reloaded[0].messages[0];
+    assert_eq!(question.role, MessageRole::User);
+    assert!(question.content.is_empty());
+    assert_eq!(question.images, vec![screenshot]);
+    assert!(reloaded[0].messages[1].images.is_empty());
+}
+
+#[test]
+fn conversation_json_round_trips_into_fresh_project() {
+    let runtime = test_runtime();
+    let source_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(source_dir.path(), "Source").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+    runtime
+        .block_on(state.send_user_message("export me", "mock", 0.6))
+        .expect("send");
+    let original = state.active_conversation().expect("conversation");
+    let json = state.conversation_json(original.id).expect("json");
+
+    let target_dir = TempDir::new().expect("temp dir");
+    let target = ProjectHandle::create(target_dir.path(), "Target").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let imported_state = AppState::new(target.clone(), driver);
+    let id = imported_state
+        .import_conversation_json(&json)
+        .expect("import");
+    assert_eq!(id, original.id);
+
+    let driver = runtime.block_on(LlmDriver::fake());
+    let reloaded = AppState::new(target, driver);
+    let imported = reloaded
+        .active_conversation()
+        .expect("imported conversation");
+    assert_eq!(imported.title, original.title);
+    let contents = |c: &Conversation| {
+        c.messages
+            .iter()
+            .map(|m| (m.role.clone(), m.content.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(contents(&imported), contents(&original));
+
+    let duplicate = imported_state
+        .import_conversation_json(&json)
+        .expect("second import");
+    assert_ne!(duplicate, original.id);
+}
+
+#[test]
+fn conversation_settings_override_defaults_and_persist() {
+    let defaults = ChatParameters {
+        model: "gpt-4o".to_string(),
+        temperature: 0.6,
+        max_request_messages: 0,
+    };
+    let inherited = ConversationSettings::default();
+    assert!(inherited.is_empty());
+    assert_eq!(inherited.resolve(&defaults), defaults);
+
+    let overrides = ConversationSettings {
+        model: Some("gpt-4o-mini".to_string()),
+        max_request_messages: Some(4),
+        ..ConversationSettings::default()
+    };
+    let effective = overrides.resolve(&defaults);
+    assert_eq!(effective.model, "gpt-4o-mini");
+    assert_eq!(effective.temperature, 0.6);
+    assert_eq!(effective.max_request_messages, 4);
+
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Settings").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    let id = state.active_conversation().expect("conversation").id;
+    state
+        .set_conversation_settings(id, overrides.clone())
+        .expect("save settings");
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert_eq!(reloaded[0].settings, overrides);
+}
+
+#[test]
+fn system_prompt_leads_request_history_and_persists() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Prompt").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    let id = state.active_conversation().expect("conversation").id;
+    state
+        .set_system_prompt(id, "Answer in French.")
+        .expect("save prompt");
+
+    let history = state.request_history(id);
+    assert_eq!(history[0].role, MessageRole::System);
+    assert_eq!(history[0].content, "Answer in French.");
+    assert_eq!(history.len(), 3);
+    let conversation = state.active_conversation().expect("conversation");
+    assert!(conversation
+        .messages
+        .iter()
+        .all(|message| message.role != MessageRole::System));
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert_eq!(
+        reloaded[0].system_prompt.as_deref(),
+        Some("Answer in French.")
+    );
+
+    state.set_system_prompt(id, "  ").expect("clear prompt");
+    assert_eq!(state.request_history(id)[0].role, MessageRole::User);
+}
+
+#[test]
+fn retry_answers_pending_user_turn_without_duplicating_it() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Retry").expect("project");
+    let store = project.transcript_store();
+    let pending = Conversation::with_id(Uuid::new_v4(), "Pending");
+    store
+        .append_message(
+            pending.id,
+            &ChatMessage::new(MessageRole::User, "try again"),
+        )
+        .expect("append");
+    store.persist_metadata(&pending).expect("metadata");
+
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::with_store(project, store, driver);
+    runtime.block_on(async {
+        let (_, mut stream) = state
+            .retry_last_turn_streaming(pending.id, "mock", 0.6)
+            .await
+            .expect("retry");
+        while let Some(chunk) = stream.recv().await {
+            if chunk.expect("chunk").done {
+                break;
+            }
+        }
+    });
+
+    let conversation = state.active_conversation().expect("conversation");
+    let roles: Vec<_> = conversation
+        .messages
+        .iter()
+        .map(|m| m.role.clone())
+        .collect();
+    assert_eq!(roles, [MessageRole::User, MessageRole::Assistant]);
+    assert!(runtime
+        .block_on(state.retry_last_turn_streaming(pending.id, "mock", 0.6))
+        .is_err());
+}
+
+#[test]
+fn rapid_sends_to_one_conversation_produce_one_reply() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Busy").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+
+    let (first, second) = runtime.block_on(async {
+        tokio::join!(
+            state.send_user_message("first", "mock", 0.6),
+            state.send_user_message("second", "mock", 0.6)
+        )
+    });
+    first.expect("first send");
+    assert!(second.is_err());
+
+    let conversation = state.active_conversation().expect("conversation");
+    assert!(!state.is_generating(conversation.id));
+    let roles: Vec<_> = conversation
+        .messages
+        .iter()
+        .map(|m| m.role.clone())
+        .collect();
+    assert_eq!(roles, [MessageRole::User, MessageRole::Assistant]);
+}
+
+#[test]
+fn regenerate_keeps_alternatives_and_persists_selection() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Variants").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("hello", "mock", 0.6))
+        .expect("send");
+    let conversation = state.active_conversation().expect("conversation");
+    let reply = conversation.messages[1].clone();
+    assert_eq!(reply.alternative_count(), 1);
+
+    runtime
+        .block_on(state.add_alternative(conversation.id, reply.id, "other", 0.6))
+        .expect("regenerate");
+    let regenerated = state.active_conversation().expect("conversation").messages[1].clone();
+    assert_eq!(regenerated.id, reply.id);
+    assert_eq!(regenerated.alternative_count(), 2);
+    assert_eq!(regenerated.selected_alternative, 1);
+    assert_eq!(regenerated.alternatives[0], reply.content);
+    assert_eq!(regenerated.content, regenerated.alternatives[1]);
+    assert_ne!(regenerated.content, reply.content);
+
+    state
+        .select_alternative(conversation.id, reply.id, 0)
+        .expect("select");
+    assert!(state
+        .select_alternative(conversation.id, reply.id, 5)
+        .is_err());
+    let user_id = conversation.messages[0].id;
+    assert!(runtime
+        .block_on(state.add_alternative(conversation.id, user_id, "mock", 0.6))
+        .is_err());
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    let stored = &reloaded[0].messages[1];
+    assert_eq!(stored.selected_alternative, 0);
+    assert_eq!(stored.content, reply.content);
+    assert_eq!(stored.alternatives, regenerated.alternatives);
+}
+
+#[test]
+fn messages_without_alternatives_keep_the_old_format() {
+    let legacy = r#"{"id":"6f1c0d4e-8a55-4d62-9b1e-1f7f2f0c7d11","role":"assistant","content":"hi","created_at":"2024-01-01T00:00:00Z"}"#;
+    let message: ChatMessage = serde_json::from_str(legacy).expect("legacy message");
+    assert!(message.alternatives.is_empty());
+    assert_eq!(message.selected_alternative, 0);
+    assert_eq!(message.alternative_count(), 1);
+
+    let encoded = serde_json::to_string(&message).expect("encode");
+    assert!(!encoded.contains("alternatives"));
+    assert!(!encoded.contains("selected_alternative"));
+}
+
+#[test]
+fn locked_conversation_keeps_frozen_parameters() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Locked").expect("project");
+    let (driver, requests) = canned_openai(&runtime, vec![(200, "first"), (200, "second")]);
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("first", "frozen-model", 0.3))
+        .expect("send");
+    let id = state.active_conversation().expect("conversation").id;
+    let overrides = ConversationSettings {
+        model: Some("frozen-model".to_string()),
+        ..ConversationSettings::default()
+    };
+    state
+        .set_conversation_settings(id, overrides.clone())
+        .expect("override");
+
+    let defaults = ChatParameters {
+        model: "global-model".to_string(),
+        temperature: 0.3,
+        max_request_messages: 0,
+    };
+    state.lock_parameters(id, &defaults).expect("lock");
+    assert!(state
+        .set_conversation_settings(id, ConversationSettings::default())
+        .is_err());
+
+    let drifted = ChatParameters {
+        model: "drifted-model".to_string(),
+        temperature: 1.5,
+        max_request_messages: 2,
+    };
+    let settings = state.active_conversation().expect("conversation").settings;
+    assert!(settings.locked);
+    let params = settings.resolve(&drifted);
+    runtime.block_on(async {
+        let (_, mut rx) = state
+            .send_user_message_streaming("second", params.model, params.temperature)
+            .await
+            .expect("send locked");
+        while let Some(chunk) = rx.recv().await {
+            if chunk.expect("chunk").done {
+                break;
+            }
+        }
+    });
+    let body = requests.json(1);
+    assert_eq!(body["model"], "frozen-model");
+    let temperature = body["temperature"].as_f64().expect("temperature");
+    assert!((temperature - 0.3).abs() < 1e-6, "{temperature}");
+    assert_eq!(
+        body["messages"].as_array().expect("messages").len(),
+        3,
+        "the frozen history limit of 0 sends the whole chat"
+    );
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert_eq!(reloaded[0].settings, settings);
+
+    state.unlock_parameters(id).expect("unlock");
+    let settings = state.active_conversation().expect("conversation").settings;
+    assert_eq!(settings, overrides);
+    assert_eq!(
+        settings.resolve(&drifted),
+        ChatParameters {
+            model: "frozen-model".to_string(),
+            ..drifted
+        }
+    );
+}
+
+fn title_for(content: &str) -> String {
+    let mut conversation = Conversation::with_id(Uuid::new_v4(), "New chat");
+    conversation.add_message(ChatMessage::new(MessageRole::User, content));
+    conversation.title
+}
+
+#[test]
+fn titles_are_cut_on_word_boundaries() {
+    assert_eq!(
+        title_for("How do I configure a reverse proxy for several backend services?"),
+        "How do I configure a reverse proxy for…"
+    );
+    assert_eq!(
+        title_for("  exactly forty-two characters in this title  "),
+        "exactly forty-two characters in this title"
+    );
+    assert_eq!(
+        title_for("this title is forty-three characters long!!"),
+        "this title is forty-three characters…"
+    );
+    let exact = "a".repeat(42);
+    assert_eq!(title_for(&exact), exact);
+    assert_eq!(title_for(&"b".repeat(50)), format!("{}…", "b".repeat(42)));
+    assert_eq!(
+        title_for("🦀🦀 Rust crabs 🦀 are everywhere in this 🦀 codebase today 🦀🦀"),
+        "🦀🦀 Rust crabs 🦀 are everywhere in this 🦀…"
+    );
+}
+
+#[test]
+fn retitle_uses_the_current_first_user_message() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Retitle").expect("project");
+    let store = project.transcript_store();
+    let mut conversation = Conversation::with_id(Uuid::new_v4(), "New chat");
+    conversation.add_message(ChatMessage::new(MessageRole::User, "original question"));
+    store
+        .append_message(conversation.id, &conversation.messages[0])
+        .expect("append");
+    store.persist_metadata(&conversation).expect("metadata");
+
+    let edited = "an edited first question that is long enough to be truncated";
+    conversation.messages[0].content = edited.to_string();
+    store
+        .rewrite_transcript(conversation.id, &conversation.messages)
+        .expect("rewrite");
+
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::with_store(project.clone(), store, driver);
+    assert_eq!(
+        state.active_conversation().expect("conversation").title,
+        "original question"
+    );
+    let title = state
+        .retitle_from_content(conversation.id)
+        .expect("retitle");
+    assert_eq!(title, "an edited first question that is long…");
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert_eq!(reloaded[0].title, title);
+
+    project
+        .transcript_store()
+        .rewrite_transcript(conversation.id, &[])
+        .expect("clear");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let cleared = AppState::new(project, driver);
+    assert_eq!(
+        cleared
+            .retitle_from_content(conversation.id)
+            .expect("retitle"),
+        "New chat"
+    );
+}
+
+#[test]
+fn prune_removes_only_empty_conversations() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Prune").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+
+    let stale = project.transcript_store();
+    stale
+        .persist_metadata(&Conversation::with_id(Uuid::new_v4(), "Left over"))
+        .expect("stale metadata");
+    runtime
+        .block_on(state.send_user_message("keep me", "mock", 0.6))
+        .expect("send");
+    let kept = state.active_conversation().expect("conversation").id;
+    state.start_new_conversation();
+    state.start_new_conversation();
+    let selected = state.start_new_conversation();
+
+    assert_eq!(state.prune_empty_conversations().expect("prune"), 3);
+    let remaining: Vec<_> = state
+        .conversation_summaries()
+        .iter()
+        .map(|c| c.id)
+        .collect();
+    assert_eq!(remaining.len(), 2);
+    assert!(remaining.contains(&kept));
+    assert!(remaining.contains(&selected));
+    assert!(stale.metadata_only_ids().iter().all(|id| *id == selected));
+    assert_eq!(state.prune_empty_conversations().expect("prune again"), 0);
+}
+
+fn exported_contents(output: &[u8]) -> Vec<String> {
+    String::from_utf8(output.to_vec())
+        .expect("utf-8")
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).expect("record");
+            record["content"].as_str().expect("content").to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn incremental_export_only_emits_new_messages() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Incremental").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+
+    runtime
+        .block_on(state.send_user_message("first question", "mock", 0.6))
+        .expect("send");
+    let id = state.active_conversation().expect("conversation").id;
+
+    let mut first = Vec::new();
+    assert_eq!(state.export_incremental(&mut first).expect("export"), 2);
+    assert_eq!(exported_contents(&first)[0], "first question");
+    let marker = project.transcript_store().export_markers()[&id];
+    assert_eq!(marker, state.active_conversation().unwrap().messages[1].id);
+
+    let mut unchanged = Vec::new();
+    assert_eq!(state.export_incremental(&mut unchanged).expect("export"), 0);
+    assert!(unchanged.is_empty());
+
+    runtime
+        .block_on(state.send_user_message("second question", "mock", 0.6))
+        .expect("send");
+    let mut second = Vec::new();
+    assert_eq!(state.export_incremental(&mut second).expect("export"), 2);
+    let contents = exported_contents(&second);
+    assert_eq!(contents[0], "second question");
+    assert!(!contents.contains(&"first question".to_string()));
+
+    let conversation = state.active_conversation().unwrap();
+    assert_eq!(
+        project.transcript_store().export_markers()[&id],
+        conversation.messages.last().unwrap().id
+    );
+}
+
+#[test]
+fn incremental_export_keeps_markers_of_conversations_left_out() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Markers").expect("project");
+    let store = project.transcript_store();
+    let conversation = |content: &str| {
+        let mut conversation = Conversation::new();
+        conversation.add_message(ChatMessage::new(MessageRole::User, content));
+        conversation
+    };
+    let (kept, skipped) = (conversation("kept"), conversation("skipped"));
+
+    let mut first = Vec::new();
+    let both = [kept.clone(), skipped.clone()];
+    assert_eq!(
+        store.export_incremental(&both, &mut first).expect("export"),
+        2
+    );
+
+    // An unreadable transcript is left out of the next run without losing its place.
+    let mut second = Vec::new();
+    assert_eq!(
+        store
+            .export_incremental(std::slice::from_ref(&kept), &mut second)
+            .expect("export"),
+        0
+    );
+    assert_eq!(store.export_markers()[&skipped.id], skipped.messages[0].id);
+    let mut third = Vec::new();
+    assert_eq!(
+        store.export_incremental(&both, &mut third).expect("export"),
+        0
+    );
+}
+
+#[test]
+fn message_search_matches_bodies_and_titles_once_per_conversation() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Search").expect("project");
+    let store = project.transcript_store();
+    let mut rust = Conversation::with_id(Uuid::new_v4(), "New chat");
+    rust.add_message(ChatMessage::new(
+        MessageRole::User,
+        "How do lifetimes work?",
+    ));
+    rust.add_message(ChatMessage::new(
+        MessageRole::Assistant,
+        "Lifetimes tie a borrow to its owner.",
+    ));
+    let mut cooking = Conversation::with_id(Uuid::new_v4(), "New chat");
+    cooking.add_message(ChatMessage::new(
+        MessageRole::User,
+        "Which pans have a LIFETIME warranty?",
+    ));
+    // Adding the first message titles a chat after it; name these explicitly.
+    rust.title = "Rust questions".into();
+    cooking.title = "Cooking".into();
+    for conversation in [&rust, &cooking] {
+        for message in &conversation.messages {
+            store
+                .append_message(conversation.id, message)
+                .expect("append");
+        }
+        store.persist_metadata(conversation).expect("metadata");
+    }
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::with_store(project, store, driver);
+
+    let mut hits = state.search_messages("lifetime");
+    hits.sort_by_key(|(id, _)| *id != rust.id);
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].0, rust.id);
+    assert_eq!(hits[0].1.id, rust.messages[0].id);
+    assert_eq!(hits[1].0, cooking.id);
+
+    let title_hits = state.search_messages("  RUST ");
+    assert_eq!(title_hits.len(), 1);
+    assert_eq!(title_hits[0].1.id, rust.messages[0].id);
+    assert!(state.search_messages("   ").is_empty());
+
+    // Messages added after the first search are found too
+    state.select_conversation(cooking.id);
+    runtime
+        .block_on(state.send_user_message("pancakes for breakfast", "mock", 0.6))
+        .expect("send");
+    let hits = state.search_messages("Pancakes");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].0, cooking.id);
+}
+
+#[test]
+fn duplicate_copies_messages_under_new_ids() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Duplicate").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver);
+    runtime
+        .block_on(state.send_user_message("branch from here", "mock", 0.6))
+        .expect("send");
+    let original = state.active_conversation().expect("conversation");
+
+    let copy_id = state
+        .duplicate_conversation(original.id)
+        .expect("duplicate");
+    assert_ne!(copy_id, original.id);
+    let summaries = state.conversation_summaries();
+    assert_eq!(summaries[0].id, copy_id);
+    assert_eq!(summaries[0].title, format!("Copy of {}", original.title));
+
+    let copy = state.active_conversation().expect("copy is selected");
+    assert_eq!(copy.id, copy_id);
+    assert_eq!(copy.messages.len(), original.messages.len());
+    for (copied, source) in copy.messages.iter().zip(&original.messages) {
+        assert_ne!(copied.id, source.id);
+        assert_eq!(copied.content, source.content);
+        assert_eq!(copied.created_at, source.created_at);
+    }
+
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    let stored = reloaded
+        .iter()
+        .find(|conversation| conversation.id == copy_id)
+        .expect("copy persisted");
+    assert_eq!(stored.messages.len(), original.messages.len());
+    assert!(reloaded
+        .iter()
+        .any(|conversation| conversation.id == original.id
+            && conversation.messages.len() == original.messages.len()));
+    assert!(state.duplicate_conversation(Uuid::new_v4()).is_err());
+}
+
+#[test]
+fn mcp_servers_that_fail_to_start_are_reported_disconnected() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Servers").expect("project");
+    let manifest = std::fs::read_to_string(&project.paths().pat_file).expect("manifest");
+    std::fs::write(
+        &project.paths().pat_file,
+        format!(
+            "{manifest}\n[[mcp.endpoints]]\nid = \"missing\"\nmode = \"ServerManaged\"\n\n\
+             [mcp.endpoints.transport]\ntype = \"child_process\"\n\
+             program = \"/nonexistent/patina-mcp-server\"\n"
+        ),
+    )
+    .expect("write manifest");
+    let project = ProjectHandle::open(&project.paths().root).expect("reopen");
+    let endpoints = project.mcp_endpoints().expect("endpoints");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+    let mut events = state.take_mcp_events().expect("events");
+    assert!(state.take_mcp_events().is_none());
+
+    let event = runtime.block_on(async {
+        state.connect_mcp_servers(endpoints).await;
+        events.recv().await.expect("event")
+    });
+    match event {
+        McpEvent::Disconnected { endpoint, reason } => {
+            assert_eq!(endpoint, "missing");
+            assert!(reason.is_some());
+        }
+        other => panic!("unexpected event {other:?}"),
+    }
+    assert!(runtime
+        .block_on(state.reconnect_mcp_server("unknown"))
+        .is_err());
+}
+
+#[test]
+fn tool_results_stay_out_of_the_request_history() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "ToolResults").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+    let mut conversation = Conversation::new();
+    conversation.add_message(ChatMessage::new(MessageRole::User, "list the files"));
+    conversation.add_message(ChatMessage::new(MessageRole::Tool, "```json\n{}\n```"));
+    let json = serde_json::to_string(&conversation).expect("json");
+    let id = state.import_conversation_json(&json).expect("import");
+
+    let history = state.request_history(id);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].role, MessageRole::User);
+}
+
+#[test]
+fn conversations_load_their_messages_when_first_selected() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Lazy").expect("project");
+    let store = project.transcript_store();
+    let mut older = Conversation::new();
+    older.add_message(ChatMessage::new(MessageRole::User, "older question"));
+    older.add_message(ChatMessage::new(MessageRole::Assistant, "older answer"));
+    store
+        .rewrite_transcript(older.id, &older.messages)
+        .expect("older transcript");
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let newer = Conversation::new();
+    store
+        .append_message(newer.id, &ChatMessage::new(MessageRole::User, "newer"))
+        .expect("newer transcript");
+
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::with_store(project, store.clone(), driver);
+    let summaries = state.conversation_summaries();
+    assert_eq!(summaries[0].id, newer.id);
+    assert_eq!(summaries[1].id, older.id);
+    assert_eq!(summaries[1].title, "older question");
+    assert_eq!(summaries[1].message_count, 2);
+
+    // Not read yet, so a message written behind the state's back still shows up.
+    store
+        .append_message(older.id, &ChatMessage::new(MessageRole::User, "follow-up"))
+        .expect("append");
+    state.select_conversation(older.id);
+    let conversation = state.active_conversation().expect("conversation");
+    assert_eq!(conversation.id, older.id);
+    assert_eq!(conversation.messages.len(), 3);
+    assert_eq!(state.conversation_summaries()[1].message_count, 3);
+}
+
+#[test]
+fn reordered_conversations_keep_their_order_after_reload() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Order").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project.clone(), driver.clone());
+    let mut ids = Vec::new();
+    for content in ["first", "second", "third"] {
+        state.start_new_conversation();
+        runtime
+            .block_on(state.send_user_message(content, "mock", 0.6))
+            .expect("send");
+        ids.push(state.active_conversation().expect("conversation").id);
+    }
+    // Listed newest first: third, second, first. Drag "first" to the top.
+    state
+        .reorder_conversations(ids[0], ids[2])
+        .expect("reorder");
+    let order = |state: &AppState| -> Vec<Uuid> {
+        state
+            .conversation_summaries()
+            .iter()
+            .map(|c| c.id)
+            .collect()
+    };
+    assert_eq!(order(&state), [ids[0], ids[2], ids[1]]);
+
+    let reloaded = AppState::new(project.clone(), driver.clone());
+    assert_eq!(order(&reloaded), [ids[0], ids[2], ids[1]]);
+
+    // Chats created after the order was saved are listed above it.
+    let fresh = reloaded.start_new_conversation();
+    runtime
+        .block_on(reloaded.send_user_message("fourth", "mock", 0.6))
+        .expect("send");
+    let reloaded = AppState::new(project, driver);
+    assert_eq!(order(&reloaded), [fresh, ids[0], ids[2], ids[1]]);
+}
+
+/// An OpenAI-compatible endpoint answering one request per connection with the next
+/// canned status and reply text, and the requests it receives.
+fn canned_openai(
+    runtime: &tokio::runtime::Runtime,
+    replies: Vec<(u16, &'static str)>,
+) -> (LlmDriver, ReceivedRequests) {
+    let (base_url, requests) = fake_server_with(
+        replies
+            .into_iter()
+            .map(|(status, content)| (status, "", chat_completion(content)))
+            .collect(),
+    );
+    let settings = AiRuntimeSettings {
+        provider: LlmProviderKind::OpenAi,
+        openai: Some(OpenAiSettings {
+            api_key: "key".into(),
+            model: Some("gpt-4o-mini".into()),
+            base_url: Some(base_url),
+            organization: None,
+            project: None,
+        }),
+        azure: None,
+        ollama: None,
+        model: None,
+        retry: RetryPolicy::none(),
+        proxy: None,
+    };
+    let driver = runtime
+        .block_on(LlmDriver::from_settings(settings))
+        .expect("driver");
+    (driver, requests)
+}
+
+fn wait_for_titles(runtime: &tokio::runtime::Runtime, state: &AppState) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while state.is_generating_title() {
+        assert!(std::time::Instant::now() < deadline, "title request hung");
+        runtime.block_on(async { tokio::time::sleep(std::time::Duration::from_millis(10)).await });
+    }
+}
+
+#[test]
+fn generated_titles_replace_the_snippet_after_the_first_reply() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Titles").expect("project");
+    let (driver, _) = canned_openai(
+        &runtime,
+        vec![
+            (200, "Hello! How can I help?"),
+            (200, "\"Friendly Greeting Exchange.\""),
+            (200, "Sure."),
+        ],
+    );
+    let state = AppState::new(project.clone(), driver);
+    state.set_auto_title(true);
+
+    runtime
+        .block_on(state.send_user_message("hi", "gpt-4o-mini", 0.2))
+        .expect("send");
+    wait_for_titles(&runtime, &state);
+    assert_eq!(
+        state.active_conversation().expect("conversation").title,
+        "Friendly Greeting Exchange"
+    );
+    let reloaded = project
+        .transcript_store()
+        .load_conversations()
+        .expect("reload");
+    assert_eq!(reloaded[0].title, "Friendly Greeting Exchange");
+
+    // Only the first exchange is titled.
+    runtime
+        .block_on(state.send_user_message("and again", "gpt-4o-mini", 0.2))
+        .expect("send");
+    assert!(!state.is_generating_title());
+}
+
+#[test]
+fn failed_or_mock_title_requests_keep_the_snippet() {
+    let runtime = test_runtime();
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Titles").expect("project");
+    let (driver, _) = canned_openai(&runtime, vec![(200, "Hello!"), (500, "")]);
+    let state = AppState::new(project.clone(), driver);
+    state.set_auto_title(true);
+    runtime
+        .block_on(state.send_user_message("hi there", "gpt-4o-mini", 0.2))
+        .expect("send");
+    wait_for_titles(&runtime, &state);
+    assert_eq!(
+        state.active_conversation().expect("conversation").title,
+        "hi there"
+    );
+
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Mock").expect("project");
+    let state = AppState::new(project, runtime.block_on(LlmDriver::fake()));
+    state.set_auto_title(true);
+    runtime
+        .block_on(state.send_user_message("hello mock", "mock", 0.2))
+        .expect("send");
+    assert!(!state.is_generating_title());
+    assert_eq!(
+        state.active_conversation().expect("conversation").title,
+        "hello mock"
+    );
+}
@@ -0,0 +1,173 @@
+use egui::{CentralPanel, Context, Pos2, RawInput, Rect, Vec2};
+use egui_commonmark::CommonMarkCache;
+use patina::app::StreamingMessage;
+use patina::config::Pricing;
+use patina::highlight::CodeHighlighter;
+use patina::streaming::StreamCoalescer;
+use patina::ui::{ChatPanel, ChatPanelState, ThemePalette};
+use patina_core::state::{ChatMessage, ChatParameters, Conversation, MessageRole};
+use patina_core::TokenCounter;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+#[test]
+fn coalescer_holds_deltas_until_the_window_elapses() {
+    let start = Instant::now();
+    let mut buffer = StreamCoalescer::new(Duration::from_millis(50));
+    assert_eq!(buffer.next_flush(start), None);
+
+    buffer.push("Hel", start);
+    buffer.push("lo", start + Duration::from_millis(20));
+    assert_eq!(buffer.poll(start + Duration::from_millis(30)), None);
+    assert_eq!(
+        buffer.next_flush(start + Duration::from_millis(30)),
+        Some(Duration::from_millis(20))
+    );
+    assert_eq!(
+        buffer.poll(start + Duration::from_millis(50)).as_deref(),
+        Some("Hello")
+    );
+    assert_eq!(buffer.poll(start + Duration::from_millis(200)), None);
+
+    // The window restarts with the next delta after a flush.
+    buffer.push(", world", start + Duration::from_millis(60));
+    assert_eq!(buffer.poll(start + Duration::from_millis(100)), None);
+    assert_eq!(
+        buffer.poll(start + Duration::from_millis(110)).as_deref(),
+        Some(", world")
+    );
+}
+
+#[test]
+fn coalescer_flushes_immediately_when_asked_or_without_a_window() {
+    let now = Instant::now();
+    let mut buffer = StreamCoalescer::new(Duration::from_secs(10));
+    buffer.push("final words", now);
+    assert_eq!(buffer.flush().as_deref(), Some("final words"));
+    assert_eq!(buffer.flush(), None);
+    assert_eq!(buffer.next_flush(now), None);
+
+    let mut immediate = StreamCoalescer::new(Duration::ZERO);
+    immediate.push("a", now);
+    assert_eq!(immediate.poll(now).as_deref(), Some("a"));
+    immediate.push("", now);
+    assert_eq!(immediate.poll(now), None);
+}
+
+/// A mock stream sending `deltas` from another thread, two milliseconds apart.
+fn fast_mock_stream(deltas: &[String]) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    let deltas = deltas.to_vec();
+    std::thread::spawn(move || {
+        for delta in deltas {
+            std::thread::sleep(Duration::from_millis(2));
+            if tx.send(delta).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Renders the chat panel once per frame the UI would paint while a fast mock
+/// stream delivers `deltas`, waking like the app does on each delta or when the
+/// buffered text falls due; returns the frame count, the time spent in those
+/// frames and the final streamed text.
+fn render_stream(deltas: &[String], window: Duration) -> (usize, Duration, String) {
+    let mut conversation = Conversation::new();
+    conversation
+        .messages
+        .push(ChatMessage::new(MessageRole::User, "Tell me a story"));
+    let ctx = Context::default();
+    let palette = ThemePalette::for_dark();
+    let mut state = ChatPanelState::default();
+    let mut markdown_cache = CommonMarkCache::default();
+    let mut highlighter = CodeHighlighter::default();
+    let tokens = TokenCounter::for_model("gpt-4o");
+    let defaults = ChatParameters {
+        model: "gpt-4o".into(),
+        temperature: 0.7,
+        max_request_messages: 0,
+    };
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(1024.0, 768.0))),
+        ..RawInput::default()
+    };
+    let mut streaming = StreamingMessage {
+        conversation_id: conversation.id,
+        message_id: uuid::Uuid::new_v4(),
+        content: String::new(),
+    };
+    let mut buffer = StreamCoalescer::new(window);
+    let mut frames = 0;
+    let mut frame_time = Duration::ZERO;
+    let mut render = |streaming: &StreamingMessage| {
+        let started = Instant::now();
+        let _ = ctx.run(input.clone(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                ChatPanel::show(
+                    ui,
+                    &palette,
+                    &mut state,
+                    &conversation,
+                    Some(streaming),
+                    &mut markdown_cache,
+                    &mut highlighter,
+                    &tokens,
+                    &defaults,
+                    &[],
+                    &Pricing::default(),
+                    false,
+                    true,
+                );
+            });
+        });
+        frames += 1;
+        frame_time += started.elapsed();
+    };
+
+    let stream = fast_mock_stream(deltas);
+    loop {
+        let wait = buffer
+            .next_flush(Instant::now())
+            .unwrap_or(Duration::from_secs(5));
+        match stream.recv_timeout(wait) {
+            Ok(delta) => buffer.push(&delta, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let Some(text) = buffer.poll(Instant::now()) {
+            streaming.content.push_str(&text);
+            render(&streaming);
+        }
+    }
+    if let Some(text) = buffer.flush() {
+        streaming.content.push_str(&text);
+        render(&streaming);
+    }
+    (frames, frame_time, streaming.content)
+}
+
+#[test]
+fn coalescing_a_fast_stream_paints_far_fewer_frames() {
+    let deltas: Vec<String> = (0..500).map(|index| format!("word{index} ")).collect();
+    let expected: String = deltas.concat();
+
+    let (per_token_frames, per_token_time, per_token_text) = render_stream(&deltas, Duration::ZERO);
+    let (coalesced_frames, coalesced_time, coalesced_text) =
+        render_stream(&deltas, Duration::from_millis(50));
+
+    assert_eq!(per_token_text, expected);
+    assert_eq!(coalesced_text, expected);
+    // Without a window every delta is painted as it arrives.
+    assert!(per_token_frames > 400, "painted {per_token_frames} frames");
+    // About one frame per 50 ms window over a stream of a second or so.
+    assert!(
+        coalesced_frames * 10 < per_token_frames,
+        "painted {coalesced_frames} frames"
+    );
+    assert!(
+        coalesced_time < per_token_time,
+        "coalesced {coalesced_time:?} vs per token {per_token_time:?}"
+    );
+}
@@ -0,0 +1,16 @@
+use patina_core::telemetry::{init_tracing, set_log_level};
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+#[test]
+fn log_level_can_be_raised_and_restored_at_runtime() {
+    init_tracing(EnvFilter::new("warn"), None).expect("init");
+    assert!(!tracing::enabled!(Level::DEBUG));
+
+    set_log_level(Some(Level::TRACE)).expect("raise");
+    assert!(tracing::enabled!(Level::TRACE));
+
+    set_log_level(None).expect("restore");
+    assert!(!tracing::enabled!(Level::DEBUG));
+    assert!(tracing::enabled!(Level::WARN));
+}
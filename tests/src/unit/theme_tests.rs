@@ -0,0 +1,83 @@
+use egui::Color32;
+use patina::ui::{ThemeMode, ThemePalette};
+
+fn relative_luminance(color: Color32) -> f64 {
+    let channel = |value: u8| {
+        let value = f64::from(value) / 255.0;
+        if value <= 0.03928 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+}
+
+fn contrast_ratio(a: Color32, b: Color32) -> f64 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+#[test]
+fn high_contrast_palette_meets_wcag_aa() {
+    let palette = ThemePalette::for_high_contrast();
+    for surface in [
+        palette.background,
+        palette.sidebar_background,
+        palette.surface,
+        palette.user_bubble,
+        palette.assistant_bubble,
+    ] {
+        assert!(contrast_ratio(palette.text_primary, surface) >= 7.0);
+        assert!(contrast_ratio(palette.text_secondary, surface) >= 4.5);
+        assert!(contrast_ratio(palette.accent, surface) >= 4.5);
+        assert!(contrast_ratio(palette.warning, surface) >= 4.5);
+        assert!(contrast_ratio(palette.border, surface) >= 3.0);
+    }
+}
+
+#[test]
+fn high_contrast_mode_is_selectable_and_persisted() {
+    assert!(ThemeMode::ALL.contains(&ThemeMode::HighContrast));
+    assert_eq!(
+        serde_json::to_string(&ThemeMode::HighContrast).expect("serialize"),
+        "\"high-contrast\""
+    );
+    let visuals = ThemePalette::for_high_contrast().high_contrast_visuals();
+    assert!(visuals.dark_mode);
+    assert!(visuals.selection.stroke.width >= 2.0);
+}
+
+#[test]
+fn color_overrides_replace_only_valid_fields() {
+    use patina::settings::ColorOverrides;
+    use patina::ui::parse_hex_color;
+
+    assert_eq!(
+        parse_hex_color(" #FF8800 "),
+        Some(Color32::from_rgb(255, 136, 0))
+    );
+    assert_eq!(
+        parse_hex_color("00ff00"),
+        Some(Color32::from_rgb(0, 255, 0))
+    );
+    assert_eq!(parse_hex_color("#FFF"), None);
+    assert_eq!(parse_hex_color("#GG0000"), None);
+    assert_eq!(parse_hex_color("#+12345"), None);
+
+    let overrides = ColorOverrides {
+        accent: "#FF8800".into(),
+        user_bubble: "not a color".into(),
+        assistant_bubble: String::new(),
+    };
+    assert!(!overrides.is_valid());
+    let base = ThemePalette::for_dark();
+    let palette = ThemePalette::for_dark().with_overrides(&overrides);
+    assert_eq!(palette.accent, Color32::from_rgb(255, 136, 0));
+    assert_eq!(palette.user_bubble, base.user_bubble);
+    assert_eq!(palette.assistant_bubble, base.assistant_bubble);
+    assert_eq!(palette.background, base.background);
+
+    assert!(ColorOverrides::default().is_empty());
+    assert!(ColorOverrides::default().is_valid());
+}
@@ -0,0 +1,625 @@
+use patina::app::regeneration_on_model_change;
+use patina::config::{self, NoModelsReason, ProviderConfig, Scope, UiSettings};
+use patina::ui::{format_cost, SessionUsage, TimestampFormat};
+use patina::PatinaEguiApp;
+use patina_core::llm::{LlmDriver, ModelUsage};
+use patina_core::project::ProjectHandle;
+use patina_core::state::{AppState, ChatMessage, Conversation, MessageRole};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Ctrl+Plus, which zooms the UI in one step and so changes a saved setting.
+fn zoom_in() -> egui::Event {
+    egui::Event::Key {
+        key: egui::Key::Plus,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers: egui::Modifiers::COMMAND,
+    }
+}
+
+fn frame(ctx: &egui::Context, app: &mut PatinaEguiApp, events: Vec<egui::Event>) {
+    let input = egui::RawInput {
+        events,
+        ..egui::RawInput::default()
+    };
+    let _ = ctx.run(input, |ctx| patina::render_ui(ctx, app));
+}
+
+#[test]
+fn persist_now_writes_settings_despite_pending_debounce() {
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("runtime"),
+    );
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let settings = UiSettings {
+        model: "debounced-model".to_string(),
+        autosave_delay_ms: 60_000,
+        ..UiSettings::default()
+    };
+    let driver = runtime.block_on(LlmDriver::fake());
+    let mut app = PatinaEguiApp::new(
+        None,
+        driver,
+        runtime.clone(),
+        scope.clone(),
+        settings,
+        ProviderConfig {
+            available_models: vec!["debounced-model".to_string()],
+            ..ProviderConfig::default()
+        },
+    );
+
+    let settings_file = temp_dir.path().join(".patina").join("ui_settings.json");
+    let ctx = egui::Context::default();
+    frame(&ctx, &mut app, vec![zoom_in()]);
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!settings_file.exists(), "save should still be debounced");
+
+    app.persist_now();
+
+    let saved = runtime
+        .block_on(config::load_ui_settings(&scope))
+        .expect("load settings");
+    assert_eq!(saved.model, "debounced-model");
+    assert_eq!(saved.autosave_delay_ms, 60_000);
+    assert_eq!(saved.ui_scale, 1.1);
+}
+
+#[test]
+fn a_burst_of_changes_is_written_once_after_the_autosave_delay() {
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("runtime"),
+    );
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let delay = Duration::from_secs(2);
+    let settings = UiSettings {
+        model: "gpt-4o".to_string(),
+        autosave_delay_ms: delay.as_millis() as u64,
+        ..UiSettings::default()
+    };
+    let driver = runtime.block_on(LlmDriver::fake());
+    let mut app = PatinaEguiApp::new(
+        None,
+        driver,
+        runtime.clone(),
+        scope.clone(),
+        settings,
+        ProviderConfig {
+            available_models: vec!["gpt-4o".to_string()],
+            ..ProviderConfig::default()
+        },
+    );
+    let settings_file = temp_dir.path().join(".patina").join("ui_settings.json");
+    let ctx = egui::Context::default();
+    let run_frames_until = |app: &mut PatinaEguiApp, until: Instant| {
+        while Instant::now() < until {
+            frame(&ctx, app, Vec::new());
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    };
+
+    let started = Instant::now();
+    for _ in 0..3 {
+        frame(&ctx, &mut app, vec![zoom_in()]);
+    }
+    run_frames_until(&mut app, started + delay / 4);
+    assert!(
+        !settings_file.exists(),
+        "written before the auto-save delay passed"
+    );
+
+    run_frames_until(&mut app, started + delay + Duration::from_millis(500));
+    let saved = runtime
+        .block_on(config::load_ui_settings(&scope))
+        .expect("load settings");
+    assert!(settings_file.exists(), "not written after the delay");
+    assert!((saved.ui_scale - 1.3).abs() < 1e-6, "{}", saved.ui_scale);
+
+    // The burst was one write: nothing else is pending to write the file again.
+    std::fs::remove_file(&settings_file).expect("remove settings");
+    run_frames_until(
+        &mut app,
+        Instant::now() + delay + Duration::from_millis(500),
+    );
+    assert!(
+        !settings_file.exists(),
+        "the burst was written more than once"
+    );
+}
+
+#[test]
+fn settings_changes_are_written_once_the_autosave_delay_passes() {
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("runtime"),
+    );
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let settings = UiSettings {
+        autosave_delay_ms: 0,
+        ..UiSettings::default()
+    };
+    let driver = runtime.block_on(LlmDriver::fake());
+    let mut app = PatinaEguiApp::new(
+        None,
+        driver,
+        runtime.clone(),
+        scope,
+        settings,
+        ProviderConfig::default(),
+    );
+    let settings_file = temp_dir.path().join(".patina").join("ui_settings.json");
+    assert!(
+        !settings_file.exists(),
+        "nothing is written before a frame runs"
+    );
+
+    let ctx = egui::Context::default();
+    let _ = ctx.run(egui::RawInput::default(), |ctx| {
+        patina::render_ui(ctx, &mut app)
+    });
+    for _ in 0..100 {
+        if settings_file.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(
+        settings_file.exists(),
+        "the first frame flushes a zero delay"
+    );
+}
+
+#[test]
+fn model_change_regenerates_only_when_enabled_and_answered() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "ModelSwitch").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+
+    let enabled = UiSettings {
+        regenerate_on_model_change: true,
+        ..UiSettings::default()
+    };
+    let disabled = UiSettings::default();
+
+    let mut pending = Conversation::new();
+    pending.add_message(ChatMessage::new(MessageRole::User, "unanswered"));
+    assert_eq!(regeneration_on_model_change(&enabled, Some(&pending)), None);
+    assert_eq!(regeneration_on_model_change(&enabled, None), None);
+
+    runtime
+        .block_on(state.send_user_message("hello", "old-model", 0.6))
+        .expect("send");
+    let conversation = state.active_conversation().expect("conversation");
+    assert_eq!(
+        regeneration_on_model_change(&disabled, Some(&conversation)),
+        None
+    );
+    let reply_id = regeneration_on_model_change(&enabled, Some(&conversation))
+        .expect("regenerate after a completed turn");
+    assert_eq!(reply_id, conversation.messages[1].id);
+
+    runtime
+        .block_on(state.add_alternative(conversation.id, reply_id, "new-model", 0.6))
+        .expect("regenerate");
+    let reply = state.active_conversation().expect("conversation").messages[1].clone();
+    assert!(reply.content.contains("new-model"));
+    assert!(reply.alternatives[0].contains("old-model"));
+}
+
+#[test]
+fn retry_save_retries_until_the_attempts_run_out() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let calls = std::cell::Cell::new(0);
+    let result = runtime.block_on(config::retry_save(3, Duration::ZERO, || {
+        calls.set(calls.get() + 1);
+        async { Err(anyhow::anyhow!("disk full")) }
+    }));
+    assert!(result.is_err());
+    assert_eq!(calls.get(), 3);
+
+    calls.set(0);
+    let result = runtime.block_on(config::retry_save(3, Duration::ZERO, || {
+        calls.set(calls.get() + 1);
+        let attempt = calls.get();
+        async move {
+            if attempt < 2 {
+                Err(anyhow::anyhow!("busy"))
+            } else {
+                Ok(())
+            }
+        }
+    }));
+    assert!(result.is_ok());
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn failed_settings_save_sets_and_clears_the_warning_flag() {
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("runtime"),
+    );
+    let temp_dir = TempDir::new().expect("temp dir");
+    // A file where the settings directory should be makes every write fail.
+    let blocker = temp_dir.path().join(".patina");
+    std::fs::write(&blocker, "not a directory").expect("blocker");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let driver = runtime.block_on(LlmDriver::fake());
+    let mut app = PatinaEguiApp::new(
+        None,
+        driver,
+        runtime.clone(),
+        scope,
+        UiSettings::default(),
+        ProviderConfig::default(),
+    );
+
+    assert!(!app.last_save_failed());
+    app.persist_now();
+    assert!(app.last_save_failed());
+
+    std::fs::remove_file(&blocker).expect("remove blocker");
+    app.persist_now();
+    assert!(!app.last_save_failed());
+}
+
+#[test]
+fn provider_config_reads_fail_on_broken_yaml_but_loads_fall_back() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let dir = temp_dir.path().join(".patina");
+    std::fs::create_dir_all(&dir).expect("config dir");
+
+    std::fs::write(
+        dir.join("patina.yaml"),
+        "app:\n  provider: mock\n  available_models: [alpha, beta]\n",
+    )
+    .expect("write");
+    let config = runtime
+        .block_on(config::read_provider_config(&scope))
+        .expect("read");
+    assert_eq!(config.available_models, ["alpha", "beta"]);
+
+    std::fs::write(dir.join("patina.yaml"), "app: [unclosed").expect("write");
+    let error = runtime
+        .block_on(config::read_provider_config(&scope))
+        .expect_err("broken yaml");
+    assert!(error.to_string().contains("failed to decode"), "{error}");
+    let config = runtime
+        .block_on(config::load_provider_config(&scope))
+        .expect("load");
+    assert!(config.available_models.is_empty());
+}
+
+#[test]
+fn provider_config_watcher_reports_edits() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let watcher = config::ProviderConfigWatcher::new(&scope, || {}).expect("watcher");
+    assert!(!watcher.take_changed());
+
+    std::fs::write(temp_dir.path().join(".patina").join("notes.txt"), "x").expect("write");
+    std::fs::write(
+        temp_dir.path().join(".patina").join("patina.yaml"),
+        "app: {}\n",
+    )
+    .expect("write");
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while !watcher.take_changed() {
+        assert!(std::time::Instant::now() < deadline, "no change reported");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn provider_config_lists_profiles_and_uses_the_active_ones_models() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let dir = temp_dir.path().join(".patina");
+    std::fs::create_dir_all(&dir).expect("config dir");
+    let profiles = "  profiles:\n    work:\n      provider: azure_open_ai\n      azure_openai:\n        available_models: [gpt-4o-work]\n    local:\n      provider: ollama\n      ollama:\n        model: llama3.2\n";
+
+    std::fs::write(
+        dir.join("patina.yaml"),
+        format!("app:\n  provider: mock\n  available_models: [mock-model]\n{profiles}"),
+    )
+    .expect("write");
+    let config = runtime
+        .block_on(config::read_provider_config(&scope))
+        .expect("read");
+    assert_eq!(config.profiles, ["local", "work"]);
+    assert_eq!(config.active_profile, None);
+    assert_eq!(config.available_models, ["mock-model"]);
+
+    std::fs::write(
+        dir.join("patina.yaml"),
+        format!("app:\n  provider: mock\n  active_profile: work\n{profiles}"),
+    )
+    .expect("write");
+    let config = runtime
+        .block_on(config::read_provider_config(&scope))
+        .expect("read");
+    assert_eq!(config.active_profile.as_deref(), Some("work"));
+    assert_eq!(config.available_models, ["gpt-4o-work"]);
+
+    std::fs::write(
+        dir.join("patina.yaml"),
+        format!("app:\n  provider: mock\n  active_profile: gone\n{profiles}"),
+    )
+    .expect("write");
+    let error = runtime
+        .block_on(config::read_provider_config(&scope))
+        .expect_err("unknown profile");
+    assert!(format!("{error:#}").contains("`gone`"), "{error:#}");
+}
+
+#[test]
+fn ui_scale_defaults_to_one_and_is_clamped() {
+    let settings: UiSettings = serde_json::from_str("{}").expect("defaults");
+    assert_eq!(settings.ui_scale, 1.0);
+    assert_eq!(settings.clamped_ui_scale(), 1.0);
+
+    for (stored, used) in [(5.0, 2.0), (0.1, 0.75), (1.25, 1.25), (f32::NAN, 1.0)] {
+        let settings = UiSettings {
+            ui_scale: stored,
+            ..UiSettings::default()
+        };
+        assert_eq!(settings.clamped_ui_scale(), used, "{stored}");
+    }
+}
+
+#[test]
+fn timestamp_format_defaults_to_short_and_round_trips() {
+    let settings: UiSettings = serde_json::from_str("{}").expect("defaults");
+    assert_eq!(settings.timestamp_format, TimestampFormat::Short);
+
+    let settings: UiSettings =
+        serde_json::from_str(r#"{"timestamp_format": {"custom": "%H:%M"}}"#).expect("custom");
+    assert_eq!(
+        settings.timestamp_format,
+        TimestampFormat::Custom("%H:%M".to_string())
+    );
+    let json = serde_json::to_string(&UiSettings {
+        timestamp_format: TimestampFormat::Relative,
+        ..UiSettings::default()
+    })
+    .expect("serialize");
+    assert!(json.contains(r#""timestamp_format":"relative""#), "{json}");
+}
+
+#[test]
+fn empty_model_lists_say_which_section_needs_models() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let read = |yaml: &str| {
+        let dir = temp_dir.path().join(".patina");
+        std::fs::create_dir_all(&dir).expect("config dir");
+        std::fs::write(dir.join("patina.yaml"), yaml).expect("write");
+        runtime
+            .block_on(config::read_provider_config(&scope))
+            .expect("read")
+            .no_models
+    };
+
+    let absent = runtime
+        .block_on(config::read_provider_config(&scope))
+        .expect("read");
+    assert_eq!(absent.no_models, Some(NoModelsReason::NoConfigFile));
+
+    assert_eq!(
+        read("app:\n  provider: azure_openai\n"),
+        Some(NoModelsReason::MissingSection {
+            provider: "Azure OpenAI".into(),
+            section: "app.azure_openai".into(),
+        })
+    );
+    let empty =
+        read("app:\n  provider: azure_openai\n  azure_openai:\n    endpoint: x\n").expect("reason");
+    assert_eq!(
+        empty.hint(),
+        "Add models under app.azure_openai.available_models"
+    );
+    assert!(empty.to_string().contains("Azure OpenAI"), "{empty}");
+    assert_eq!(
+        read("app:\n  active_profile: home\n  profiles:\n    home:\n      provider: ollama\n      ollama: {}\n"),
+        Some(NoModelsReason::EmptySection {
+            provider: "Ollama".into(),
+            path: "app.profiles.home.ollama.model".into(),
+        })
+    );
+    assert_eq!(
+        read("app:\n  provider: openai\n  openai:\n    available_models: [gpt-4o]\n"),
+        None
+    );
+}
+
+#[test]
+fn a_saved_model_the_provider_lacks_is_replaced_at_startup() {
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("runtime"),
+    );
+    let temp_dir = TempDir::new().expect("temp dir");
+    let scope = Scope::Project(temp_dir.path().to_path_buf());
+    let settings = UiSettings {
+        model: "gpt-4o".to_string(),
+        ..UiSettings::default()
+    };
+    let driver = runtime.block_on(LlmDriver::fake());
+    let mut app = PatinaEguiApp::new(
+        None,
+        driver,
+        runtime.clone(),
+        scope.clone(),
+        settings,
+        ProviderConfig {
+            available_models: vec!["llama3".to_string(), "mistral".to_string()],
+            ..ProviderConfig::default()
+        },
+    );
+    app.persist_now();
+
+    let saved = runtime
+        .block_on(config::load_ui_settings(&scope))
+        .expect("load settings");
+    assert_eq!(saved.model, "llama3");
+}
+
+#[test]
+fn a_project_config_overrides_only_the_keys_it_sets() {
+    let user = "app:\n  provider: azure_openai\n  azure_openai:\n    endpoint: https://example.openai.azure.com\n    available_models: [gpt-4o]\n";
+    let project = "app:\n  azure_openai:\n    available_models: [gpt-4o-mini]\n  openai:\n    available_models: [gpt-3.5-turbo]\n";
+    let config = config::parse_provider_config(&[user, project]).expect("merged");
+    assert_eq!(config.available_models, ["gpt-4o-mini"]);
+    assert_eq!(config.no_models, None);
+
+    let config = config::parse_provider_config(&[user, ""]).expect("empty project file");
+    assert_eq!(config.available_models, ["gpt-4o"]);
+
+    let config = config::parse_provider_config(&[user, project, "app:\n  provider: openai\n"])
+        .expect("three layers");
+    assert_eq!(config.available_models, ["gpt-3.5-turbo"]);
+}
+
+#[test]
+fn pricing_from_patina_yaml_prices_usage_per_thousand_tokens() {
+    let config = config::parse_provider_config(&[
+        "app:\n  provider: mock\n  available_models: [gpt-4o]\n  pricing:\n    GPT-4o:\n      prompt: 0.005\n      completion: 0.015\n",
+    ])
+    .expect("config");
+    let usage = ModelUsage {
+        prompt_tokens: 2_000,
+        completion_tokens: 1_000,
+    };
+    let cost = config.pricing.cost("gpt-4o", &usage).expect("priced");
+    assert!((cost - 0.025).abs() < 1e-9, "{cost}");
+    assert_eq!(config.pricing.cost("llama3", &usage), None);
+
+    let mut by_model = BTreeMap::new();
+    by_model.insert(Some("gpt-4o".to_string()), usage.clone());
+    by_model.insert(None, usage.clone());
+    let total = config
+        .pricing
+        .total_cost(&by_model, "gpt-4o")
+        .expect("both priced");
+    assert!((total - 0.05).abs() < 1e-9, "{total}");
+    assert_eq!(config.pricing.total_cost(&by_model, "llama3"), None);
+
+    assert_eq!(format_cost(0.05), "$0.05");
+    assert_eq!(format_cost(0.00042), "$0.0004");
+    assert!(config::parse_provider_config(&["app:\n  provider: mock\n"])
+        .expect("config")
+        .pricing
+        .is_empty());
+}
+
+#[test]
+fn session_usage_counts_sent_streamed_and_regenerated_replies_per_model() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let temp_dir = TempDir::new().expect("temp dir");
+    let project = ProjectHandle::create(temp_dir.path(), "Session").expect("project");
+    let driver = runtime.block_on(LlmDriver::fake());
+    let state = AppState::new(project, driver);
+
+    runtime
+        .block_on(state.send_user_message("hello", "gpt-4o", 0.6))
+        .expect("send");
+    runtime.block_on(async {
+        let (_, mut rx) = state
+            .send_user_message_streaming("again", "gpt-4o", 0.6)
+            .await
+            .expect("stream");
+        while let Some(chunk) = rx.recv().await {
+            if chunk.expect("chunk").done {
+                break;
+            }
+        }
+    });
+    let conversation = state.active_conversation().expect("conversation");
+    runtime
+        .block_on(state.add_alternative(
+            conversation.id,
+            conversation.messages[1].id,
+            "llama3",
+            0.6,
+        ))
+        .expect("regenerate");
+
+    let finished = state.take_usage();
+    let models: Vec<&str> = finished.iter().map(|(model, _)| model.as_str()).collect();
+    assert_eq!(models, ["gpt-4o", "gpt-4o", "llama3"]);
+    assert!(state.take_usage().is_empty());
+
+    let mut session = SessionUsage::default();
+    for (model, usage) in &finished {
+        session.record(model, usage);
+    }
+    assert_eq!(session.requests, 3);
+    assert_eq!(session.by_model.len(), 2);
+    let total: usize = finished.iter().map(|(_, usage)| usage.total_tokens()).sum();
+    assert_eq!(session.total_tokens(), total);
+
+    let gpt_only = config::parse_provider_config(&[
+        "app:\n  provider: mock\n  pricing:\n    gpt-4o:\n      prompt: 0.005\n      completion: 0.015\n",
+    ])
+    .expect("config")
+    .pricing;
+    assert_eq!(session.cost(&gpt_only), None);
+    let both = config::parse_provider_config(&[
+        "app:\n  provider: mock\n  pricing:\n    gpt-4o:\n      prompt: 0.005\n      completion: 0.015\n    llama3:\n      prompt: 0.0\n      completion: 0.0\n",
+    ])
+    .expect("config")
+    .pricing;
+    let expected = gpt_only
+        .cost("gpt-4o", &session.by_model["gpt-4o"])
+        .expect("priced");
+    let cost = session.cost(&both).expect("every model priced");
+    assert!((cost - expected).abs() < 1e-12, "{cost}");
+}
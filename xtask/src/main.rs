@@ -23,7 +23,7 @@ enum Commands {
 }
 
 fn main() -> Result<()> {
-    telemetry::init_tracing(EnvFilter::new("info"))?;
+    telemetry::init_tracing(EnvFilter::new("info"), None)?;
     let cli = Cli::parse();
 
     match cli.command {